@@ -0,0 +1,59 @@
+//! Fuzz the `transfer.forward` entrypoint of the `transfer.wasm` test contract,
+//! which forwards the CCD it receives to the account in the parameter.
+#![no_main]
+use concordium_smart_contract_testing::*;
+use libfuzzer_sys::fuzz_target;
+use std::cell::RefCell;
+
+/// The test contract, relative to the directory of this crate.
+const MODULE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../concordium-rust-sdk/concordium-base",
+    "/smart-contracts/testdata/contracts/v1/transfer.wasm"
+);
+
+/// The account that deploys, initializes and updates the contract.
+const ACC_0: AccountAddress = AccountAddress([0; 32]);
+
+/// A second account, so that inputs can forward CCD to an existing account.
+const ACC_1: AccountAddress = AccountAddress([1; 32]);
+
+/// Create a chain with two accounts and an instance of the contract.
+fn prepare_chain() -> (Chain, ContractAddress) {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(ACC_0, Amount::from_ccd(10000)));
+    chain.create_account(Account::new(ACC_1, Amount::from_ccd(10000)));
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            ACC_0,
+            module_load_v1_raw(MODULE).expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+    let res_init = chain
+        .contract_init(Signer::with_one_key(), ACC_0, Energy::from(10000), InitContractPayload {
+            mod_ref:   res_deploy.module_reference,
+            init_name: OwnedContractName::new_unchecked("init_transfer".into()),
+            param:     OwnedParameter::empty(),
+            amount:    Amount::zero(),
+        })
+        .expect("Initializing valid contract should work");
+    (chain, res_init.contract_address)
+}
+
+thread_local! {
+    static TARGET: RefCell<EntrypointFuzzTarget> = {
+        let (chain, contract) = prepare_chain();
+        RefCell::new(
+            EntrypointFuzzTarget::new(
+                chain,
+                ACC_0,
+                contract,
+                OwnedReceiveName::new_unchecked("transfer.forward".into()),
+            )
+            .amount(Amount::from_micro_ccd(123)),
+        )
+    };
+}
+
+fuzz_target!(|data: &[u8]| TARGET.with(|target| target.borrow_mut().fuzz(data)));
@@ -0,0 +1,167 @@
+//! Energy regression baselines for integration tests.
+//!
+//! The baseline is a JSON file mapping names of test cases to the energy they
+//! used. See [`EnergyBaseline`] for details.
+use crate::types::*;
+use concordium_rust_sdk::base::base::Energy;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// The environment variable which, when set to anything other than `0` or
+/// `false`, makes [`EnergyBaseline::check`] record the measured energy instead
+/// of comparing it to the baseline.
+pub const BLESS_ENERGY_ENV_VAR: &str = "CONCORDIUM_BLESS_ENERGY";
+
+/// Lock held while reading and writing baseline files, since tests in the same
+/// test binary run in parallel threads.
+static BASELINE_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+impl EnergyBaseline {
+    /// Create a baseline backed by the JSON file at `path`. The file is created
+    /// when the first measurement is recorded.
+    ///
+    /// The default tolerance is 0%, i.e., any increase in energy is a
+    /// regression.
+    ///
+    /// Since the file is updated by every test that records a new
+    /// measurement, it is recommended to use a separate file per test binary,
+    /// i.e., per file in the `tests` folder.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use concordium_smart_contract_testing::*;
+    /// # fn update() -> ContractInvokeSuccess { todo!() }
+    /// let baseline = EnergyBaseline::new("tests/energy-baseline.json").tolerance_percent(5);
+    ///
+    /// let res_update = update();
+    /// baseline.check("cis2_transfer", res_update.energy_used).unwrap();
+    /// ```
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path:              path.into(),
+            tolerance_percent: 0,
+            warn_only:         false,
+        }
+    }
+
+    /// Set the tolerance in percent. Using more than `tolerance_percent`
+    /// percent energy than the baseline is a regression.
+    pub fn tolerance_percent(mut self, tolerance_percent: u64) -> Self {
+        self.tolerance_percent = tolerance_percent;
+        self
+    }
+
+    /// Only print a warning to stderr on regressions, instead of returning an
+    /// error from [`check`](Self::check).
+    pub fn warn_only(mut self) -> Self {
+        self.warn_only = true;
+        self
+    }
+
+    /// The path to the baseline file.
+    pub fn path(&self) -> &Path { &self.path }
+
+    /// Compare the energy used by the test case `name` to the baseline.
+    ///
+    /// - If the test case is not in the baseline file, or if the environment
+    ///   variable [`BLESS_ENERGY_ENV_VAR`] is set, then `energy_used` is
+    ///   recorded in the file as the new baseline.
+    /// - Otherwise, a regression is reported if `energy_used` exceeds the
+    ///   baseline by more than the tolerance. Regressions are returned as
+    ///   errors, unless [`warn_only`](Self::warn_only) is set, in which case
+    ///   they are printed to stderr.
+    pub fn check(&self, name: &str, energy_used: Energy) -> Result<(), EnergyBaselineError> {
+        let to_error = |kind| EnergyBaselineError {
+            path: self.path.clone(),
+            name: name.to_string(),
+            kind,
+        };
+        // Tests that panic while holding the lock poison it, but the file is still
+        // consistent since it is written in one go.
+        let _guard = BASELINE_FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entries = self.read_entries().map_err(to_error)?;
+
+        match entries.get(name) {
+            Some(baseline) if !is_bless_enabled() => {
+                let baseline = Energy::from(*baseline);
+                let allowed = baseline
+                    .energy
+                    .saturating_add(baseline.energy.saturating_mul(self.tolerance_percent) / 100);
+                if energy_used.energy > allowed {
+                    let kind = EnergyBaselineErrorKind::Regression {
+                        baseline,
+                        energy_used,
+                        tolerance_percent: self.tolerance_percent,
+                    };
+                    if self.warn_only {
+                        eprintln!("Warning: {}", to_error(kind));
+                        return Ok(());
+                    }
+                    return Err(to_error(kind));
+                }
+                Ok(())
+            }
+            _ => {
+                entries.insert(name.to_string(), energy_used.energy);
+                self.write_entries(&entries).map_err(to_error)
+            }
+        }
+    }
+
+    /// Read the entries of the baseline file. A missing file has no entries.
+    fn read_entries(&self) -> Result<BTreeMap<String, u64>, EnergyBaselineErrorKind> {
+        let contents = match std::fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(serde_json::from_slice(&contents)?)
+    }
+
+    /// Write the entries to the baseline file.
+    fn write_entries(
+        &self,
+        entries: &BTreeMap<String, u64>,
+    ) -> Result<(), EnergyBaselineErrorKind> {
+        let mut contents = serde_json::to_vec_pretty(entries)?;
+        contents.push(b'\n');
+        std::fs::write(&self.path, contents)?;
+        Ok(())
+    }
+}
+
+/// Return whether the environment variable [`BLESS_ENERGY_ENV_VAR`] is set to
+/// something other than `0` or `false`.
+fn is_bless_enabled() -> bool {
+    let Ok(value) = std::env::var(BLESS_ENERGY_ENV_VAR) else {
+        return false;
+    };
+    value != "0" && value != "false"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the first measurement is recorded, and that later ones are
+    /// compared to it with the tolerance.
+    #[test]
+    fn test_energy_baseline() {
+        let path = std::env::temp_dir()
+            .join(format!("concordium-energy-baseline-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let baseline = EnergyBaseline::new(&path).tolerance_percent(10);
+
+        baseline.check("transfer", Energy::from(1000)).expect("Recording works");
+        baseline.check("transfer", Energy::from(1100)).expect("Within tolerance");
+        let err = baseline.check("transfer", Energy::from(1101)).expect_err("Regression");
+        assert!(matches!(err.kind, EnergyBaselineErrorKind::Regression { .. }));
+        baseline.warn_only().check("transfer", Energy::from(1101)).expect("Only a warning");
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,57 @@
+//! A local node emulator backed by a [`Chain`], which serves a subset of the
+//! gRPC API of the node. See [`NodeEmulator`] for the supported methods.
+//!
+//! Usage:
+//!
+//! ```text
+//! node-emulator [--port <PORT>] [--chain <FILE>] [--account <ADDRESS>=<CCD>]...
+//! ```
+//!
+//! The chain is loaded from a file saved with [`Chain::save_to`] if `--chain`
+//! is given, and otherwise it starts out empty. Each `--account` creates an
+//! account with the given balance in CCD.
+use anyhow::{bail, Context};
+use concordium_smart_contract_testing::*;
+use std::net::TcpListener;
+
+/// The port used if none is given.
+const DEFAULT_PORT: u16 = 20100;
+
+fn main() -> anyhow::Result<()> {
+    let mut port = DEFAULT_PORT;
+    let mut chain = None;
+    let mut accounts = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().with_context(|| format!("Missing value for {arg}"));
+        match arg.as_str() {
+            "--port" => port = value()?.parse().context("Invalid port")?,
+            "--chain" => chain = Some(value()?),
+            "--account" => {
+                let account = value()?;
+                let (address, ccd) =
+                    account.split_once('=').context("Accounts must be given as <ADDRESS>=<CCD>")?;
+                let address: AccountAddress =
+                    address.parse().map_err(|_| anyhow::anyhow!("Invalid address {address}"))?;
+                let ccd: u64 = ccd.parse().with_context(|| format!("Invalid balance {ccd}"))?;
+                accounts.push(Account::new(address, Amount::from_ccd(ccd)));
+            }
+            _ => bail!("Unknown argument {arg}"),
+        }
+    }
+
+    let mut chain = match chain {
+        Some(path) => Chain::load_from(&path).with_context(|| format!("Could not load {path}"))?,
+        None => Chain::new(),
+    };
+    for account in accounts {
+        chain.create_account(account);
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("Could not listen on port {port}"))?;
+    println!("Serving the gRPC API of the node on http://{}", listener.local_addr()?);
+    NodeEmulator::new(chain).serve(listener)?;
+    Ok(())
+}
@@ -0,0 +1,234 @@
+//! Human-readable call trees of contract updates and invocations.
+use crate::{
+    trace_walk::{self, Call, TraceEvent, TraceVisitor},
+    types::*,
+};
+use concordium_rust_sdk::base::{
+    contracts_common::{Address, ContractAddress},
+    smart_contracts::{ContractEvent, ContractTraceElement},
+};
+use std::fmt;
+
+impl ContractInvokeSuccess {
+    /// Get the call tree of the update or invocation. See [`CallTree`] for
+    /// details.
+    pub fn call_tree(&self) -> CallTree<'_> { CallTree::new(&self.trace_elements, None) }
+}
+
+impl ContractInvokeError {
+    /// Get the call tree of the update or invocation until the failure. See
+    /// [`CallTree`] for details.
+    pub fn call_tree(&self) -> CallTree<'_> {
+        CallTree::new(&self.trace_elements, Some(&self.kind))
+    }
+}
+
+impl<'a> CallTree<'a> {
+    /// Construct the tree from the trace elements.
+    fn new(
+        trace_elements: &'a [DebugTraceElement],
+        error: Option<&'a ContractInvokeErrorKind>,
+    ) -> Self {
+        let mut builder = TreeBuilder::default();
+        trace_walk::walk(trace_elements, error.is_some(), &mut builder);
+        Self {
+            calls: builder.calls,
+            error,
+            chain: None,
+        }
+    }
+
+    /// Decode events and parameters to JSON with the schemas embedded in the
+    /// modules of the contracts on the chain, when displaying the tree.
+    ///
+    /// The chain should be the one the update was executed on. Events and
+    /// parameters that cannot be decoded are displayed as hex.
+    pub fn with_schemas(mut self, chain: &'a Chain) -> Self {
+        self.chain = Some(chain);
+        self
+    }
+
+    /// Display a call and its items, indented by the depth of the call.
+    fn fmt_call(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        call: &CallTreeNode<'a>,
+        depth: usize,
+    ) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        write!(f, "{indent}{}.{}", call.address, call.entrypoint)?;
+        if let Some(sender) = call.sender {
+            write!(f, " from {}", display_address(sender))?;
+        }
+        if let Some(amount) = call.amount {
+            write!(f, " with {amount}")?;
+        }
+        if call.rolled_back {
+            write!(f, " (rolled back)")?;
+        }
+        writeln!(f)?;
+        if let Some(parameter) = call.parameter {
+            let decoded = self.chain.and_then(|chain| {
+                chain.decode_parameter_json(call.address, &call.entrypoint.to_string(), parameter)
+            });
+            match decoded {
+                Some(json) => writeln!(f, "{indent}  parameter: {json}")?,
+                None => writeln!(f, "{indent}  parameter: {} bytes", parameter.len())?,
+            }
+        }
+        for item in &call.items {
+            match item {
+                CallTreeItem::Call(inner) => self.fmt_call(f, inner, depth + 1)?,
+                CallTreeItem::Events(events) => {
+                    for event in events.iter() {
+                        writeln!(f, "{indent}  event: {}", self.display_event(call.address, event))?
+                    }
+                }
+                CallTreeItem::Transfer {
+                    amount,
+                    to,
+                } => writeln!(f, "{indent}  transferred {amount} to {to}")?,
+                CallTreeItem::Upgrade {
+                    from,
+                    to,
+                } => writeln!(f, "{indent}  upgraded from module {from} to {to}")?,
+            }
+        }
+        if let Some(failure) = call.failure {
+            writeln!(f, "{indent}  failed: {failure:?}")?;
+        }
+        Ok(())
+    }
+
+    /// Display an event as JSON if it can be decoded, and as hex otherwise.
+    fn display_event(&self, address: ContractAddress, event: &ContractEvent) -> String {
+        match self.chain.and_then(|chain| chain.decode_event_json(address, event)) {
+            Some(json) => json.to_string(),
+            None => {
+                let hex: String = event.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+                format!("0x{hex}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for CallTree<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for call in &self.calls {
+            self.fmt_call(f, call, 0)?;
+        }
+        if let Some(error) = self.error {
+            writeln!(f, "failed: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Display an account or contract address.
+fn display_address(address: Address) -> String {
+    match address {
+        Address::Account(address) => address.to_string(),
+        Address::Contract(address) => address.to_string(),
+    }
+}
+
+/// Helper for reconstructing the call tree from the trace elements.
+#[derive(Default)]
+struct TreeBuilder<'a> {
+    calls: Vec<CallTreeNode<'a>>,
+    /// The nodes of the calls on the stack of the walk.
+    stack: Vec<CallTreeNode<'a>>,
+}
+
+impl<'a> TreeBuilder<'a> {
+    /// Add an item to the current call. Empty lists of events are skipped.
+    fn add(&mut self, item: CallTreeItem<'a>) {
+        if matches!(item, CallTreeItem::Events(events) if events.is_empty()) {
+            return;
+        }
+        if let Some(node) = self.stack.last_mut() {
+            node.items.push(item);
+        }
+    }
+}
+
+impl<'a> TraceVisitor<'a> for TreeBuilder<'a> {
+    fn enter(&mut self, stack: &[Call<'a>], rolled_back: bool) {
+        let Some(call) = stack.last() else {
+            return;
+        };
+        self.stack.push(CallTreeNode {
+            address: call.address,
+            entrypoint: call.entrypoint,
+            sender: None,
+            amount: None,
+            parameter: None,
+            items: Vec::new(),
+            failure: None,
+            rolled_back,
+        });
+    }
+
+    fn event(&mut self, _stack: &[Call<'a>], event: TraceEvent<'a>, _rolled_back: bool) {
+        match event {
+            TraceEvent::Element {
+                trace_element,
+                ..
+            } => match trace_element {
+                ContractTraceElement::Interrupted {
+                    events,
+                    ..
+                } => self.add(CallTreeItem::Events(events)),
+                ContractTraceElement::Resumed {
+                    ..
+                } => {}
+                ContractTraceElement::Transferred {
+                    amount,
+                    to,
+                    ..
+                } => self.add(CallTreeItem::Transfer {
+                    amount: *amount,
+                    to:     *to,
+                }),
+                ContractTraceElement::Upgraded {
+                    from,
+                    to,
+                    ..
+                } => self.add(CallTreeItem::Upgrade {
+                    from: *from,
+                    to:   *to,
+                }),
+                ContractTraceElement::Updated {
+                    data,
+                } => {
+                    self.add(CallTreeItem::Events(&data.events));
+                    if let Some(node) = self.stack.last_mut() {
+                        node.sender = Some(data.instigator);
+                        node.amount = Some(data.amount);
+                        node.parameter = Some(data.message.as_ref());
+                    }
+                }
+            },
+            TraceEvent::Debug(_) => {}
+            TraceEvent::Failed {
+                error,
+                ..
+            } => {
+                if let Some(node) = self.stack.last_mut() {
+                    node.failure = Some(error);
+                }
+            }
+        }
+    }
+
+    /// Complete the current call and add it to its caller.
+    fn exit(&mut self, _stack: &[Call<'a>], _rolled_back: bool) {
+        let Some(node) = self.stack.pop() else {
+            return;
+        };
+        match self.stack.last_mut() {
+            Some(caller) => caller.items.push(CallTreeItem::Call(node)),
+            None => self.calls.push(node),
+        }
+    }
+}
@@ -0,0 +1,1082 @@
+//! Instrumentation of Wasm modules with probes for recording coverage.
+//!
+//! A probe is inserted at the start of every function, at the start of every
+//! loop body, after the end of every block, and on both sides of every `if`
+//! and `br_if`. An `if` without an `else` gets an `else` with only a probe,
+//! and a `br_if` to a label without values becomes an `if` with a probe and a
+//! `br` in one branch and a probe in the other.
+//!
+//! A probe is a call to the `debug_print` host function with an empty message
+//! and file name, where the line is the id of the module and the column is
+//! the index of the probe. The calls are recorded in the debug trace of the
+//! execution, from where they are counted and removed. Modules that already
+//! import `debug_print` use the existing import. Otherwise it is added as the
+//! last imported function, and the indices of the functions defined in the
+//! module are shifted by one.
+//!
+//! The instrumentation works directly on the binary format. Custom sections
+//! are not included in the instrumented module, but the name section and the
+//! DWARF sections are returned for mapping the probes to functions and source
+//! lines.
+use crate::types::CoverageError;
+use std::collections::BTreeMap;
+
+/// The module of the host function called by the probes.
+const PROBE_IMPORT_MODULE: &[u8] = b"concordium";
+
+/// The name of the host function called by the probes.
+const PROBE_IMPORT_NAME: &[u8] = b"debug_print";
+
+/// The number of `i32` parameters of `debug_print`.
+const PROBE_PARAMETERS: usize = 6;
+
+/// The magic number and version that every module starts with.
+const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+/// The encoding of the value type `i32`.
+const I32: u8 = 0x7f;
+
+/// The block type of a block without results.
+const EMPTY_BLOCK_TYPE: u8 = 0x40;
+
+/// Section ids.
+const CUSTOM_SECTION: u8 = 0;
+const TYPE_SECTION: u8 = 1;
+const IMPORT_SECTION: u8 = 2;
+const FUNCTION_SECTION: u8 = 3;
+const EXPORT_SECTION: u8 = 7;
+const START_SECTION: u8 = 8;
+const ELEMENT_SECTION: u8 = 9;
+const CODE_SECTION: u8 = 10;
+
+/// Opcodes that are handled specially.
+const OP_BLOCK: u8 = 0x02;
+const OP_LOOP: u8 = 0x03;
+const OP_IF: u8 = 0x04;
+const OP_ELSE: u8 = 0x05;
+const OP_END: u8 = 0x0b;
+const OP_BR: u8 = 0x0c;
+const OP_BR_IF: u8 = 0x0d;
+const OP_CALL: u8 = 0x10;
+const OP_I32_CONST: u8 = 0x41;
+
+/// A function type with the encodings of the parameter and result types.
+type FunctionType = (Vec<u8>, Vec<u8>);
+
+/// A location in the code of a module that is counted by a probe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Probe {
+    /// The index of the function in the original module.
+    pub(crate) function: u32,
+    /// The offset of the instruction in the code section of the original
+    /// module, which is the address used by the DWARF debug information.
+    pub(crate) offset:   u32,
+    /// What the probe counts.
+    pub(crate) kind:     ProbeKind,
+}
+
+/// What a [`Probe`] counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ProbeKind {
+    /// Calls of the function.
+    Entry,
+    /// Executions of a basic block, i.e., a loop body or the code after a
+    /// block or a `br_if`.
+    Block,
+    /// Executions of one side of an `if` or `br_if`, where the offset of the
+    /// probe is the offset of the branch instruction. The side is `taken` for
+    /// the `then` branch of an `if` and for a `br_if` that branches.
+    Branch {
+        taken: bool,
+    },
+}
+
+/// A module with probes inserted.
+#[derive(Debug)]
+pub(crate) struct InstrumentedModule {
+    /// The binary of the instrumented module.
+    pub(crate) source:         Vec<u8>,
+    /// The probes, where the index of a probe is its column in the calls to
+    /// `debug_print`.
+    pub(crate) probes:         Vec<Probe>,
+    /// The names of the functions defined in the module by their index, from
+    /// the name section or the exports. Functions without a name are
+    /// omitted.
+    pub(crate) function_names: BTreeMap<u32, String>,
+    /// The custom sections with DWARF debug information by name, e.g.,
+    /// `.debug_line`.
+    pub(crate) debug_sections: BTreeMap<String, Vec<u8>>,
+}
+
+/// Insert probes into a module, where the probes use the `module_id` as the
+/// line in the calls to `debug_print`.
+pub(crate) fn instrument(
+    source: &[u8],
+    module_id: u32,
+) -> Result<InstrumentedModule, CoverageError> {
+    let sections = read_sections(source)?;
+    let section = |id| sections.iter().find(|section| section.id == id).map(|s| s.payload);
+
+    let types = match section(TYPE_SECTION) {
+        Some(payload) => read_types(payload)?,
+        None => Vec::new(),
+    };
+    let imports = match section(IMPORT_SECTION) {
+        Some(payload) => read_imports(payload)?,
+        None => Imports::default(),
+    };
+    let function_types = match section(FUNCTION_SECTION) {
+        Some(payload) => read_function_section(payload)?,
+        None => Vec::new(),
+    };
+
+    // Use the existing import of `debug_print`, or add it after the other
+    // imported functions.
+    let probe_type = (vec![I32; PROBE_PARAMETERS], Vec::new());
+    let (probe_function, added_type) = match imports.probe_function {
+        Some(index) => (index, None),
+        None => match types.iter().position(|ty| *ty == probe_type) {
+            Some(type_index) => (imports.functions, Some((type_index as u32, false))),
+            None => (imports.functions, Some((types.len() as u32, true))),
+        },
+    };
+    let shift = FunctionShift {
+        imported: imports.functions,
+        added:    added_type.is_some(),
+    };
+
+    let mut context = CodeContext {
+        module_id,
+        probe_function,
+        shift,
+        types: &types,
+        function_types: &function_types,
+        imported_functions: imports.functions,
+        probes: Vec::new(),
+    };
+
+    let mut function_names = BTreeMap::new();
+    let mut debug_sections = BTreeMap::new();
+    let mut out = HEADER.to_vec();
+    // The type and import sections that must be written, if any.
+    let mut pending_types = added_type;
+    let mut pending_import = added_type.map(|(type_index, _)| type_index);
+    for section in sections.iter() {
+        if section.id == CUSTOM_SECTION {
+            let mut reader = Reader::new(section.payload);
+            let name = reader.name()?;
+            let contents = reader.rest();
+            if name == b"name" {
+                read_function_names(contents, &mut function_names)?;
+            } else if let Some(name) =
+                std::str::from_utf8(name).ok().filter(|n| n.starts_with(".debug_"))
+            {
+                debug_sections.insert(name.to_string(), contents.to_vec());
+            }
+            continue;
+        }
+        // Add the type and import sections before the first section that
+        // comes after them, if the module does not have them.
+        let rank = section_rank(section.id);
+        if rank > section_rank(TYPE_SECTION) {
+            if let Some((_, true)) = pending_types.take() {
+                write_section(
+                    &mut out,
+                    TYPE_SECTION,
+                    &extend_vector(&[], 0, &type_entry(&probe_type)),
+                );
+            }
+        }
+        if rank > section_rank(IMPORT_SECTION) {
+            if let Some(type_index) = pending_import.take() {
+                write_section(
+                    &mut out,
+                    IMPORT_SECTION,
+                    &extend_vector(&[], 0, &probe_import(type_index)),
+                );
+            }
+        }
+        let payload = match section.id {
+            TYPE_SECTION => match pending_types.take() {
+                Some((_, true)) => {
+                    let (count, entries) = split_vector(section.payload)?;
+                    extend_vector(entries, count, &type_entry(&probe_type))
+                }
+                _ => section.payload.to_vec(),
+            },
+            IMPORT_SECTION => match pending_import.take() {
+                Some(type_index) => {
+                    let (count, entries) = split_vector(section.payload)?;
+                    extend_vector(entries, count, &probe_import(type_index))
+                }
+                None => section.payload.to_vec(),
+            },
+            EXPORT_SECTION => rewrite_exports(section.payload, shift, &mut function_names)?,
+            START_SECTION => {
+                let mut reader = Reader::new(section.payload);
+                let mut payload = Vec::new();
+                write_u32(&mut payload, shift.apply(reader.u32()?));
+                payload
+            }
+            ELEMENT_SECTION => rewrite_elements(section.payload, shift)?,
+            CODE_SECTION => instrument_code(section.payload, &mut context)?,
+            _ => section.payload.to_vec(),
+        };
+        write_section(&mut out, section.id, &payload);
+    }
+    if let Some((_, true)) = pending_types {
+        write_section(&mut out, TYPE_SECTION, &extend_vector(&[], 0, &type_entry(&probe_type)));
+    }
+    if let Some(type_index) = pending_import {
+        write_section(&mut out, IMPORT_SECTION, &extend_vector(&[], 0, &probe_import(type_index)));
+    }
+
+    // Only the names of the functions defined in the module are needed.
+    function_names.retain(|index, _| *index >= imports.functions);
+    Ok(InstrumentedModule {
+        source: out,
+        probes: context.probes,
+        function_names,
+        debug_sections,
+    })
+}
+
+/// A section of a module.
+struct Section<'a> {
+    id:      u8,
+    payload: &'a [u8],
+}
+
+/// Split a module into its sections.
+fn read_sections(source: &[u8]) -> Result<Vec<Section<'_>>, CoverageError> {
+    if !source.starts_with(&HEADER) {
+        return Err(CoverageError::Malformed("The module does not start with the Wasm header."));
+    }
+    let mut reader = Reader::new(&source[HEADER.len()..]);
+    let mut sections = Vec::new();
+    while !reader.is_empty() {
+        let id = reader.byte()?;
+        let size = reader.u32()? as usize;
+        let payload = reader.bytes(size)?;
+        sections.push(Section {
+            id,
+            payload,
+        });
+    }
+    Ok(sections)
+}
+
+/// The position of a section in the required order of the sections. The data
+/// count section, which has id 12, comes before the code section.
+fn section_rank(id: u8) -> u8 {
+    match id {
+        12 => 2 * CODE_SECTION - 1,
+        id => 2 * id,
+    }
+}
+
+/// Write a section with its id and size.
+fn write_section(out: &mut Vec<u8>, id: u8, payload: &[u8]) {
+    out.push(id);
+    write_u32(out, payload.len() as u32);
+    out.extend_from_slice(payload);
+}
+
+/// Split a vector into its length and the encoding of its elements.
+fn split_vector(payload: &[u8]) -> Result<(u32, &[u8]), CoverageError> {
+    let mut reader = Reader::new(payload);
+    let count = reader.u32()?;
+    Ok((count, reader.rest()))
+}
+
+/// Encode a vector with the given elements followed by a new element.
+fn extend_vector(entries: &[u8], count: u32, entry: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_u32(&mut out, count + 1);
+    out.extend_from_slice(entries);
+    out.extend_from_slice(entry);
+    out
+}
+
+/// Encode a function type.
+fn type_entry((parameters, results): &FunctionType) -> Vec<u8> {
+    let mut out = vec![0x60];
+    write_u32(&mut out, parameters.len() as u32);
+    out.extend_from_slice(parameters);
+    write_u32(&mut out, results.len() as u32);
+    out.extend_from_slice(results);
+    out
+}
+
+/// Encode the import of `debug_print` with the given type.
+fn probe_import(type_index: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    for name in [PROBE_IMPORT_MODULE, PROBE_IMPORT_NAME] {
+        write_u32(&mut out, name.len() as u32);
+        out.extend_from_slice(name);
+    }
+    out.push(0x00);
+    write_u32(&mut out, type_index);
+    out
+}
+
+/// Read the function types, as pairs of parameter and result types.
+fn read_types(payload: &[u8]) -> Result<Vec<FunctionType>, CoverageError> {
+    let mut reader = Reader::new(payload);
+    let count = reader.u32()?;
+    let mut types = Vec::new();
+    for _ in 0..count {
+        if reader.byte()? != 0x60 {
+            return Err(CoverageError::Malformed("Invalid function type."));
+        }
+        let parameters = reader.name()?.to_vec();
+        let results = reader.name()?.to_vec();
+        types.push((parameters, results));
+    }
+    Ok(types)
+}
+
+/// The imports of a module that are relevant for the instrumentation.
+#[derive(Default)]
+struct Imports {
+    /// The number of imported functions.
+    functions:      u32,
+    /// The index of `debug_print`, if it is imported.
+    probe_function: Option<u32>,
+}
+
+/// Read the imports.
+fn read_imports(payload: &[u8]) -> Result<Imports, CoverageError> {
+    let mut reader = Reader::new(payload);
+    let count = reader.u32()?;
+    let mut imports = Imports::default();
+    for _ in 0..count {
+        let module = reader.name()?;
+        let name = reader.name()?;
+        match reader.byte()? {
+            // A function with its type index.
+            0x00 => {
+                reader.u32()?;
+                if module == PROBE_IMPORT_MODULE && name == PROBE_IMPORT_NAME {
+                    imports.probe_function = Some(imports.functions);
+                }
+                imports.functions += 1;
+            }
+            // A table with its element type and limits.
+            0x01 => {
+                reader.byte()?;
+                read_limits(&mut reader)?;
+            }
+            // A memory with its limits.
+            0x02 => read_limits(&mut reader)?,
+            // A global with its value type and mutability.
+            0x03 => {
+                reader.bytes(2)?;
+            }
+            _ => return Err(CoverageError::Malformed("Invalid import.")),
+        }
+    }
+    Ok(imports)
+}
+
+/// Read the limits of a table or memory.
+fn read_limits(reader: &mut Reader) -> Result<(), CoverageError> {
+    match reader.byte()? {
+        0x00 => {
+            reader.u32()?;
+        }
+        0x01 => {
+            reader.u32()?;
+            reader.u32()?;
+        }
+        _ => return Err(CoverageError::Malformed("Invalid limits.")),
+    }
+    Ok(())
+}
+
+/// Read the type indices of the functions defined in the module.
+fn read_function_section(payload: &[u8]) -> Result<Vec<u32>, CoverageError> {
+    let mut reader = Reader::new(payload);
+    let count = reader.u32()?;
+    (0..count).map(|_| reader.u32()).collect()
+}
+
+/// Read the function names subsection of the name section.
+fn read_function_names(
+    contents: &[u8],
+    names: &mut BTreeMap<u32, String>,
+) -> Result<(), CoverageError> {
+    let mut reader = Reader::new(contents);
+    while !reader.is_empty() {
+        let id = reader.byte()?;
+        let size = reader.u32()? as usize;
+        let subsection = reader.bytes(size)?;
+        if id != 1 {
+            continue;
+        }
+        let mut reader = Reader::new(subsection);
+        let count = reader.u32()?;
+        for _ in 0..count {
+            let index = reader.u32()?;
+            let name = String::from_utf8_lossy(reader.name()?);
+            names.insert(index, format!("{:#}", rustc_demangle::demangle(&name)));
+        }
+    }
+    Ok(())
+}
+
+/// How the indices of functions change when `debug_print` is added as an
+/// import.
+#[derive(Debug, Clone, Copy)]
+struct FunctionShift {
+    /// The number of imported functions in the original module.
+    imported: u32,
+    /// Whether `debug_print` was added after the imported functions.
+    added:    bool,
+}
+
+impl FunctionShift {
+    /// Get the index in the instrumented module of a function in the original
+    /// module.
+    fn apply(self, index: u32) -> u32 {
+        if self.added && index >= self.imported {
+            index + 1
+        } else {
+            index
+        }
+    }
+}
+
+/// Rewrite the function indices of the exports, and add the names of the
+/// exported functions that have no name in the name section.
+fn rewrite_exports(
+    payload: &[u8],
+    shift: FunctionShift,
+    names: &mut BTreeMap<u32, String>,
+) -> Result<Vec<u8>, CoverageError> {
+    let mut reader = Reader::new(payload);
+    let count = reader.u32()?;
+    let mut out = Vec::new();
+    write_u32(&mut out, count);
+    let mut export_names = BTreeMap::new();
+    for _ in 0..count {
+        let name = reader.name()?;
+        let kind = reader.byte()?;
+        let mut index = reader.u32()?;
+        write_u32(&mut out, name.len() as u32);
+        out.extend_from_slice(name);
+        out.push(kind);
+        if kind == 0x00 {
+            export_names.insert(index, String::from_utf8_lossy(name).into_owned());
+            index = shift.apply(index);
+        }
+        write_u32(&mut out, index);
+    }
+    // The name section comes after the exports, so the names are added once
+    // all sections have been read.
+    for (index, name) in export_names {
+        names.entry(index).or_insert(name);
+    }
+    Ok(out)
+}
+
+/// Rewrite the function indices of the element segments. Only the segments
+/// of Wasm 1.0 are supported.
+fn rewrite_elements(payload: &[u8], shift: FunctionShift) -> Result<Vec<u8>, CoverageError> {
+    let mut reader = Reader::new(payload);
+    let count = reader.u32()?;
+    let mut out = Vec::new();
+    write_u32(&mut out, count);
+    for _ in 0..count {
+        if reader.u32()? != 0 {
+            return Err(CoverageError::Malformed("Only active element segments are supported."));
+        }
+        out.push(0x00);
+        // The offset is a constant expression.
+        let start = reader.position;
+        loop {
+            match reader.byte()? {
+                OP_END => break,
+                OP_I32_CONST | 0x42 => reader.skip_leb()?,
+                0x23 => {
+                    reader.u32()?;
+                }
+                _ => return Err(CoverageError::Malformed("Invalid element segment offset.")),
+            }
+        }
+        out.extend_from_slice(&reader.bytes[start..reader.position]);
+        let functions = reader.u32()?;
+        write_u32(&mut out, functions);
+        for _ in 0..functions {
+            write_u32(&mut out, shift.apply(reader.u32()?));
+        }
+    }
+    Ok(out)
+}
+
+/// The information needed for instrumenting the code section.
+struct CodeContext<'a> {
+    /// The id of the module, used as the line of the probes.
+    module_id:          u32,
+    /// The index of `debug_print` in the instrumented module.
+    probe_function:     u32,
+    /// How function indices change.
+    shift:              FunctionShift,
+    /// The function types of the module.
+    types:              &'a [FunctionType],
+    /// The type indices of the functions defined in the module.
+    function_types:     &'a [u32],
+    /// The number of imported functions in the original module.
+    imported_functions: u32,
+    /// The probes inserted so far.
+    probes:             Vec<Probe>,
+}
+
+impl CodeContext<'_> {
+    /// Add a probe and write the call to `debug_print` for it.
+    fn probe(&mut self, out: &mut Vec<u8>, function: u32, offset: usize, kind: ProbeKind) {
+        let index = self.probes.len() as u32;
+        self.probes.push(Probe {
+            function,
+            offset: offset as u32,
+            kind,
+        });
+        // The message and file name are empty.
+        for _ in 0..4 {
+            out.extend_from_slice(&[OP_I32_CONST, 0x00]);
+        }
+        for value in [self.module_id, index] {
+            out.push(OP_I32_CONST);
+            write_i32(out, value as i32);
+        }
+        out.push(OP_CALL);
+        write_u32(out, self.probe_function);
+    }
+}
+
+/// A block of code that can be the target of a branch.
+enum Frame {
+    /// The body of the function with the number of results.
+    Function {
+        arity: u32,
+    },
+    /// A `block` with the number of results.
+    Block {
+        arity: u32,
+    },
+    /// A `loop`. A branch to a loop takes no values.
+    Loop,
+    /// An `if` with the number of results, its offset, and whether its `else`
+    /// has been seen.
+    If {
+        arity:    u32,
+        offset:   usize,
+        has_else: bool,
+    },
+}
+
+impl Frame {
+    /// The number of values taken by a branch to the frame.
+    fn label_arity(&self) -> u32 {
+        match self {
+            Frame::Function {
+                arity,
+            }
+            | Frame::Block {
+                arity,
+            }
+            | Frame::If {
+                arity,
+                ..
+            } => *arity,
+            Frame::Loop => 0,
+        }
+    }
+}
+
+/// Insert probes into the function bodies of the code section. The offsets of
+/// the probes are relative to the start of the section payload.
+fn instrument_code(payload: &[u8], context: &mut CodeContext) -> Result<Vec<u8>, CoverageError> {
+    let mut reader = Reader::new(payload);
+    let count = reader.u32()?;
+    if count as usize != context.function_types.len() {
+        return Err(CoverageError::Malformed("The number of function bodies is wrong."));
+    }
+    let mut out = Vec::new();
+    write_u32(&mut out, count);
+    for defined_index in 0..count {
+        let size = reader.u32()? as usize;
+        let start = reader.position;
+        reader.bytes(size)?;
+        let mut body_reader = Reader {
+            bytes:    &payload[..start + size],
+            position: start,
+        };
+        let function = context.imported_functions + defined_index;
+        let arity = context
+            .function_types
+            .get(defined_index as usize)
+            .and_then(|type_index| context.types.get(*type_index as usize))
+            .map(|(_, results)| results.len() as u32)
+            .ok_or(CoverageError::Malformed("The type of a function does not exist."))?;
+        let body = instrument_body(&mut body_reader, context, function, arity)?;
+        write_u32(&mut out, body.len() as u32);
+        out.extend_from_slice(&body);
+    }
+    Ok(out)
+}
+
+/// Insert probes into a function body, which starts with the declaration of
+/// the locals.
+fn instrument_body(
+    reader: &mut Reader,
+    context: &mut CodeContext,
+    function: u32,
+    arity: u32,
+) -> Result<Vec<u8>, CoverageError> {
+    let mut out = Vec::new();
+    let locals_start = reader.position;
+    let local_groups = reader.u32()?;
+    for _ in 0..local_groups {
+        reader.u32()?;
+        reader.byte()?;
+    }
+    out.extend_from_slice(&reader.bytes[locals_start..reader.position]);
+
+    let mut frames = vec![Frame::Function {
+        arity,
+    }];
+    context.probe(&mut out, function, reader.position, ProbeKind::Entry);
+    loop {
+        let offset = reader.position;
+        let opcode = reader.byte()?;
+        match opcode {
+            OP_BLOCK | OP_LOOP | OP_IF => {
+                let block_type = reader.byte()?;
+                let arity = match block_type {
+                    EMPTY_BLOCK_TYPE => 0,
+                    0x7c..=0x7f => 1,
+                    _ => {
+                        return Err(CoverageError::UnsupportedInstruction {
+                            opcode,
+                            offset,
+                        })
+                    }
+                };
+                out.extend_from_slice(&[opcode, block_type]);
+                match opcode {
+                    OP_BLOCK => frames.push(Frame::Block {
+                        arity,
+                    }),
+                    OP_LOOP => {
+                        frames.push(Frame::Loop);
+                        context.probe(&mut out, function, offset, ProbeKind::Block);
+                    }
+                    _ => {
+                        frames.push(Frame::If {
+                            arity,
+                            offset,
+                            has_else: false,
+                        });
+                        context.probe(&mut out, function, offset, ProbeKind::Branch {
+                            taken: true,
+                        });
+                    }
+                }
+            }
+            OP_ELSE => {
+                let Some(Frame::If {
+                    offset: if_offset,
+                    has_else,
+                    ..
+                }) = frames.last_mut()
+                else {
+                    return Err(CoverageError::Malformed("An else is not in an if."));
+                };
+                *has_else = true;
+                let if_offset = *if_offset;
+                out.push(OP_ELSE);
+                context.probe(&mut out, function, if_offset, ProbeKind::Branch {
+                    taken: false,
+                });
+            }
+            OP_END => {
+                match frames.pop() {
+                    // Count the executions where the condition is false.
+                    Some(Frame::If {
+                        offset: if_offset,
+                        has_else: false,
+                        ..
+                    }) => {
+                        out.push(OP_ELSE);
+                        context.probe(&mut out, function, if_offset, ProbeKind::Branch {
+                            taken: false,
+                        });
+                    }
+                    Some(_) => {}
+                    None => return Err(CoverageError::Malformed("An end is not in a block.")),
+                }
+                out.push(OP_END);
+                if frames.is_empty() {
+                    if !reader.is_empty() {
+                        return Err(CoverageError::Malformed("Code after the end of a function."));
+                    }
+                    return Ok(out);
+                }
+                context.probe(&mut out, function, reader.position, ProbeKind::Block);
+            }
+            OP_BR_IF => {
+                let depth = reader.u32()?;
+                let label_arity = frames
+                    .len()
+                    .checked_sub(depth as usize + 1)
+                    .and_then(|index| frames.get(index))
+                    .map(Frame::label_arity)
+                    .ok_or(CoverageError::Malformed("A branch to a label that does not exist."))?;
+                if label_arity == 0 {
+                    // The branch is moved into an `if`, which adds a label.
+                    out.extend_from_slice(&[OP_IF, EMPTY_BLOCK_TYPE]);
+                    context.probe(&mut out, function, offset, ProbeKind::Branch {
+                        taken: true,
+                    });
+                    out.push(OP_BR);
+                    write_u32(&mut out, depth + 1);
+                    out.push(OP_ELSE);
+                    context.probe(&mut out, function, offset, ProbeKind::Branch {
+                        taken: false,
+                    });
+                    out.push(OP_END);
+                } else {
+                    // The values for the label cannot be passed into an `if`
+                    // in Wasm 1.0, so only the code after the branch is counted.
+                    out.push(OP_BR_IF);
+                    write_u32(&mut out, depth);
+                    context.probe(&mut out, function, reader.position, ProbeKind::Block);
+                }
+            }
+            OP_CALL => {
+                let index = reader.u32()?;
+                out.push(OP_CALL);
+                write_u32(&mut out, context.shift.apply(index));
+            }
+            _ => {
+                skip_immediates(reader, opcode, offset)?;
+                out.extend_from_slice(&reader.bytes[offset..reader.position]);
+            }
+        }
+    }
+}
+
+/// Skip the immediates of an instruction that is copied unchanged.
+fn skip_immediates(reader: &mut Reader, opcode: u8, offset: usize) -> Result<(), CoverageError> {
+    match opcode {
+        // unreachable, nop, return, drop, select and the numeric instructions,
+        // including the sign extension instructions.
+        0x00 | 0x01 | 0x0f | 0x1a | 0x1b | 0x45..=0xc4 => {}
+        // br and the variable instructions.
+        OP_BR | 0x20..=0x24 => {
+            reader.u32()?;
+        }
+        // br_table with the labels and the default label.
+        0x0e => {
+            let labels = reader.u32()?;
+            for _ in 0..=labels {
+                reader.u32()?;
+            }
+        }
+        // call_indirect with the type index and the table index.
+        0x11 => {
+            reader.u32()?;
+            reader.byte()?;
+        }
+        // Loads and stores with the alignment and offset.
+        0x28..=0x3e => {
+            reader.u32()?;
+            reader.u32()?;
+        }
+        // memory.size and memory.grow with the memory index.
+        0x3f | 0x40 => {
+            reader.byte()?;
+        }
+        // i32.const and i64.const.
+        OP_I32_CONST | 0x42 => reader.skip_leb()?,
+        // f32.const and f64.const.
+        0x43 => {
+            reader.bytes(4)?;
+        }
+        0x44 => {
+            reader.bytes(8)?;
+        }
+        _ => {
+            return Err(CoverageError::UnsupportedInstruction {
+                opcode,
+                offset,
+            })
+        }
+    }
+    Ok(())
+}
+
+/// A reader of the binary format.
+struct Reader<'a> {
+    bytes:    &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Read the bytes from the start.
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            position: 0,
+        }
+    }
+
+    /// Whether all bytes have been read.
+    fn is_empty(&self) -> bool { self.position >= self.bytes.len() }
+
+    /// The bytes that have not been read.
+    fn rest(&self) -> &'a [u8] { &self.bytes[self.position.min(self.bytes.len())..] }
+
+    /// Read a byte.
+    fn byte(&mut self) -> Result<u8, CoverageError> { Ok(self.bytes(1)?[0]) }
+
+    /// Read a number of bytes.
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], CoverageError> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(CoverageError::Malformed("Unexpected end of the module."))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    /// Read a name or another vector of bytes.
+    fn name(&mut self) -> Result<&'a [u8], CoverageError> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    /// Read an unsigned LEB128 encoded `u32`.
+    fn u32(&mut self) -> Result<u32, CoverageError> {
+        let mut value = 0u64;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return u32::try_from(value)
+                    .map_err(|_| CoverageError::Malformed("An integer is too large."));
+            }
+        }
+        Err(CoverageError::Malformed("An integer is too long."))
+    }
+
+    /// Skip a signed LEB128 encoded integer of at most 64 bits.
+    fn skip_leb(&mut self) -> Result<(), CoverageError> {
+        for _ in 0..10 {
+            if self.byte()? & 0x80 == 0 {
+                return Ok(());
+            }
+        }
+        Err(CoverageError::Malformed("An integer is too long."))
+    }
+}
+
+/// Write an unsigned LEB128 encoded `u32`.
+fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Write a signed LEB128 encoded `i32`.
+fn write_i32(out: &mut Vec<u8>, mut value: i32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A module with one function that counts down from its parameter in a
+    /// loop, with an `if` without an `else` and a `br_if`. The function is
+    /// exported as `count` and is called from the start function.
+    ///
+    /// ```wat
+    /// (module
+    ///   (func $count (param i32)
+    ///     (block
+    ///       (loop
+    ///         (br_if 1 (i32.eqz (local.get 0)))
+    ///         (if (i32.eq (local.get 0) (i32.const 2)) (then nop))
+    ///         (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+    ///         (br 0))))
+    ///   (func $start (call $count (i32.const 3)))
+    ///   (export "count" (func $count))
+    ///   (start $start))
+    /// ```
+    fn test_module() -> Vec<u8> {
+        let mut module = HEADER.to_vec();
+        let types = [0x02, 0x60, 0x01, I32, 0x00, 0x60, 0x00, 0x00];
+        write_section(&mut module, TYPE_SECTION, &types);
+        write_section(&mut module, FUNCTION_SECTION, &[0x02, 0x00, 0x01]);
+        write_section(&mut module, EXPORT_SECTION, &[
+            0x01, 0x05, b'c', b'o', b'u', b'n', b't', 0x00, 0x00,
+        ]);
+        write_section(&mut module, START_SECTION, &[0x01]);
+        let count = [
+            0x00, // No locals.
+            OP_BLOCK,
+            EMPTY_BLOCK_TYPE,
+            OP_LOOP,
+            EMPTY_BLOCK_TYPE,
+            0x20,
+            0x00,
+            0x45,
+            OP_BR_IF,
+            0x01,
+            0x20,
+            0x00,
+            OP_I32_CONST,
+            0x02,
+            0x46,
+            OP_IF,
+            EMPTY_BLOCK_TYPE,
+            0x01,
+            OP_END,
+            0x20,
+            0x00,
+            OP_I32_CONST,
+            0x01,
+            0x6b,
+            0x21,
+            0x00,
+            OP_BR,
+            0x00,
+            OP_END,
+            OP_END,
+            OP_END,
+        ];
+        let start = [0x00, OP_I32_CONST, 0x03, OP_CALL, 0x00, OP_END];
+        let mut code = vec![0x02];
+        for body in [&count[..], &start[..]] {
+            write_u32(&mut code, body.len() as u32);
+            code.extend_from_slice(body);
+        }
+        write_section(&mut module, CODE_SECTION, &code);
+        module
+    }
+
+    /// Test that the import is added, the function indices are shifted, and
+    /// the probes are placed at the expected instructions.
+    #[test]
+    fn test_instrument() {
+        let module = test_module();
+        let instrumented = instrument(&module, 7).expect("The module can be instrumented");
+        let sections = read_sections(&instrumented.source).expect("The output is a module");
+        let ids: Vec<_> = sections.iter().map(|section| section.id).collect();
+        assert_eq!(ids, [
+            TYPE_SECTION,
+            IMPORT_SECTION,
+            FUNCTION_SECTION,
+            EXPORT_SECTION,
+            START_SECTION,
+            CODE_SECTION
+        ]);
+        let section = |id| sections.iter().find(|s| s.id == id).unwrap().payload;
+        let types = read_types(section(TYPE_SECTION)).unwrap();
+        assert_eq!(types[2], (vec![I32; PROBE_PARAMETERS], Vec::new()));
+        let imports = read_imports(section(IMPORT_SECTION)).unwrap();
+        assert_eq!(imports.probe_function, Some(0));
+        assert_eq!(section(EXPORT_SECTION).last(), Some(&0x01));
+        assert_eq!(section(START_SECTION), [0x02]);
+
+        // The code section starts at offset 0 with the number of bodies and
+        // the size of the first body, so the first instruction is at offset 3.
+        let kinds: Vec<_> = instrumented
+            .probes
+            .iter()
+            .map(|probe| (probe.function, probe.offset, probe.kind))
+            .collect();
+        let branch = |taken| ProbeKind::Branch {
+            taken,
+        };
+        assert_eq!(kinds, [
+            (0, 3, ProbeKind::Entry),
+            (0, 5, ProbeKind::Block),
+            (0, 10, branch(true)),
+            (0, 10, branch(false)),
+            (0, 17, branch(true)),
+            (0, 17, branch(false)),
+            (0, 21, ProbeKind::Block),
+            (0, 31, ProbeKind::Block),
+            (0, 32, ProbeKind::Block),
+            (1, 35, ProbeKind::Entry),
+        ]);
+        assert_eq!(instrumented.function_names, BTreeMap::from([(0, "count".to_string())]));
+    }
+
+    /// Test that an existing import of `debug_print` is used, so the function
+    /// indices are unchanged.
+    #[test]
+    fn test_instrument_with_debug_print() {
+        let mut module = HEADER.to_vec();
+        write_section(&mut module, TYPE_SECTION, &type_entry_vector());
+        let mut imports = vec![0x01];
+        imports.extend_from_slice(&probe_import(0)[..]);
+        write_section(&mut module, IMPORT_SECTION, &imports);
+        write_section(&mut module, FUNCTION_SECTION, &[0x01, 0x01]);
+        write_section(&mut module, CODE_SECTION, &[0x01, 0x04, 0x00, OP_CALL, 0x01, OP_END]);
+        let instrumented = instrument(&module, 0).expect("The module can be instrumented");
+        let sections = read_sections(&instrumented.source).unwrap();
+        assert_eq!(sections[0].payload, type_entry_vector());
+        assert_eq!(sections[1].payload, imports);
+        // The call to the function itself is unchanged, and the probe calls
+        // the existing import.
+        let code = sections[3].payload;
+        assert!(code.ends_with(&[OP_CALL, 0x00, OP_CALL, 0x01, OP_END]));
+        assert_eq!(instrumented.probes.len(), 1);
+    }
+
+    /// The type section with the type of `debug_print` and a function type
+    /// without parameters and results.
+    fn type_entry_vector() -> Vec<u8> {
+        let mut types = vec![0x02];
+        types.extend(type_entry(&(vec![I32; PROBE_PARAMETERS], Vec::new())));
+        types.extend(type_entry(&(Vec::new(), Vec::new())));
+        types
+    }
+
+    /// Test the encoding of signed integers.
+    #[test]
+    fn test_write_i32() {
+        for (value, expected) in [
+            (0, &[0x00][..]),
+            (63, &[0x3f]),
+            (64, &[0xc0, 0x00]),
+            (-1, &[0x7f]),
+            (-65, &[0xbf, 0x7f]),
+        ] {
+            let mut out = Vec::new();
+            write_i32(&mut out, value);
+            assert_eq!(out, expected, "Encoding {value}");
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! Mapping of code offsets to source lines with the DWARF debug information
+//! of a module.
+//!
+//! Modules built with debug information, e.g., with `cargo concordium build
+//! --out-dir-debug`, have the DWARF sections as custom sections. The addresses
+//! in the line tables are offsets in the code section, starting at the
+//! payload of the section.
+use crate::types::SourceLocation;
+use gimli::{EndianSlice, LittleEndian, Reader};
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// A line table of a module, for looking up the source line of an offset in
+/// the code section.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LineTable {
+    /// The source files referenced by the rows.
+    files: Vec<PathBuf>,
+    /// The rows of all the sequences, sorted by address. A row without a
+    /// location ends a sequence.
+    rows:  Vec<Row>,
+}
+
+/// A row of a [`LineTable`].
+#[derive(Debug, Clone, Copy)]
+struct Row {
+    /// The first address of the code of the row.
+    address:  u64,
+    /// The index of the file and the line, unless the row ends a sequence.
+    location: Option<(usize, u32)>,
+}
+
+impl LineTable {
+    /// Read the line table from the DWARF sections of a module. Returns `None`
+    /// if the module has no line information.
+    pub(crate) fn from_sections(sections: &BTreeMap<String, Vec<u8>>) -> Option<Self> {
+        if !sections.contains_key(".debug_line") {
+            return None;
+        }
+        // The line table is only used for reporting, so it is omitted if the
+        // debug information is malformed.
+        Self::read(sections).ok().filter(|table| !table.rows.is_empty())
+    }
+
+    /// Read the line programs of all the compilation units.
+    fn read(sections: &BTreeMap<String, Vec<u8>>) -> gimli::Result<Self> {
+        let load = |id: gimli::SectionId| -> gimli::Result<EndianSlice<LittleEndian>> {
+            let data = sections.get(id.name()).map_or(&[][..], Vec::as_slice);
+            Ok(EndianSlice::new(data, LittleEndian))
+        };
+        let dwarf = gimli::Dwarf::load(load)?;
+        let mut table = Self::default();
+        let mut file_indices = BTreeMap::new();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let Some(program) = unit.line_program.clone() else {
+                continue;
+            };
+            let mut sequence = Vec::new();
+            let mut rows = program.rows();
+            while let Some((header, row)) = rows.next_row()? {
+                if row.end_sequence() {
+                    // Sequences of code removed by the linker start at address
+                    // `0` or at a tombstone address close to `u32::MAX`.
+                    let start = sequence.first().map_or(0, |row: &Row| row.address);
+                    if start != 0 && start < 0xffff_fff0 {
+                        table.rows.append(&mut sequence);
+                        table.rows.push(Row {
+                            address:  row.address(),
+                            location: None,
+                        });
+                    }
+                    sequence.clear();
+                    continue;
+                }
+                let Some(line) = row.line() else {
+                    continue;
+                };
+                let Some(file) = row.file(header) else {
+                    continue;
+                };
+                let path = file_path(&dwarf, &unit, header, file)?;
+                let next_index = table.files.len();
+                let file_index = *file_indices.entry(path.clone()).or_insert_with(|| {
+                    table.files.push(path);
+                    next_index
+                });
+                sequence.push(Row {
+                    address:  row.address(),
+                    location: Some((file_index, u32::try_from(line.get()).unwrap_or(u32::MAX))),
+                });
+            }
+        }
+        // A sequence can start at the address where another ends, in which case
+        // the start of the sequence must come last.
+        table.rows.sort_by_key(|row| (row.address, row.location.is_some()));
+        Ok(table)
+    }
+
+    /// Get the source location of the code at an offset in the code section.
+    pub(crate) fn lookup(&self, offset: u32) -> Option<SourceLocation> {
+        let index = self.rows.partition_point(|row| row.address <= u64::from(offset));
+        let (file, line) = self.rows.get(index.checked_sub(1)?)?.location?;
+        Some(SourceLocation {
+            file: self.files[file].clone(),
+            line,
+        })
+    }
+}
+
+/// Get the path of a file in a line program, including the directory of the
+/// file and the compilation directory if the path is relative.
+fn file_path<R: Reader>(
+    dwarf: &gimli::Dwarf<R>,
+    unit: &gimli::Unit<R>,
+    header: &gimli::LineProgramHeader<R>,
+    file: &gimli::FileEntry<R>,
+) -> gimli::Result<PathBuf> {
+    let mut path = PathBuf::new();
+    if let Some(comp_dir) = &unit.comp_dir {
+        path.push(comp_dir.to_string_lossy()?.as_ref());
+    }
+    if let Some(directory) = file.directory(header) {
+        path.push(dwarf.attr_string(unit, directory)?.to_string_lossy()?.as_ref());
+    }
+    path.push(dwarf.attr_string(unit, file.path_name())?.to_string_lossy()?.as_ref());
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gimli::write::{
+        Address, AttributeValue, DwarfUnit, EndianVec, LineProgram, LineString, Sections,
+    };
+
+    /// Write the DWARF sections of a unit with a line program for `src/lib.rs`
+    /// with a sequence from `0x10` to `0x20`, and a sequence of removed code
+    /// at address `0`.
+    fn debug_sections() -> BTreeMap<String, Vec<u8>> {
+        let encoding = gimli::Encoding {
+            format:       gimli::Format::Dwarf32,
+            version:      4,
+            address_size: 4,
+        };
+        let mut dwarf = DwarfUnit::new(encoding);
+        let mut program = LineProgram::new(
+            encoding,
+            gimli::LineEncoding::default(),
+            LineString::String(b"/contract".to_vec()),
+            LineString::String(b"src/lib.rs".to_vec()),
+            None,
+        );
+        let directory = program.add_directory(LineString::String(b"src".to_vec()));
+        let file = program.add_file(LineString::String(b"lib.rs".to_vec()), directory, None);
+        for (start, rows, end) in [(0x10, [(0, 3), (8, 5)], 0x10), (0, [(0, 9), (4, 10)], 8)] {
+            program.begin_sequence(Some(Address::Constant(start)));
+            for (address_offset, line) in rows {
+                program.row().file = file;
+                program.row().address_offset = address_offset;
+                program.row().line = line;
+                program.generate_row();
+            }
+            program.end_sequence(end);
+        }
+        dwarf.unit.line_program = program;
+        let root = dwarf.unit.root();
+        dwarf
+            .unit
+            .get_mut(root)
+            .set(gimli::DW_AT_comp_dir, AttributeValue::String(b"/contract".to_vec()));
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        dwarf.write(&mut sections).expect("The DWARF sections can be written");
+        let mut out = BTreeMap::new();
+        sections
+            .for_each(|id, data| {
+                if !data.slice().is_empty() {
+                    out.insert(id.name().to_string(), data.slice().to_vec());
+                }
+                Ok::<_, ()>(())
+            })
+            .expect("Collecting the sections does not fail");
+        out
+    }
+
+    /// Test that offsets are mapped to the line of the row they belong to,
+    /// and that the removed code and the code outside the sequences have no
+    /// line.
+    #[test]
+    fn test_lookup() {
+        let table = LineTable::from_sections(&debug_sections()).expect("The table has lines");
+        let location = |line| {
+            Some(SourceLocation {
+                file: PathBuf::from("/contract/src/lib.rs"),
+                line,
+            })
+        };
+        assert_eq!(table.lookup(0x04), None);
+        assert_eq!(table.lookup(0x10), location(3));
+        assert_eq!(table.lookup(0x17), location(3));
+        assert_eq!(table.lookup(0x18), location(5));
+        assert_eq!(table.lookup(0x1f), location(5));
+        assert_eq!(table.lookup(0x20), None);
+    }
+
+    /// Test that a module without line information has no table.
+    #[test]
+    fn test_no_debug_information() {
+        assert!(LineTable::from_sections(&BTreeMap::new()).is_none());
+    }
+}
@@ -0,0 +1,591 @@
+//! Code coverage of contract modules.
+//!
+//! When coverage is enabled, modules are instrumented with probes the first
+//! time they are executed, see [`instrument`], and the instrumented artifact
+//! is executed instead of the deployed one. The probes report their execution
+//! as `debug_print` calls, which are counted and removed from the debug traces
+//! of the results, so they are not visible to the tests.
+//!
+//! The counts are mapped to functions by the name section and exports of the
+//! modules, and to source lines by their DWARF debug information, if any.
+use crate::types::*;
+use concordium_rust_sdk::{
+    base::contracts_common::ModuleReference,
+    smart_contracts::engine::{
+        v1::{self, DebugTracker, HostFunctionV1},
+        wasm::{self, artifact, validate::ValidationConfig},
+    },
+};
+use instrument::{Probe, ProbeKind};
+use lines::LineTable;
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+mod instrument;
+mod lines;
+
+/// The instrumented modules of a [`Chain`] and the counts of their probes.
+#[derive(Debug, Default)]
+pub(crate) struct CoverageState {
+    /// The modules in the order they were instrumented. The index of a module
+    /// is its id in the probes.
+    modules: Vec<CoveredModule>,
+    /// The ids of the modules.
+    ids:     BTreeMap<ModuleReference, u32>,
+}
+
+/// A module that was instrumented, or could not be.
+#[derive(Debug)]
+struct CoveredModule {
+    instrumented: Result<InstrumentedArtifact, CoverageError>,
+}
+
+/// An instrumented module with the counts of its probes.
+#[derive(Debug)]
+struct InstrumentedArtifact {
+    /// The artifact that is executed instead of the deployed one.
+    artifact:       Arc<artifact::Artifact<v1::ProcessedImports, artifact::CompiledFunction>>,
+    probes:         Vec<Probe>,
+    function_names: BTreeMap<u32, String>,
+    lines:          Option<LineTable>,
+    /// The number of executions of each probe.
+    counts:         Vec<u64>,
+}
+
+impl Chain {
+    /// Start recording code coverage of the deployed modules. Any coverage
+    /// recorded so far is discarded.
+    ///
+    /// While coverage is enabled, modules are executed with probes that count
+    /// the calls of each function, the executions of each basic block and the
+    /// outcomes of each branch. Inits, updates and invocations are recorded,
+    /// including the code of contracts that are called by other contracts and
+    /// executions that fail or are rolled back.
+    ///
+    /// The probes use energy, so the energy used and the transaction fees are
+    /// higher than without coverage. Tests that check the exact energy used
+    /// should not enable coverage.
+    pub fn coverage_enable(&mut self) {
+        self.coverage = Some(RefCell::new(CoverageState::default()));
+    }
+
+    /// Stop recording coverage and return the coverage recorded, if coverage
+    /// was enabled.
+    pub fn coverage_disable(&mut self) -> Option<Coverage> {
+        let coverage = self.coverage();
+        self.coverage = None;
+        coverage
+    }
+
+    /// Get the coverage recorded since [`Chain::coverage_enable`] was called,
+    /// or `None` if coverage is not enabled.
+    ///
+    /// The coverage includes all the modules currently deployed, so modules
+    /// that were never executed are included with the count `0` for all their
+    /// functions.
+    pub fn coverage(&self) -> Option<Coverage> {
+        let mut state = self.coverage.as_ref()?.borrow_mut();
+        let modules = self
+            .modules
+            .iter()
+            .map(|(module_reference, module)| {
+                let id = state.instrument(*module_reference, module);
+                (*module_reference, state.modules[id as usize].report())
+            })
+            .collect();
+        Some(Coverage {
+            modules,
+        })
+    }
+
+    /// Get the module to execute for a deployed module, which is the
+    /// instrumented module if coverage is enabled and the module could be
+    /// instrumented.
+    pub(crate) fn coverage_artifact(
+        &self,
+        module_reference: ModuleReference,
+        module: &ContractModule,
+    ) -> ContractModule {
+        let Some(coverage) = &self.coverage else {
+            return module.clone();
+        };
+        let mut state = coverage.borrow_mut();
+        let id = state.instrument(module_reference, module);
+        match &state.modules[id as usize].instrumented {
+            Ok(instrumented) => ContractModule {
+                artifact: instrumented.artifact.clone(),
+                ..module.clone()
+            },
+            Err(_) => module.clone(),
+        }
+    }
+
+    /// Count the probes executed in an init and remove them from the debug
+    /// trace, if coverage is enabled.
+    pub(crate) fn coverage_record_init(
+        &self,
+        result: &mut Result<ContractInitSuccess, ContractInitError>,
+    ) {
+        let Some(coverage) = &self.coverage else {
+            return;
+        };
+        let mut state = coverage.borrow_mut();
+        match result {
+            Ok(success) => state.record(&mut success.debug_trace),
+            Err(error) => match &mut error.kind {
+                ContractInitErrorKind::ExecutionError {
+                    debug_trace,
+                    ..
+                }
+                | ContractInitErrorKind::OutOfEnergy {
+                    debug_trace,
+                } => state.record(debug_trace),
+                _ => {}
+            },
+        }
+    }
+
+    /// Count the probes executed in an update or invocation and remove them
+    /// from the debug traces, if coverage is enabled.
+    pub(crate) fn coverage_record_invoke(
+        &self,
+        result: &mut Result<ContractInvokeSuccess, ContractInvokeError>,
+    ) {
+        let Some(coverage) = &self.coverage else {
+            return;
+        };
+        let mut state = coverage.borrow_mut();
+        match result {
+            Ok(success) => state.record_trace(&mut success.trace_elements),
+            Err(error) => {
+                state.record_trace(&mut error.trace_elements);
+                if let ContractInvokeErrorKind::OutOfEnergy {
+                    debug_trace,
+                } = &mut error.kind
+                {
+                    state.record(debug_trace);
+                }
+            }
+        }
+    }
+}
+
+impl CoverageState {
+    /// Instrument a module, unless it has been instrumented already, and
+    /// return its id.
+    fn instrument(&mut self, module_reference: ModuleReference, module: &ContractModule) -> u32 {
+        if let Some(id) = self.ids.get(&module_reference) {
+            return *id;
+        }
+        let id = self.modules.len() as u32;
+        self.modules.push(CoveredModule {
+            instrumented: InstrumentedArtifact::new(module, id),
+        });
+        self.ids.insert(module_reference, id);
+        id
+    }
+
+    /// Count the probes in the debug traces of the trace elements and remove
+    /// them.
+    fn record_trace(&mut self, trace_elements: &mut [DebugTraceElement]) {
+        for element in trace_elements {
+            match element {
+                DebugTraceElement::Regular {
+                    debug_trace,
+                    ..
+                }
+                | DebugTraceElement::Debug {
+                    debug_trace,
+                    ..
+                } => self.record(debug_trace),
+                DebugTraceElement::WithFailures {
+                    trace_elements,
+                    debug_trace,
+                    ..
+                } => {
+                    self.record_trace(trace_elements);
+                    self.record(debug_trace);
+                }
+            }
+        }
+    }
+
+    /// Count the probes in a debug trace and remove them, together with the
+    /// host calls of the probes.
+    fn record(&mut self, debug_trace: &mut DebugTracker) {
+        // Whether each debug statement is a probe, in the order they were emitted.
+        let mut probes = Vec::new();
+        debug_trace.emitted_events.retain(|(_, statement)| {
+            let is_probe = statement.filename.is_empty() && statement.msg.is_empty();
+            if is_probe {
+                self.count(statement.line, statement.column);
+            }
+            probes.push(is_probe);
+            !is_probe
+        });
+        let mut probes = probes.into_iter();
+        debug_trace.host_call_trace.retain(|(_, host_call)| {
+            !matches!(host_call.host_function, HostFunctionV1::Common(v1::CommonFunc::DebugPrint))
+                || !probes.next().unwrap_or(false)
+        });
+    }
+
+    /// Count an execution of a probe.
+    fn count(&mut self, module_id: u32, probe: u32) {
+        let count = self
+            .modules
+            .get_mut(module_id as usize)
+            .and_then(|module| module.instrumented.as_mut().ok())
+            .and_then(|instrumented| instrumented.counts.get_mut(probe as usize));
+        if let Some(count) = count {
+            *count += 1;
+        }
+    }
+}
+
+impl InstrumentedArtifact {
+    /// Instrument a module and create the artifact to execute. Debug output is
+    /// allowed in the artifact, since the probes use it.
+    fn new(module: &ContractModule, id: u32) -> Result<Self, CoverageError> {
+        let instrumented = instrument::instrument(module.source.source.as_ref(), id)?;
+        let artifact = wasm::utils::instantiate_with_metering::<v1::ProcessedImports, _>(
+            ValidationConfig::V1,
+            &v1::ConcordiumAllowedImports {
+                support_upgrade: true,
+                enable_debug:    true,
+            },
+            &instrumented.source,
+        )
+        .map_err(|error| CoverageError::Instantiation(error.to_string()))?;
+        Ok(Self {
+            artifact:       Arc::new(artifact.artifact),
+            counts:         vec![0; instrumented.probes.len()],
+            probes:         instrumented.probes,
+            function_names: instrumented.function_names,
+            lines:          LineTable::from_sections(&instrumented.debug_sections),
+        })
+    }
+}
+
+impl CoveredModule {
+    /// Create the report of the coverage of the module.
+    fn report(&self) -> ModuleCoverage {
+        let instrumented = match &self.instrumented {
+            Ok(instrumented) => instrumented,
+            Err(error) => {
+                return ModuleCoverage {
+                    functions: Vec::new(),
+                    error:     Some(error.clone()),
+                }
+            }
+        };
+        let location = |offset| instrumented.lines.as_ref().and_then(|lines| lines.lookup(offset));
+        let mut functions: BTreeMap<u32, FunctionCoverage> = BTreeMap::new();
+        for (probe, count) in instrumented.probes.iter().zip(instrumented.counts.iter().copied()) {
+            let function = functions.entry(probe.function).or_insert_with(|| FunctionCoverage {
+                index:    probe.function,
+                name:     instrumented
+                    .function_names
+                    .get(&probe.function)
+                    .cloned()
+                    .unwrap_or_else(|| format!("func[{}]", probe.function)),
+                count:    0,
+                location: None,
+                blocks:   Vec::new(),
+                branches: Vec::new(),
+            });
+            match probe.kind {
+                ProbeKind::Entry => {
+                    function.count = count;
+                    function.location = location(probe.offset);
+                }
+                ProbeKind::Block => function.blocks.push(BlockCoverage {
+                    offset: probe.offset,
+                    count,
+                    location: location(probe.offset),
+                }),
+                ProbeKind::Branch {
+                    taken,
+                } => {
+                    let index =
+                        match function.branches.iter().position(|b| b.offset == probe.offset) {
+                            Some(index) => index,
+                            None => {
+                                function.branches.push(BranchCoverage {
+                                    offset:    probe.offset,
+                                    taken:     0,
+                                    not_taken: 0,
+                                    location:  location(probe.offset),
+                                });
+                                function.branches.len() - 1
+                            }
+                        };
+                    let branch = &mut function.branches[index];
+                    if taken {
+                        branch.taken = count;
+                    } else {
+                        branch.not_taken = count;
+                    }
+                }
+            }
+        }
+        ModuleCoverage {
+            functions: functions.into_values().collect(),
+            error:     None,
+        }
+    }
+}
+
+impl Coverage {
+    /// Get the coverage of a module.
+    pub fn module(&self, module_reference: ModuleReference) -> Option<&ModuleCoverage> {
+        self.modules.get(&module_reference)
+    }
+
+    /// Get the functions that were never called, per module. Modules where
+    /// all the functions were called are omitted.
+    pub fn not_executed(&self) -> BTreeMap<ModuleReference, Vec<&str>> {
+        self.modules
+            .iter()
+            .filter_map(|(module_reference, module)| {
+                let names: Vec<_> = module
+                    .functions
+                    .iter()
+                    .filter(|function| function.count == 0)
+                    .map(|function| function.name.as_str())
+                    .collect();
+                (!names.is_empty()).then_some((*module_reference, names))
+            })
+            .collect()
+    }
+
+    /// Get the coverage in the LCOV tracefile format, which is supported by
+    /// tools such as `genhtml` and most editors.
+    ///
+    /// Only the code with a source location is included, so modules built
+    /// without DWARF debug information are omitted. Lines are reported with
+    /// the highest count of the basic blocks starting on them, and functions
+    /// with the same name in a source file, e.g., instances of a generic
+    /// function, are combined.
+    pub fn to_lcov(&self) -> String {
+        #[derive(Default)]
+        struct SourceFile {
+            functions: BTreeMap<String, (u32, u64)>,
+            branches:  Vec<(u32, u64, u64)>,
+            lines:     BTreeMap<u32, u64>,
+        }
+        fn add_line<'a>(
+            files: &'a mut BTreeMap<PathBuf, SourceFile>,
+            location: &SourceLocation,
+            count: u64,
+        ) -> &'a mut SourceFile {
+            let file = files.entry(location.file.clone()).or_default();
+            let line = file.lines.entry(location.line).or_default();
+            *line = (*line).max(count);
+            file
+        }
+        let mut files = BTreeMap::new();
+        for function in self.modules.values().flat_map(|module| module.functions.iter()) {
+            if let Some(location) = &function.location {
+                let file = add_line(&mut files, location, function.count);
+                let (line, count) =
+                    file.functions.entry(function.name.clone()).or_insert((location.line, 0));
+                *line = (*line).min(location.line);
+                *count += function.count;
+            }
+            for block in &function.blocks {
+                if let Some(location) = &block.location {
+                    add_line(&mut files, location, block.count);
+                }
+            }
+            for branch in &function.branches {
+                if let Some(location) = &branch.location {
+                    let file: &mut SourceFile = files.entry(location.file.clone()).or_default();
+                    file.branches.push((location.line, branch.taken, branch.not_taken));
+                }
+            }
+        }
+
+        let mut out = String::new();
+        for (path, file) in files {
+            // Writing to a string does not fail.
+            let _ = writeln!(out, "TN:\nSF:{}", path.display());
+            for (name, (line, _)) in &file.functions {
+                let _ = writeln!(out, "FN:{line},{name}");
+            }
+            for (name, (_, count)) in &file.functions {
+                let _ = writeln!(out, "FNDA:{count},{name}");
+            }
+            let functions_hit = file.functions.values().filter(|(_, count)| *count > 0).count();
+            let _ = writeln!(out, "FNF:{}\nFNH:{functions_hit}", file.functions.len());
+            let mut branches_hit = 0;
+            for (block, (line, taken, not_taken)) in file.branches.iter().enumerate() {
+                for (branch, count) in [taken, not_taken].into_iter().enumerate() {
+                    if taken + not_taken == 0 {
+                        let _ = writeln!(out, "BRDA:{line},{block},{branch},-");
+                    } else {
+                        let _ = writeln!(out, "BRDA:{line},{block},{branch},{count}");
+                    }
+                    branches_hit += usize::from(*count > 0);
+                }
+            }
+            let _ = writeln!(out, "BRF:{}\nBRH:{branches_hit}", 2 * file.branches.len());
+            for (line, count) in &file.lines {
+                let _ = writeln!(out, "DA:{line},{count}");
+            }
+            let lines_hit = file.lines.values().filter(|count| **count > 0).count();
+            let _ = writeln!(out, "LF:{}\nLH:{lines_hit}\nend_of_record", file.lines.len());
+        }
+        out
+    }
+
+    /// Write the coverage to a file in the LCOV tracefile format, see
+    /// [`Coverage::to_lcov`].
+    pub fn write_lcov(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_lcov())
+    }
+}
+
+impl ModuleCoverage {
+    /// Get the coverage of a function by its name.
+    pub fn function(&self, name: &str) -> Option<&FunctionCoverage> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+}
+
+/// Displays the number of calls of each function, per module, along with how
+/// many of the functions, basic blocks and branches were executed.
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (module_reference, module) in &self.modules {
+            if let Some(error) = &module.error {
+                writeln!(f, "module {module_reference}: not instrumented: {error}")?;
+                continue;
+            }
+            let functions = &module.functions;
+            let called = functions.iter().filter(|function| function.count > 0).count();
+            let blocks = functions.iter().flat_map(|function| function.blocks.iter());
+            let blocks_executed = blocks.clone().filter(|block| block.count > 0).count();
+            let branches = functions.iter().flat_map(|function| function.branches.iter());
+            let sides_executed: usize = branches
+                .clone()
+                .map(|branch| usize::from(branch.taken > 0) + usize::from(branch.not_taken > 0))
+                .sum();
+            writeln!(
+                f,
+                "module {module_reference}: {called}/{} functions, {blocks_executed}/{} blocks \
+                 and {sides_executed}/{} branches executed",
+                functions.len(),
+                blocks.count(),
+                2 * branches.count()
+            )?;
+            for function in functions {
+                write!(f, "  {:>6}  {}", function.count, function.name)?;
+                if let Some(location) = &function.location {
+                    write!(f, " ({}:{})", location.file.display(), location.line)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A coverage report of a module with a called function with a branch and
+    /// a block, and a function that was never called.
+    fn coverage() -> (ModuleReference, Coverage) {
+        let module_reference = ModuleReference::from([0u8; 32]);
+        let location = |line| {
+            Some(SourceLocation {
+                file: PathBuf::from("src/lib.rs"),
+                line,
+            })
+        };
+        let coverage = Coverage {
+            modules: BTreeMap::from([(module_reference, ModuleCoverage {
+                functions: vec![
+                    FunctionCoverage {
+                        index:    0,
+                        name:     "counter::increment".to_string(),
+                        count:    3,
+                        location: location(10),
+                        blocks:   vec![BlockCoverage {
+                            offset:   20,
+                            count:    0,
+                            location: location(12),
+                        }],
+                        branches: vec![BranchCoverage {
+                            offset:    15,
+                            taken:     3,
+                            not_taken: 0,
+                            location:  location(11),
+                        }],
+                    },
+                    FunctionCoverage {
+                        index:    1,
+                        name:     "init_counter".to_string(),
+                        count:    0,
+                        location: location(3),
+                        blocks:   Vec::new(),
+                        branches: Vec::new(),
+                    },
+                ],
+                error:     None,
+            })]),
+        };
+        (module_reference, coverage)
+    }
+
+    /// Test the report of executed and not executed functions.
+    #[test]
+    fn test_coverage_report() {
+        let (module_reference, coverage) = coverage();
+        assert_eq!(
+            coverage.not_executed(),
+            BTreeMap::from([(module_reference, vec!["init_counter"])])
+        );
+        assert_eq!(coverage.to_string().lines().collect::<Vec<_>>(), [
+            format!(
+                "module {module_reference}: 1/2 functions, 0/1 blocks and 1/2 branches executed"
+            ),
+            "       3  counter::increment (src/lib.rs:10)".to_string(),
+            "       0  init_counter (src/lib.rs:3)".to_string(),
+        ]);
+    }
+
+    /// Test the LCOV output.
+    #[test]
+    fn test_coverage_lcov() {
+        let (_, coverage) = coverage();
+        assert_eq!(coverage.to_lcov().lines().collect::<Vec<_>>(), [
+            "TN:",
+            "SF:src/lib.rs",
+            "FN:10,counter::increment",
+            "FN:3,init_counter",
+            "FNDA:3,counter::increment",
+            "FNDA:0,init_counter",
+            "FNF:2",
+            "FNH:1",
+            "BRDA:11,0,0,3",
+            "BRDA:11,0,1,0",
+            "BRF:2",
+            "BRH:1",
+            "DA:3,0",
+            "DA:10,3",
+            "DA:12,0",
+            "LF:3",
+            "LH:1",
+            "end_of_record",
+        ]);
+    }
+}
@@ -0,0 +1,1054 @@
+//! A local emulator of a node, backed by a [`Chain`].
+//!
+//! The emulator serves the methods `GetAccountInfo`, `GetInstanceInfo`,
+//! `InvokeInstance`, `SendBlockItem` and `GetBlockItemStatus` of the
+//! `concordium.v2.Queries` gRPC service of the node, with the protobuf messages
+//! generated in the SDK. Clients of the node API, e.g., the
+//! [`v2::Client`](concordium_rust_sdk::v2::Client) of the SDK, can therefore
+//! connect to it. The other methods of the node respond with `UNIMPLEMENTED`.
+//!
+//! The emulator has no blocks. Queries are answered with the current state of
+//! the chain, regardless of the block requested, and transactions are executed
+//! when they are received and are reported as finalized in a block whose hash
+//! is all zeros. The requests are executed on the chain one at a time, in the
+//! order they are received.
+use crate::{external_node::to_hex, types::*};
+use concordium_rust_sdk::{
+    base::{
+        base::{AccountAddressEq, Energy, Nonce},
+        common::{
+            self,
+            types::{CredentialIndex, KeyIndex, Signature, TransactionSignature, TransactionTime},
+        },
+        contracts_common::{
+            AccountAddress, Address, Amount, ContractAddress, ModuleReference, OwnedContractName,
+            OwnedParameter, Timestamp,
+        },
+        curve_arithmetic::Curve,
+        hashes::TransactionHash,
+        id::constants::ArCurve,
+        smart_contracts::{ContractEvent, ContractTraceElement, OwnedReceiveName, WasmVersion},
+        transactions::{
+            AccountTransaction, BlockItem, EncodedPayload, InitContractPayload, Payload,
+            TransactionHeader, UpdateContractPayload,
+        },
+    },
+    smart_contracts::engine::v1,
+    v2::generated::{self, account_transaction_effects::Effect, reject_reason::Reason},
+};
+use std::{
+    borrow::Borrow,
+    collections::BTreeMap,
+    convert::Infallible,
+    net::TcpListener,
+    sync::{Arc, Mutex},
+};
+use tokio::runtime;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{
+    body::BoxBody,
+    codec::ProstCodec,
+    codegen::{http, Body, BoxFuture, Context, Poll, Service, StdError},
+    server::{NamedService, UnaryService},
+    Status,
+};
+
+/// The energy used for `InvokeInstance` if none is provided, which is the same
+/// default as the node uses.
+const DEFAULT_INVOKE_ENERGY: u64 = 3_000_000;
+
+/// The hash of the block that all queries are answered in and all transactions
+/// are finalized in.
+const BLOCK_HASH: [u8; 32] = [0; 32];
+
+impl NodeEmulator {
+    /// Create an emulator that executes the transactions it receives on the
+    /// `chain`.
+    pub fn new(chain: Chain) -> Self {
+        Self {
+            chain,
+            transactions: BTreeMap::new(),
+        }
+    }
+
+    /// Get the chain of the emulator.
+    pub fn chain(&self) -> &Chain { &self.chain }
+
+    /// Get the chain of the emulator, e.g., for creating accounts before it
+    /// is served.
+    pub fn chain_mut(&mut self) -> &mut Chain { &mut self.chain }
+
+    /// Serve the gRPC API of the node on the `listener`, e.g., to the
+    /// [`v2::Client`](concordium_rust_sdk::v2::Client) of the SDK connected
+    /// to `http://127.0.0.1:20100`.
+    ///
+    /// This blocks and only returns if the server fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut chain = Chain::new();
+    /// chain.create_account(Account::new(AccountAddress([0; 32]), Amount::from_ccd(1000)));
+    /// let listener = std::net::TcpListener::bind("127.0.0.1:20100").unwrap();
+    /// NodeEmulator::new(chain).serve(listener).unwrap();
+    /// ```
+    pub fn serve(self, listener: TcpListener) -> Result<(), EmulatorError> {
+        let runtime = runtime::Builder::new_multi_thread()
+            .enable_io()
+            .enable_time()
+            .build()
+            .expect("Internal error: Could not create Tokio runtime.");
+        runtime.block_on(async move {
+            listener.set_nonblocking(true)?;
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            tonic::transport::Server::builder()
+                .add_service(QueriesService {
+                    emulator: Arc::new(Mutex::new(self)),
+                })
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await?;
+            Ok(())
+        })
+    }
+
+    /// Handle `GetAccountInfo`.
+    ///
+    /// The chain does not model credentials, encrypted balances and the
+    /// details of the stake, so the accounts have no credentials, a
+    /// placeholder encryption key and an encrypted balance of zero. The index
+    /// of an account is its position among the accounts ordered by address.
+    fn get_account_info(
+        &self,
+        request: generated::AccountInfoRequest,
+    ) -> Result<generated::AccountInfo, Status> {
+        use generated::account_identifier_input::AccountIdentifierInput;
+        let identifier = require(request.account_identifier, "account identifier")?;
+        let index = match require(identifier.account_identifier_input, "account identifier")? {
+            AccountIdentifierInput::Address(address) => {
+                let address = AccountAddressEq::from(account_address(address)?);
+                self.chain.accounts.keys().position(|key| *key == address)
+            }
+            AccountIdentifierInput::AccountIndex(index) => usize::try_from(index.value).ok(),
+            AccountIdentifierInput::CredId(_) => {
+                return Err(Status::unimplemented(
+                    "Accounts cannot be looked up by their credentials.",
+                ))
+            }
+        };
+        let (index, account) = index
+            .and_then(|index| Some((index, self.chain.accounts.values().nth(index)?)))
+            .ok_or_else(|| Status::not_found("The account does not exist."))?;
+
+        // The placeholder encryption key has the generator of the group as both
+        // the generator and the key, and the encrypted balance consists of two
+        // ciphertexts of the identity.
+        let encryption_key = (0..2).flat_map(|_| common::to_bytes(&ArCurve::one_point())).collect();
+        let encrypted_zero =
+            (0..4).flat_map(|_| common::to_bytes(&ArCurve::zero_point())).collect();
+        Ok(generated::AccountInfo {
+            sequence_number: Some(generated::SequenceNumber {
+                value: account.next_nonce().nonce,
+            }),
+            amount: Some(amount_message(account.balance.total)),
+            schedule: Some(generated::ReleaseSchedule {
+                total:     Some(amount_message(account.balance.locked)),
+                schedules: account
+                    .release_schedule
+                    .iter()
+                    .map(|(timestamp, amount)| generated::Release {
+                        timestamp:    Some(timestamp_message(*timestamp)),
+                        amount:       Some(amount_message(*amount)),
+                        transactions: Vec::new(),
+                    })
+                    .collect(),
+            }),
+            threshold: Some(generated::AccountThreshold {
+                value: u8::from(account.keys.threshold).into(),
+            }),
+            encrypted_balance: Some(generated::EncryptedBalance {
+                self_amount: Some(generated::EncryptedAmount {
+                    value: encrypted_zero,
+                }),
+                ..Default::default()
+            }),
+            encryption_key: Some(generated::EncryptionKey {
+                value: encryption_key,
+            }),
+            index: Some(generated::AccountIndex {
+                value: index as u64,
+            }),
+            address: Some(account_address_message(account.address)),
+            ..Default::default()
+        })
+    }
+
+    /// Handle `GetInstanceInfo`. The methods of a contract are the receive
+    /// functions of the contract exported by its module.
+    fn get_instance_info(
+        &self,
+        request: generated::InstanceInfoRequest,
+    ) -> Result<generated::InstanceInfo, Status> {
+        let address = contract_address(require(request.address, "contract address")?);
+        let contract = self
+            .chain
+            .get_contract(address)
+            .ok_or_else(|| Status::not_found("The contract does not exist."))?;
+        let module = self
+            .chain
+            .get_module(contract.module_reference)
+            .ok_or_else(|| Status::internal("The module of the contract does not exist."))?;
+        let prefix = format!("{}.", contract.contract_name.as_contract_name().contract_name());
+        let methods = module
+            .artifact
+            .export
+            .keys()
+            .map(|name| -> &str { name.borrow() })
+            .filter(|name| name.starts_with(&prefix))
+            .map(|name| generated::ReceiveName {
+                value: name.to_string(),
+            })
+            .collect();
+        Ok(generated::InstanceInfo {
+            version: Some(generated::instance_info::Version::V1(generated::instance_info::V1 {
+                owner: Some(account_address_message(contract.owner)),
+                amount: Some(amount_message(contract.self_balance)),
+                methods,
+                name: Some(init_name_message(&contract.contract_name)),
+                source_module: Some(module_reference_message(contract.module_reference)),
+            })),
+        })
+    }
+
+    /// Handle `InvokeInstance`. The contract is invoked without saving the
+    /// changes. The invoker defaults to the owner of the contract, and the
+    /// energy defaults to the same as for the node.
+    fn invoke_instance(
+        &self,
+        request: generated::InvokeInstanceRequest,
+    ) -> Result<generated::InvokeInstanceResponse, Status> {
+        use generated::invoke_instance_response as response;
+        let address = contract_address(require(request.instance, "contract address")?);
+        let contract = self
+            .chain
+            .get_contract(address)
+            .ok_or_else(|| Status::not_found("The contract does not exist."))?;
+        let (invoker, sender) = match request.invoker.and_then(|invoker| invoker.r#type) {
+            None => (contract.owner, Address::Account(contract.owner)),
+            Some(generated::address::Type::Account(invoker)) => {
+                let invoker = account_address(invoker)?;
+                (invoker, Address::Account(invoker))
+            }
+            Some(generated::address::Type::Contract(invoker)) => {
+                let invoker = contract_address(invoker);
+                let owner = self
+                    .chain
+                    .get_contract(invoker)
+                    .ok_or_else(|| Status::not_found("The invoking contract does not exist."))?
+                    .owner;
+                (owner, Address::Contract(invoker))
+            }
+        };
+        let receive_name = OwnedReceiveName::new(require(request.entrypoint, "entrypoint")?.value)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let message = OwnedParameter::try_from(request.parameter.unwrap_or_default().value)
+            .map_err(|_| Status::invalid_argument("The parameter is too large."))?;
+        let payload = UpdateContractPayload {
+            address,
+            receive_name,
+            message,
+            amount: request
+                .amount
+                .map_or_else(Amount::zero, |amount| Amount::from_micro_ccd(amount.value)),
+        };
+        let energy = request.energy.map_or(DEFAULT_INVOKE_ENERGY, |energy| energy.value);
+
+        let result = match self.chain.contract_invoke(
+            invoker,
+            sender,
+            Energy::from(energy),
+            payload.clone(),
+        ) {
+            Ok(success) => response::Result::Success(response::Success {
+                return_value: Some(success.return_value.clone()),
+                used_energy:  Some(energy_message(success.energy_used)),
+                effects:      success
+                    .effective_trace_elements()
+                    .map(trace_element_message)
+                    .collect(),
+            }),
+            Err(error) => {
+                let reason = self
+                    .update_reject_reason(&error.kind, invoker, &payload)
+                    .ok_or_else(|| Status::invalid_argument(error.kind.to_string()))?;
+                response::Result::Failure(response::Failure {
+                    return_value: error.return_value().map(<[u8]>::to_vec),
+                    used_energy:  Some(energy_message(error.energy_used)),
+                    reason:       Some(reject_reason_message(reason)),
+                })
+            }
+        };
+        Ok(generated::InvokeInstanceResponse {
+            result: Some(result),
+        })
+    }
+
+    /// Handle `SendBlockItem` by executing the transaction on the chain and
+    /// recording its outcome.
+    ///
+    /// Module deployments, contract inits and updates, and transfers, also with
+    /// a memo or a schedule, are supported. Transactions that are expired, do
+    /// not have the next nonce of the sender, or that the node would not have
+    /// accepted for another reason, e.g., because the sender cannot pay for
+    /// them, fail with `INVALID_ARGUMENT`. If the chain is in strict mode, see
+    /// [`Chain::set_strict_transactions`], the signatures are also verified.
+    fn send_block_item(
+        &mut self,
+        request: generated::SendBlockItemRequest,
+    ) -> Result<generated::TransactionHash, Status> {
+        use generated::send_block_item_request::BlockItem as Item;
+        let transaction = match require(request.block_item, "block item")? {
+            Item::AccountTransaction(transaction) => account_transaction(transaction)?,
+            _ => return Err(Status::unimplemented("Only account transactions are supported.")),
+        };
+        let hash = BlockItem::AccountTransaction(transaction.clone()).hash();
+        if self.transactions.contains_key(&hash) {
+            return Err(Status::already_exists("The transaction has already been sent."));
+        }
+        let sender = transaction.header.sender;
+        if !self.chain.account_exists(sender) {
+            return Err(Status::invalid_argument("The sender does not exist."));
+        }
+        // In strict mode, the signatures are verified like the node does when
+        // the transaction is submitted.
+        if self.chain.is_strict_transactions() {
+            self.chain
+                .verify_transaction(&transaction)
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        }
+        let signer = Signer::with_keys(transaction.signature.num_keys())
+            .map_err(|_| Status::invalid_argument("The transaction is not signed."))?;
+        let payload = transaction
+            .payload
+            .decode()
+            .map_err(|_| Status::invalid_argument("The payload cannot be decoded."))?;
+        let header = &transaction.header;
+        let submitted = Transaction {
+            signer,
+            sender,
+            nonce: header.nonce,
+            expiry: header.expiry,
+            energy_reserved: header.energy_amount,
+            payload: payload.clone(),
+        };
+        let outcome = self
+            .chain
+            .without_strict_transactions(|chain| chain.submit_transaction(submitted))
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let (energy_used, cost, effect) = self.transaction_effect(sender, payload, outcome)?;
+        let summary = generated::BlockItemSummary {
+            index:       Some(generated::block_item_summary::TransactionIndex {
+                value: self.transactions.len() as u64,
+            }),
+            energy_cost: Some(energy_message(energy_used)),
+            hash:        Some(transaction_hash_message(hash)),
+            details:     Some(generated::block_item_summary::Details::AccountTransaction(
+                generated::AccountTransactionDetails {
+                    cost:    Some(amount_message(cost)),
+                    sender:  Some(account_address_message(sender)),
+                    effects: Some(generated::AccountTransactionEffects {
+                        effect: Some(effect),
+                    }),
+                },
+            )),
+        };
+        self.transactions.insert(hash, summary);
+        Ok(transaction_hash_message(hash))
+    }
+
+    /// Handle `GetBlockItemStatus`. All the transactions sent to the emulator
+    /// are finalized.
+    fn get_block_item_status(
+        &self,
+        request: generated::TransactionHash,
+    ) -> Result<generated::BlockItemStatus, Status> {
+        use generated::block_item_status::{Finalized, Status as ItemStatus};
+        let hash = TransactionHash::new(
+            request
+                .value
+                .try_into()
+                .map_err(|_| Status::invalid_argument("Invalid transaction hash."))?,
+        );
+        let summary = self
+            .transactions
+            .get(&hash)
+            .ok_or_else(|| Status::not_found("The transaction is unknown."))?;
+        Ok(generated::BlockItemStatus {
+            status: Some(ItemStatus::Finalized(Finalized {
+                outcome: Some(generated::BlockItemSummaryInBlock {
+                    block_hash: Some(generated::BlockHash {
+                        value: BLOCK_HASH.to_vec(),
+                    }),
+                    outcome:    Some(summary.clone()),
+                }),
+            })),
+        })
+    }
+
+    /// Get the energy used, the cost and the effect of an executed transaction.
+    ///
+    /// Fails with `INVALID_ARGUMENT` if the transaction failed for a reason
+    /// that the node would not have accepted the transaction for, since such
+    /// transactions are not included in a block by the node.
+    fn transaction_effect(
+        &self,
+        sender: AccountAddress,
+        payload: Payload,
+        outcome: BlockItemOutcome,
+    ) -> Result<(Energy, Amount, Effect), Status> {
+        use generated::{
+            account_transaction_effects::{AccountTransfer, ContractUpdateIssued},
+            TransactionType,
+        };
+        let transfer = |receiver: AccountAddress, amount: Amount, memo: Option<&[u8]>| {
+            Effect::AccountTransfer(AccountTransfer {
+                amount:   Some(amount_message(amount)),
+                receiver: Some(account_address_message(receiver)),
+                memo:     memo.map(|memo| generated::Memo {
+                    value: memo.to_vec(),
+                }),
+            })
+        };
+        Ok(match (payload, outcome) {
+            (_, BlockItemOutcome::ModuleDeploy(Ok(success))) => (
+                success.energy_used,
+                success.transaction_fee,
+                Effect::ModuleDeployed(module_reference_message(success.module_reference)),
+            ),
+            (_, BlockItemOutcome::ModuleDeploy(Err(error))) => (
+                error.energy_used,
+                error.transaction_fee,
+                rejected_effect(
+                    TransactionType::DeployModule,
+                    deploy_reject_reason(&error.kind),
+                    &error,
+                )?,
+            ),
+            (
+                Payload::InitContract {
+                    payload,
+                },
+                BlockItemOutcome::ContractInit(Ok(success)),
+            ) => (
+                success.energy_used,
+                success.transaction_fee,
+                Effect::ContractInitialized(generated::ContractInitializedEvent {
+                    contract_version: generated::ContractVersion::V1 as i32,
+                    origin_ref:       Some(module_reference_message(payload.mod_ref)),
+                    address:          Some(contract_address_message(success.contract_address)),
+                    amount:           Some(amount_message(payload.amount)),
+                    init_name:        Some(init_name_message(&payload.init_name)),
+                    events:           events_message(&success.events),
+                }),
+            ),
+            (
+                Payload::InitContract {
+                    payload,
+                },
+                BlockItemOutcome::ContractInit(Err(error)),
+            ) => (
+                error.energy_used,
+                error.transaction_fee,
+                rejected_effect(
+                    TransactionType::InitContract,
+                    init_reject_reason(&error.kind, sender, &payload),
+                    &error,
+                )?,
+            ),
+            (_, BlockItemOutcome::ContractUpdate(Ok(success))) => (
+                success.energy_used,
+                success.transaction_fee,
+                Effect::ContractUpdateIssued(ContractUpdateIssued {
+                    effects: success
+                        .effective_trace_elements()
+                        .map(trace_element_message)
+                        .collect(),
+                }),
+            ),
+            (
+                Payload::Update {
+                    payload,
+                },
+                BlockItemOutcome::ContractUpdate(Err(error)),
+            ) => (
+                error.energy_used,
+                error.transaction_fee,
+                rejected_effect(
+                    TransactionType::Update,
+                    self.update_reject_reason(&error.kind, sender, &payload),
+                    &error,
+                )?,
+            ),
+            (
+                Payload::Transfer {
+                    to_address,
+                    amount,
+                },
+                BlockItemOutcome::AccountTransfer(Ok(success)),
+            ) => (success.energy_used, success.transaction_fee, transfer(to_address, amount, None)),
+            (
+                Payload::TransferWithMemo {
+                    to_address,
+                    memo,
+                    amount,
+                },
+                BlockItemOutcome::AccountTransfer(Ok(success)),
+            ) => (
+                success.energy_used,
+                success.transaction_fee,
+                transfer(to_address, amount, Some(memo.as_ref())),
+            ),
+            (
+                Payload::TransferWithSchedule {
+                    to,
+                    schedule,
+                },
+                BlockItemOutcome::AccountTransfer(Ok(success)),
+            ) => (
+                success.energy_used,
+                success.transaction_fee,
+                Effect::TransferredWithSchedule(
+                    generated::account_transaction_effects::TransferredWithSchedule {
+                        receiver: Some(account_address_message(to)),
+                        amount:   schedule
+                            .into_iter()
+                            .map(|(timestamp, amount)| generated::NewRelease {
+                                timestamp: Some(timestamp_message(timestamp)),
+                                amount:    Some(amount_message(amount)),
+                            })
+                            .collect(),
+                        memo:     None,
+                    },
+                ),
+            ),
+            (payload, BlockItemOutcome::AccountTransfer(Err(error))) => {
+                let transaction_type = match payload {
+                    Payload::TransferWithMemo {
+                        ..
+                    } => TransactionType::TransferWithMemo,
+                    Payload::TransferWithSchedule {
+                        ..
+                    } => TransactionType::TransferWithSchedule,
+                    _ => TransactionType::Transfer,
+                };
+                (
+                    error.energy_used,
+                    error.transaction_fee,
+                    rejected_effect(
+                        transaction_type,
+                        transfer_reject_reason(&error.kind, sender),
+                        &error,
+                    )?,
+                )
+            }
+            _ => unreachable!("The chain executes each payload as the matching transaction."),
+        })
+    }
+
+    /// Get the reject reason of the node for a failed contract update or
+    /// invocation, or `None` if the node would not have executed it.
+    fn update_reject_reason(
+        &self,
+        kind: &ContractInvokeErrorKind,
+        invoker: AccountAddress,
+        payload: &UpdateContractPayload,
+    ) -> Option<Reason> {
+        use generated::reject_reason::{AmountTooLarge, InvalidReceiveMethod, RejectedReceive};
+        Some(match kind {
+            ContractInvokeErrorKind::ExecutionError {
+                failure_kind:
+                    v1::InvokeFailure::ContractReject {
+                        code,
+                        ..
+                    },
+            } => Reason::RejectedReceive(RejectedReceive {
+                reject_reason:    *code,
+                contract_address: Some(contract_address_message(payload.address)),
+                receive_name:     Some(receive_name_message(&payload.receive_name)),
+                parameter:        Some(generated::Parameter {
+                    value: payload.message.as_ref().to_vec(),
+                }),
+            }),
+            ContractInvokeErrorKind::ExecutionError {
+                ..
+            } => Reason::RuntimeFailure(generated::Empty {}),
+            ContractInvokeErrorKind::OutOfEnergy {
+                ..
+            } => Reason::OutOfEnergy(generated::Empty {}),
+            ContractInvokeErrorKind::ModuleDoesNotExist(error) => {
+                Reason::InvalidModuleReference(module_reference_message(error.module_reference))
+            }
+            ContractInvokeErrorKind::ContractDoesNotExist(error) => {
+                Reason::InvalidContractAddress(contract_address_message(error.address))
+            }
+            ContractInvokeErrorKind::EntrypointDoesNotExist(_) => {
+                Reason::InvalidReceiveMethod(InvalidReceiveMethod {
+                    module_ref:   self
+                        .chain
+                        .get_contract(payload.address)
+                        .map(|contract| module_reference_message(contract.module_reference)),
+                    receive_name: Some(receive_name_message(&payload.receive_name)),
+                })
+            }
+            ContractInvokeErrorKind::InvokerDoesNotExist(error) => {
+                Reason::InvalidAccountReference(account_address_message(error.address))
+            }
+            ContractInvokeErrorKind::AmountTooLarge => Reason::AmountTooLarge(AmountTooLarge {
+                address: Some(address_message(Address::Account(invoker))),
+                amount:  Some(amount_message(payload.amount)),
+            }),
+            _ => return None,
+        })
+    }
+}
+
+/// The `concordium.v2.Queries` gRPC service of the node, with the methods
+/// supported by a [`NodeEmulator`].
+#[derive(Clone)]
+struct QueriesService {
+    /// The emulator, which handles one request at a time.
+    emulator: Arc<Mutex<NodeEmulator>>,
+}
+
+impl NamedService for QueriesService {
+    const NAME: &'static str = "concordium.v2.Queries";
+}
+
+impl<B> Service<http::Request<B>> for QueriesService
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+    type Response = http::Response<BoxBody>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let emulator = self.emulator.clone();
+        match request.uri().path() {
+            "/concordium.v2.Queries/GetAccountInfo" => {
+                unary(request, emulator, |emulator, request| emulator.get_account_info(request))
+            }
+            "/concordium.v2.Queries/GetInstanceInfo" => {
+                unary(request, emulator, |emulator, request| emulator.get_instance_info(request))
+            }
+            "/concordium.v2.Queries/InvokeInstance" => {
+                unary(request, emulator, |emulator, request| emulator.invoke_instance(request))
+            }
+            "/concordium.v2.Queries/SendBlockItem" => {
+                unary(request, emulator, NodeEmulator::send_block_item)
+            }
+            "/concordium.v2.Queries/GetBlockItemStatus" => {
+                unary(request, emulator, |emulator, request| {
+                    emulator.get_block_item_status(request)
+                })
+            }
+            path => {
+                let status = Status::unimplemented(format!("{path} is not supported."));
+                Box::pin(async move { Ok(status.to_http()) })
+            }
+        }
+    }
+}
+
+/// A unary method of the [`QueriesService`], which locks the emulator and
+/// calls the `handler` with the request.
+struct Unary<F> {
+    emulator: Arc<Mutex<NodeEmulator>>,
+    handler:  F,
+}
+
+impl<Req, Res, F> UnaryService<Req> for Unary<F>
+where
+    F: Fn(&mut NodeEmulator, Req) -> Result<Res, Status>,
+{
+    type Future = std::future::Ready<Result<tonic::Response<Res>, Status>>;
+    type Response = Res;
+
+    fn call(&mut self, request: tonic::Request<Req>) -> Self::Future {
+        let mut emulator = self.emulator.lock().unwrap_or_else(|e| e.into_inner());
+        let result = (self.handler)(&mut emulator, request.into_inner()).map(|response| {
+            // The node includes the block that a query is answered in, and the
+            // SDK requires it.
+            let mut response = tonic::Response::new(response);
+            let block_hash = to_hex(&BLOCK_HASH).parse().expect("Hex is valid metadata.");
+            response.metadata_mut().insert("blockhash", block_hash);
+            response
+        });
+        std::future::ready(result)
+    }
+}
+
+/// Decode a unary request, handle it with the `handler` and encode the
+/// response.
+fn unary<B, Req, Res, F>(
+    request: http::Request<B>,
+    emulator: Arc<Mutex<NodeEmulator>>,
+    handler: F,
+) -> BoxFuture<http::Response<BoxBody>, Infallible>
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+    Req: prost::Message + Default + Send + 'static,
+    Res: prost::Message + Send + 'static,
+    F: Fn(&mut NodeEmulator, Req) -> Result<Res, Status> + Send + 'static, {
+    Box::pin(async move {
+        let mut grpc = tonic::server::Grpc::new(ProstCodec::<Res, Req>::default());
+        Ok(grpc
+            .unary(
+                Unary {
+                    emulator,
+                    handler,
+                },
+                request,
+            )
+            .await)
+    })
+}
+
+/// Get a field of a request, or fail with `INVALID_ARGUMENT` if it is missing.
+fn require<T>(field: Option<T>, name: &str) -> Result<T, Status> {
+    field.ok_or_else(|| Status::invalid_argument(format!("The {name} is missing.")))
+}
+
+/// Convert an account transaction sent with `SendBlockItem`. Only transactions
+/// with a raw payload, which is how the SDK sends them, are supported.
+fn account_transaction(
+    transaction: generated::AccountTransaction,
+) -> Result<AccountTransaction<EncodedPayload>, Status> {
+    let index = |index: u32| {
+        u8::try_from(index).map_err(|_| Status::invalid_argument("Invalid signature index."))
+    };
+    let signatures = require(transaction.signature, "signature")?
+        .signatures
+        .into_iter()
+        .map(|(credential, signatures)| {
+            let signatures = signatures
+                .signatures
+                .into_iter()
+                .map(|(key, signature)| {
+                    Ok((KeyIndex::from(index(key)?), Signature {
+                        sig: signature.value,
+                    }))
+                })
+                .collect::<Result<_, Status>>()?;
+            Ok((CredentialIndex::from(index(credential)?), signatures))
+        })
+        .collect::<Result<_, Status>>()?;
+    let payload = match require(transaction.payload, "payload")?.payload {
+        Some(generated::account_transaction_payload::Payload::RawPayload(payload)) => {
+            EncodedPayload::try_from(payload)
+                .map_err(|e| Status::invalid_argument(e.to_string()))?
+        }
+        _ => return Err(Status::unimplemented("Only raw payloads are supported.")),
+    };
+    let header = require(transaction.header, "transaction header")?;
+    Ok(AccountTransaction {
+        signature: TransactionSignature {
+            signatures,
+        },
+        header: TransactionHeader {
+            sender:        account_address(require(header.sender, "sender")?)?,
+            nonce:         Nonce::from(require(header.sequence_number, "sequence number")?.value),
+            energy_amount: Energy::from(require(header.energy_amount, "energy amount")?.value),
+            payload_size:  payload.size(),
+            expiry:        TransactionTime::from_seconds(require(header.expiry, "expiry")?.value),
+        },
+        payload,
+    })
+}
+
+/// The effect of a transaction rejected for the `reason`, or `INVALID_ARGUMENT`
+/// with the `error` if the node would not have executed the transaction.
+fn rejected_effect(
+    transaction_type: generated::TransactionType,
+    reason: Option<Reason>,
+    error: &dyn std::fmt::Display,
+) -> Result<Effect, Status> {
+    let reason = reason.ok_or_else(|| Status::invalid_argument(error.to_string()))?;
+    Ok(Effect::None(generated::account_transaction_effects::None {
+        transaction_type: Some(transaction_type as i32),
+        reject_reason:    Some(reject_reason_message(reason)),
+    }))
+}
+
+/// Get the reject reason of the node for a failed module deployment, or `None`
+/// if the node would not have executed it.
+fn deploy_reject_reason(kind: &ModuleDeployErrorKind) -> Option<Reason> {
+    match kind {
+        ModuleDeployErrorKind::InvalidModule(_)
+        | ModuleDeployErrorKind::UnsupportedModuleVersion(_) => {
+            Some(Reason::ModuleNotWf(generated::Empty {}))
+        }
+        ModuleDeployErrorKind::DuplicateModule(module_reference) => {
+            Some(Reason::ModuleHashAlreadyExists(module_reference_message(*module_reference)))
+        }
+        _ => None,
+    }
+}
+
+/// Get the reject reason of the node for a failed contract initialization, or
+/// `None` if the node would not have executed it.
+fn init_reject_reason(
+    kind: &ContractInitErrorKind,
+    sender: AccountAddress,
+    payload: &InitContractPayload,
+) -> Option<Reason> {
+    use generated::reject_reason::{AmountTooLarge, InvalidInitMethod, RejectedInit};
+    Some(match kind {
+        ContractInitErrorKind::ExecutionError {
+            error:
+                InitExecutionError::Reject {
+                    reason,
+                    ..
+                },
+            ..
+        } => Reason::RejectedInit(RejectedInit {
+            reject_reason: *reason,
+        }),
+        ContractInitErrorKind::ExecutionError {
+            error: InitExecutionError::OutOfEnergy,
+            ..
+        }
+        | ContractInitErrorKind::OutOfEnergy {
+            ..
+        } => Reason::OutOfEnergy(generated::Empty {}),
+        ContractInitErrorKind::ExecutionError {
+            ..
+        } => Reason::RuntimeFailure(generated::Empty {}),
+        ContractInitErrorKind::ModuleDoesNotExist(error) => {
+            Reason::InvalidModuleReference(module_reference_message(error.module_reference))
+        }
+        ContractInitErrorKind::ContractNotPresentInModule {
+            name,
+        } => Reason::InvalidInitMethod(InvalidInitMethod {
+            module_ref: Some(module_reference_message(payload.mod_ref)),
+            init_name:  Some(init_name_message(name)),
+        }),
+        ContractInitErrorKind::AmountTooLarge => Reason::AmountTooLarge(AmountTooLarge {
+            address: Some(address_message(Address::Account(sender))),
+            amount:  Some(amount_message(payload.amount)),
+        }),
+        _ => return None,
+    })
+}
+
+/// Get the reject reason of the node for a failed transfer, or `None` if the
+/// node would not have executed it.
+fn transfer_reject_reason(
+    kind: &AccountTransferErrorKind,
+    sender: AccountAddress,
+) -> Option<Reason> {
+    Some(match kind {
+        AccountTransferErrorKind::ReceiverDoesNotExist(error) => {
+            Reason::InvalidAccountReference(account_address_message(error.address))
+        }
+        AccountTransferErrorKind::AmountTooLarge {
+            amount,
+        } => Reason::AmountTooLarge(generated::reject_reason::AmountTooLarge {
+            address: Some(address_message(Address::Account(sender))),
+            amount:  Some(amount_message(*amount)),
+        }),
+        AccountTransferErrorKind::InvalidSchedule(ReleaseScheduleError::NonIncreasing) => {
+            Reason::NonIncreasingSchedule(generated::Empty {})
+        }
+        AccountTransferErrorKind::InvalidSchedule(ReleaseScheduleError::ZeroAmount) => {
+            Reason::ZeroScheduledAmount(generated::Empty {})
+        }
+        AccountTransferErrorKind::InvalidSchedule(ReleaseScheduleError::FirstReleaseExpired) => {
+            Reason::FirstScheduledReleaseExpired(generated::Empty {})
+        }
+        AccountTransferErrorKind::InvalidSchedule(ReleaseScheduleError::SelfTransfer) => {
+            Reason::ScheduledSelfTransfer(account_address_message(sender))
+        }
+        _ => return None,
+    })
+}
+
+/// Convert a contract trace element to its protobuf message.
+fn trace_element_message(element: &ContractTraceElement) -> generated::ContractTraceElement {
+    use generated::contract_trace_element::{Element, Interrupted, Resumed, Transferred, Upgraded};
+    let element = match element {
+        ContractTraceElement::Updated {
+            data,
+        } => Element::Updated(generated::InstanceUpdatedEvent {
+            contract_version: contract_version_message(data.contract_version) as i32,
+            address:          Some(contract_address_message(data.address)),
+            instigator:       Some(address_message(data.instigator)),
+            amount:           Some(amount_message(data.amount)),
+            parameter:        Some(generated::Parameter {
+                value: data.message.as_ref().to_vec(),
+            }),
+            receive_name:     Some(receive_name_message(&data.receive_name)),
+            events:           events_message(&data.events),
+        }),
+        ContractTraceElement::Transferred {
+            from,
+            amount,
+            to,
+        } => Element::Transferred(Transferred {
+            sender:   Some(contract_address_message(*from)),
+            amount:   Some(amount_message(*amount)),
+            receiver: Some(account_address_message(*to)),
+        }),
+        ContractTraceElement::Interrupted {
+            address,
+            events,
+        } => Element::Interrupted(Interrupted {
+            address: Some(contract_address_message(*address)),
+            events:  events_message(events),
+        }),
+        ContractTraceElement::Resumed {
+            address,
+            success,
+        } => Element::Resumed(Resumed {
+            address: Some(contract_address_message(*address)),
+            success: *success,
+        }),
+        ContractTraceElement::Upgraded {
+            address,
+            from,
+            to,
+        } => Element::Upgraded(Upgraded {
+            address: Some(contract_address_message(*address)),
+            from:    Some(module_reference_message(*from)),
+            to:      Some(module_reference_message(*to)),
+        }),
+    };
+    generated::ContractTraceElement {
+        element: Some(element),
+    }
+}
+
+/// Wrap a reason in a reject reason message.
+fn reject_reason_message(reason: Reason) -> generated::RejectReason {
+    generated::RejectReason {
+        reason: Some(reason),
+    }
+}
+
+/// Parse an account address message.
+fn account_address(address: generated::AccountAddress) -> Result<AccountAddress, Status> {
+    let bytes = address
+        .value
+        .try_into()
+        .map_err(|_| Status::invalid_argument("Invalid account address."))?;
+    Ok(AccountAddress(bytes))
+}
+
+/// Parse a contract address message.
+fn contract_address(address: generated::ContractAddress) -> ContractAddress {
+    ContractAddress::new(address.index, address.subindex)
+}
+
+/// Convert an account address to its protobuf message.
+fn account_address_message(address: AccountAddress) -> generated::AccountAddress {
+    generated::AccountAddress {
+        value: address.0.to_vec(),
+    }
+}
+
+/// Convert a contract address to its protobuf message.
+fn contract_address_message(address: ContractAddress) -> generated::ContractAddress {
+    generated::ContractAddress {
+        index:    address.index,
+        subindex: address.subindex,
+    }
+}
+
+/// Convert an address to its protobuf message.
+fn address_message(address: Address) -> generated::Address {
+    let address = match address {
+        Address::Account(address) => {
+            generated::address::Type::Account(account_address_message(address))
+        }
+        Address::Contract(address) => {
+            generated::address::Type::Contract(contract_address_message(address))
+        }
+    };
+    generated::Address {
+        r#type: Some(address),
+    }
+}
+
+/// Convert an amount to its protobuf message.
+fn amount_message(amount: Amount) -> generated::Amount {
+    generated::Amount {
+        value: amount.micro_ccd,
+    }
+}
+
+/// Convert energy to its protobuf message.
+fn energy_message(energy: Energy) -> generated::Energy {
+    generated::Energy {
+        value: energy.energy,
+    }
+}
+
+/// Convert a timestamp to its protobuf message.
+fn timestamp_message(timestamp: Timestamp) -> generated::Timestamp {
+    generated::Timestamp {
+        value: timestamp.timestamp_millis(),
+    }
+}
+
+/// Convert a module reference to its protobuf message.
+fn module_reference_message(module_reference: ModuleReference) -> generated::ModuleRef {
+    generated::ModuleRef {
+        value: module_reference.bytes.to_vec(),
+    }
+}
+
+/// Convert a transaction hash to its protobuf message.
+fn transaction_hash_message(hash: TransactionHash) -> generated::TransactionHash {
+    generated::TransactionHash {
+        value: hash.bytes.to_vec(),
+    }
+}
+
+/// Convert a contract name to the protobuf message of its init name.
+fn init_name_message(name: &OwnedContractName) -> generated::InitName {
+    generated::InitName {
+        value: name.as_contract_name().get_chain_name().to_string(),
+    }
+}
+
+/// Convert a receive name to its protobuf message.
+fn receive_name_message(name: &OwnedReceiveName) -> generated::ReceiveName {
+    generated::ReceiveName {
+        value: name.as_receive_name().get_chain_name().to_string(),
+    }
+}
+
+/// Convert the version of a module to the protobuf contract version.
+fn contract_version_message(version: WasmVersion) -> generated::ContractVersion {
+    match version {
+        WasmVersion::V0 => generated::ContractVersion::V0,
+        WasmVersion::V1 => generated::ContractVersion::V1,
+    }
+}
+
+/// Convert contract events to their protobuf messages.
+fn events_message(events: &[ContractEvent]) -> Vec<generated::ContractEvent> {
+    events
+        .iter()
+        .map(|event| generated::ContractEvent {
+            value: event.as_ref().to_vec(),
+        })
+        .collect()
+}
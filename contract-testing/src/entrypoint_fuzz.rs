@@ -0,0 +1,223 @@
+//! A fuzz target for sending arbitrary parameter bytes to a receive entrypoint.
+//!
+//! The target is meant to be driven by a fuzzer such as `cargo fuzz`
+//! (libFuzzer), and the `fuzz` directory of this crate contains a `cargo fuzz`
+//! crate with a target for a test contract. The coverage that guides the
+//! fuzzer is the coverage of the native code, i.e., of the parameter handling
+//! in this library and of the Wasm interpreter.
+//!
+//! The branches taken in the contract itself do not guide the fuzzer, so they
+//! are only observed indirectly through the interpreter. They can be counted
+//! with [`Chain::coverage_enable`] on the chain of the target, but the counts
+//! are not passed to the fuzzer.
+use crate::types::*;
+use concordium_rust_sdk::{
+    base::{
+        base::Energy,
+        contracts_common::{
+            constants::MAX_PARAMETER_LEN, AccountAddress, Address, Amount, ContractAddress,
+            OwnedParameter, OwnedReceiveName,
+        },
+        transactions::UpdateContractPayload,
+    },
+    smart_contracts::engine::v1,
+};
+
+impl EntrypointFuzzTarget {
+    /// Create a fuzz target for the entrypoint `receive_name` of the contract
+    /// at `address`. The `invoker` is the invoker and sender of every update.
+    /// By default, updates are sent with no CCD and 100000 NRG.
+    ///
+    /// The chain is prepared once, e.g., with accounts and contracts in an
+    /// interesting state, and each input is executed on the chain in that
+    /// state.
+    ///
+    /// # Example
+    ///
+    /// A `cargo fuzz` target in `fuzz/fuzz_targets/transfer.rs`, where
+    /// `prepare_chain` is a function written for the contract under test. See
+    /// `fuzz/fuzz_targets/transfer_forward.rs` in this crate for a complete
+    /// target:
+    ///
+    /// ```ignore
+    /// #![no_main]
+    /// use concordium_smart_contract_testing::*;
+    /// use libfuzzer_sys::fuzz_target;
+    /// use std::cell::RefCell;
+    ///
+    /// thread_local! {
+    ///     static TARGET: RefCell<EntrypointFuzzTarget> = {
+    ///         let (chain, contract) = prepare_chain();
+    ///         RefCell::new(EntrypointFuzzTarget::new(
+    ///             chain,
+    ///             AccountAddress([0; 32]),
+    ///             contract,
+    ///             OwnedReceiveName::new_unchecked("cis2_wCCD.transfer".into()),
+    ///         ))
+    ///     };
+    /// }
+    ///
+    /// fuzz_target!(|data: &[u8]| TARGET.with(|target| target.borrow_mut().fuzz(data)));
+    /// ```
+    pub fn new(
+        chain: Chain,
+        invoker: AccountAddress,
+        address: ContractAddress,
+        receive_name: OwnedReceiveName,
+    ) -> Self {
+        let snapshot = chain.snapshot();
+        Self {
+            chain,
+            snapshot,
+            invoker,
+            sender: Address::Account(invoker),
+            energy: Energy::from(100_000),
+            address,
+            receive_name,
+            amount: Amount::zero(),
+        }
+    }
+
+    /// Set the sender of the updates.
+    pub fn sender(mut self, sender: Address) -> Self {
+        self.sender = sender;
+        self
+    }
+
+    /// Set the energy reserved for each update.
+    pub fn energy(mut self, energy: Energy) -> Self {
+        self.energy = energy;
+        self
+    }
+
+    /// Set the amount of CCD sent with each update.
+    pub fn amount(mut self, amount: Amount) -> Self {
+        self.amount = amount;
+        self
+    }
+
+    /// Get the chain. After an input has been executed, the chain is in the
+    /// state resulting from that input.
+    pub fn chain(&self) -> &Chain { &self.chain }
+
+    /// Execute the input as the parameter of an update and check the outcome.
+    ///
+    /// Inputs larger than the maximum parameter size are ignored. The chain is
+    /// restored to its initial state before the update, so the inputs do not
+    /// affect each other.
+    pub fn execute(&mut self, data: &[u8]) -> Option<EntrypointFuzzFinding> {
+        if data.len() > MAX_PARAMETER_LEN {
+            return None;
+        }
+        self.chain.restore(&self.snapshot);
+        let message = OwnedParameter::try_from(data.to_vec()).ok()?;
+        let before = total_ccd(&self.chain);
+        let result = self.chain.contract_update(
+            Signer::with_one_key(),
+            self.invoker,
+            self.sender,
+            self.energy,
+            UpdateContractPayload {
+                amount: self.amount,
+                address: self.address,
+                receive_name: self.receive_name.clone(),
+                message,
+            },
+        );
+        let after = total_ccd(&self.chain);
+        let transaction_fee = match result {
+            Ok(success) => success.transaction_fee,
+            Err(error) => match &error.kind {
+                ContractInvokeErrorKind::ExecutionError {
+                    failure_kind: v1::InvokeFailure::RuntimeError,
+                } => {
+                    return Some(EntrypointFuzzFinding::Trap {
+                        error,
+                    })
+                }
+                ContractInvokeErrorKind::OutOfEnergy {
+                    ..
+                } => {
+                    return Some(EntrypointFuzzFinding::OutOfEnergy {
+                        error,
+                    })
+                }
+                _ => error.transaction_fee,
+            },
+        };
+        let (Some(before), Some(after)) = (before, after) else {
+            return Some(EntrypointFuzzFinding::BalanceOverflow);
+        };
+        check_balance(before, after, transaction_fee)
+    }
+
+    /// Execute the input and panic if there is a finding, which is how
+    /// findings are reported to `cargo fuzz`.
+    pub fn fuzz(&mut self, data: &[u8]) {
+        if let Some(finding) = self.execute(data) {
+            panic!("{finding}");
+        }
+    }
+}
+
+/// The total amount of CCD on accounts and contracts, or `None` if it
+/// overflows.
+fn total_ccd(chain: &Chain) -> Option<Amount> {
+    let accounts = chain.accounts.values().map(|account| account.balance.total);
+    let contracts = chain.contracts.values().map(|contract| contract.self_balance);
+    accounts.chain(contracts).try_fold(Amount::zero(), |total, amount| total.checked_add(amount))
+}
+
+/// Check that the total amount of CCD only decreased by the transaction fee.
+fn check_balance(
+    before: Amount,
+    after: Amount,
+    transaction_fee: Amount,
+) -> Option<EntrypointFuzzFinding> {
+    if after.checked_add(transaction_fee) == Some(before) {
+        None
+    } else {
+        Some(EntrypointFuzzFinding::BalanceInconsistency {
+            before,
+            after,
+            transaction_fee,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the total amount of CCD includes accounts and contracts, and
+    /// that an overflow is detected.
+    #[test]
+    fn test_total_ccd() {
+        let mut chain = Chain::new();
+        chain.create_account(Account::new(AccountAddress([0; 32]), Amount::from_ccd(10)));
+        chain.create_account(Account::new(AccountAddress([1; 32]), Amount::from_ccd(5)));
+        assert_eq!(total_ccd(&chain), Some(Amount::from_ccd(15)));
+
+        // An overflow is reported as `None`, which is a finding in `execute`.
+        chain.create_account(Account::new(
+            AccountAddress([2; 32]),
+            Amount::from_micro_ccd(u64::MAX),
+        ));
+        assert_eq!(total_ccd(&chain), None);
+    }
+
+    /// Test that only a decrease by exactly the transaction fee is accepted.
+    #[test]
+    fn test_check_balance() {
+        let fee = Amount::from_micro_ccd(3);
+        assert!(check_balance(Amount::from_micro_ccd(10), Amount::from_micro_ccd(7), fee).is_none());
+        assert!(matches!(
+            check_balance(Amount::from_micro_ccd(10), Amount::from_micro_ccd(8), fee),
+            Some(EntrypointFuzzFinding::BalanceInconsistency { .. })
+        ));
+        assert!(matches!(
+            check_balance(Amount::from_micro_ccd(10), Amount::from_micro_ccd(6), fee),
+            Some(EntrypointFuzzFinding::BalanceInconsistency { .. })
+        ));
+    }
+}
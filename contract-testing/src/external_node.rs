@@ -0,0 +1,761 @@
+//! The backends used for communicating with an external node.
+//!
+//! [`GrpcNode`] queries a node via gRPC, and [`FixtureNode`] serves responses
+//! recorded in a JSON file, so tests using external contracts can run without
+//! network access.
+use crate::types::*;
+use concordium_rust_sdk::{
+    self as sdk,
+    base::{
+        base::Energy,
+        common,
+        contracts_common::{
+            AccountAddress, AccountBalance, Amount, ContractAddress, ExchangeRate, ExchangeRates,
+            ModuleReference, OwnedContractName, Timestamp,
+        },
+        hashes::{BlockHash, TransactionHash},
+        smart_contracts::{ModuleSource, WasmModule, WasmVersion},
+    },
+    types::{
+        smart_contracts::{ContractContext, InstanceInfo, InvokeContractResult},
+        AccountStakingInfo,
+    },
+    v2::{AccountIdentifier, BlockIdentifier, Endpoint},
+};
+use futures::TryStreamExt;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tokio::{runtime, time::timeout};
+
+/// The timeout duration set for queries with an external node.
+const EXTERNAL_NODE_QUERY_TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_secs(10);
+
+/// The timeout duration set for connecting to an external node.
+const EXTERNAL_NODE_CONNECT_TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_secs(3);
+
+/// Lock held while reading and writing fixture files, since tests in the same
+/// test binary run in parallel threads.
+static FIXTURE_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+impl GrpcNode {
+    /// Connect to a node via gRPC.
+    pub fn new(endpoint: Endpoint) -> Result<Self, SetupExternalNodeError> {
+        // Create the Tokio runtime. This should never fail, unless nested runtimes are
+        // created.
+        let runtime = runtime::Builder::new_multi_thread()
+            // Enable time, so timeouts can be used.
+            .enable_time()
+            // Enable I/O, so networking and other types of calls are possible.
+            .enable_io()
+            .build()
+            .expect("Internal error: Could not create Tokio runtime.");
+        let client = runtime.block_on(async {
+            timeout(EXTERNAL_NODE_CONNECT_TIMEOUT, sdk::v2::Client::new(endpoint))
+                .await
+                .map_err(|_| SetupExternalNodeError::ConnectTimeout)?
+                .map_err(SetupExternalNodeError::from)
+        })?;
+        Ok(Self {
+            client,
+            runtime,
+        })
+    }
+
+    /// Execute an async task with the [`sdk::v2::Client`].
+    ///
+    /// If the task takes longer than [`EXTERNAL_NODE_QUERY_TIMEOUT`] then the
+    /// query times out and an [`ExternalNodeError::QueryTimeout`] is returned.
+    ///
+    /// *This method cannot be nested, as that will cause a panic.*
+    pub(crate) fn with_client<T, E, F, Fut>(&self, f: F) -> Result<T, E>
+    where
+        E: From<ExternalNodeError>,
+        F: FnOnce(sdk::v2::Client) -> Fut,
+        Fut: Future<Output = Result<T, E>>, {
+        // Clone the client so it can be moved to the async block.
+        let client = self.client.clone();
+        // Run the future and timeout if it takes too long.
+        self.runtime.block_on(async move {
+            timeout(EXTERNAL_NODE_QUERY_TIMEOUT, f(client))
+                .await
+                .map_err(|_| E::from(ExternalNodeError::QueryTimeout))?
+        })
+    }
+}
+
+impl ExternalNodeBackend for GrpcNode {
+    fn block_hash(&self, block: Option<BlockHash>) -> Result<BlockHash, ExternalNodeError> {
+        let block_identifier = match block {
+            Some(block) => BlockIdentifier::Given(block),
+            None => BlockIdentifier::LastFinal,
+        };
+        self.with_client(|mut client| async move {
+            Ok(client.get_block_info(block_identifier).await?.block_hash)
+        })
+    }
+
+    fn block_time(&self, block: BlockHash) -> Result<Timestamp, ExternalNodeError> {
+        self.with_client(|mut client| async move {
+            let block_info = client.get_block_info(BlockIdentifier::Given(block)).await?.response;
+            // The node never returns timestamps < 0, so it is safe to cast it to `u64`.
+            Ok(Timestamp::from_timestamp_millis(
+                block_info.block_slot_time.timestamp_millis() as u64
+            ))
+        })
+    }
+
+    fn exchange_rates(&self, block: BlockHash) -> Result<ExchangeRates, ExternalNodeError> {
+        self.with_client(|mut client| async move {
+            let (euro_per_energy, micro_ccd_per_euro) = match client
+                .get_block_chain_parameters(BlockIdentifier::Given(block))
+                .await?
+                .response
+            {
+                sdk::v2::ChainParameters::V0(p) => (p.euro_per_energy, p.micro_ccd_per_euro),
+                sdk::v2::ChainParameters::V1(p) => (p.euro_per_energy, p.micro_ccd_per_euro),
+                sdk::v2::ChainParameters::V2(p) => (p.euro_per_energy, p.micro_ccd_per_euro),
+            };
+            Ok(ExchangeRates {
+                euro_per_energy,
+                micro_ccd_per_euro,
+            })
+        })
+    }
+
+    fn check_account(
+        &self,
+        block: BlockHash,
+        address: AccountAddress,
+    ) -> Result<(), ExternalNodeError> {
+        self.with_client(|mut client| async move {
+            client
+                .get_account_info(
+                    &AccountIdentifier::Address(address),
+                    BlockIdentifier::Given(block),
+                )
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn check_contract(
+        &self,
+        block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<(), ExternalNodeError> {
+        self.with_client(|mut client| async move {
+            client.get_instance_info(address, BlockIdentifier::Given(block)).await?;
+            Ok(())
+        })
+    }
+
+    fn invoke_instance(
+        &self,
+        block: BlockHash,
+        context: &ContractContext,
+    ) -> Result<InvokeContractResult, ExternalNodeError> {
+        let context = context.clone();
+        self.with_client(|mut client| async move {
+            Ok(client.invoke_instance(BlockIdentifier::Given(block), &context).await?.response)
+        })
+    }
+
+    fn contract_exists(
+        &self,
+        block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<bool, ExternalNodeError> {
+        not_found_as_none(self.check_contract(block, address)).map(|found| found.is_some())
+    }
+
+    fn module_source(
+        &self,
+        block: BlockHash,
+        module_reference: ModuleReference,
+    ) -> Result<Option<WasmModule>, ExternalNodeError> {
+        not_found_as_none(self.with_client(|mut client| async move {
+            Ok(client
+                .get_module_source(&module_reference, BlockIdentifier::Given(block))
+                .await?
+                .response)
+        }))
+    }
+
+    fn instance(
+        &self,
+        block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<Option<ExternalInstance>, ExternalNodeError> {
+        not_found_as_none(self.with_client(|mut client| async move {
+            let info = client.get_instance_info(address, BlockIdentifier::Given(block)).await?;
+            let InstanceInfo::V1 {
+                owner,
+                amount,
+                name,
+                source_module,
+                ..
+            } = info.response
+            else {
+                return Err(ExternalNodeError::UnsupportedInstance {
+                    address,
+                });
+            };
+            let state = client
+                .get_instance_state(address, BlockIdentifier::Given(block))
+                .await?
+                .response
+                .try_collect()
+                .await
+                .map_err(sdk::endpoints::QueryError::from)?;
+            Ok(ExternalInstance {
+                module_reference: source_module,
+                contract_name: name,
+                owner,
+                self_balance: amount,
+                state,
+            })
+        }))
+    }
+
+    fn account_balance(
+        &self,
+        block: BlockHash,
+        address: AccountAddress,
+    ) -> Result<Option<AccountBalance>, ExternalNodeError> {
+        not_found_as_none(self.with_client(|mut client| async move {
+            let info = client
+                .get_account_info(
+                    &AccountIdentifier::Address(address),
+                    BlockIdentifier::Given(block),
+                )
+                .await?
+                .response;
+            let staked = match info.account_stake {
+                Some(AccountStakingInfo::Baker {
+                    staked_amount,
+                    ..
+                })
+                | Some(AccountStakingInfo::Delegator {
+                    staked_amount,
+                    ..
+                }) => staked_amount,
+                None => Amount::zero(),
+            };
+            Ok(AccountBalance {
+                total: info.account_amount,
+                staked,
+                locked: info.account_release_schedule.total,
+            })
+        }))
+    }
+
+    fn transaction(&self, transaction: TransactionHash) -> Result<ReplayTransaction, ReplayError> {
+        self.replay_transaction(transaction)
+    }
+}
+
+/// Turn the error of a query for something that does not exist into `None`.
+fn not_found_as_none<T>(
+    result: Result<T, ExternalNodeError>,
+) -> Result<Option<T>, ExternalNodeError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ExternalNodeError::QueryError {
+            error: sdk::endpoints::QueryError::NotFound,
+        }) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+impl FixtureNode {
+    /// Serve the responses recorded in the JSON file at `path`. Queries that
+    /// have not been recorded fail with [`ExternalNodeError::NotRecorded`].
+    ///
+    /// This is meant for running tests, e.g., in CI, without network access.
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self, ExternalNodeError> {
+        Self::new(path.into(), None)
+    }
+
+    /// Serve the responses recorded in the JSON file at `path`, and record the
+    /// responses from the node at `endpoint` for queries that have not been
+    /// recorded yet. The file is created if it does not exist.
+    ///
+    /// The connection to the node is only made once a query has not been
+    /// recorded, so a test using a complete recording also runs without
+    /// network access.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use concordium_smart_contract_testing::*;
+    /// let fixture = FixtureNode::record(
+    ///     "tests/fixtures/cis2.json",
+    ///     Endpoint::from_static("http://node.testnet.concordium.com:20000"),
+    /// )
+    /// .unwrap();
+    /// let mut chain = Chain::builder()
+    ///     .external_node_backend(fixture)
+    ///     .external_query_block(
+    ///         "45c53a19cd782a8de981941feb5e0f875cefaba8d2cda958e76f471a4710a797".parse().unwrap(),
+    ///     )
+    ///     .build()
+    ///     .unwrap();
+    /// let contract = chain.add_external_contract(ContractAddress::new(5089, 0)).unwrap();
+    /// ```
+    pub fn record(path: impl Into<PathBuf>, endpoint: Endpoint) -> Result<Self, ExternalNodeError> {
+        Self::new(path.into(), Some(endpoint))
+    }
+
+    /// Load the recorded responses.
+    fn new(path: PathBuf, endpoint: Option<Endpoint>) -> Result<Self, ExternalNodeError> {
+        let responses = {
+            let _lock = FIXTURE_FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            read_responses(&path)?
+        };
+        Ok(Self {
+            path,
+            endpoint,
+            node: Default::default(),
+            responses: Mutex::new(responses),
+        })
+    }
+
+    /// Get the recorded response to a query, or query the node and record the
+    /// response if the fixture is recording.
+    fn query<T: Serialize + DeserializeOwned>(
+        &self,
+        query: &str,
+        arguments: &impl Serialize,
+        f: impl FnOnce(&GrpcNode) -> Result<T, ExternalNodeError>,
+    ) -> Result<T, ExternalNodeError> {
+        self.query_with_error(query, arguments, f)
+    }
+
+    /// Like [`Self::query`], except that querying the node can fail with
+    /// another error, e.g., a [`ReplayError`].
+    fn query_with_error<T: Serialize + DeserializeOwned, E: From<ExternalNodeError>>(
+        &self,
+        query: &str,
+        arguments: &impl Serialize,
+        f: impl FnOnce(&GrpcNode) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let key = query_key(query, arguments);
+        let recorded = self.lock_responses().get(&key).cloned();
+        if let Some(response) = recorded {
+            return serde_json::from_value(response).map_err(|e| self.file_error(e.into()).into());
+        }
+        let Some(endpoint) = &self.endpoint else {
+            return Err(ExternalNodeError::NotRecorded {
+                query: key,
+            }
+            .into());
+        };
+        if self.node.get().is_none() {
+            let node = GrpcNode::new(endpoint.clone()).map_err(|error| {
+                ExternalNodeError::CannotConnect {
+                    error: Box::new(error),
+                }
+            })?;
+            let _ = self.node.set(node);
+        }
+        let node = self.node.get().expect("The node has just been connected.");
+        let response = f(node)?;
+        let value = serde_json::to_value(&response).map_err(|e| self.file_error(e.into()))?;
+        self.lock_responses().insert(key.clone(), value.clone());
+
+        // Add the response to the file, which may have been updated by another fixture.
+        let _lock = FIXTURE_FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut responses = read_responses(&self.path)?;
+        responses.insert(key, value);
+        write_responses(&self.path, &responses)?;
+        Ok(response)
+    }
+
+    /// Lock the recorded responses.
+    fn lock_responses(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, serde_json::Value>> {
+        self.responses.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Construct an error about the fixture file.
+    fn file_error(&self, kind: FixtureFileErrorKind) -> ExternalNodeError {
+        ExternalNodeError::FixtureFile {
+            path: self.path.clone(),
+            kind,
+        }
+    }
+}
+
+impl ExternalNodeBackend for FixtureNode {
+    fn block_hash(&self, block: Option<BlockHash>) -> Result<BlockHash, ExternalNodeError> {
+        self.query("block_hash", &block, |node| node.block_hash(block))
+    }
+
+    fn block_time(&self, block: BlockHash) -> Result<Timestamp, ExternalNodeError> {
+        let millis = self
+            .query("block_time", &block, |node| Ok(node.block_time(block)?.timestamp_millis()))?;
+        Ok(Timestamp::from_timestamp_millis(millis))
+    }
+
+    fn exchange_rates(&self, block: BlockHash) -> Result<ExchangeRates, ExternalNodeError> {
+        // The rates are stored as pairs of numerator and denominator.
+        let [euro_per_energy, micro_ccd_per_euro] =
+            self.query("exchange_rates", &block, |node| {
+                let rates = node.exchange_rates(block)?;
+                Ok([rates.euro_per_energy, rates.micro_ccd_per_euro]
+                    .map(|rate| (rate.numerator(), rate.denominator())))
+            })?;
+        let to_rate = |(numerator, denominator)| ExchangeRate::new(numerator, denominator);
+        match (to_rate(euro_per_energy), to_rate(micro_ccd_per_euro)) {
+            (Some(euro_per_energy), Some(micro_ccd_per_euro)) => Ok(ExchangeRates {
+                euro_per_energy,
+                micro_ccd_per_euro,
+            }),
+            _ => Err(self.file_error(FixtureFileErrorKind::InvalidResponse {
+                query: query_key("exchange_rates", &block),
+            })),
+        }
+    }
+
+    fn check_account(
+        &self,
+        block: BlockHash,
+        address: AccountAddress,
+    ) -> Result<(), ExternalNodeError> {
+        self.query("check_account", &(block, address), |node| node.check_account(block, address))
+    }
+
+    fn check_contract(
+        &self,
+        block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<(), ExternalNodeError> {
+        self.query("check_contract", &(block, address), |node| node.check_contract(block, address))
+    }
+
+    fn invoke_instance(
+        &self,
+        block: BlockHash,
+        context: &ContractContext,
+    ) -> Result<InvokeContractResult, ExternalNodeError> {
+        self.query("invoke_instance", &(block, context), |node| {
+            node.invoke_instance(block, context)
+        })
+    }
+
+    fn contract_exists(
+        &self,
+        block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<bool, ExternalNodeError> {
+        self.query("contract_exists", &(block, address), |node| {
+            node.contract_exists(block, address)
+        })
+    }
+
+    fn module_source(
+        &self,
+        block: BlockHash,
+        module_reference: ModuleReference,
+    ) -> Result<Option<WasmModule>, ExternalNodeError> {
+        // The module is stored as its version and the hex encoding of its source.
+        let module = self.query("module_source", &(block, module_reference), |node| {
+            let module = node.module_source(block, module_reference)?;
+            Ok(module.map(|module| {
+                let version = match module.version {
+                    WasmVersion::V0 => 0u8,
+                    WasmVersion::V1 => 1u8,
+                };
+                (version, to_hex(module.source.as_ref()))
+            }))
+        })?;
+        let Some((version, source)) = module else {
+            return Ok(None);
+        };
+        let invalid = || {
+            self.file_error(FixtureFileErrorKind::InvalidResponse {
+                query: query_key("module_source", &(block, module_reference)),
+            })
+        };
+        let version = match version {
+            0 => WasmVersion::V0,
+            1 => WasmVersion::V1,
+            _ => return Err(invalid()),
+        };
+        let source = from_hex(&source).ok_or_else(invalid)?;
+        Ok(Some(WasmModule {
+            version,
+            source: ModuleSource::from(source),
+        }))
+    }
+
+    fn instance(
+        &self,
+        block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<Option<ExternalInstance>, ExternalNodeError> {
+        let instance = self.query("instance", &(block, address), |node| {
+            Ok(node.instance(block, address)?.map(|instance| RecordedInstance {
+                module_reference: instance.module_reference,
+                contract_name:    instance.contract_name.to_string(),
+                owner:            instance.owner,
+                self_balance:     instance.self_balance,
+                state:            instance
+                    .state
+                    .iter()
+                    .map(|(key, value)| (to_hex(key), to_hex(value)))
+                    .collect(),
+            }))
+        })?;
+        let Some(instance) = instance else {
+            return Ok(None);
+        };
+        let invalid = || {
+            self.file_error(FixtureFileErrorKind::InvalidResponse {
+                query: query_key("instance", &(block, address)),
+            })
+        };
+        let state = instance
+            .state
+            .iter()
+            .map(|(key, value)| Some((from_hex(key)?, from_hex(value)?)))
+            .collect::<Option<_>>()
+            .ok_or_else(invalid)?;
+        Ok(Some(ExternalInstance {
+            module_reference: instance.module_reference,
+            contract_name: OwnedContractName::new(instance.contract_name).map_err(|_| invalid())?,
+            owner: instance.owner,
+            self_balance: instance.self_balance,
+            state,
+        }))
+    }
+
+    fn account_balance(
+        &self,
+        block: BlockHash,
+        address: AccountAddress,
+    ) -> Result<Option<AccountBalance>, ExternalNodeError> {
+        // The balance is stored as the total, staked and locked amounts.
+        let balance = self.query("account_balance", &(block, address), |node| {
+            let balance = node.account_balance(block, address)?;
+            Ok(balance.map(|balance| (balance.total, balance.staked, balance.locked)))
+        })?;
+        Ok(balance.map(|(total, staked, locked)| AccountBalance {
+            total,
+            staked,
+            locked,
+        }))
+    }
+
+    fn transaction(&self, transaction: TransactionHash) -> Result<ReplayTransaction, ReplayError> {
+        let recorded = self.query_with_error("transaction", &transaction, |node| {
+            node.transaction(transaction).map(RecordedTransaction::from)
+        })?;
+        recorded.try_into().map_err(|()| {
+            ReplayError::Node(self.file_error(FixtureFileErrorKind::InvalidResponse {
+                query: query_key("transaction", &transaction),
+            }))
+        })
+    }
+}
+
+/// A contract instance as stored in a fixture file.
+#[derive(Serialize, Deserialize)]
+struct RecordedInstance {
+    module_reference: ModuleReference,
+    contract_name:    String,
+    owner:            AccountAddress,
+    self_balance:     Amount,
+    /// The hex encoded keys and values of the state.
+    state:            Vec<(String, String)>,
+}
+
+/// A transaction to replay as stored in a fixture file.
+#[derive(Serialize, Deserialize)]
+struct RecordedTransaction {
+    hash:               TransactionHash,
+    block:              BlockHash,
+    parent_block:       BlockHash,
+    /// The block time in milliseconds.
+    block_time:         u64,
+    /// The exchange rates as pairs of numerator and denominator.
+    euro_per_energy:    (u64, u64),
+    micro_ccd_per_euro: (u64, u64),
+    sender:             AccountAddress,
+    num_signatures:     u32,
+    energy_reserved:    u64,
+    /// The address of the initialized contract, if the transaction is a
+    /// contract init, and `None` if it is an update.
+    init_address:       Option<ContractAddress>,
+    /// The hex encoded binary serialization of the init or update payload.
+    payload:            String,
+    contracts:          Vec<ContractAddress>,
+    modules:            Vec<ModuleReference>,
+    accounts:           Vec<AccountAddress>,
+}
+
+impl From<ReplayTransaction> for RecordedTransaction {
+    fn from(transaction: ReplayTransaction) -> Self {
+        let (init_address, payload) = match &transaction.payload {
+            ReplayPayload::InitContract {
+                payload,
+                address,
+            } => (Some(*address), common::to_bytes(payload)),
+            ReplayPayload::Update {
+                payload,
+            } => (None, common::to_bytes(payload)),
+        };
+        let rate = |rate: ExchangeRate| (rate.numerator(), rate.denominator());
+        Self {
+            hash: transaction.hash,
+            block: transaction.block,
+            parent_block: transaction.parent_block,
+            block_time: transaction.block_time.timestamp_millis(),
+            euro_per_energy: rate(transaction.euro_per_energy),
+            micro_ccd_per_euro: rate(transaction.micro_ccd_per_euro),
+            sender: transaction.sender,
+            num_signatures: transaction.num_signatures,
+            energy_reserved: transaction.energy_reserved.energy,
+            init_address,
+            payload: to_hex(&payload),
+            contracts: transaction.contracts,
+            modules: transaction.modules,
+            accounts: transaction.accounts,
+        }
+    }
+}
+
+impl TryFrom<RecordedTransaction> for ReplayTransaction {
+    type Error = ();
+
+    fn try_from(recorded: RecordedTransaction) -> Result<Self, ()> {
+        let payload = from_hex(&recorded.payload).ok_or(())?;
+        let mut payload = std::io::Cursor::new(payload);
+        let payload = match recorded.init_address {
+            Some(address) => ReplayPayload::InitContract {
+                payload: common::from_bytes(&mut payload).map_err(|_| ())?,
+                address,
+            },
+            None => ReplayPayload::Update {
+                payload: common::from_bytes(&mut payload).map_err(|_| ())?,
+            },
+        };
+        let rate = |(numerator, denominator)| ExchangeRate::new(numerator, denominator).ok_or(());
+        Ok(Self {
+            hash: recorded.hash,
+            block: recorded.block,
+            parent_block: recorded.parent_block,
+            block_time: Timestamp::from_timestamp_millis(recorded.block_time),
+            euro_per_energy: rate(recorded.euro_per_energy)?,
+            micro_ccd_per_euro: rate(recorded.micro_ccd_per_euro)?,
+            sender: recorded.sender,
+            num_signatures: recorded.num_signatures,
+            energy_reserved: Energy::from(recorded.energy_reserved),
+            payload,
+            contracts: recorded.contracts,
+            modules: recorded.modules,
+            accounts: recorded.accounts,
+        })
+    }
+}
+
+/// Encode bytes as hex.
+pub(crate) fn to_hex(bytes: &[u8]) -> String { bytes.iter().map(|b| format!("{b:02x}")).collect() }
+
+/// Decode hex, or return `None` if it is invalid.
+pub(crate) fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// The key of a query in a fixture file, which is the name of the query
+/// followed by its arguments as JSON.
+fn query_key(query: &str, arguments: &impl Serialize) -> String {
+    // The arguments are plain data, so serializing them cannot fail.
+    let arguments = serde_json::to_string(arguments).unwrap_or_default();
+    format!("{query} {arguments}")
+}
+
+/// Read the responses in a fixture file. A missing file has no responses.
+fn read_responses(path: &Path) -> Result<BTreeMap<String, serde_json::Value>, ExternalNodeError> {
+    let to_error = |kind| ExternalNodeError::FixtureFile {
+        path: path.to_path_buf(),
+        kind,
+    };
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(to_error(e.into())),
+    };
+    serde_json::from_slice(&contents).map_err(|e| to_error(e.into()))
+}
+
+/// Write the responses to a fixture file.
+fn write_responses(
+    path: &Path,
+    responses: &BTreeMap<String, serde_json::Value>,
+) -> Result<(), ExternalNodeError> {
+    let to_error = |kind| ExternalNodeError::FixtureFile {
+        path: path.to_path_buf(),
+        kind,
+    };
+    let mut contents = serde_json::to_vec_pretty(responses).map_err(|e| to_error(e.into()))?;
+    contents.push(b'\n');
+    std::fs::write(path, contents).map_err(|e| to_error(e.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that hex encoding round-trips and that invalid hex is rejected.
+    #[test]
+    fn test_hex() {
+        assert_eq!(to_hex(&[0, 1, 171, 255]), "0001abff");
+        assert_eq!(from_hex("0001abff"), Some(vec![0, 1, 171, 255]));
+        assert_eq!(from_hex("0g"), None);
+        assert_eq!(from_hex("abc"), None);
+    }
+
+    /// Test that recorded responses are served, and that queries which have
+    /// not been recorded fail when replaying.
+    #[test]
+    fn test_fixture_replay() {
+        let path = std::env::temp_dir()
+            .join(format!("concordium-external-node-fixture-{}.json", std::process::id()));
+        let block = BlockHash::new([1; 32]);
+        let responses = BTreeMap::from([
+            (query_key("block_hash", &None::<BlockHash>), serde_json::to_value(block).unwrap()),
+            (query_key("block_time", &block), serde_json::json!(1000)),
+            (query_key("exchange_rates", &block), serde_json::json!([[1, 50000], [50000, 1]])),
+        ]);
+        write_responses(&path, &responses).expect("Writing the fixture works");
+
+        let fixture = FixtureNode::replay(&path).expect("Loading the fixture works");
+        assert_eq!(fixture.block_hash(None).unwrap(), block);
+        assert_eq!(fixture.block_time(block).unwrap(), Timestamp::from_timestamp_millis(1000));
+        let rates = fixture.exchange_rates(block).unwrap();
+        assert_eq!(rates.euro_per_energy, ExchangeRate::new_unchecked(1, 50000));
+        assert_eq!(rates.micro_ccd_per_euro, ExchangeRate::new_unchecked(50000, 1));
+        assert!(matches!(
+            fixture.check_contract(block, ContractAddress::new(0, 0)),
+            Err(ExternalNodeError::NotRecorded { .. })
+        ));
+
+        let chain = Chain::builder()
+            .external_node_backend(fixture)
+            .euro_per_energy_from_external()
+            .block_time_from_external()
+            .build()
+            .expect("Building the chain from the fixture works");
+        assert_eq!(chain.external_query_block().unwrap(), block);
+        assert_eq!(chain.block_time(), Timestamp::from_timestamp_millis(1000));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,321 @@
+//! Fork mode, where a [`Chain`] lazily loads accounts, contracts and modules
+//! from the external node, so they can be used and modified locally.
+//!
+//! Items are loaded on first use by [`Chain::contract_init`] and
+//! [`Chain::contract_update`]. The invocation handler records the items it
+//! looks up that do not exist on the chain, e.g., a contract called by another
+//! contract. After an update, these are loaded and, if anything was loaded,
+//! the update is executed again before its changes are saved. An update that
+//! only uses items that are already loaded is therefore executed once.
+use crate::{state::persistent_state_from_entries, types::*};
+use concordium_rust_sdk::{
+    self as sdk,
+    base::{
+        base::{Energy, InitContractPayload, UpdateContractPayload},
+        contracts_common::{AccountAddress, Address, ContractAddress, ModuleReference},
+        smart_contracts::WasmVersion,
+    },
+};
+
+impl Chain {
+    /// Check whether the chain is a fork of the external node, i.e., whether
+    /// it was built with [`ChainBuilder::fork`].
+    pub fn is_fork(&self) -> bool { self.fork.is_some() }
+
+    /// Load an account from the external node, if the chain is a fork and the
+    /// account does not exist locally.
+    ///
+    /// Only the balance of the account is loaded. The locked amount is part of
+    /// the balance, but the release schedule is not loaded, since the
+    /// [`ExternalNodeBackend`] does not provide it, so the amount stays locked
+    /// regardless of the block time. The keys and policy of the account are
+    /// the defaults used by [`Account::new_with_balance`].
+    ///
+    /// Returns whether the account exists on the chain afterwards.
+    pub fn fork_load_account(
+        &mut self,
+        address: AccountAddress,
+    ) -> Result<bool, ExternalNodeError> {
+        if self.account_exists(address) {
+            return Ok(true);
+        }
+        let Some((connection, fork)) = self.fork_connection() else {
+            return Ok(false);
+        };
+        if fork.absent.accounts.contains(&address) {
+            return Ok(false);
+        }
+        match connection.backend.account_balance(connection.query_block, address)? {
+            Some(balance) => {
+                self.create_account(Account::new_with_balance(address, balance));
+                Ok(true)
+            }
+            None => {
+                self.fork_mut().absent.accounts.insert(address);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Load a contract from the external node, including its state and
+    /// module, if the chain is a fork and the contract does not exist
+    /// locally.
+    ///
+    /// Returns whether the contract exists on the chain afterwards.
+    pub fn fork_load_contract(
+        &mut self,
+        address: ContractAddress,
+    ) -> Result<bool, ExternalNodeError> {
+        if self.contract_exists(address) {
+            return Ok(true);
+        }
+        let Some((connection, fork)) = self.fork_connection() else {
+            return Ok(false);
+        };
+        if fork.absent.contracts.contains(&address) {
+            return Ok(false);
+        }
+        let Some(instance) = connection.backend.instance(connection.query_block, address)? else {
+            self.fork_mut().absent.contracts.insert(address);
+            return Ok(false);
+        };
+        // The module of an existing contract always exists on the node.
+        if !self.fork_load_module(instance.module_reference)? {
+            return Err(ExternalNodeError::QueryError {
+                error: sdk::endpoints::QueryError::NotFound,
+            });
+        }
+        self.contracts.insert(address, Contract {
+            address,
+            module_reference: instance.module_reference,
+            contract_name: instance.contract_name,
+            state: persistent_state_from_entries(instance.state),
+            owner: instance.owner,
+            self_balance: instance.self_balance,
+        });
+        Ok(true)
+    }
+
+    /// Load a module from the external node, if the chain is a fork and the
+    /// module does not exist locally. The module is loaded without support
+    /// for debug output.
+    ///
+    /// Returns whether the module exists on the chain afterwards.
+    pub fn fork_load_module(
+        &mut self,
+        module_reference: ModuleReference,
+    ) -> Result<bool, ExternalNodeError> {
+        if self.modules.contains_key(&module_reference) {
+            return Ok(true);
+        }
+        let Some((connection, fork)) = self.fork_connection() else {
+            return Ok(false);
+        };
+        if fork.absent.modules.contains(&module_reference) {
+            return Ok(false);
+        }
+        let Some(wasm_module) =
+            connection.backend.module_source(connection.query_block, module_reference)?
+        else {
+            self.fork_mut().absent.modules.insert(module_reference);
+            return Ok(false);
+        };
+        if wasm_module.version != WasmVersion::V1 {
+            return Err(ExternalNodeError::UnsupportedModule {
+                module_reference,
+            });
+        }
+        let module = ContractModule::from_wasm_module(wasm_module, false).map_err(|error| {
+            ExternalNodeError::InvalidModule {
+                module_reference,
+                error,
+            }
+        })?;
+        self.modules.insert(module_reference, module);
+        Ok(true)
+    }
+
+    /// Load everything needed for updating a contract from the external node,
+    /// if the chain is a fork.
+    ///
+    /// This is done automatically by [`Chain::contract_update`], but it must
+    /// be called before [`Chain::contract_invoke`], which cannot modify the
+    /// chain. The parameters are the same as for [`Chain::contract_invoke`].
+    ///
+    /// The contracts, accounts and modules used by the contract are found by
+    /// repeatedly invoking it and loading the ones that were missing, until
+    /// nothing new is found.
+    pub fn fork_load_invocation(
+        &mut self,
+        invoker: AccountAddress,
+        sender: Address,
+        energy_reserved: Energy,
+        payload: &UpdateContractPayload,
+    ) -> Result<(), ExternalNodeError> {
+        if !self.fork_load_update(invoker, sender, payload)? {
+            return Ok(());
+        }
+
+        // The dry runs should not be part of the coverage or the traced records.
+        let coverage = self.coverage.take();
+        let call_records = self.call_records.take();
+        let result = loop {
+            let _ = self.contract_invoke(invoker, sender, energy_reserved, payload.clone());
+            match self.fork_load_missing() {
+                Ok(true) => {}
+                Ok(false) => break Ok(()),
+                Err(error) => break Err(error),
+            }
+        };
+        self.coverage = coverage;
+        *self.call_records.get_mut() = call_records;
+        result
+    }
+
+    /// Load the invoker, the sender and the contract to update from the
+    /// external node, if the chain is a fork. The items used by the contract
+    /// are loaded by [`Chain::fork_load_missing`] after an invocation.
+    ///
+    /// Returns whether the chain is a fork and the contract exists.
+    pub(crate) fn fork_load_update(
+        &mut self,
+        invoker: AccountAddress,
+        sender: Address,
+        payload: &UpdateContractPayload,
+    ) -> Result<bool, ExternalNodeError> {
+        let Some(fork) = &mut self.fork else {
+            return Ok(false);
+        };
+        // Forget the items recorded by earlier invocations, e.g., by
+        // `contract_invoke`, so only the items used by this update are loaded.
+        *fork.missing.get_mut() = ForkItems::default();
+        self.fork_load_account(invoker)?;
+        match sender {
+            Address::Account(address) => self.fork_load_account(address)?,
+            Address::Contract(address) => self.fork_load_contract(address)?,
+        };
+        self.fork_load_contract(payload.address)
+    }
+
+    /// Load the items that the invocation handler looked up since the last
+    /// call, but which did not exist on the chain.
+    ///
+    /// Returns whether anything was loaded, in which case an invocation must
+    /// be executed again to see the loaded items.
+    pub(crate) fn fork_load_missing(&mut self) -> Result<bool, ExternalNodeError> {
+        let Some(fork) = &mut self.fork else {
+            return Ok(false);
+        };
+        let missing = std::mem::take(fork.missing.get_mut());
+        // An item can be recorded more than once, e.g., a module that is loaded
+        // together with a contract, so only count the items that did not exist.
+        let mut loaded = false;
+        for address in missing.accounts {
+            loaded |= !self.account_exists(address) && self.fork_load_account(address)?;
+        }
+        for address in missing.contracts {
+            loaded |= !self.contract_exists(address) && self.fork_load_contract(address)?;
+        }
+        for module_reference in missing.modules {
+            loaded |= !self.modules.contains_key(&module_reference)
+                && self.fork_load_module(module_reference)?;
+        }
+        Ok(loaded)
+    }
+
+    /// Load everything needed for initializing a contract from the external
+    /// node, if the chain is a fork. This also ensures that the new contract
+    /// gets an index after the contracts on the external node.
+    pub(crate) fn fork_load_init(
+        &mut self,
+        sender: AccountAddress,
+        payload: &InitContractPayload,
+    ) -> Result<(), ExternalNodeError> {
+        if !self.is_fork() {
+            return Ok(());
+        }
+        self.fork_load_account(sender)?;
+        self.fork_load_module(payload.mod_ref)?;
+        let count = match self.fork_mut().external_contract_count {
+            Some(count) => count,
+            None => {
+                let count = self.fork_external_contract_count()?;
+                self.fork_mut().external_contract_count = Some(count);
+                count
+            }
+        };
+        self.next_contract_index = self.next_contract_index.max(count);
+        Ok(())
+    }
+
+    /// Find the number of contracts on the external node. Contracts are never
+    /// removed, so the indices of the contracts are `0..count`, and the count
+    /// can be found with a binary search.
+    fn fork_external_contract_count(&self) -> Result<u64, ExternalNodeError> {
+        let Some((connection, _)) = self.fork_connection() else {
+            return Ok(0);
+        };
+        let exists = |index| {
+            connection
+                .backend
+                .contract_exists(connection.query_block, ContractAddress::new(index, 0))
+        };
+        if !exists(0)? {
+            return Ok(0);
+        }
+        // Find an index that does not exist, such that the count is in
+        // `existing + 1..=missing`.
+        let (mut existing, mut missing) = (0u64, 1u64);
+        while exists(missing)? {
+            existing = missing;
+            missing = missing.saturating_mul(2);
+        }
+        while missing - existing > 1 {
+            let middle = existing + (missing - existing) / 2;
+            if exists(middle)? {
+                existing = middle;
+            } else {
+                missing = middle;
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Record that an account was looked up during an invocation, but did not
+    /// exist on the chain.
+    pub(crate) fn fork_record_missing_account(&self, address: AccountAddress) {
+        if let Some(fork) = &self.fork {
+            fork.missing.borrow_mut().accounts.insert(address);
+        }
+    }
+
+    /// Record that a contract was looked up during an invocation, but did not
+    /// exist on the chain.
+    pub(crate) fn fork_record_missing_contract(&self, address: ContractAddress) {
+        if let Some(fork) = &self.fork {
+            fork.missing.borrow_mut().contracts.insert(address);
+        }
+    }
+
+    /// Record that a module was looked up during an invocation, but did not
+    /// exist on the chain.
+    pub(crate) fn fork_record_missing_module(&self, module_reference: ModuleReference) {
+        if let Some(fork) = &self.fork {
+            fork.missing.borrow_mut().modules.insert(module_reference);
+        }
+    }
+
+    /// Get the external node connection and the fork state, if the chain is a
+    /// fork.
+    fn fork_connection(&self) -> Option<(&ExternalNodeConnection, &Fork)> {
+        Some((self.external_node_connection.as_ref()?, self.fork.as_ref()?))
+    }
+
+    /// Get the fork state.
+    ///
+    /// **Preconditions:**
+    ///  - The chain is a fork.
+    fn fork_mut(&mut self) -> &mut Fork {
+        self.fork.as_mut().expect("Precondition violation: the chain must be a fork")
+    }
+}
@@ -0,0 +1,417 @@
+//! A stateful fuzzing harness, which executes random sequences of
+//! transactions and time ticks on a [`Chain`] and checks invariants over the
+//! chain state after every step.
+use crate::types::*;
+use concordium_rust_sdk::base::{
+    base::Energy,
+    contracts_common::{AccountAddress, Address, Duration},
+    transactions::UpdateContractPayload,
+};
+use rand::{Rng, SeedableRng};
+
+/// The energy used by [`FuzzStep::update`].
+const DEFAULT_ENERGY: Energy = Energy {
+    energy: 100_000,
+};
+
+/// A step of a sequence together with the index of the action that generated
+/// it.
+type Step = (usize, FuzzStep);
+
+/// An invariant violation: the number of steps executed before the violation,
+/// the index of the invariant, and its message.
+type Violation = (usize, usize, String);
+
+impl FuzzStep {
+    /// Update a contract with the invoker as the sender, using a default
+    /// amount of energy of 100000 NRG.
+    pub fn update(invoker: AccountAddress, payload: UpdateContractPayload) -> Self {
+        FuzzStep::Update {
+            invoker,
+            sender: Address::Account(invoker),
+            energy: DEFAULT_ENERGY,
+            payload,
+        }
+    }
+
+    /// Execute the step on the chain and describe the outcome.
+    ///
+    /// Failing transactions are not errors, since a random sequence of
+    /// transactions is expected to contain some that fail.
+    fn execute(&self, chain: &mut Chain) -> String {
+        match self {
+            FuzzStep::Update {
+                invoker,
+                sender,
+                energy,
+                payload,
+            } => {
+                match chain.contract_update(
+                    Signer::with_one_key(),
+                    *invoker,
+                    *sender,
+                    *energy,
+                    payload.clone(),
+                ) {
+                    Ok(success) => format!("Succeeded using {}NRG.", success.energy_used),
+                    Err(error) => format!("Failed: {error}"),
+                }
+            }
+            FuzzStep::Transfer {
+                sender,
+                receiver,
+                amount,
+            } => {
+                match chain.account_transfer(Signer::with_one_key(), *sender, *receiver, *amount) {
+                    Ok(_) => "Succeeded.".to_string(),
+                    Err(error) => format!("Failed: {error}"),
+                }
+            }
+            FuzzStep::TickTime(duration) => match chain.tick_block_time(*duration) {
+                Ok(()) => format!("Block time is now {}.", chain.block_time()),
+                Err(error) => format!("Failed: {error}"),
+            },
+        }
+    }
+}
+
+impl ChainFuzzer {
+    /// Create a fuzzer which starts every run from the given chain.
+    ///
+    /// By default, 100 sequences of up to 20 steps are tried, starting from a
+    /// random seed. Every step is generated by a randomly chosen action, which
+    /// is given the random number generator and the current chain.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use concordium_smart_contract_testing::*;
+    /// # use rand::Rng;
+    /// # let chain = Chain::new();
+    /// # let contract = ContractAddress::new(0, 0);
+    /// const ALICE: AccountAddress = AccountAddress([0; 32]);
+    /// const BOB: AccountAddress = AccountAddress([1; 32]);
+    ///
+    /// let mut fuzzer = ChainFuzzer::new(chain)
+    ///     .action("transfer", |rng, _chain| FuzzStep::Transfer {
+    ///         sender:   ALICE,
+    ///         receiver: BOB,
+    ///         amount:   Amount::from_micro_ccd(rng.gen_range(0..1000)),
+    ///     })
+    ///     .action("tick", |rng, _chain| {
+    ///         FuzzStep::TickTime(Duration::from_millis(rng.gen_range(0..1000)))
+    ///     })
+    ///     .invariant("contract keeps its balance", move |chain| {
+    ///         match chain.contract_balance(contract) {
+    ///             Some(balance) if balance == Amount::from_ccd(10) => Ok(()),
+    ///             other => Err(format!("The contract balance is {other:?}")),
+    ///         }
+    ///     });
+    ///
+    /// // Panics with the seed and a shrunk trace if an invariant fails.
+    /// fuzzer.run().unwrap();
+    /// ```
+    pub fn new(chain: Chain) -> Self {
+        Self {
+            chain,
+            actions: Vec::new(),
+            invariants: Vec::new(),
+            seed: rand::random(),
+            runs: 100,
+            max_steps: 20,
+        }
+    }
+
+    /// Set the seed of the first run. Run `n` uses the seed `seed + n`.
+    ///
+    /// A failure reported by [`ChainFuzzer::run`] can be reproduced with
+    /// `.seed(failure.seed).runs(1)`.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set the number of random sequences to try.
+    pub fn runs(mut self, runs: u64) -> Self {
+        self.runs = runs;
+        self
+    }
+
+    /// Set the maximum number of steps in a sequence.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Add an action, which generates a step from the random number generator
+    /// and the current state of the chain.
+    pub fn action(
+        mut self,
+        name: impl Into<String>,
+        generate: impl Fn(&mut FuzzRng, &Chain) -> FuzzStep + 'static,
+    ) -> Self {
+        self.actions.push(FuzzAction {
+            name:     name.into(),
+            generate: Box::new(generate),
+        });
+        self
+    }
+
+    /// Add an invariant, which must hold for the initial chain and after every
+    /// step. A violation is reported by returning an error message.
+    pub fn invariant(
+        mut self,
+        name: impl Into<String>,
+        check: impl Fn(&Chain) -> Result<(), String> + 'static,
+    ) -> Self {
+        self.invariants.push(FuzzInvariant {
+            name:  name.into(),
+            check: Box::new(check),
+        });
+        self
+    }
+
+    /// Get the chain that every run starts from.
+    pub fn chain(&self) -> &Chain { &self.chain }
+
+    /// Run the fuzzer.
+    ///
+    /// Returns the first invariant violation found, with the sequence of steps
+    /// shrunk to a minimal sequence that still violates the same invariant.
+    /// The chain is restored to its initial state afterwards.
+    pub fn run(&mut self) -> Result<(), FuzzFailure> {
+        let snapshot = self.chain.snapshot();
+        let mut result = Ok(());
+        for run in 0..self.runs {
+            let seed = self.seed.wrapping_add(run);
+            self.chain.restore(&snapshot);
+            if let Some((steps, invariant)) = self.generate(seed) {
+                result = Err(self.shrink(&snapshot, seed, steps, invariant));
+                break;
+            }
+        }
+        self.chain.restore(&snapshot);
+        result
+    }
+
+    /// Generate and execute a random sequence of steps. Returns the steps and
+    /// the index of the violated invariant if an invariant fails.
+    fn generate(&mut self, seed: u64) -> Option<(Vec<Step>, usize)> {
+        if let Err((invariant, _)) = self.check_invariants() {
+            return Some((Vec::new(), invariant));
+        }
+        if self.actions.is_empty() {
+            return None;
+        }
+        let mut rng = FuzzRng::seed_from_u64(seed);
+        let len = rng.gen_range(1..=self.max_steps.max(1));
+        let mut steps = Vec::with_capacity(len);
+        for _ in 0..len {
+            let index = rng.gen_range(0..self.actions.len());
+            let step = (self.actions[index].generate)(&mut rng, &self.chain);
+            step.execute(&mut self.chain);
+            steps.push((index, step));
+            if let Err((invariant, _)) = self.check_invariants() {
+                return Some((steps, invariant));
+            }
+        }
+        None
+    }
+
+    /// Shrink a failing sequence by greedily removing steps for as long as the
+    /// same invariant is still violated, and then replay it to build the
+    /// failure.
+    fn shrink(
+        &mut self,
+        snapshot: &ChainSnapshot,
+        seed: u64,
+        mut steps: Vec<Step>,
+        invariant: usize,
+    ) -> FuzzFailure {
+        let mut shrunk = true;
+        while shrunk {
+            shrunk = false;
+            let mut i = steps.len();
+            while i > 0 {
+                i -= 1;
+                let mut candidate = steps.clone();
+                candidate.remove(i);
+                if let Some((len, violated, _)) = self.replay(snapshot, &candidate, &mut Vec::new())
+                {
+                    if violated == invariant {
+                        candidate.truncate(len);
+                        steps = candidate;
+                        i = i.min(steps.len());
+                        shrunk = true;
+                    }
+                }
+            }
+        }
+
+        let mut outcomes = Vec::new();
+        let (len, violated, message) = self
+            .replay(snapshot, &steps, &mut outcomes)
+            .expect("The shrunk sequence violates the invariant.");
+        steps.truncate(len);
+        let steps = steps
+            .into_iter()
+            .zip(outcomes)
+            .map(|((index, step), outcome)| FuzzTraceStep {
+                action: self.actions[index].name.clone(),
+                step,
+                outcome,
+            })
+            .collect();
+        FuzzFailure {
+            seed,
+            invariant: self.invariants[violated].name.clone(),
+            message,
+            steps,
+        }
+    }
+
+    /// Execute the steps from the snapshot and return the first invariant
+    /// violation, if any. The outcome of each executed step is added to
+    /// `outcomes`.
+    fn replay(
+        &mut self,
+        snapshot: &ChainSnapshot,
+        steps: &[Step],
+        outcomes: &mut Vec<String>,
+    ) -> Option<Violation> {
+        self.chain.restore(snapshot);
+        if let Err((invariant, message)) = self.check_invariants() {
+            return Some((0, invariant, message));
+        }
+        for (n, (_, step)) in steps.iter().enumerate() {
+            outcomes.push(step.execute(&mut self.chain));
+            if let Err((invariant, message)) = self.check_invariants() {
+                return Some((n + 1, invariant, message));
+            }
+        }
+        None
+    }
+
+    /// Check the invariants in order. Returns the index and message of the
+    /// first violated invariant.
+    fn check_invariants(&self) -> Result<(), (usize, String)> {
+        for (index, invariant) in self.invariants.iter().enumerate() {
+            (invariant.check)(&self.chain).map_err(|message| (index, message))?;
+        }
+        Ok(())
+    }
+}
+
+/// Display the steps of a failing sequence, one numbered step per line.
+pub(crate) fn display_fuzz_trace(steps: &[FuzzTraceStep]) -> String {
+    let mut out = String::new();
+    for (n, trace_step) in steps.iter().enumerate() {
+        let step = match &trace_step.step {
+            FuzzStep::Update {
+                invoker,
+                sender,
+                energy,
+                payload,
+            } => {
+                let sender = match sender {
+                    Address::Account(address) => address.to_string(),
+                    Address::Contract(address) => address.to_string(),
+                };
+                let message: String =
+                    payload.message.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+                format!(
+                    "Update {} with '{}', sent by {sender} (invoker {invoker}, {energy}NRG), \
+                     amount {} and parameter 0x{message}",
+                    payload.address, payload.receive_name, payload.amount
+                )
+            }
+            FuzzStep::Transfer {
+                sender,
+                receiver,
+                amount,
+            } => format!("Transfer {amount} from {sender} to {receiver}"),
+            FuzzStep::TickTime(duration) => {
+                format!("Tick the block time by {}ms", duration.millis())
+            }
+        };
+        out.push_str(&format!(
+            "{:>4}. [{}] {step}\n      => {}\n",
+            n + 1,
+            trace_step.action,
+            trace_step.outcome
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use concordium_rust_sdk::base::contracts_common::Amount;
+
+    const ALICE: AccountAddress = AccountAddress([0; 32]);
+    const BOB: AccountAddress = AccountAddress([1; 32]);
+
+    fn fuzzer() -> ChainFuzzer {
+        let mut chain = Chain::new();
+        chain.create_account(Account::new(ALICE, Amount::from_ccd(1000)));
+        chain.create_account(Account::new(BOB, Amount::from_ccd(1000)));
+        ChainFuzzer::new(chain)
+            .seed(7)
+            .runs(50)
+            .action("transfer to bob", |rng, _| FuzzStep::Transfer {
+                sender:   ALICE,
+                receiver: BOB,
+                amount:   Amount::from_ccd(rng.gen_range(0..10)),
+            })
+            .action("tick", |rng, _| {
+                FuzzStep::TickTime(Duration::from_millis(rng.gen_range(0..10)))
+            })
+    }
+
+    /// Test that a holding invariant passes and that the chain is restored.
+    #[test]
+    fn test_invariant_holds() {
+        let mut fuzzer = fuzzer().invariant("bob has at least 1000 CCD", |chain| {
+            match chain.account_balance_available(BOB) {
+                Some(balance) if balance >= Amount::from_ccd(1000) => Ok(()),
+                other => Err(format!("Bob has {other:?}")),
+            }
+        });
+        fuzzer.run().expect("The invariant holds");
+        assert_eq!(fuzzer.chain().account_balance_available(BOB), Some(Amount::from_ccd(1000)));
+        assert_eq!(fuzzer.chain().block_time().timestamp_millis(), 0);
+    }
+
+    /// Test that a violation is shrunk to a single step and is reproducible
+    /// from the reported seed.
+    #[test]
+    fn test_violation_is_shrunk_and_reproducible() {
+        let invariant = |chain: &Chain| match chain.account_balance_available(BOB) {
+            Some(balance) if balance < Amount::from_ccd(1005) => Ok(()),
+            other => Err(format!("Bob has {other:?}")),
+        };
+        let failure = fuzzer()
+            .max_steps(50)
+            .invariant("bob has less than 1005 CCD", invariant)
+            .run()
+            .expect_err("The invariant is violated");
+        assert_eq!(failure.invariant, "bob has less than 1005 CCD");
+        // Time ticks never affect the invariant, so they are removed.
+        assert!(!failure.steps.is_empty());
+        assert!(
+            failure.steps.iter().all(|step| step.action == "transfer to bob"),
+            "The sequence is shrunk: {failure}"
+        );
+
+        let reproduced = fuzzer()
+            .max_steps(50)
+            .seed(failure.seed)
+            .runs(1)
+            .invariant("bob has less than 1005 CCD", invariant)
+            .run()
+            .expect_err("The failure is reproducible");
+        assert_eq!(reproduced.seed, failure.seed);
+        assert_eq!(reproduced.steps.len(), failure.steps.len());
+    }
+}
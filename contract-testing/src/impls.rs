@@ -7,17 +7,19 @@ use anyhow::anyhow;
 use concordium_rust_sdk::{
     self as sdk, base,
     base::{
-        base::{AccountThreshold, Energy, InsufficientEnergy},
+        base::{AccountThreshold, Energy, InsufficientEnergy, Nonce},
+        common::types::{CredentialIndex, KeyIndex},
         constants::MAX_WASM_MODULE_SIZE,
         contracts_common::{
             self, AccountAddress, AccountBalance, Address, Amount, ChainMetadata, ContractAddress,
             Deserial, Duration, ExchangeRate, ExchangeRates, ModuleReference, OwnedPolicy,
-            ParseResult, SlotTime, Timestamp,
+            ParseResult, SignatureThreshold, SlotTime, Timestamp,
         },
         hashes::BlockHash,
+        id::types::AccountKeys,
         smart_contracts::{ContractEvent, ModuleSource, WasmModule, WasmVersion},
         transactions::{
-            self, cost, AccountAccessStructure, InitContractPayload, UpdateContractPayload,
+            self, cost, AccountAccessStructure, InitContractPayload, Memo, UpdateContractPayload,
         },
     },
     smart_contracts::engine::{
@@ -31,20 +33,17 @@ use concordium_rust_sdk::{
 };
 use num_bigint::BigUint;
 use num_integer::Integer;
+use rand::{rngs::StdRng, SeedableRng};
 use sdk::types::smart_contracts::InvokeContractResult;
 use std::{
+    cell::RefCell,
     collections::{BTreeMap, BTreeSet},
-    future::Future,
     path::Path,
     sync::Arc,
 };
-use tokio::{runtime, time::timeout};
 
-/// The timeout duration set for queries with an external node.
-const EXTERNAL_NODE_QUERY_TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_secs(10);
-
-/// The timeout duration set for connecting to an external node.
-const EXTERNAL_NODE_CONNECT_TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_secs(3);
+/// The default cooldown period for stake reductions, which is 21 days.
+const DEFAULT_STAKE_COOLDOWN_MILLIS: u64 = 21 * 24 * 60 * 60 * 1000;
 
 impl Default for Chain {
     fn default() -> Self { Self::new() }
@@ -94,6 +93,8 @@ impl ChainParameters {
             block_time,
             micro_ccd_per_euro,
             euro_per_energy,
+            stake_cooldown: Duration::from_millis(DEFAULT_STAKE_COOLDOWN_MILLIS),
+            strict_transactions: false,
         })
     }
 
@@ -127,6 +128,7 @@ impl ChainBuilder {
     pub fn new() -> Self {
         Self {
             external_node_endpoint: None,
+            external_node_backend: None,
             external_query_block: None,
             micro_ccd_per_euro: None,
             micro_ccd_per_euro_from_external: false,
@@ -134,6 +136,8 @@ impl ChainBuilder {
             euro_per_energy_from_external: false,
             block_time: None,
             block_time_from_external: false,
+            fork: false,
+            strict_transactions: false,
         }
     }
 
@@ -156,13 +160,35 @@ impl ChainBuilder {
         self
     }
 
+    /// Configure the backend used for communicating with an external node,
+    /// e.g., a [`FixtureNode`] serving recorded responses.
+    ///
+    /// The backend can be used in place of
+    /// [`external_node_connection`][Self::external_node_connection], which
+    /// uses a [`GrpcNode`], and it cannot be used together with it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use concordium_smart_contract_testing::*;
+    /// let chain = Chain::builder()
+    ///     .external_node_backend(FixtureNode::replay("tests/fixtures/node.json").unwrap())
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn external_node_backend(mut self, backend: impl ExternalNodeBackend + 'static) -> Self {
+        self.external_node_backend = Some(Box::new(backend));
+        self
+    }
+
     /// Configure the block to be used for all external queries.
     ///
     /// If this is not set, then the last final block will be queried during
     /// [`ChainBuilder::build`] and saved, so it can be used for future queries.
     ///
     /// This can only be used in combination with
-    /// [`external_node_connection`][Self::external_node_connection].
+    /// [`external_node_connection`][Self::external_node_connection] or
+    /// [`external_node_backend`][Self::external_node_backend].
     ///
     /// To view the configured block, see [`Chain::external_query_block`].
     ///
@@ -306,6 +332,48 @@ impl ChainBuilder {
         self
     }
 
+    /// Make the chain a fork of the external node, which lazily loads
+    /// accounts, contracts and modules from the `external_query_block` on
+    /// first use. They can then be modified locally, e.g., with
+    /// [`Chain::contract_update`], without affecting the external node.
+    ///
+    /// This can only be used in combination with
+    /// [`external_node_connection`][Self::external_node_connection] or
+    /// [`external_node_backend`][Self::external_node_backend].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut chain = Chain::builder()
+    ///     .external_node_connection(Endpoint::from_static("http://node.testnet.concordium.com:20000"))
+    ///     .fork()
+    ///     .build()
+    ///     .unwrap();
+    /// // Load a contract and its module, e.g., to inspect its state.
+    /// chain.fork_load_contract(ContractAddress::new(2059, 0)).unwrap();
+    /// ```
+    pub fn fork(mut self) -> Self {
+        self.fork = true;
+        self
+    }
+
+    /// Make the chain only accept module deployments, contract inits and
+    /// contract updates as signed transactions. See
+    /// [`Chain::set_strict_transactions`] for details.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let chain = Chain::builder().strict_transactions().build().unwrap();
+    /// assert!(chain.is_strict_transactions());
+    /// ```
+    pub fn strict_transactions(mut self) -> Self {
+        self.strict_transactions = true;
+        self
+    }
+
     /// Build the [`Chain`] with the configured options.
     ///
     /// # Example
@@ -326,10 +394,25 @@ impl ChainBuilder {
 
         // Setup the external node connection if provided. This also forwards and sets
         // the external query block.
-        if let Some(endpoint) = self.external_node_endpoint {
-            chain.setup_external_node_connection(endpoint, self.external_query_block)?;
+        let backend = match (self.external_node_endpoint, self.external_node_backend) {
+            (Some(_), Some(_)) => return Err(ChainBuilderError::ConflictingExternalNode),
+            (Some(endpoint), None) => Some(Box::new(GrpcNode::new(endpoint)?) as Box<_>),
+            (None, backend) => backend,
+        };
+        if let Some(backend) = backend {
+            chain.setup_external_node_connection(backend, self.external_query_block)?;
+        }
+
+        // Make the chain a fork of the external node.
+        if self.fork {
+            if chain.external_node_connection.is_none() {
+                return Err(ChainBuilderError::ForkWithoutExternalNode);
+            }
+            chain.fork = Some(Fork::default());
         }
 
+        chain.parameters.strict_transactions = self.strict_transactions;
+
         // Check for conflicting exchange rate configurations.
         if self.micro_ccd_per_euro.is_some() && self.micro_ccd_per_euro_from_external {
             return Err(ChainBuilderError::ConflictingMicroCCDPerEuro);
@@ -429,6 +512,9 @@ impl Chain {
             contracts:                BTreeMap::new(),
             next_contract_index:      0,
             external_node_connection: None,
+            coverage:                 None,
+            fork:                     None,
+            call_records:             RefCell::new(None),
         })
     }
 
@@ -524,6 +610,14 @@ impl Chain {
         // If users use our tools to deploy modules the costs are calculated for them so
         // that deployment should never fail with out of energy. Not requiring energy
         // provides a more ergonomic experience.
+        if self.parameters.strict_transactions {
+            return Err(ModuleDeployError {
+                kind:            TransactionVerificationError::UnsignedTransaction.into(),
+                energy_used:     0.into(),
+                transaction_fee: Amount::zero(),
+            });
+        }
+
         let Ok(sender_account) = self.accounts
             .get_mut(&sender.into())
             .ok_or(AccountDoesNotExist { address: sender }) else {
@@ -578,30 +672,24 @@ impl Chain {
             });
         };
 
-        // Charge the account.
+        // Charge the account and use its nonce.
         sender_account.balance.total -= transaction_fee;
+        sender_account.nonce = sender_account.nonce.next();
+
+        let module_reference: ModuleReference = wasm_module.get_module_ref();
 
         // Construct the artifact.
-        let artifact = match wasm::utils::instantiate_with_metering::<v1::ProcessedImports, _>(
-            ValidationConfig::V1,
-            &v1::ConcordiumAllowedImports {
-                support_upgrade: true,
-                enable_debug,
-            },
-            wasm_module.source.as_ref(),
-        ) {
-            Ok(artifact) => artifact,
+        let module = match ContractModule::from_wasm_module(wasm_module, enable_debug) {
+            Ok(module) => module,
             Err(err) => {
                 return Err(ModuleDeployError {
-                    kind: ModuleInvalidError(err).into(),
+                    kind: err.into(),
                     energy_used,
                     transaction_fee,
                 })
             }
         };
 
-        let module_reference: ModuleReference = wasm_module.get_module_ref();
-
         // Ensure module hasn't been deployed before.
         if self.modules.contains_key(&module_reference) {
             return Err(ModuleDeployError {
@@ -610,12 +698,7 @@ impl Chain {
                 transaction_fee,
             });
         }
-        self.modules.insert(module_reference, ContractModule {
-            // we follow protocol 6 semantics, and don't count the custom section size towards
-            // module size.
-            size:     wasm_module.source.size().saturating_sub(artifact.custom_sections_size),
-            artifact: Arc::new(artifact.artifact),
-        });
+        self.modules.insert(module_reference, module);
         Ok(ModuleDeploySuccess {
             module_reference,
             energy_used,
@@ -645,6 +728,23 @@ impl Chain {
         energy_reserved: Energy,
         payload: InitContractPayload,
     ) -> Result<ContractInitSuccess, ContractInitError> {
+        if self.parameters.strict_transactions {
+            return Err(ContractInitError {
+                energy_used:     Energy::from(0),
+                transaction_fee: Amount::zero(),
+                kind:            TransactionVerificationError::UnsignedTransaction.into(),
+            });
+        }
+
+        // Load the sender and module if the chain is a fork.
+        if let Err(error) = self.fork_load_init(sender, &payload) {
+            return Err(ContractInitError {
+                energy_used:     Energy::from(0),
+                transaction_fee: Amount::zero(),
+                kind:            ContractInitErrorKind::ForkLoad(error),
+            });
+        }
+
         let mut remaining_energy = energy_reserved;
         if !self.account_exists(sender) {
             return Err(self.convert_to_init_error(
@@ -664,7 +764,7 @@ impl Chain {
             &mut remaining_energy,
         );
 
-        let (res, transaction_fee) = match res {
+        let (mut res, transaction_fee) = match res {
             Ok(s) => {
                 let transaction_fee = s.transaction_fee;
                 (Ok(s), transaction_fee)
@@ -676,9 +776,15 @@ impl Chain {
             }
         };
 
-        // Charge the account.
-        self.account_mut(sender).expect("existence already checked").balance.total -=
-            transaction_fee;
+        // Charge the account. The nonce is only used if the transaction used
+        // energy, since it is otherwise not included in a block by the node.
+        let energy_used = energy_reserved - remaining_energy;
+        let account = self.account_mut(sender).expect("existence already checked");
+        account.balance.total -= transaction_fee;
+        if energy_used.energy > 0 {
+            account.nonce = account.nonce.next();
+        }
+        self.coverage_record_init(&mut res);
         res
     }
 
@@ -752,14 +858,7 @@ impl Chain {
 
         // Sender policies have a very bespoke serialization in
         // order to allow skipping portions of them in smart contracts.
-        let sender_policies = {
-            let mut out = Vec::new();
-            account_info
-                .policy
-                .serial_for_smart_contract(&mut out)
-                .expect("Writing to a vector should succeed.");
-            out
-        };
+        let sender_policies = account_info.policies_for_smart_contract();
 
         // Construct the context.
         let init_ctx = v0::InitContext {
@@ -1046,6 +1145,20 @@ impl Chain {
         energy_reserved: Energy,
         payload: UpdateContractPayload,
     ) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+        if self.parameters.strict_transactions {
+            return Err(ContractInvokeError {
+                energy_used:     Energy::from(0),
+                transaction_fee: Amount::zero(),
+                trace_elements:  Vec::new(),
+                kind:            TransactionVerificationError::UnsignedTransaction.into(),
+            });
+        }
+
+        // Load the invoker, sender and contract if the chain is a fork.
+        if let Err(error) = self.fork_load_update(invoker, sender, &payload) {
+            return Err(fork_load_error(error));
+        }
+
         // Ensure the sender exists.
         if !self.address_exists(sender) {
             // This situation never happens on the chain since to send a message the sender
@@ -1105,15 +1218,34 @@ impl Chain {
         }
 
         let contract_address = payload.address;
-        let res = self.contract_invocation_worker(
-            invoker,
-            sender,
-            energy_reserved,
-            invoker_amount_reserved_for_nrg,
-            payload,
-            &mut remaining_energy,
-        );
-        let res = match res {
+        let energy_after_header = remaining_energy;
+        // The records of an invocation that is executed again in fork mode are
+        // discarded, so it is only traced once.
+        let records_len = self.call_records_len();
+        let res = loop {
+            let res = self.contract_invocation_worker(
+                invoker,
+                sender,
+                energy_reserved,
+                invoker_amount_reserved_for_nrg,
+                payload.clone(),
+                &mut remaining_energy,
+            );
+            // If the chain is a fork, load the items that were missing during the
+            // invocation. Nothing has been saved yet, so the invocation can be executed
+            // again to use them.
+            match self.fork_load_missing() {
+                Ok(false) => break res,
+                Ok(true) => {
+                    remaining_energy = energy_after_header;
+                    if let Some(records) = self.call_records.get_mut() {
+                        records.truncate(records_len);
+                    }
+                }
+                Err(error) => return Err(fork_load_error(error)),
+            }
+        };
+        let mut res = match res {
             Ok((result, changeset, trace_elements)) => {
                 // Charge energy for contract storage. Or return an error if out
                 // of energy.
@@ -1150,13 +1282,18 @@ impl Chain {
             Err(e) => Err(e),
         };
 
-        let transaction_fee = match &res {
-            Ok(s) => s.transaction_fee,
-            Err(e) => e.transaction_fee,
+        let (energy_used, transaction_fee) = match &res {
+            Ok(s) => (s.energy_used, s.transaction_fee),
+            Err(e) => (e.energy_used, e.transaction_fee),
         };
-        // Charge for execution.
-        self.account_mut(invoker).expect("existence already checked").balance.total -=
-            transaction_fee;
+        // Charge for execution. The nonce is only used if the transaction used
+        // energy, since it is otherwise not included in a block by the node.
+        let account = self.account_mut(invoker).expect("existence already checked");
+        account.balance.total -= transaction_fee;
+        if energy_used.energy > 0 {
+            account.nonce = account.nonce.next();
+        }
+        self.coverage_record_invoke(&mut res);
         res
     }
 
@@ -1227,7 +1364,7 @@ impl Chain {
             payload,
             &mut remaining_energy,
         );
-        match res {
+        let mut res = match res {
             Ok((result, changeset, trace_elements)) => {
                 // Charge energy for contract storage. Or return an error if out
                 // of energy.
@@ -1258,7 +1395,9 @@ impl Chain {
                 )
             }
             Err(e) => Err(e),
-        }
+        };
+        self.coverage_record_invoke(&mut res);
+        res
     }
 
     /// Invoke an external contract entrypoint.
@@ -1320,24 +1459,17 @@ impl Chain {
         let connection = self.external_node_connection().unwrap();
 
         // Make the invocation.
-        let invoke_result: InvokeContractResult =
-            connection.with_client(block, |block_identifier, mut client| async move {
-                let invoke_result = client
-                    .invoke_instance(
-                        block_identifier,
-                        &sdk::types::smart_contracts::ContractContext {
-                            invoker:   sender.map(|ext_addr| ext_addr.to_address()),
-                            contract:  payload.address.address,
-                            amount:    payload.amount,
-                            method:    payload.receive_name,
-                            parameter: payload.message,
-                            energy:    Some(energy_reserved),
-                        },
-                    )
-                    .await?
-                    .response;
-                Ok::<_, ExternalNodeError>(invoke_result)
-            })?;
+        let invoke_result: InvokeContractResult = connection.backend.invoke_instance(
+            block.unwrap_or(connection.query_block),
+            &sdk::types::smart_contracts::ContractContext {
+                invoker:   sender.map(|ext_addr| ext_addr.to_address()),
+                contract:  payload.address.address,
+                amount:    payload.amount,
+                method:    payload.receive_name,
+                parameter: payload.message,
+                energy:    Some(energy_reserved),
+            },
+        )?;
 
         // Convert the result.
         match invoke_result {
@@ -1393,6 +1525,384 @@ impl Chain {
         self.accounts.insert(account.address.into(), account)
     }
 
+    /// Transfer CCD from one account to another.
+    ///
+    /// The `sender` is charged the transaction fee of a simple transfer, which
+    /// depends on the number of keys in the `signer`.
+    ///
+    /// If the `sender` cannot pay the transaction fee, then nothing is charged
+    /// and an [`AccountTransferErrorKind::InsufficientFunds`] error is
+    /// returned. All other errors are rejections, in which case the fee is
+    /// still charged but no CCD is moved.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut chain = Chain::new();
+    /// let acc_0 = AccountAddress([0; 32]);
+    /// let acc_1 = AccountAddress([1; 32]);
+    /// chain.create_account(Account::new(acc_0, Amount::from_ccd(100)));
+    /// chain.create_account(Account::new(acc_1, Amount::from_ccd(0)));
+    ///
+    /// let transfer =
+    ///     chain.account_transfer(Signer::with_one_key(), acc_0, acc_1, Amount::from_ccd(10)).unwrap();
+    ///
+    /// assert_eq!(chain.account_balance_available(acc_1), Some(Amount::from_ccd(10)));
+    /// assert_eq!(
+    ///     chain.account_balance_available(acc_0),
+    ///     Some(Amount::from_ccd(90) - transfer.transaction_fee)
+    /// );
+    /// ```
+    pub fn account_transfer(
+        &mut self,
+        signer: Signer,
+        sender: AccountAddress,
+        receiver: AccountAddress,
+        amount: Amount,
+    ) -> Result<AccountTransferSuccess, AccountTransferError> {
+        // +1 for the tag, +32 for the receiver, +8 for the amount.
+        let payload_size = 1 + 32 + 8;
+        let energy_used = cost::base_cost(
+            transactions::construct::TRANSACTION_HEADER_SIZE + payload_size,
+            signer.num_keys,
+        ) + cost::SIMPLE_TRANSFER;
+        self.account_transfer_worker(
+            sender,
+            receiver,
+            TransferAmount::Unlocked(amount),
+            energy_used,
+        )
+    }
+
+    /// Like [`account_transfer`](Self::account_transfer), except that a
+    /// [`Memo`] is included in the transfer. The memo only affects the
+    /// transaction fee, since it increases the size of the transaction.
+    pub fn account_transfer_with_memo(
+        &mut self,
+        signer: Signer,
+        sender: AccountAddress,
+        receiver: AccountAddress,
+        amount: Amount,
+        memo: Memo,
+    ) -> Result<AccountTransferSuccess, AccountTransferError> {
+        // +1 for the tag, +32 for the receiver, +2 for the memo length, +8 for the
+        // amount.
+        let payload_size = 1 + 32 + 2 + memo.as_ref().len() as u64 + 8;
+        let energy_used = cost::base_cost(
+            transactions::construct::TRANSACTION_HEADER_SIZE + payload_size,
+            signer.num_keys,
+        ) + cost::SIMPLE_TRANSFER;
+        self.account_transfer_worker(
+            sender,
+            receiver,
+            TransferAmount::Unlocked(amount),
+            energy_used,
+        )
+    }
+
+    /// Transfer CCD from one account to another with a release schedule.
+    ///
+    /// The `schedule` is a list of pairs of release times and amounts. The
+    /// total amount is transferred immediately, but is locked on the
+    /// `receiver` account, i.e., included in [`AccountBalance::locked`], until
+    /// its release time has been reached with
+    /// [`tick_block_time`](Self::tick_block_time).
+    ///
+    /// The schedule must be non-empty, have at most 255 releases, have strictly
+    /// increasing release times with the first one not being in the past, and
+    /// every amount must be positive. Transferring to oneself with a schedule
+    /// is not allowed.
+    ///
+    /// See [`account_transfer`](Self::account_transfer) for how the fees and
+    /// errors work.
+    pub fn account_transfer_with_schedule(
+        &mut self,
+        signer: Signer,
+        sender: AccountAddress,
+        receiver: AccountAddress,
+        schedule: Vec<(Timestamp, Amount)>,
+    ) -> Result<AccountTransferSuccess, AccountTransferError> {
+        let num_releases = schedule.len() as u64;
+        // +1 for the tag, +32 for the receiver, +1 for the length of the schedule,
+        // +16 for each release.
+        let payload_size = 1 + 32 + 1 + 16 * num_releases;
+        let energy_used = cost::base_cost(
+            transactions::construct::TRANSACTION_HEADER_SIZE + payload_size,
+            signer.num_keys,
+        ) + cost::scheduled_transfer(num_releases.try_into().unwrap_or(u16::MAX));
+        self.account_transfer_worker(
+            sender,
+            receiver,
+            TransferAmount::Scheduled(schedule),
+            energy_used,
+        )
+    }
+
+    /// Helper for the account transfer methods. A scheduled transfer is
+    /// validated after the fee has been charged, like on the chain.
+    fn account_transfer_worker(
+        &mut self,
+        sender: AccountAddress,
+        receiver: AccountAddress,
+        transfer: TransferAmount,
+        energy_used: Energy,
+    ) -> Result<AccountTransferSuccess, AccountTransferError> {
+        let transaction_fee = self.parameters.calculate_energy_cost(energy_used);
+        let block_time = self.parameters.block_time;
+
+        // Charge the sender, if it exists and can pay the fee.
+        let sender_account = match self.account_mut(sender) {
+            Ok(account) => account,
+            Err(e) => {
+                return Err(AccountTransferError {
+                    energy_used:     0.into(),
+                    transaction_fee: Amount::zero(),
+                    kind:            AccountTransferErrorKind::SenderDoesNotExist(e),
+                })
+            }
+        };
+        if sender_account.balance.available() < transaction_fee {
+            return Err(AccountTransferError {
+                energy_used:     0.into(),
+                transaction_fee: Amount::zero(),
+                kind:            AccountTransferErrorKind::InsufficientFunds,
+            });
+        }
+        sender_account.balance.total -= transaction_fee;
+        sender_account.nonce = sender_account.nonce.next();
+        let sender_available = sender_account.balance.available();
+
+        let reject = |kind| AccountTransferError {
+            energy_used,
+            transaction_fee,
+            kind,
+        };
+
+        if !self.account_exists(receiver) {
+            return Err(reject(AccountTransferErrorKind::ReceiverDoesNotExist(
+                AccountDoesNotExist {
+                    address: receiver,
+                },
+            )));
+        }
+        let (amount, schedule) = match transfer {
+            TransferAmount::Unlocked(amount) => (amount, Vec::new()),
+            TransferAmount::Scheduled(schedule) => {
+                let amount = check_release_schedule(&schedule, block_time)
+                    .map_err(|e| reject(AccountTransferErrorKind::InvalidSchedule(e)))?;
+                if sender.is_alias(&receiver) {
+                    return Err(reject(AccountTransferErrorKind::InvalidSchedule(
+                        ReleaseScheduleError::SelfTransfer,
+                    )));
+                }
+                (amount, schedule)
+            }
+        };
+        if sender_available < amount {
+            return Err(reject(AccountTransferErrorKind::AmountTooLarge {
+                amount,
+            }));
+        }
+
+        // Move the CCD. The accounts are known to exist at this point.
+        self.account_mut(sender).expect("Sender exists").balance.total -= amount;
+        let receiver_account = self.account_mut(receiver).expect("Receiver exists");
+        receiver_account.balance.total += amount;
+        for (release_time, release_amount) in schedule {
+            receiver_account.balance.locked += release_amount;
+            *receiver_account.release_schedule.entry(release_time).or_insert_with(Amount::zero) +=
+                release_amount;
+        }
+
+        Ok(AccountTransferSuccess {
+            energy_used,
+            transaction_fee,
+        })
+    }
+
+    /// Release the scheduled amounts on all accounts with a release time that
+    /// is at or before the current block time.
+    fn release_scheduled_amounts(&mut self) {
+        let block_time = self.parameters.block_time;
+        for account in self.accounts.values_mut() {
+            let mut released = Amount::zero();
+            account.release_schedule.retain(|release_time, amount| {
+                if *release_time <= block_time {
+                    released += *amount;
+                    false
+                } else {
+                    true
+                }
+            });
+            account.balance.locked -= released;
+        }
+    }
+
+    /// Make an account a baker with the provided `stake`.
+    ///
+    /// This is an administrative operation which does not cost anything. The
+    /// stake becomes part of [`AccountBalance::staked`] immediately.
+    ///
+    /// If `restake_earnings` is true, then rewards paid with
+    /// [`account_reward`](Self::account_reward) are added to the stake.
+    ///
+    /// Returns an error if the account does not exist, is already staking, or
+    /// if the stake is zero or exceeds the total balance of the account.
+    pub fn account_add_baker(
+        &mut self,
+        address: AccountAddress,
+        stake: Amount,
+        restake_earnings: bool,
+    ) -> Result<(), StakeError> {
+        self.account_add_stake(address, StakeKind::Baker, stake, restake_earnings)
+    }
+
+    /// Make an account a delegator with the provided `stake`.
+    ///
+    /// Works like [`account_add_baker`](Self::account_add_baker).
+    pub fn account_add_delegation(
+        &mut self,
+        address: AccountAddress,
+        stake: Amount,
+        restake_earnings: bool,
+    ) -> Result<(), StakeError> {
+        self.account_add_stake(address, StakeKind::Delegator, stake, restake_earnings)
+    }
+
+    /// Helper for adding a stake of a certain kind to an account.
+    fn account_add_stake(
+        &mut self,
+        address: AccountAddress,
+        kind: StakeKind,
+        stake: Amount,
+        restake_earnings: bool,
+    ) -> Result<(), StakeError> {
+        let account =
+            self.account_mut(address).map_err(|_| StakeError::AccountDoesNotExist(address))?;
+        if account.stake.is_some() {
+            return Err(StakeError::AlreadyStaking);
+        }
+        if stake == Amount::zero() {
+            return Err(StakeError::ZeroStake);
+        }
+        if stake > account.balance.total {
+            return Err(StakeError::InsufficientFunds {
+                stake,
+            });
+        }
+        account.balance.staked = stake;
+        account.stake = Some(AccountStake {
+            kind,
+            restake_earnings,
+            pending_change: None,
+        });
+        Ok(())
+    }
+
+    /// Change the stake of a baker or delegator.
+    ///
+    /// An increase of the stake takes effect immediately, whereas a reduction
+    /// takes effect once the block time has been advanced past the stake
+    /// cooldown period with [`tick_block_time`](Self::tick_block_time). Until
+    /// then, the stake remains part of [`AccountBalance::staked`]. Reducing
+    /// the stake to zero removes the baker or delegator.
+    ///
+    /// Returns an error if the account does not exist, is not staking, has a
+    /// pending change, or if the new stake exceeds its total balance.
+    pub fn account_update_stake(
+        &mut self,
+        address: AccountAddress,
+        new_stake: Amount,
+    ) -> Result<(), StakeError> {
+        let effective_time = self
+            .parameters
+            .block_time
+            .checked_add(self.parameters.stake_cooldown)
+            .unwrap_or_else(|| Timestamp::from_timestamp_millis(u64::MAX));
+        let account =
+            self.account_mut(address).map_err(|_| StakeError::AccountDoesNotExist(address))?;
+        let Some(stake) = account.stake.as_mut() else {
+            return Err(StakeError::NotStaking);
+        };
+        if stake.pending_change.is_some() {
+            return Err(StakeError::PendingChange);
+        }
+        if new_stake > account.balance.total {
+            return Err(StakeError::InsufficientFunds {
+                stake: new_stake,
+            });
+        }
+        if new_stake >= account.balance.staked {
+            account.balance.staked = new_stake;
+        } else {
+            stake.pending_change = Some(PendingStakeChange {
+                effective_time,
+                new_stake,
+            });
+        }
+        Ok(())
+    }
+
+    /// Remove the stake of a baker or delegator after the stake cooldown
+    /// period.
+    ///
+    /// This is the same as calling
+    /// [`account_update_stake`](Self::account_update_stake) with a zero
+    /// stake.
+    pub fn account_remove_stake(&mut self, address: AccountAddress) -> Result<(), StakeError> {
+        self.account_update_stake(address, Amount::zero())
+    }
+
+    /// Pay a reward to an account, e.g., for baking or delegating.
+    ///
+    /// The reward is added to the total balance, and also to the stake if the
+    /// account is staking with `restake_earnings` enabled.
+    pub fn account_reward(
+        &mut self,
+        address: AccountAddress,
+        reward: Amount,
+    ) -> Result<(), AccountDoesNotExist> {
+        let account = self.account_mut(address)?;
+        account.balance.total += reward;
+        if account.stake.as_ref().map_or(false, |stake| stake.restake_earnings) {
+            account.balance.staked += reward;
+        }
+        Ok(())
+    }
+
+    /// Set the cooldown period for reductions and removals of stake.
+    ///
+    /// Only affects stake changes made after this call.
+    pub fn set_stake_cooldown(&mut self, cooldown: Duration) {
+        self.parameters.stake_cooldown = cooldown;
+    }
+
+    /// Return the cooldown period for reductions and removals of stake.
+    pub fn stake_cooldown(&self) -> Duration { self.parameters.stake_cooldown }
+
+    /// Apply the pending stake changes of all accounts whose effective time is
+    /// at or before the current block time.
+    fn apply_pending_stake_changes(&mut self) {
+        let block_time = self.parameters.block_time;
+        for account in self.accounts.values_mut() {
+            let Some(stake) = account.stake.as_mut() else {
+                continue;
+            };
+            let Some(change) = stake.pending_change else {
+                continue;
+            };
+            if change.effective_time > block_time {
+                continue;
+            }
+            stake.pending_change = None;
+            account.balance.staked = change.new_stake;
+            if change.new_stake == Amount::zero() {
+                account.stake = None;
+            }
+        }
+    }
+
     /// Add an external account from a connected external node.
     ///
     /// If the account exists on the external node at the time of the
@@ -1408,20 +1918,11 @@ impl Chain {
     ) -> Result<ExternalAccountAddress, ExternalNodeError> {
         let connection = self.external_node_connection_mut()?;
 
-        let external_addr =
-            connection.with_client(None, |block_identifier, mut client| async move {
-                // Try to get the account info to verify the existence of the account, but
-                // discard the result.
-                client
-                    .get_account_info(
-                        &sdk::v2::AccountIdentifier::Address(address),
-                        block_identifier,
-                    )
-                    .await?;
-                Ok::<_, ExternalNodeError>(ExternalAccountAddress {
-                    address,
-                })
-            })?;
+        // Verify the existence of the account.
+        connection.backend.check_account(connection.query_block, address)?;
+        let external_addr = ExternalAccountAddress {
+            address,
+        };
 
         connection.accounts.insert(external_addr);
 
@@ -1443,15 +1944,11 @@ impl Chain {
     ) -> Result<ExternalContractAddress, ExternalNodeError> {
         let connection = self.external_node_connection_mut()?;
 
-        let external_addr =
-            connection.with_client(None, |block_identifier, mut client| async move {
-                // Try to get the contract instance info to verify the existence of the
-                // contract, but discard the result.
-                client.get_instance_info(address, block_identifier).await?;
-                Ok::<_, ExternalNodeError>(ExternalContractAddress {
-                    address,
-                })
-            })?;
+        // Verify the existence of the contract.
+        connection.backend.check_contract(connection.query_block, address)?;
+        let external_addr = ExternalContractAddress {
+            address,
+        };
 
         connection.contracts.insert(external_addr);
 
@@ -1467,11 +1964,17 @@ impl Chain {
     }
 
     /// Returns the balance of an account if it exists.
+    ///
+    /// The `address` can be any alias of the account, see
+    /// [`Chain::account_alias`].
     pub fn account_balance(&self, address: AccountAddress) -> Option<AccountBalance> {
         self.accounts.get(&address.into()).map(|ai| ai.balance)
     }
 
     /// Returns the available balance of an account if it exists.
+    ///
+    /// The `address` can be any alias of the account, see
+    /// [`Chain::account_alias`].
     pub fn account_balance_available(&self, address: AccountAddress) -> Option<Amount> {
         self.accounts.get(&address.into()).map(|ai| ai.balance.available())
     }
@@ -1481,8 +1984,25 @@ impl Chain {
         self.contracts.get(&address).map(|ci| ci.self_balance)
     }
 
-    /// Helper method for looking up part of the state of a smart contract,
-    /// which is a key-value store.
+    /// Set the balance of a contract directly, without any transfers.
+    /// Returns the previous balance.
+    ///
+    /// This is useful for setting up tests, e.g., of contracts that hold
+    /// large amounts of CCD. Together with [`Chain::contract_state_modify`] it
+    /// allows setting up the full state of a contract instance.
+    pub fn contract_set_balance(
+        &mut self,
+        address: ContractAddress,
+        amount: Amount,
+    ) -> Result<Amount, ContractDoesNotExist> {
+        let contract = self.contracts.get_mut(&address).ok_or(ContractDoesNotExist {
+            address,
+        })?;
+        Ok(std::mem::replace(&mut contract.self_balance, amount))
+    }
+
+    /// Helper method for looking up part of the state of a smart contract,
+    /// which is a key-value store.
     pub fn contract_state_lookup(&self, address: ContractAddress, key: &[u8]) -> Option<Vec<u8>> {
         let mut loader = v1::trie::Loader::new(&[][..]);
         self.contracts.get(&address)?.state.lookup(&mut loader, key)
@@ -1497,7 +2017,7 @@ impl Chain {
         let module = self.modules.get(&module_ref).ok_or(ModuleDoesNotExist {
             module_reference: module_ref,
         })?;
-        Ok(module.clone())
+        Ok(self.coverage_artifact(module_ref, module))
     }
 
     /// Returns an immutable reference to an [`Account`].
@@ -1522,6 +2042,38 @@ impl Chain {
         self.accounts.contains_key(&address.into())
     }
 
+    /// Get alias number `n` of an existing account.
+    ///
+    /// An account has `2^24` aliases, which share the first 29 bytes of the
+    /// address and refer to the same account. All methods on the [`Chain`]
+    /// accept any alias of an account, and contracts see the exact alias that
+    /// was used, e.g., as the invoker or the receiver of a transfer. Alias `0`
+    /// is the canonical address that contracts get with `get_alias(0)`.
+    ///
+    /// Returns an error if the account does not exist or if `n` is not less
+    /// than `2^24`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut chain = Chain::new();
+    /// let address = AccountAddress([1; 32]);
+    /// chain.create_account(Account::new(address, Amount::from_ccd(10)));
+    /// let alias = chain.account_alias(address, 1).unwrap();
+    /// assert_ne!(alias, address);
+    /// assert!(alias.is_alias(&address));
+    /// assert_eq!(chain.account_balance_available(alias), Some(Amount::from_ccd(10)));
+    /// ```
+    pub fn account_alias(
+        &self,
+        address: AccountAddress,
+        n: u32,
+    ) -> Result<AccountAddress, AccountAliasError> {
+        self.account(address)?;
+        address.get_alias(n).ok_or(AccountAliasError::CounterTooLarge(n))
+    }
+
     /// Check whether a [`Contract`] exists.
     pub fn contract_exists(&self, address: ContractAddress) -> bool {
         self.contracts.contains_key(&address)
@@ -1625,18 +2177,7 @@ impl Chain {
         let connection = self.external_node_connection()?;
 
         // Get the values from the external node.
-        connection.with_client(None, |block_identifier, mut client| async move {
-            let (euro_per_energy, micro_ccd_per_euro) =
-                match client.get_block_chain_parameters(block_identifier).await?.response {
-                    sdk::v2::ChainParameters::V0(p) => (p.euro_per_energy, p.micro_ccd_per_euro),
-                    sdk::v2::ChainParameters::V1(p) => (p.euro_per_energy, p.micro_ccd_per_euro),
-                    sdk::v2::ChainParameters::V2(p) => (p.euro_per_energy, p.micro_ccd_per_euro),
-                };
-            Ok(ExchangeRates {
-                euro_per_energy,
-                micro_ccd_per_euro,
-            })
-        })
+        connection.backend.exchange_rates(connection.query_block)
     }
 
     /// Tick the block time on the [`Chain`] by a [`Duration`].
@@ -1660,6 +2201,8 @@ impl Chain {
     pub fn tick_block_time(&mut self, duration: Duration) -> Result<(), BlockTimeOverflow> {
         self.parameters.block_time =
             self.parameters.block_time.checked_add(duration).ok_or(BlockTimeOverflow)?;
+        self.release_scheduled_amounts();
+        self.apply_pending_stake_changes();
         Ok(())
     }
 
@@ -1673,20 +2216,8 @@ impl Chain {
     fn set_block_time_via_external_node(&mut self) -> Result<(), ExternalNodeError> {
         let connection = self.external_node_connection_mut()?;
 
-        // Get the timestamp in milliseconds.
-        let timestamp =
-            connection.with_client(None, |block_identifier, mut client| async move {
-                Ok(client
-                    .get_block_info(block_identifier)
-                    .await?
-                    .response
-                    .block_slot_time
-                    .timestamp_millis() as u64) // The node never returns
-                                                // timestamps < 0, so it is safe
-                                                // to cast it to `u64`.
-            })?;
         // Update the block time.
-        self.parameters.block_time = Timestamp::from_timestamp_millis(timestamp);
+        self.parameters.block_time = connection.backend.block_time(connection.query_block)?;
 
         Ok(())
     }
@@ -1699,71 +2230,43 @@ impl Chain {
     /// will be saved in [`ExternalNodeConnection`].
     fn setup_external_node_connection(
         &mut self,
-        endpoint: Endpoint,
+        backend: Box<dyn ExternalNodeBackend>,
         query_block: Option<BlockHash>,
     ) -> Result<(), SetupExternalNodeError> {
-        // Create the Tokio runtime. This should never fail, unless nested runtimes are
-        // created.
-        let runtime = runtime::Builder::new_multi_thread()
-            // Enable time, so timeouts can be used.
-            .enable_time()
-            // Enable I/O, so networking and other types of calls are possible.
-            .enable_io()
-            .build()
-            .expect("Internal error: Could not create Tokio runtime.");
-
-        // A future for getting the client. Executed below.
-        let get_client = async {
-            // Try to create the client, which also checks that the connection is valid.
-            let client = sdk::v2::Client::new(endpoint).await?;
-            Ok::<sdk::v2::Client, SetupExternalNodeError>(client)
-        };
-
-        // A future for checking the block.
-        let get_block_info = |mut client: sdk::v2::Client| async move {
-            let block_identifier = if let Some(query_block) = query_block {
-                sdk::v2::BlockIdentifier::Given(query_block)
-            } else {
-                sdk::v2::BlockIdentifier::LastFinal
-            };
-
-            let block_hash = match client.get_block_info(block_identifier).await {
-                Ok(res) => res.block_hash,
-                Err(sdk::v2::QueryError::NotFound) => {
-                    return Err(SetupExternalNodeError::QueryBlockDoesNotExist {
-                        // It should never be possible to get `NotFound` when querying `LastFinal`,
-                        // and so, the `query_block` must be `Some`.
-                        query_block: query_block.expect(
-                            "Internal error: Got `QueryError::NotFound` for when querying last \
-                             final block.",
-                        ),
-                    });
-                }
-                Err(sdk::v2::QueryError::RPCError(error)) => {
-                    return Err(SetupExternalNodeError::CannotCheckQueryBlockExistence {
-                        error,
-                    })
-                }
-            };
-            Ok(block_hash)
+        let checked_query_block = match backend.block_hash(query_block) {
+            Ok(block_hash) => block_hash,
+            Err(ExternalNodeError::QueryError {
+                error: sdk::v2::QueryError::NotFound,
+            }) => {
+                return Err(SetupExternalNodeError::QueryBlockDoesNotExist {
+                    // It should never be possible to get `NotFound` when querying `LastFinal`,
+                    // and so, the `query_block` must be `Some`.
+                    query_block: query_block.expect(
+                        "Internal error: Got `QueryError::NotFound` for when querying last final \
+                         block.",
+                    ),
+                });
+            }
+            Err(ExternalNodeError::QueryError {
+                error: sdk::v2::QueryError::RPCError(error),
+            }) => {
+                return Err(SetupExternalNodeError::CannotCheckQueryBlockExistence {
+                    error,
+                })
+            }
+            Err(ExternalNodeError::QueryTimeout) => {
+                return Err(SetupExternalNodeError::CheckQueryBlockTimeout)
+            }
+            Err(error) => {
+                return Err(SetupExternalNodeError::Backend {
+                    error: Box::new(error),
+                })
+            }
         };
 
-        // Get the client synchronously by blocking until the async returns.
-        let (client, checked_query_block) = runtime.block_on(async {
-            let client = timeout(EXTERNAL_NODE_CONNECT_TIMEOUT, get_client)
-                .await
-                .map_err(|_| SetupExternalNodeError::ConnectTimeout)??;
-            let checked_query_block =
-                timeout(EXTERNAL_NODE_QUERY_TIMEOUT, get_block_info(client.clone()))
-                    .await
-                    .map_err(|_| SetupExternalNodeError::CheckQueryBlockTimeout)??;
-            Ok::<_, SetupExternalNodeError>((client, checked_query_block))
-        })?;
-
         // Set or replace the node connection.
         self.external_node_connection = Some(ExternalNodeConnection {
-            client,
-            runtime,
+            backend,
             query_block: checked_query_block,
             accounts: BTreeSet::new(),
             contracts: BTreeSet::new(),
@@ -1821,41 +2324,332 @@ impl Chain {
     pub fn external_query_block(&self) -> Result<BlockHash, ExternalNodeNotConfigured> {
         self.external_node_connection().map(|conn| conn.query_block)
     }
-}
 
-impl ExternalNodeConnection {
-    /// Execute an async task with the [`sdk::v2::Client`].
+    /// Take a snapshot of the accounts, modules, contracts and chain
+    /// parameters, i.e., block time and exchange rates.
     ///
-    /// If a block is provided, it will be used for the query. Otherwise, it
-    /// will use the default query block.
+    /// The snapshot can later be used with [`Chain::restore`] to roll the
+    /// chain back. This is useful for running several test paths from a
+    /// single, possibly expensive, setup.
     ///
-    /// If the task takes longer than [`EXTERNAL_NODE_TIMEOUT_DURATION`] then
-    /// the connection times out and an [`Err(ExternalNodeError::Timeout)`] is
-    /// returned.
+    /// Taking a snapshot is cheap since the contract states are persistent
+    /// data structures, which are shared between the chain and the snapshot.
     ///
-    /// *This method cannot be nested, as that will cause a panic.*
-    fn with_client<T, F, Fut>(
-        &self,
-        block: Option<BlockHash>,
-        f: F,
-    ) -> Result<T, ExternalNodeError>
-    where
-        F: FnOnce(sdk::v2::BlockIdentifier, sdk::v2::Client) -> Fut,
-        Fut: Future<Output = Result<T, ExternalNodeError>>, {
-        // Get the block identifier, either using the provided block or the default
-        // query block.
-        let block_identifier = if let Some(block) = block {
-            sdk::v2::BlockIdentifier::Given(block)
-        } else {
-            sdk::v2::BlockIdentifier::Given(self.query_block)
+    /// The external node connection is *not* part of the snapshot.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut chain = Chain::new();
+    /// let snapshot = chain.snapshot();
+    ///
+    /// chain.tick_block_time(Duration::from_millis(123)).unwrap();
+    /// assert_eq!(chain.block_time(), Timestamp::from_timestamp_millis(123));
+    ///
+    /// chain.restore(&snapshot);
+    /// assert_eq!(chain.block_time(), Timestamp::from_timestamp_millis(0));
+    /// ```
+    pub fn snapshot(&self) -> ChainSnapshot {
+        ChainSnapshot {
+            parameters:          self.parameters.clone(),
+            accounts:            self.accounts.clone(),
+            modules:             self.modules.clone(),
+            contracts:           self.contracts.clone(),
+            next_contract_index: self.next_contract_index,
+        }
+    }
+
+    /// Restore the chain to the state it had when the [`ChainSnapshot`] was
+    /// taken with [`Chain::snapshot`].
+    ///
+    /// The same snapshot can be restored any number of times.
+    /// The external node connection is left untouched.
+    pub fn restore(&mut self, snapshot: &ChainSnapshot) {
+        self.parameters = snapshot.parameters.clone();
+        self.accounts = snapshot.accounts.clone();
+        self.modules = snapshot.modules.clone();
+        self.contracts = snapshot.contracts.clone();
+        self.next_contract_index = snapshot.next_contract_index;
+    }
+
+    /// Execute a number of transactions in a single block.
+    ///
+    /// The transactions are added to the block via the [`BlockBuilder`]
+    /// provided to the closure and are executed immediately and in order. All
+    /// of them observe the same block time, which cannot be changed while the
+    /// block is under construction.
+    ///
+    /// Returns a [`BlockSummary`] with the outcomes of the individual
+    /// transactions along with the total energy used and fees paid.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use concordium_smart_contract_testing::*;
+    /// # let mut chain = Chain::new();
+    /// # const ACC: AccountAddress = AccountAddress([0; 32]);
+    /// # let contract_address = ContractAddress::new(0, 0);
+    /// let summary = chain.block(|b| {
+    ///     b.contract_update(
+    ///         Signer::with_one_key(),
+    ///         ACC,
+    ///         Address::Account(ACC),
+    ///         Energy::from(10000),
+    ///         UpdateContractPayload {
+    ///             address:      contract_address,
+    ///             receive_name: OwnedReceiveName::new_unchecked("auction.bid".into()),
+    ///             message:      OwnedParameter::empty(),
+    ///             amount:       Amount::from_ccd(10),
+    ///         },
+    ///     );
+    ///     b.contract_update(
+    ///         Signer::with_one_key(),
+    ///         ACC,
+    ///         Address::Account(ACC),
+    ///         Energy::from(10000),
+    ///         UpdateContractPayload {
+    ///             address:      contract_address,
+    ///             receive_name: OwnedReceiveName::new_unchecked("auction.finalize".into()),
+    ///             message:      OwnedParameter::empty(),
+    ///             amount:       Amount::zero(),
+    ///         },
+    ///     );
+    /// });
+    /// assert_eq!(summary.outcomes.len(), 2);
+    /// ```
+    pub fn block(&mut self, f: impl FnOnce(&mut BlockBuilder)) -> BlockSummary {
+        let block_time = self.parameters.block_time;
+        let mut builder = BlockBuilder {
+            chain:    self,
+            outcomes: Vec::new(),
         };
-        // Clone the client so it can be moved to the async block.
-        let client = self.client.clone();
-        // Run the future and timeout if it takes too long.
-        self.runtime.block_on(async move {
-            timeout(EXTERNAL_NODE_QUERY_TIMEOUT, f(block_identifier, client))
-                .await
-                .map_err(|_| ExternalNodeError::QueryTimeout)?
+        f(&mut builder);
+        let outcomes = builder.outcomes;
+        let energy_used =
+            outcomes.iter().fold(Energy::from(0), |acc, outcome| acc + outcome.energy_used());
+        let transaction_fee =
+            outcomes.iter().fold(Amount::zero(), |acc, outcome| acc + outcome.transaction_fee());
+        BlockSummary {
+            block_time,
+            outcomes,
+            energy_used,
+            transaction_fee,
+        }
+    }
+}
+
+impl<'a> BlockBuilder<'a> {
+    /// Deploy a module as part of the block.
+    ///
+    /// See [`Chain::module_deploy_v1`] for details.
+    pub fn module_deploy_v1(
+        &mut self,
+        signer: Signer,
+        sender: AccountAddress,
+        wasm_module: WasmModule,
+    ) -> &Result<ModuleDeploySuccess, ModuleDeployError> {
+        let result = self.chain.module_deploy_v1(signer, sender, wasm_module);
+        self.outcomes.push(BlockItemOutcome::ModuleDeploy(result));
+        match self.outcomes.last() {
+            Some(BlockItemOutcome::ModuleDeploy(result)) => result,
+            _ => unreachable!("The outcome was just added."),
+        }
+    }
+
+    /// Initialize a contract as part of the block.
+    ///
+    /// See [`Chain::contract_init`] for details.
+    pub fn contract_init(
+        &mut self,
+        signer: Signer,
+        sender: AccountAddress,
+        energy_reserved: Energy,
+        payload: InitContractPayload,
+    ) -> &Result<ContractInitSuccess, ContractInitError> {
+        let result = self.chain.contract_init(signer, sender, energy_reserved, payload);
+        self.outcomes.push(BlockItemOutcome::ContractInit(result));
+        match self.outcomes.last() {
+            Some(BlockItemOutcome::ContractInit(result)) => result,
+            _ => unreachable!("The outcome was just added."),
+        }
+    }
+
+    /// Update a contract as part of the block.
+    ///
+    /// See [`Chain::contract_update`] for details.
+    pub fn contract_update(
+        &mut self,
+        signer: Signer,
+        invoker: AccountAddress,
+        sender: Address,
+        energy_reserved: Energy,
+        payload: UpdateContractPayload,
+    ) -> &Result<ContractInvokeSuccess, ContractInvokeError> {
+        let result = self.chain.contract_update(signer, invoker, sender, energy_reserved, payload);
+        self.outcomes.push(BlockItemOutcome::ContractUpdate(result));
+        match self.outcomes.last() {
+            Some(BlockItemOutcome::ContractUpdate(result)) => result,
+            _ => unreachable!("The outcome was just added."),
+        }
+    }
+
+    /// Transfer CCD between two accounts as part of the block.
+    ///
+    /// See [`Chain::account_transfer`] for details.
+    pub fn account_transfer(
+        &mut self,
+        signer: Signer,
+        sender: AccountAddress,
+        receiver: AccountAddress,
+        amount: Amount,
+    ) -> &Result<AccountTransferSuccess, AccountTransferError> {
+        let result = self.chain.account_transfer(signer, sender, receiver, amount);
+        self.push_account_transfer(result)
+    }
+
+    /// Transfer CCD between two accounts with a memo as part of the block.
+    ///
+    /// See [`Chain::account_transfer_with_memo`] for details.
+    pub fn account_transfer_with_memo(
+        &mut self,
+        signer: Signer,
+        sender: AccountAddress,
+        receiver: AccountAddress,
+        amount: Amount,
+        memo: Memo,
+    ) -> &Result<AccountTransferSuccess, AccountTransferError> {
+        let result = self.chain.account_transfer_with_memo(signer, sender, receiver, amount, memo);
+        self.push_account_transfer(result)
+    }
+
+    /// Transfer CCD between two accounts with a release schedule as part of
+    /// the block.
+    ///
+    /// See [`Chain::account_transfer_with_schedule`] for details.
+    pub fn account_transfer_with_schedule(
+        &mut self,
+        signer: Signer,
+        sender: AccountAddress,
+        receiver: AccountAddress,
+        schedule: Vec<(Timestamp, Amount)>,
+    ) -> &Result<AccountTransferSuccess, AccountTransferError> {
+        let result = self.chain.account_transfer_with_schedule(signer, sender, receiver, schedule);
+        self.push_account_transfer(result)
+    }
+
+    /// Helper for recording the outcome of an account transfer.
+    fn push_account_transfer(
+        &mut self,
+        result: Result<AccountTransferSuccess, AccountTransferError>,
+    ) -> &Result<AccountTransferSuccess, AccountTransferError> {
+        self.outcomes.push(BlockItemOutcome::AccountTransfer(result));
+        match self.outcomes.last() {
+            Some(BlockItemOutcome::AccountTransfer(result)) => result,
+            _ => unreachable!("The outcome was just added."),
+        }
+    }
+
+    /// Get an immutable reference to the chain, with the effects of the
+    /// transactions executed so far in the block.
+    ///
+    /// This can, for example, be used for calling [`Chain::contract_invoke`]
+    /// in the middle of a block.
+    pub fn chain(&self) -> &Chain { self.chain }
+
+    /// The outcomes of the transactions executed so far in the block.
+    pub fn outcomes(&self) -> &[BlockItemOutcome] { &self.outcomes }
+}
+
+impl BlockItemOutcome {
+    /// Whether the transaction succeeded.
+    pub fn is_success(&self) -> bool {
+        match self {
+            BlockItemOutcome::ModuleDeploy(result) => result.is_ok(),
+            BlockItemOutcome::ContractInit(result) => result.is_ok(),
+            BlockItemOutcome::ContractUpdate(result) => result.is_ok(),
+            BlockItemOutcome::AccountTransfer(result) => result.is_ok(),
+        }
+    }
+
+    /// The energy used by the transaction.
+    pub fn energy_used(&self) -> Energy {
+        match self {
+            BlockItemOutcome::ModuleDeploy(Ok(s)) => s.energy_used,
+            BlockItemOutcome::ModuleDeploy(Err(e)) => e.energy_used,
+            BlockItemOutcome::ContractInit(Ok(s)) => s.energy_used,
+            BlockItemOutcome::ContractInit(Err(e)) => e.energy_used,
+            BlockItemOutcome::ContractUpdate(Ok(s)) => s.energy_used,
+            BlockItemOutcome::ContractUpdate(Err(e)) => e.energy_used,
+            BlockItemOutcome::AccountTransfer(Ok(s)) => s.energy_used,
+            BlockItemOutcome::AccountTransfer(Err(e)) => e.energy_used,
+        }
+    }
+
+    /// The transaction fee paid for the transaction.
+    pub fn transaction_fee(&self) -> Amount {
+        match self {
+            BlockItemOutcome::ModuleDeploy(Ok(s)) => s.transaction_fee,
+            BlockItemOutcome::ModuleDeploy(Err(e)) => e.transaction_fee,
+            BlockItemOutcome::ContractInit(Ok(s)) => s.transaction_fee,
+            BlockItemOutcome::ContractInit(Err(e)) => e.transaction_fee,
+            BlockItemOutcome::ContractUpdate(Ok(s)) => s.transaction_fee,
+            BlockItemOutcome::ContractUpdate(Err(e)) => e.transaction_fee,
+            BlockItemOutcome::AccountTransfer(Ok(s)) => s.transaction_fee,
+            BlockItemOutcome::AccountTransfer(Err(e)) => e.transaction_fee,
+        }
+    }
+
+    /// The events logged by contracts in the transaction, paired with the
+    /// address of the contract that emitted them. Failed transactions have no
+    /// events.
+    pub fn events(&self) -> Vec<(ContractAddress, &[ContractEvent])> {
+        match self {
+            BlockItemOutcome::ContractInit(Ok(s)) => {
+                vec![(s.contract_address, s.events.as_slice())]
+            }
+            BlockItemOutcome::ContractUpdate(Ok(s)) => s.events().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl BlockSummary {
+    /// The events logged by contracts in the block, in the order that they
+    /// were emitted, and paired with the address of the contract that emitted
+    /// them.
+    pub fn events(&self) -> impl Iterator<Item = (ContractAddress, &[ContractEvent])> {
+        self.outcomes.iter().flat_map(BlockItemOutcome::events)
+    }
+
+    /// The number of transactions in the block that failed.
+    pub fn num_failed(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| !outcome.is_success()).count()
+    }
+}
+
+impl ContractModule {
+    /// Create the runnable artifact from a v1 [`WasmModule`], optionally
+    /// allowing debug output in the module.
+    pub(crate) fn from_wasm_module(
+        wasm_module: WasmModule,
+        enable_debug: bool,
+    ) -> Result<Self, ModuleInvalidError> {
+        let artifact = wasm::utils::instantiate_with_metering::<v1::ProcessedImports, _>(
+            ValidationConfig::V1,
+            &v1::ConcordiumAllowedImports {
+                support_upgrade: true,
+                enable_debug,
+            },
+            wasm_module.source.as_ref(),
+        )?;
+        Ok(Self {
+            // we follow protocol 6 semantics, and don't count the custom section size towards
+            // module size.
+            size: wasm_module.source.size().saturating_sub(artifact.custom_sections_size),
+            artifact: Arc::new(artifact.artifact),
+            source: Arc::new(wasm_module),
+            enable_debug,
         })
     }
 }
@@ -1873,6 +2667,13 @@ impl Account {
             policy,
             address,
             keys,
+            release_schedule: BTreeMap::new(),
+            stake: None,
+            signing_keys: None,
+            nonce: Nonce {
+                nonce: 1,
+            },
+            additional_policies: Vec::new(),
         }
     }
 
@@ -1888,9 +2689,82 @@ impl Account {
             policy: Self::empty_policy(),
             address,
             keys,
+            release_schedule: BTreeMap::new(),
+            stake: None,
+            signing_keys: None,
+            nonce: Nonce {
+                nonce: 1,
+            },
+            additional_policies: Vec::new(),
         }
     }
 
+    /// Create a new [`Account`](Self) with the provided balance and signing
+    /// keys, and a default account policy.
+    ///
+    /// The account keys are the public keys of the `signing_keys`, so
+    /// signatures from [`sign_message`](Self::sign_message) can be verified
+    /// with them, e.g., by contracts using `check_account_signature`.
+    ///
+    /// See [`account_keys_from_seed`] for generating keys with multiple
+    /// credentials and thresholds.
+    pub fn new_with_signing_keys(
+        address: AccountAddress,
+        balance: AccountBalance,
+        signing_keys: AccountKeys,
+    ) -> Self {
+        let mut account = Self::new_with_keys(address, balance, (&signing_keys).into());
+        account.signing_keys = Some(signing_keys);
+        account
+    }
+
+    /// Create a new [`Account`](Self) with the provided total balance and a
+    /// single key pair generated deterministically from the `seed`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let account = Account::new_from_seed(AccountAddress([0; 32]), Amount::from_ccd(10), 42);
+    /// let signatures = account.sign_message(b"hello").unwrap();
+    /// assert_eq!(signatures.num_signatures(), 1);
+    /// ```
+    pub fn new_from_seed(address: AccountAddress, total_balance: Amount, seed: u64) -> Self {
+        let signing_keys = account_keys_from_seed(seed, AccountThreshold::ONE, &[(
+            0.into(),
+            SignatureThreshold::ONE,
+            &[0.into()],
+        )]);
+        Self::new_with_signing_keys(
+            address,
+            AccountBalance {
+                total:  total_balance,
+                staked: Amount::zero(),
+                locked: Amount::zero(),
+            },
+            signing_keys,
+        )
+    }
+
+    /// The key pairs of the account, if it was created with them, e.g., with
+    /// [`new_from_seed`](Self::new_from_seed).
+    pub fn signing_keys(&self) -> Option<&AccountKeys> { self.signing_keys.as_ref() }
+
+    /// Sign a message with all the keys of the account.
+    ///
+    /// Returns an error if the account was not created with signing keys.
+    /// Use [`AccountSignatures::sign_with`] for signing with only some of the
+    /// keys.
+    pub fn sign_message(
+        &self,
+        message: &[u8],
+    ) -> Result<AccountSignatures, MissingSigningKeysError> {
+        let keys = self.signing_keys.as_ref().ok_or(MissingSigningKeysError {
+            address: self.address,
+        })?;
+        Ok(AccountSignatures::sign(keys, message))
+    }
+
     /// Create new [`Account`](Self) with the provided account policy.
     /// The account keys are initialized with an [`AccountAccessStructure`]
     /// with a threshold of 1, and no keys. So it is impossible to verify any
@@ -1940,6 +2814,22 @@ impl Account {
         )
     }
 
+    /// The amounts that are locked on the account due to scheduled transfers,
+    /// keyed by their release time.
+    ///
+    /// See [`Chain::account_transfer_with_schedule`].
+    pub fn release_schedule(&self) -> &BTreeMap<Timestamp, Amount> { &self.release_schedule }
+
+    /// Information about the stake of the account, if it is a baker or a
+    /// delegator.
+    pub fn stake(&self) -> Option<&AccountStake> { self.stake.as_ref() }
+
+    /// The nonce that the next transaction from the account must have. This
+    /// starts at `1` and is incremented by each transaction from the account
+    /// that is included in a block, i.e., that is charged a fee, whether it
+    /// succeeds or is rejected.
+    pub fn next_nonce(&self) -> Nonce { self.nonce }
+
     /// Helper for creating an empty policy.
     ///
     /// It has identity provider `0`, no items, and is valid from unix epoch
@@ -1989,6 +2879,38 @@ pub fn module_load_v1_raw(module_path: impl AsRef<Path>) -> Result<WasmModule, M
     })
 }
 
+/// Generate account keys deterministically from a `seed`, with the given
+/// `threshold` and credentials. Each credential is given by its index, its
+/// signature threshold, and the indices of its keys.
+///
+/// The same seed always gives the same keys, so tests can use fixed keys
+/// without storing them.
+///
+/// # Example
+///
+/// ```
+/// # use concordium_smart_contract_testing::*;
+/// // Two credentials, where the first one needs two of its three keys.
+/// let keys = account_keys_from_seed(7, AccountThreshold::TWO, &[
+///     (0.into(), SignatureThreshold::TWO, &[0.into(), 1.into(), 2.into()]),
+///     (1.into(), SignatureThreshold::ONE, &[0.into()]),
+/// ]);
+/// let account = Account::new_with_signing_keys(
+///     AccountAddress([0; 32]),
+///     AccountBalance::new(Amount::from_ccd(10), Amount::zero(), Amount::zero()).unwrap(),
+///     keys,
+/// );
+/// assert_eq!(account.sign_message(b"hello").unwrap().num_signatures(), 4);
+/// ```
+pub fn account_keys_from_seed(
+    seed: u64,
+    threshold: AccountThreshold,
+    credentials: &[(CredentialIndex, SignatureThreshold, &[KeyIndex])],
+) -> AccountKeys {
+    let mut rng = StdRng::seed_from_u64(seed);
+    AccountKeys::generate(threshold, credentials, &mut rng)
+}
+
 /// Load a v1 wasm module as it is output from `cargo concordium build`,
 /// i.e. **including** the prefix of 4 version bytes and 4 module length
 /// bytes.
@@ -2031,6 +2953,16 @@ impl Signer {
             num_keys,
         })
     }
+
+    /// Create a signer for signing with all the keys in `keys`, e.g., the
+    /// [`signing_keys`](Account::signing_keys) of an account. The signer has
+    /// at least one key, even if `keys` is empty.
+    pub fn with_account_keys(keys: &AccountKeys) -> Self {
+        let num_keys = keys.keys.values().map(|credential| credential.keys.len() as u32).sum();
+        Self {
+            num_keys: u32::max(num_keys, 1),
+        }
+    }
 }
 
 impl ContractInvokeError {
@@ -2137,6 +3069,55 @@ pub fn energy_to_amount(
     Amount::from_micro_ccd(cost)
 }
 
+/// The amount of an account transfer.
+enum TransferAmount {
+    /// A regular transfer, where the amount is available to the receiver
+    /// immediately.
+    Unlocked(Amount),
+    /// A transfer with a release schedule.
+    Scheduled(Vec<(Timestamp, Amount)>),
+}
+
+/// The error returned by an update when loading items from the external node
+/// fails in fork mode. No energy is charged, since the update is not executed.
+fn fork_load_error(error: ExternalNodeError) -> ContractInvokeError {
+    ContractInvokeError {
+        energy_used:     Energy::from(0),
+        transaction_fee: Amount::zero(),
+        trace_elements:  Vec::new(),
+        kind:            ContractInvokeErrorKind::ForkLoad(error),
+    }
+}
+
+/// Check that a release schedule is non-empty, has at most 255 releases with
+/// positive amounts, and has strictly increasing release times where the first
+/// one is not before the `block_time`.
+///
+/// Returns the total amount of the releases.
+fn check_release_schedule(
+    schedule: &[(Timestamp, Amount)],
+    block_time: SlotTime,
+) -> Result<Amount, ReleaseScheduleError> {
+    let Some((first_release, _)) = schedule.first() else {
+        return Err(ReleaseScheduleError::Empty);
+    };
+    if schedule.len() > 255 {
+        return Err(ReleaseScheduleError::TooManyReleases);
+    }
+    if *first_release < block_time {
+        return Err(ReleaseScheduleError::FirstReleaseExpired);
+    }
+    if schedule.windows(2).any(|w| w[0].0 >= w[1].0) {
+        return Err(ReleaseScheduleError::NonIncreasing);
+    }
+    if schedule.iter().any(|(_, amount)| *amount == Amount::zero()) {
+        return Err(ReleaseScheduleError::ZeroAmount);
+    }
+    schedule.iter().try_fold(Amount::zero(), |total, (_, amount)| {
+        total.checked_add(*amount).ok_or(ReleaseScheduleError::AmountOverflow)
+    })
+}
+
 /// Helper function that checks the validity of the exchange rates.
 ///
 /// More specifically, it checks that the cost of one energy is <= `u64::MAX /
@@ -2279,6 +3260,164 @@ mod tests {
 
         assert!(matches!(error, ChainBuilderError::ExchangeRateError));
     }
+
+    /// Test that a simple transfer moves the amount and charges the fee, and
+    /// that a transfer exceeding the available balance is rejected.
+    #[test]
+    fn test_account_transfer() {
+        let acc_0 = AccountAddress([0; 32]);
+        let acc_1 = AccountAddress([1; 32]);
+        let mut chain = Chain::new();
+        chain.create_account(Account::new(acc_0, Amount::from_ccd(100)));
+        chain.create_account(Account::new(acc_1, Amount::from_ccd(0)));
+
+        let res = chain
+            .account_transfer(Signer::with_one_key(), acc_0, acc_1, Amount::from_ccd(10))
+            .expect("Transfer should succeed");
+        assert_eq!(res.transaction_fee, chain.calculate_energy_cost(res.energy_used));
+        assert_eq!(
+            chain.account_balance_available(acc_0),
+            Some(Amount::from_ccd(90) - res.transaction_fee)
+        );
+        assert_eq!(chain.account_balance_available(acc_1), Some(Amount::from_ccd(10)));
+
+        let err = chain
+            .account_transfer(Signer::with_one_key(), acc_0, acc_1, Amount::from_ccd(90))
+            .expect_err("Transfer should fail");
+        assert!(matches!(err.kind, AccountTransferErrorKind::AmountTooLarge { .. }));
+        assert_eq!(
+            chain.account_balance_available(acc_0),
+            Some(Amount::from_ccd(90) - res.transaction_fee - err.transaction_fee)
+        );
+        assert_eq!(chain.account_balance_available(acc_1), Some(Amount::from_ccd(10)));
+    }
+
+    /// Test that scheduled amounts are locked until their release time is
+    /// reached.
+    #[test]
+    fn test_account_transfer_with_schedule() {
+        let acc_0 = AccountAddress([0; 32]);
+        let acc_1 = AccountAddress([1; 32]);
+        let mut chain = Chain::new();
+        chain.create_account(Account::new(acc_0, Amount::from_ccd(100)));
+        chain.create_account(Account::new(acc_1, Amount::from_ccd(0)));
+
+        let schedule = vec![
+            (Timestamp::from_timestamp_millis(10), Amount::from_ccd(1)),
+            (Timestamp::from_timestamp_millis(20), Amount::from_ccd(2)),
+        ];
+        chain
+            .account_transfer_with_schedule(Signer::with_one_key(), acc_0, acc_1, schedule)
+            .expect("Transfer should succeed");
+        let balance = chain.account_balance(acc_1).unwrap();
+        assert_eq!(balance.total, Amount::from_ccd(3));
+        assert_eq!(balance.locked, Amount::from_ccd(3));
+
+        chain.tick_block_time(Duration::from_millis(15)).unwrap();
+        assert_eq!(chain.account_balance(acc_1).unwrap().locked, Amount::from_ccd(2));
+        assert_eq!(chain.account_balance_available(acc_1), Some(Amount::from_ccd(1)));
+
+        chain.tick_block_time(Duration::from_millis(5)).unwrap();
+        assert_eq!(chain.account_balance_available(acc_1), Some(Amount::from_ccd(3)));
+        assert!(chain.account(acc_1).unwrap().release_schedule().is_empty());
+
+        let err = chain
+            .account_transfer_with_schedule(Signer::with_one_key(), acc_0, acc_1, vec![(
+                Timestamp::from_timestamp_millis(5),
+                Amount::from_ccd(1),
+            )])
+            .expect_err("Transfer should fail");
+        assert!(matches!(
+            err.kind,
+            AccountTransferErrorKind::InvalidSchedule(ReleaseScheduleError::FirstReleaseExpired)
+        ));
+
+        // Empty schedules are rejected, but the fee is still charged.
+        let balance_before = chain.account_balance_available(acc_0).unwrap();
+        let err = chain
+            .account_transfer_with_schedule(Signer::with_one_key(), acc_0, acc_1, Vec::new())
+            .expect_err("Transfer should fail");
+        assert!(matches!(
+            err.kind,
+            AccountTransferErrorKind::InvalidSchedule(ReleaseScheduleError::Empty)
+        ));
+        assert_eq!(
+            chain.account_balance_available(acc_0),
+            Some(balance_before - err.transaction_fee)
+        );
+
+        let err = chain
+            .account_transfer_with_schedule(Signer::with_one_key(), acc_0, acc_1, vec![
+                (Timestamp::from_timestamp_millis(100), Amount::from_micro_ccd(u64::MAX)),
+                (Timestamp::from_timestamp_millis(200), Amount::from_micro_ccd(1)),
+            ])
+            .expect_err("Transfer should fail");
+        assert!(matches!(
+            err.kind,
+            AccountTransferErrorKind::InvalidSchedule(ReleaseScheduleError::AmountOverflow)
+        ));
+    }
+
+    /// Test that a stake reduction only takes effect after the cooldown, and
+    /// that rewards are restaked when configured.
+    #[test]
+    fn test_stake_cooldown_and_rewards() {
+        let acc = AccountAddress([0; 32]);
+        let mut chain = Chain::new();
+        chain.create_account(Account::new(acc, Amount::from_ccd(100)));
+        chain.set_stake_cooldown(Duration::from_millis(100));
+
+        chain.account_add_baker(acc, Amount::from_ccd(60), true).expect("Adding baker works");
+        assert_eq!(chain.account_balance_available(acc), Some(Amount::from_ccd(40)));
+        assert_eq!(
+            chain.account_add_delegation(acc, Amount::from_ccd(1), false),
+            Err(StakeError::AlreadyStaking)
+        );
+
+        chain.account_reward(acc, Amount::from_ccd(10)).unwrap();
+        assert_eq!(chain.account_balance(acc).unwrap().staked, Amount::from_ccd(70));
+
+        chain.account_remove_stake(acc).expect("Removing stake works");
+        chain.tick_block_time(Duration::from_millis(99)).unwrap();
+        assert_eq!(chain.account_balance(acc).unwrap().staked, Amount::from_ccd(70));
+        assert_eq!(chain.account_update_stake(acc, Amount::zero()), Err(StakeError::PendingChange));
+
+        chain.tick_block_time(Duration::from_millis(1)).unwrap();
+        assert_eq!(chain.account_balance(acc).unwrap().staked, Amount::zero());
+        assert_eq!(chain.account_balance_available(acc), Some(Amount::from_ccd(110)));
+        assert!(chain.account(acc).unwrap().stake().is_none());
+    }
+
+    /// Test that restoring a snapshot rolls back accounts and parameters, and
+    /// that a snapshot can be restored multiple times.
+    #[test]
+    fn test_snapshot_and_restore() {
+        let acc = AccountAddress([0; 32]);
+        let acc_other = AccountAddress([1; 32]);
+        let mut chain = Chain::new();
+        chain.create_account(Account::new(acc, Amount::from_ccd(10)));
+        let snapshot = chain.snapshot();
+
+        chain.create_account(Account::new(acc, Amount::from_ccd(5)));
+        chain.create_account(Account::new(acc_other, Amount::from_ccd(1)));
+        chain.tick_block_time(Duration::from_millis(100)).unwrap();
+        chain
+            .set_exchange_rates(
+                ExchangeRate::new_unchecked(1, 1),
+                ExchangeRate::new_unchecked(1, 1),
+            )
+            .unwrap();
+
+        for _ in 0..2 {
+            chain.restore(&snapshot);
+            assert_eq!(chain.account_balance_available(acc), Some(Amount::from_ccd(10)));
+            assert!(!chain.account_exists(acc_other));
+            assert_eq!(chain.block_time(), Timestamp::from_timestamp_millis(0));
+            assert_eq!(chain.micro_ccd_per_euro(), Chain::new().micro_ccd_per_euro());
+            assert_eq!(chain.euro_per_energy(), Chain::new().euro_per_energy());
+            chain.create_account(Account::new(acc_other, Amount::from_ccd(1)));
+        }
+    }
 }
 
 /// Return whether execution is running under `cargo concordium test` with
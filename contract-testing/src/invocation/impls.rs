@@ -5,8 +5,11 @@ use crate::{
         contract_events_from_logs, from_interpreter_energy, lookup_module_cost,
         to_interpreter_energy,
     },
+    state::mutable_state_entries,
+    tracer::{host_interrupt, host_interrupt_response, state_changes_between},
     types::{Account, BalanceError, Contract, ContractModule, TransferError},
-    AccountSignatures, DebugTraceElement, ExecutionError, InvokeExecutionError,
+    AccountSignatures, CallRecord, CallRecordKind, DebugTraceElement, ExecutionError,
+    InvokeExecutionError,
 };
 use concordium_rust_sdk::{
     base::{
@@ -180,18 +183,11 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
 
         // Sender policies have a very bespoke serialization in
         // order to allow skipping portions of them in smart contracts.
-        let sender_policies = {
-            let mut out = Vec::new();
-            let policy = &self
-                .chain
-                .account(invoker)
-                .expect("Precondition violation: invoker must exist.")
-                .policy;
-            policy
-                .serial_for_smart_contract(&mut out)
-                .expect("Writing to a vector should succeed.");
-            out
-        };
+        let sender_policies = self
+            .chain
+            .account(invoker)
+            .expect("Precondition violation: invoker must exist.")
+            .policies_for_smart_contract();
 
         // Construct the receive context
         let receive_ctx = v1::ReceiveContext {
@@ -218,6 +214,7 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
         let mut loader = v1::trie::Loader::new(&[][..]); // An empty loader is fine currently, as we do not use caching in this lib.
         let mut mutable_state = self.contract_state(payload.address);
         let mut mutable_state = mutable_state.make_fresh_generation(&mut loader);
+        let traced_state = self.chain.is_traced().then(|| mutable_state_entries(&mutable_state));
         let inner = mutable_state.get_inner(&mut loader);
         let instance_state = v1::InstanceState::new(loader, inner);
 
@@ -250,6 +247,8 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
             trace_elements_checkpoint,
             next_mod_idx_checkpoint: mod_idx_before_invoke,
             mod_idx_before_invoke,
+            traced_state,
+            call_records_checkpoint: self.chain.call_records_len(),
         })))
     }
 
@@ -286,6 +285,11 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                 } => {
                     match response {
                         Some(response) => {
+                            self.record_call(
+                                &data,
+                                stack.len(),
+                                CallRecordKind::Resumed(host_interrupt_response(&response)),
+                            );
                             let receive_result = self.run_interpreter(|energy| {
                                 v1::resume_receive(
                                     config,
@@ -336,9 +340,20 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                                     // Update the state field with the newest value from the
                                     // changeset.
                                     data.state = self.contract_state(data.address);
+                                    // The changes were made by the called contracts, and are
+                                    // recorded for them.
+                                    if data.traced_state.is_some() {
+                                        data.traced_state =
+                                            Some(mutable_state_entries(&data.state));
+                                    }
                                 }
                                 state_changed
                             };
+                            self.record_call(
+                                &data,
+                                stack.len(),
+                                CallRecordKind::Resumed(host_interrupt_response(&call_response)),
+                            );
 
                             // Add resume event
                             let resume_event = ContractTraceElement::Resumed {
@@ -430,6 +445,7 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                             invocation_data.address,
                             &mut invocation_data.state,
                         );
+                        self.record_state_changes(&mut invocation_data, stack.len());
                     }
 
                     invoke_response = Some(v1::InvokeResponse::Success {
@@ -462,6 +478,14 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                     } else {
                         self.modification_index(invocation_data.address)
                     };
+                    if state_changed {
+                        self.record_state_changes(&mut invocation_data, stack.len());
+                    }
+                    self.record_call(
+                        &invocation_data,
+                        stack.len(),
+                        CallRecordKind::Interrupt(host_interrupt(&interrupt)),
+                    );
                     match interrupt {
                         v1::Interrupt::Transfer {
                             to,
@@ -571,6 +595,7 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                             {
                                 // The contract to call does not exist.
                                 None => {
+                                    self.chain.fork_record_missing_contract(address);
                                     let response = v1::InvokeResponse::Failure {
                                         kind: v1::InvokeFailure::NonExistentContract,
                                     };
@@ -647,9 +672,12 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
 
                             let response =
                                 match self.chain.modules.get(&module_ref) {
-                                    None => v1::InvokeResponse::Failure {
-                                        kind: v1::InvokeFailure::UpgradeInvalidModuleRef,
-                                    },
+                                    None => {
+                                        self.chain.fork_record_missing_module(module_ref);
+                                        v1::InvokeResponse::Failure {
+                                            kind: v1::InvokeFailure::UpgradeInvalidModuleRef,
+                                        }
+                                    }
                                     Some(module) => {
                                         // Charge for the module lookup.
                                         exit_ooe!(
@@ -920,6 +948,10 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                     trace,
                 } => {
                     self.update_energy(remaining_energy);
+                    // The changes since the previous record are discarded together with the
+                    // records of the call.
+                    self.record_state_changes(&mut invocation_data, stack.len());
+                    self.chain.roll_back_call_records(invocation_data.call_records_checkpoint);
                     // Remove the failure stack traces from the list and include them in a failure
                     // element.
                     let failure_traces =
@@ -955,6 +987,10 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                     trace,
                 } => {
                     self.update_energy(remaining_energy);
+                    // The changes since the previous record are discarded together with the
+                    // records of the call.
+                    self.record_state_changes(&mut invocation_data, stack.len());
+                    self.chain.roll_back_call_records(invocation_data.call_records_checkpoint);
                     // Remove the failure stack traces from the list and include them in a failure
                     // element.
                     let failure_traces =
@@ -1007,6 +1043,7 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
     ) -> Result<Amount, TransferError> {
         // Ensure the `to` account exists.
         if !self.chain.accounts.contains_key(&to.into()) {
+            self.chain.fork_record_missing_account(to);
             return Err(TransferError::ToMissing);
         }
 
@@ -1032,6 +1069,7 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
     ) -> Result<Amount, TransferError> {
         // Ensure the `to` contract exists.
         if !self.chain.contracts.contains_key(&to) {
+            self.chain.fork_record_missing_contract(to);
             return Err(TransferError::ToMissing);
         }
 
@@ -1055,6 +1093,7 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
     ) -> Result<Amount, TransferError> {
         // Ensure the `to` account exists.
         if !self.chain.contracts.contains_key(&to) {
+            self.chain.fork_record_missing_contract(to);
             return Err(TransferError::ToMissing);
         }
 
@@ -1144,7 +1183,7 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                     .expect("Precondition violation: account assumed to exist")
                     .balance
                     .available();
-                if self.invoker == address {
+                if self.invoker.is_alias(&address) {
                     // It has been checked that the invoker account has sufficient balance for
                     // paying.
                     original_balance -= self.reserved_amount;
@@ -1184,7 +1223,13 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
     fn contract_balance(&self, address: ContractAddress) -> Option<Amount> {
         match self.changeset.current().contracts.get(&address) {
             Some(changes) => Some(changes.current_balance()),
-            None => self.chain.contracts.get(&address).map(|c| c.self_balance),
+            None => {
+                let balance = self.chain.contracts.get(&address).map(|c| c.self_balance);
+                if balance.is_none() {
+                    self.chain.fork_record_missing_contract(address);
+                }
+                balance
+            }
         }
     }
 
@@ -1196,22 +1241,25 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
     ///  - If the changeset contains a module reference, then it must refer a
     ///    deployed module.
     fn contract_module(&self, contract: &Contract) -> ContractModule {
-        match self.changeset.current().contracts.get(&contract.address).and_then(|c| c.module) {
+        let module_reference = match self
+            .changeset
+            .current()
+            .contracts
+            .get(&contract.address)
+            .and_then(|c| c.module)
+        {
             // Contract has been upgrade, new module exists.
-            Some(new_module) => self
-                .chain
-                .modules
-                .get(&new_module)
-                .expect("Precondition violation: module must exist.")
-                .clone(),
+            Some(new_module) => new_module,
             // Contract hasn't been upgraded. Use persisted module.
-            None => self
-                .chain
-                .modules
-                .get(&contract.module_reference)
-                .expect("Precondition violation: module must exist.")
-                .clone(),
-        }
+            None => contract.module_reference,
+        };
+        let module = self
+            .chain
+            .modules
+            .get(&module_reference)
+            .expect("Precondition violation: module must exist.");
+        // Execute the instrumented module if coverage is enabled.
+        self.chain.coverage_artifact(module_reference, module)
     }
 
     /// Get the contract state, either from the changeset or by thawing it from
@@ -1237,7 +1285,11 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
     /// Looks up the account balance for an account by first checking
     /// the changeset, then the persisted values.
     fn account_balance(&self, address: AccountAddress) -> Option<AccountBalance> {
-        let mut account_balance = self.chain.accounts.get(&address.into()).map(|a| a.balance)?;
+        let Some(mut account_balance) = self.chain.accounts.get(&address.into()).map(|a| a.balance)
+        else {
+            self.chain.fork_record_missing_account(address);
+            return None;
+        };
         match self.changeset.current().accounts.get(&address.into()).map(|a| a.current_balance()) {
             // Account exists in changeset.
             // Return the staked and locked balances from persistence, as they can't change during
@@ -1249,7 +1301,7 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
             }),
             // Account doesn't exist in changeset.
             None => {
-                if self.invoker == address {
+                if self.invoker.is_alias(&address) {
                     account_balance.total -= self.reserved_amount;
                 }
                 Some(account_balance)
@@ -1261,7 +1313,11 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
     fn account_keys(&self, address: AccountAddress) -> Option<&AccountAccessStructure> {
         // The account keys cannot change during a smart contract transaction, so
         // there is no need to check in the changeset.
-        self.chain.accounts.get(&address.into()).map(|a| &a.keys)
+        let keys = self.chain.accounts.get(&address.into()).map(|a| &a.keys);
+        if keys.is_none() {
+            self.chain.fork_record_missing_account(address);
+        }
+        keys
     }
 
     /// Saves a mutable state for a contract in the changeset.
@@ -1515,6 +1571,36 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
         };
         trace_elements.push(new);
     }
+
+    /// Record a host call or state change of the current call, if the
+    /// invocation is traced. The depth of the call is the number of calls
+    /// waiting on the stack.
+    fn record_call(&self, data: &InvocationData, depth: usize, kind: CallRecordKind) {
+        self.chain.record_call(CallRecord {
+            depth,
+            address: data.address,
+            entrypoint: data.entrypoint.clone(),
+            rolled_back: false,
+            kind,
+        });
+    }
+
+    /// Record the changes to the state of the current call since the previous
+    /// record, if the invocation is traced.
+    ///
+    /// Only the state of the executing contract is read, as the other
+    /// contracts cannot change while it executes.
+    fn record_state_changes(&self, data: &mut InvocationData, depth: usize) {
+        let Some(before) = data.traced_state.take() else {
+            return;
+        };
+        let after = mutable_state_entries(&data.state);
+        let changes = state_changes_between(&before, &after);
+        data.traced_state = Some(after);
+        if !changes.is_empty() {
+            self.record_call(data, depth, CallRecordKind::StateChanges(changes));
+        }
+    }
 }
 
 /// A pair of the signatures, and the data.
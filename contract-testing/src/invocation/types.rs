@@ -159,6 +159,14 @@ pub(super) struct InvocationData {
     /// Differs from the `next_mod_idx_checkpoint` in that this value can be
     /// altered during the execution of a single entrypoint.
     pub(super) mod_idx_before_invoke:     u32,
+    /// The entries of the state when the state changes were last recorded, if
+    /// the invocation is traced with [`Chain::contract_update_traced`] or
+    /// [`Chain::contract_invoke_traced`].
+    pub(super) traced_state:              Option<BTreeMap<Vec<u8>, Vec<u8>>>,
+    /// A checkpoint in the records of the traced invocation.
+    /// The records after it are marked as rolled back in case of failure of
+    /// execution.
+    pub(super) call_records_checkpoint:   usize,
 }
 
 /// A positive or negative delta in for an [`Amount`].
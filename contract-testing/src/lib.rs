@@ -85,31 +85,58 @@
 //!     - update.transaction_fee));
 //!     
 //! ```
+mod baseline;
+mod call_tree;
 mod constants;
+mod coverage;
+mod emulator;
+mod entrypoint_fuzz;
+mod external_node;
+mod fork;
+mod fuzz;
 mod impls;
 mod invocation;
+mod persistence;
+mod policy;
+mod profile;
+mod replay;
+mod scenario;
+mod schema;
+mod signed;
+mod state;
+mod trace_walk;
+mod tracer;
 mod types;
-pub use impls::{is_debug_enabled, module_load_v1, module_load_v1_raw};
+pub use baseline::BLESS_ENERGY_ENV_VAR;
+pub use impls::{account_keys_from_seed, is_debug_enabled, module_load_v1, module_load_v1_raw};
 pub use types::*;
 
 // Re-export types.
 pub use concordium_rust_sdk::{
     base::{
-        base::Energy,
-        common::types::{CredentialIndex, KeyIndex},
+        base::{Energy, Nonce},
+        common::types::{CredentialIndex, KeyIndex, TransactionTime},
         contracts_common::{
             from_bytes, to_bytes, AccountAddress, AccountBalance, AccountThreshold, Address,
             Amount, ContractAddress, ContractName, Duration, EntrypointName, ExchangeRate,
-            ModuleReference, OwnedContractName, OwnedEntrypointName, OwnedParameter,
+            ExchangeRates, ModuleReference, OwnedContractName, OwnedEntrypointName, OwnedParameter,
             OwnedReceiveName, Parameter, ReceiveName, SignatureThreshold, SlotTime, Timestamp,
         },
         ed25519,
-        hashes::BlockHash,
+        hashes::{BlockHash, TransactionHash},
         id::types::{AccountKeys, CredentialPublicKeys, VerifyKey},
-        smart_contracts::{ContractEvent, ContractTraceElement, InstanceUpdatedEvent, WasmVersion},
-        transactions::{AccountAccessStructure, InitContractPayload, UpdateContractPayload},
+        smart_contracts::{
+            ContractEvent, ContractTraceElement, InstanceUpdatedEvent, WasmModule, WasmVersion,
+        },
+        transactions::{
+            AccountAccessStructure, AccountTransaction, EncodedPayload, InitContractPayload, Memo,
+            Payload, UpdateContractPayload,
+        },
     },
     smart_contracts::engine::v1::InvokeFailure,
-    types::RejectReason,
+    types::{
+        smart_contracts::{ContractContext, InvokeContractResult},
+        RejectReason,
+    },
     v2::Endpoint,
 };
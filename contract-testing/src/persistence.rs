@@ -0,0 +1,454 @@
+//! Saving a [`Chain`] to a file and loading it back.
+//!
+//! The file format is a binary format of our own, which starts with the magic
+//! bytes [`MAGIC`] followed by a version number. Everything except the
+//! external node connection is stored, and the module artifacts are recreated
+//! from the stored module sources on load.
+use crate::types::*;
+use concordium_rust_sdk::{
+    base::{
+        base::{AccountAddressEq, Nonce},
+        common,
+        contracts_common::{
+            AccountAddress, AccountBalance, Amount, AttributeTag, AttributeValue, ContractAddress,
+            Deserial, Duration, ExchangeRate, ModuleReference, OwnedContractName, OwnedPolicy,
+            ParseError, ParseResult, Read, Serial, Timestamp, Write,
+        },
+        smart_contracts::{ModuleSource, WasmModule, WasmVersion},
+    },
+    smart_contracts::engine::v1::trie,
+};
+use std::{io::Cursor, path::Path};
+
+/// The magic bytes at the start of every chain file.
+const MAGIC: [u8; 8] = *b"CCDCHAIN";
+
+/// The version of the chain file format produced by [`Chain::save_to`].
+const VERSION: u32 = 1;
+
+impl Chain {
+    /// Save the chain to a file.
+    ///
+    /// This stores the accounts (including their keys and policies), the
+    /// deployed modules, the contract instances with their state, and the
+    /// chain parameters, i.e., block time and exchange rates.
+    /// The external node connection is *not* saved.
+    ///
+    /// The file can be loaded again with [`Chain::load_from`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut chain = Chain::new();
+    /// chain.create_account(Account::new(AccountAddress([0; 32]), Amount::from_ccd(10)));
+    /// chain.save_to("world.chain").expect("Saving the chain succeeds");
+    ///
+    /// let loaded = Chain::load_from("world.chain").expect("Loading the chain succeeds");
+    /// assert_eq!(
+    ///     loaded.account_balance_available(AccountAddress([0; 32])),
+    ///     Some(Amount::from_ccd(10))
+    /// );
+    /// ```
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<(), ChainSaveError> {
+        let path = path.as_ref();
+        let to_error = |kind| ChainSaveError {
+            path: path.to_path_buf(),
+            kind,
+        };
+        let file = std::fs::File::create(path).map_err(|e| to_error(e.into()))?;
+        let mut writer = std::io::BufWriter::new(file);
+        self.write_saved(&mut writer).map_err(to_error)?;
+        std::io::Write::flush(&mut writer).map_err(|e| to_error(e.into()))
+    }
+
+    /// Load a chain from a file produced by [`Chain::save_to`].
+    ///
+    /// The module artifacts are recreated from the saved module sources. The
+    /// loaded chain has no external node connection.
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self, ChainLoadError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| ChainLoadError {
+            path: path.to_path_buf(),
+            kind: e.into(),
+        })?;
+        Self::from_saved_bytes(&bytes).map_err(|kind| ChainLoadError {
+            path: path.to_path_buf(),
+            kind,
+        })
+    }
+
+    /// Serialize the chain into the format described in the module
+    /// documentation.
+    pub(crate) fn write_saved<W: std::io::Write>(
+        &self,
+        out: &mut W,
+    ) -> Result<(), ChainSaveErrorKind> {
+        std::io::Write::write_all(out, &MAGIC)?;
+        VERSION.serial(out)?;
+
+        // Chain parameters.
+        self.parameters.block_time.serial(out)?;
+        serial_exchange_rate(self.parameters.micro_ccd_per_euro, out)?;
+        serial_exchange_rate(self.parameters.euro_per_energy, out)?;
+        self.parameters.stake_cooldown.serial(out)?;
+        self.parameters.strict_transactions.serial(out)?;
+        self.next_contract_index.serial(out)?;
+
+        // Accounts.
+        (self.accounts.len() as u64).serial(out)?;
+        for account in self.accounts.values() {
+            account.address.serial(out)?;
+            account.balance.total.serial(out)?;
+            account.balance.staked.serial(out)?;
+            account.balance.locked.serial(out)?;
+            serial_policy(&account.policy, out)?;
+            common::to_bytes(&account.keys).serial(out)?;
+            (account.release_schedule.len() as u64).serial(out)?;
+            for (release_time, amount) in account.release_schedule.iter() {
+                release_time.serial(out)?;
+                amount.serial(out)?;
+            }
+            serial_stake(account.stake.as_ref(), out)?;
+            // The signing keys are stored as JSON.
+            let signing_keys = account.signing_keys.as_ref().map(|keys| {
+                serde_json::to_vec(keys).expect("Serializing keys to JSON cannot fail.")
+            });
+            signing_keys.serial(out)?;
+            account.nonce.nonce.serial(out)?;
+            (account.additional_policies.len() as u64).serial(out)?;
+            for policy in account.additional_policies.iter() {
+                serial_policy(policy, out)?;
+            }
+        }
+
+        // Modules.
+        (self.modules.len() as u64).serial(out)?;
+        for (module_reference, module) in self.modules.iter() {
+            module_reference.serial(out)?;
+            module.enable_debug.serial(out)?;
+            module.source.source.as_ref().to_vec().serial(out)?;
+        }
+
+        // Contracts.
+        (self.contracts.len() as u64).serial(out)?;
+        for contract in self.contracts.values() {
+            contract.address.serial(out)?;
+            contract.module_reference.serial(out)?;
+            contract.contract_name.serial(out)?;
+            contract.owner.serial(out)?;
+            contract.self_balance.serial(out)?;
+            let mut state = Vec::new();
+            let mut loader = trie::Loader::new(&[][..]);
+            contract.state.serialize(&mut loader, &mut state).map_err(ChainSaveErrorKind::State)?;
+            state.serial(out)?;
+        }
+        Ok(())
+    }
+
+    /// Deserialize a chain from the format described in the module
+    /// documentation.
+    pub(crate) fn from_saved_bytes(bytes: &[u8]) -> Result<Self, ChainLoadErrorKind> {
+        let mut source = Cursor::new(bytes);
+        let mut magic = [0u8; 8];
+        source.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(ChainLoadErrorKind::NotAChainFile);
+        }
+        let version = u32::deserial(&mut source)?;
+        if version != VERSION {
+            return Err(ChainLoadErrorKind::UnsupportedVersion(version));
+        }
+
+        // Chain parameters.
+        let block_time = Timestamp::deserial(&mut source)?;
+        let micro_ccd_per_euro = deserial_exchange_rate(&mut source)?;
+        let euro_per_energy = deserial_exchange_rate(&mut source)?;
+        let stake_cooldown = Duration::deserial(&mut source)?;
+        let strict_transactions = bool::deserial(&mut source)?;
+        let next_contract_index = u64::deserial(&mut source)?;
+        let mut chain =
+            Chain::new_with_time_and_rates(block_time, micro_ccd_per_euro, euro_per_energy)
+                .map_err(|_| ChainLoadErrorKind::InvalidExchangeRates)?;
+        chain.parameters.stake_cooldown = stake_cooldown;
+        chain.parameters.strict_transactions = strict_transactions;
+        chain.next_contract_index = next_contract_index;
+
+        // Accounts.
+        let num_accounts = u64::deserial(&mut source)?;
+        for _ in 0..num_accounts {
+            let address = AccountAddress::deserial(&mut source)?;
+            let balance = AccountBalance {
+                total:  Amount::deserial(&mut source)?,
+                staked: Amount::deserial(&mut source)?,
+                locked: Amount::deserial(&mut source)?,
+            };
+            let policy = deserial_policy(&mut source)?;
+            let keys_bytes = Vec::<u8>::deserial(&mut source)?;
+            let keys = common::from_bytes(&mut Cursor::new(keys_bytes))
+                .map_err(|_| ChainLoadErrorKind::Parse(ParseError::default()))?;
+            let mut account = Account::new_with_policy_and_keys(address, balance, policy, keys);
+            let num_releases = u64::deserial(&mut source)?;
+            for _ in 0..num_releases {
+                let release_time = Timestamp::deserial(&mut source)?;
+                let amount = Amount::deserial(&mut source)?;
+                account.release_schedule.insert(release_time, amount);
+            }
+            account.stake = deserial_stake(&mut source)?;
+            if let Some(signing_keys) = Option::<Vec<u8>>::deserial(&mut source)? {
+                account.signing_keys = Some(
+                    serde_json::from_slice(&signing_keys)
+                        .map_err(|_| ChainLoadErrorKind::Parse(ParseError::default()))?,
+                );
+            }
+            account.nonce = Nonce {
+                nonce: u64::deserial(&mut source)?,
+            };
+            let num_additional_policies = u64::deserial(&mut source)?;
+            for _ in 0..num_additional_policies {
+                account.additional_policies.push(deserial_policy(&mut source)?);
+            }
+            chain.accounts.insert(AccountAddressEq::from(address), account);
+        }
+
+        // Modules.
+        let num_modules = u64::deserial(&mut source)?;
+        for _ in 0..num_modules {
+            let module_reference = ModuleReference::deserial(&mut source)?;
+            let enable_debug = bool::deserial(&mut source)?;
+            let wasm_module = WasmModule {
+                version: WasmVersion::V1,
+                source:  ModuleSource::from(Vec::<u8>::deserial(&mut source)?),
+            };
+            let module = ContractModule::from_wasm_module(wasm_module, enable_debug)
+                .map_err(|e| ChainLoadErrorKind::InvalidModule(module_reference, e))?;
+            chain.modules.insert(module_reference, module);
+        }
+
+        // Contracts.
+        let num_contracts = u64::deserial(&mut source)?;
+        for _ in 0..num_contracts {
+            let address = ContractAddress::deserial(&mut source)?;
+            let module_reference = ModuleReference::deserial(&mut source)?;
+            let contract_name = OwnedContractName::deserial(&mut source)?;
+            let owner = AccountAddress::deserial(&mut source)?;
+            let self_balance = Amount::deserial(&mut source)?;
+            let state_bytes = Vec::<u8>::deserial(&mut source)?;
+            let state = trie::PersistentState::deserialize(&mut Cursor::new(state_bytes))
+                .map_err(ChainLoadErrorKind::State)?;
+            chain.contracts.insert(address, Contract {
+                address,
+                module_reference,
+                contract_name,
+                state,
+                owner,
+                self_balance,
+            });
+        }
+
+        if source.position() != bytes.len() as u64 {
+            return Err(ChainLoadErrorKind::TrailingData);
+        }
+        Ok(chain)
+    }
+}
+
+impl From<ParseError> for ChainLoadErrorKind {
+    fn from(err: ParseError) -> Self { Self::Parse(err) }
+}
+
+/// Serialize an [`ExchangeRate`] as its numerator followed by its denominator.
+fn serial_exchange_rate<W: Write>(rate: ExchangeRate, out: &mut W) -> Result<(), W::Err> {
+    rate.numerator().serial(out)?;
+    rate.denominator().serial(out)
+}
+
+/// Deserialize an [`ExchangeRate`] written by [`serial_exchange_rate`].
+fn deserial_exchange_rate<R: Read>(source: &mut R) -> ParseResult<ExchangeRate> {
+    let numerator = u64::deserial(source)?;
+    let denominator = u64::deserial(source)?;
+    ExchangeRate::new(numerator, denominator).ok_or_else(ParseError::default)
+}
+
+/// Serialize the optional [`AccountStake`] of an account.
+fn serial_stake<W: Write>(stake: Option<&AccountStake>, out: &mut W) -> Result<(), W::Err> {
+    let Some(stake) = stake else {
+        return 0u8.serial(out);
+    };
+    1u8.serial(out)?;
+    match stake.kind {
+        StakeKind::Baker => 0u8.serial(out)?,
+        StakeKind::Delegator => 1u8.serial(out)?,
+    }
+    stake.restake_earnings.serial(out)?;
+    match stake.pending_change {
+        None => 0u8.serial(out),
+        Some(change) => {
+            1u8.serial(out)?;
+            change.effective_time.serial(out)?;
+            change.new_stake.serial(out)
+        }
+    }
+}
+
+/// Deserialize the optional [`AccountStake`] written by [`serial_stake`].
+fn deserial_stake<R: Read>(source: &mut R) -> ParseResult<Option<AccountStake>> {
+    if !bool::deserial(source)? {
+        return Ok(None);
+    }
+    let kind = match u8::deserial(source)? {
+        0 => StakeKind::Baker,
+        1 => StakeKind::Delegator,
+        _ => return Err(ParseError::default()),
+    };
+    let restake_earnings = bool::deserial(source)?;
+    let pending_change = if bool::deserial(source)? {
+        Some(PendingStakeChange {
+            effective_time: Timestamp::deserial(source)?,
+            new_stake:      Amount::deserial(source)?,
+        })
+    } else {
+        None
+    };
+    Ok(Some(AccountStake {
+        kind,
+        restake_earnings,
+        pending_change,
+    }))
+}
+
+/// Serialize an [`OwnedPolicy`]. Each attribute value is written with a
+/// one-byte length prefix.
+fn serial_policy<W: Write>(policy: &OwnedPolicy, out: &mut W) -> Result<(), W::Err> {
+    policy.identity_provider.serial(out)?;
+    policy.created_at.serial(out)?;
+    policy.valid_to.serial(out)?;
+    (policy.items.len() as u16).serial(out)?;
+    for (tag, value) in policy.items.iter() {
+        tag.0.serial(out)?;
+        let value = value.as_ref();
+        (value.len() as u8).serial(out)?;
+        for byte in value {
+            byte.serial(out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Deserialize an [`OwnedPolicy`] written by [`serial_policy`].
+fn deserial_policy<R: Read>(source: &mut R) -> ParseResult<OwnedPolicy> {
+    let identity_provider = u32::deserial(source)?;
+    let created_at = Timestamp::deserial(source)?;
+    let valid_to = Timestamp::deserial(source)?;
+    let num_items = u16::deserial(source)?;
+    let mut items = Vec::with_capacity(num_items.into());
+    for _ in 0..num_items {
+        let tag = AttributeTag(u8::deserial(source)?);
+        let len = usize::from(u8::deserial(source)?);
+        // At most 31 bytes can be stored in an attribute value.
+        if len > 31 {
+            return Err(ParseError::default());
+        }
+        let mut buf = [0u8; 31];
+        source.read_exact(&mut buf[..len])?;
+        items.push((tag, AttributeValue::from(&buf[..len])));
+    }
+    Ok(OwnedPolicy {
+        identity_provider,
+        created_at,
+        valid_to,
+        items,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that a chain with accounts and non-default parameters survives a
+    /// round trip through the file format.
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let acc = AccountAddress([0; 32]);
+        let mut chain = Chain::new_with_time_and_rates(
+            Timestamp::from_timestamp_millis(1234),
+            ExchangeRate::new_unchecked(50000, 1),
+            ExchangeRate::new_unchecked(1, 50000),
+        )
+        .unwrap();
+        chain.create_account(Account::new_with_policy(
+            acc,
+            AccountBalance::new(Amount::from_ccd(10), Amount::from_ccd(2), Amount::from_ccd(3))
+                .unwrap(),
+            OwnedPolicy {
+                identity_provider: 1,
+                created_at:        Timestamp::from_timestamp_millis(10),
+                valid_to:          Timestamp::from_timestamp_millis(20),
+                items:             vec![(AttributeTag(5), [b'D', b'K'].into())],
+            },
+        ));
+        chain.accounts.get_mut(&acc.into()).unwrap().nonce = Nonce {
+            nonce: 5,
+        };
+        chain.set_strict_transactions(true);
+        chain.accounts.get_mut(&acc.into()).unwrap().add_credential_policy(OwnedPolicy {
+            identity_provider: 2,
+            created_at:        Timestamp::from_timestamp_millis(30),
+            valid_to:          Timestamp::from_timestamp_millis(40),
+            items:             Vec::new(),
+        });
+
+        let mut bytes = Vec::new();
+        chain.write_saved(&mut bytes).expect("Serialization succeeds");
+        let loaded = Chain::from_saved_bytes(&bytes).expect("Deserialization succeeds");
+
+        assert_eq!(loaded.block_time(), chain.block_time());
+        assert_eq!(loaded.micro_ccd_per_euro(), chain.micro_ccd_per_euro());
+        assert_eq!(loaded.euro_per_energy(), chain.euro_per_energy());
+        let account = loaded.account(acc).expect("Account exists");
+        assert_eq!(account.balance, chain.account(acc).unwrap().balance);
+        assert!(account.policies().eq(chain.account(acc).unwrap().policies()));
+        assert_eq!(account.next_nonce(), Nonce {
+            nonce: 5,
+        });
+        assert!(loaded.is_strict_transactions());
+    }
+
+    /// Test that attribute values longer than 31 bytes are rejected.
+    #[test]
+    fn test_deserial_policy_rejects_long_attributes() {
+        let policy = OwnedPolicy {
+            identity_provider: 1,
+            created_at:        Timestamp::from_timestamp_millis(10),
+            valid_to:          Timestamp::from_timestamp_millis(20),
+            items:             vec![(AttributeTag(5), [b'D', b'K'].into())],
+        };
+        let mut bytes = Vec::new();
+        serial_policy(&policy, &mut bytes).unwrap();
+        let parsed = deserial_policy(&mut Cursor::new(&bytes)).expect("Policy is valid");
+        assert_eq!(parsed.items[0].1.as_ref(), b"DK");
+
+        // Change the length of the attribute value to 32.
+        let len_index = bytes.len() - 3;
+        assert_eq!(bytes[len_index], 2);
+        bytes[len_index] = 32;
+        bytes.extend_from_slice(&[0; 30]);
+        assert!(deserial_policy(&mut Cursor::new(&bytes)).is_err());
+    }
+
+    /// Test that files with the wrong magic bytes or version are rejected.
+    #[test]
+    fn test_load_rejects_unknown_files() {
+        let mut bytes = Vec::new();
+        Chain::new().write_saved(&mut bytes).unwrap();
+        bytes[8] = 0xff;
+        assert!(matches!(
+            Chain::from_saved_bytes(&bytes),
+            Err(ChainLoadErrorKind::UnsupportedVersion(_))
+        ));
+        assert!(matches!(
+            Chain::from_saved_bytes(b"not a chain"),
+            Err(ChainLoadErrorKind::Parse(_)) | Err(ChainLoadErrorKind::NotAChainFile)
+        ));
+    }
+}
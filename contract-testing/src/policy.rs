@@ -0,0 +1,306 @@
+//! Identity credentials of test accounts.
+//!
+//! The [`PolicyBuilder`] constructs the [`OwnedPolicy`] of a credential with
+//! typed attributes, and an [`Account`] can have multiple credentials, whose
+//! policies are all visible to contracts with `ctx.policies()`.
+use crate::types::*;
+use concordium_rust_sdk::base::contracts_common::{
+    attributes::{COUNTRY_OF_RESIDENCE, DOB, FIRST_NAME, LAST_NAME, NATIONALITY},
+    AttributeTag, AttributeValue, OwnedPolicy, Timestamp,
+};
+use std::collections::BTreeMap;
+
+/// The number of milliseconds in a day.
+const MILLIS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+/// The maximum length of an attribute value.
+const MAX_ATTRIBUTE_LENGTH: usize = 31;
+
+impl PolicyBuilder {
+    /// Create a new [`PolicyBuilder`] for a credential from identity provider
+    /// `0`, which is valid from unix epoch until unix epoch + `u64::MAX`
+    /// milliseconds, and has no attributes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let policy = PolicyBuilder::new()
+    ///     .identity_provider(1)
+    ///     .nationality("DK")
+    ///     .country_of_residence("DE")
+    ///     .date_of_birth(1990, 5, 17)
+    ///     .build()
+    ///     .unwrap();
+    /// let account = Account::new_with_policy(
+    ///     AccountAddress([0; 32]),
+    ///     AccountBalance::new(Amount::from_ccd(10), Amount::zero(), Amount::zero()).unwrap(),
+    ///     policy,
+    /// );
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            identity_provider: 0,
+            created_at:        Timestamp::from_timestamp_millis(0),
+            valid_to:          Timestamp::from_timestamp_millis(u64::MAX),
+            attributes:        BTreeMap::new(),
+        }
+    }
+
+    /// Set the identity provider that issued the identity.
+    pub fn identity_provider(mut self, identity_provider: u32) -> Self {
+        self.identity_provider = identity_provider;
+        self
+    }
+
+    /// Set the time the credential was created.
+    pub fn created_at(mut self, created_at: Timestamp) -> Self {
+        self.created_at = created_at;
+        self
+    }
+
+    /// Set the time the credential is valid to.
+    pub fn valid_to(mut self, valid_to: Timestamp) -> Self {
+        self.valid_to = valid_to;
+        self
+    }
+
+    /// Reveal the first name.
+    pub fn first_name(self, first_name: &str) -> Self { self.attribute(FIRST_NAME, first_name) }
+
+    /// Reveal the last name.
+    pub fn last_name(self, last_name: &str) -> Self { self.attribute(LAST_NAME, last_name) }
+
+    /// Reveal the country of residence as a two letter ISO 3166-1 country
+    /// code, e.g., `DK`.
+    pub fn country_of_residence(self, country: &str) -> Self {
+        self.attribute(COUNTRY_OF_RESIDENCE, country)
+    }
+
+    /// Reveal the nationality as a two letter ISO 3166-1 country code, e.g.,
+    /// `DK`.
+    pub fn nationality(self, country: &str) -> Self { self.attribute(NATIONALITY, country) }
+
+    /// Reveal the date of birth, which is stored as `YYYYMMDD`.
+    pub fn date_of_birth(self, year: u16, month: u8, day: u8) -> Self {
+        self.attribute(DOB, format!("{year:04}{month:02}{day:02}"))
+    }
+
+    /// Reveal a date of birth such that the holder turns `years` old on the
+    /// day of `at`, e.g., the [block time](Chain::block_time), in UTC. A
+    /// birthday on February 29 becomes February 28 in years that are not leap
+    /// years.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let chain = Chain::new_with_time(Timestamp::from_timestamp_millis(1_700_000_000_000));
+    /// // The holder turned 18 on 2023-11-14.
+    /// let policy = PolicyBuilder::new().age(18, chain.block_time()).build().unwrap();
+    /// assert_eq!(policy.items[0].1.as_ref(), b"20051114");
+    /// ```
+    pub fn age(self, years: u16, at: Timestamp) -> Self {
+        let (year, month, day) = civil_from_days(at.timestamp_millis() / MILLIS_PER_DAY);
+        let year = year.saturating_sub(u64::from(years));
+        let day = if month == 2 && day == 29 && !is_leap_year(year) {
+            28
+        } else {
+            day
+        };
+        self.attribute(DOB, format!("{year:04}{month:02}{day:02}"))
+    }
+
+    /// Reveal an attribute with a raw value, replacing any previous value of
+    /// the attribute.
+    pub fn attribute(mut self, tag: AttributeTag, value: impl AsRef<[u8]>) -> Self {
+        self.attributes.insert(tag.0, value.as_ref().to_vec());
+        self
+    }
+
+    /// Build the policy. The attributes are ordered by their tags, like on the
+    /// chain.
+    ///
+    /// Returns an error if an attribute value is longer than 31 bytes, the
+    /// country of residence or nationality is not two uppercase letters, the
+    /// date of birth is not a valid date, or the credential is valid to a time
+    /// before it was created.
+    pub fn build(self) -> Result<OwnedPolicy, PolicyBuilderError> {
+        if self.valid_to < self.created_at {
+            return Err(PolicyBuilderError::InvalidValidity {
+                created_at: self.created_at,
+                valid_to:   self.valid_to,
+            });
+        }
+        let mut items = Vec::with_capacity(self.attributes.len());
+        for (tag, value) in self.attributes {
+            if value.len() > MAX_ATTRIBUTE_LENGTH {
+                return Err(PolicyBuilderError::AttributeTooLong {
+                    tag,
+                    length: value.len(),
+                });
+            }
+            if (tag == COUNTRY_OF_RESIDENCE.0 || tag == NATIONALITY.0)
+                && !(value.len() == 2 && value.iter().all(u8::is_ascii_uppercase))
+            {
+                return Err(PolicyBuilderError::InvalidCountryCode {
+                    tag,
+                    value,
+                });
+            }
+            if tag == DOB.0 && !is_valid_date(&value) {
+                return Err(PolicyBuilderError::InvalidDateOfBirth(value));
+            }
+            items.push((AttributeTag(tag), AttributeValue::from(value.as_slice())));
+        }
+        Ok(OwnedPolicy {
+            identity_provider: self.identity_provider,
+            created_at: self.created_at,
+            valid_to: self.valid_to,
+            items,
+        })
+    }
+}
+
+impl Default for PolicyBuilder {
+    fn default() -> Self { Self::new() }
+}
+
+impl Account {
+    /// Add a credential with the given policy to the account. Contracts see
+    /// the policies of all credentials with `ctx.policies()`, starting with
+    /// the [`policy`](Self::policy) of the first credential.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut account = Account::new(AccountAddress([0; 32]), Amount::from_ccd(10));
+    /// account.add_credential_policy(PolicyBuilder::new().nationality("DK").build().unwrap());
+    /// assert_eq!(account.policies().count(), 2);
+    /// ```
+    pub fn add_credential_policy(&mut self, policy: OwnedPolicy) {
+        self.additional_policies.push(policy);
+    }
+
+    /// The policies of all credentials of the account, starting with the
+    /// [`policy`](Self::policy) of the first credential.
+    pub fn policies(&self) -> impl Iterator<Item = &OwnedPolicy> {
+        std::iter::once(&self.policy).chain(self.additional_policies.iter())
+    }
+
+    /// Serialize the policies of all credentials in the format that contracts
+    /// read them in, which is the number of policies followed by the policies,
+    /// each prefixed by its length.
+    pub(crate) fn policies_for_smart_contract(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(self.policies().count() as u16).to_le_bytes());
+        for policy in self.policies() {
+            let mut single = Vec::new();
+            policy
+                .serial_for_smart_contract(&mut single)
+                .expect("Writing to a vector should succeed.");
+            // The serialization of a single policy starts with the number of
+            // policies, which is one, so it is skipped.
+            out.extend_from_slice(&single[2..]);
+        }
+        out
+    }
+}
+
+/// Check whether `value` is a valid date in the format `YYYYMMDD`.
+fn is_valid_date(value: &[u8]) -> bool {
+    if value.len() != 8 || !value.iter().all(u8::is_ascii_digit) {
+        return false;
+    }
+    let number = |range: std::ops::Range<usize>| {
+        value[range].iter().fold(0u64, |acc, digit| acc * 10 + u64::from(digit - b'0'))
+    };
+    let (year, month, day) = (number(0..4), number(4..6), number(6..8));
+    (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month)
+}
+
+/// Check whether `year` is a leap year in the Gregorian calendar.
+fn is_leap_year(year: u64) -> bool { year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) }
+
+/// The number of days in a month of a year.
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Convert a number of days since unix epoch to a date as `(year, month,
+/// day)` in the Gregorian calendar.
+///
+/// This is the algorithm `civil_from_days` from <https://howardhinnant.github.io/date_algorithms.html>,
+/// restricted to dates after unix epoch.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // Shift the epoch to 0000-03-01, so leap days are at the end of the year.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the conversion from days since unix epoch to dates.
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(59), (1970, 3, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_675), (2023, 11, 14));
+    }
+
+    /// Test that ages on leap days are moved to February 28 in other years,
+    /// and that invalid attributes are rejected.
+    #[test]
+    fn test_build() {
+        let leap_day = Timestamp::from_timestamp_millis(11_016 * MILLIS_PER_DAY);
+        let policy = PolicyBuilder::new().age(1, leap_day).build().unwrap();
+        assert_eq!(policy.items[0].1.as_ref(), b"19990228");
+        let policy = PolicyBuilder::new().age(4, leap_day).build().unwrap();
+        assert_eq!(policy.items[0].1.as_ref(), b"19960229");
+
+        assert_eq!(
+            PolicyBuilder::new().date_of_birth(2001, 2, 29).build(),
+            Err(PolicyBuilderError::InvalidDateOfBirth(b"20010229".to_vec()))
+        );
+        assert!(matches!(
+            PolicyBuilder::new().nationality("dk").build(),
+            Err(PolicyBuilderError::InvalidCountryCode { .. })
+        ));
+        assert!(matches!(
+            PolicyBuilder::new().first_name(&"a".repeat(32)).build(),
+            Err(PolicyBuilderError::AttributeTooLong {
+                length: 32,
+                ..
+            })
+        ));
+        assert!(matches!(
+            PolicyBuilder::new()
+                .valid_to(Timestamp::from_timestamp_millis(0))
+                .created_at(Timestamp::from_timestamp_millis(1))
+                .build(),
+            Err(PolicyBuilderError::InvalidValidity { .. })
+        ));
+    }
+}
@@ -0,0 +1,235 @@
+//! Energy profiles of contract updates and invocations.
+//!
+//! The profile is reconstructed from the trace elements, which record the
+//! energy used so far, and from the host calls recorded in the debug traces.
+use crate::{
+    trace_walk::{self, Call, TraceEvent, TraceVisitor},
+    types::*,
+};
+use concordium_rust_sdk::{
+    base::{base::Energy, contracts_common::ContractAddress},
+    smart_contracts::engine::v1::HostFunctionV1,
+};
+use std::collections::BTreeMap;
+
+/// The number of interpreter energy units per [`Energy`].
+const INTERPRETER_ENERGY_PER_ENERGY: u64 = 1000;
+
+impl ContractInvokeSuccess {
+    /// Get a breakdown of the energy used per contract, entrypoint and host
+    /// function. See [`EnergyProfile`] for details.
+    pub fn energy_profile(&self) -> EnergyProfile {
+        EnergyProfile::new(&self.trace_elements, self.energy_used, self.host_calls())
+    }
+}
+
+impl ContractInvokeError {
+    /// Get a breakdown of the energy used per contract, entrypoint and host
+    /// function up until the failure. See [`EnergyProfile`] for details.
+    pub fn energy_profile(&self) -> EnergyProfile {
+        EnergyProfile::new(&self.trace_elements, self.energy_used, self.host_calls())
+    }
+}
+
+impl EnergyProfile {
+    /// Construct the profile from the trace elements, the total energy used,
+    /// and the host calls.
+    fn new<'a>(
+        trace_elements: &[DebugTraceElement],
+        energy_used: Energy,
+        host_calls: impl Iterator<Item = HostCallInfo<'a>>,
+    ) -> Self {
+        let mut builder = ProfileBuilder::default();
+        trace_walk::walk(trace_elements, false, &mut builder);
+        let mut profile = builder.profile;
+        profile.storage =
+            energy_used.energy.saturating_sub(builder.last_energy) * INTERPRETER_ENERGY_PER_ENERGY;
+
+        // The energy used between two trace elements includes the host calls, so
+        // they are moved from execution to the host function.
+        for HostCallInfo {
+            address,
+            entrypoint,
+            host_function,
+            energy_used,
+            ..
+        } in host_calls
+        {
+            let entry = profile.entrypoints.entry((address, entrypoint.to_owned())).or_default();
+            let execution = entry.execution.checked_sub(energy_used.energy);
+            debug_assert!(
+                execution.is_some(),
+                "The host calls of {address}.{entrypoint} use more energy than the entrypoint."
+            );
+            entry.execution = execution.unwrap_or(0);
+            let host_call = entry.host_calls.entry(host_function).or_default();
+            host_call.calls += 1;
+            host_call.energy += energy_used.energy;
+        }
+        profile
+    }
+
+    /// The total energy in the profile.
+    pub fn total(&self) -> u64 { self.execution() + self.host_calls() + self.storage }
+
+    /// The energy used for executing Wasm code in all entrypoints.
+    pub fn execution(&self) -> u64 { self.entrypoints.values().map(|ep| ep.execution).sum() }
+
+    /// The energy used by host calls in all entrypoints.
+    pub fn host_calls(&self) -> u64 {
+        self.entrypoints.values().map(EntrypointEnergy::host_calls_energy).sum()
+    }
+
+    /// The energy used per contract, i.e., the sum of execution and host calls
+    /// for all its entrypoints.
+    pub fn per_contract(&self) -> BTreeMap<ContractAddress, u64> {
+        let mut out = BTreeMap::new();
+        for ((address, _), ep) in self.entrypoints.iter() {
+            *out.entry(*address).or_insert(0) += ep.execution + ep.host_calls_energy();
+        }
+        out
+    }
+
+    /// The number of calls and energy used per host function across all
+    /// contracts and entrypoints.
+    pub fn per_host_function(&self) -> BTreeMap<HostFunctionV1, HostFunctionEnergy> {
+        let mut out: BTreeMap<HostFunctionV1, HostFunctionEnergy> = BTreeMap::new();
+        for ep in self.entrypoints.values() {
+            for (host_function, host_call) in ep.host_calls.iter() {
+                let entry = out.entry(*host_function).or_default();
+                entry.calls += host_call.calls;
+                entry.energy += host_call.energy;
+            }
+        }
+        out
+    }
+
+    /// Convert the profile to JSON.
+    ///
+    /// The format is:
+    ///
+    /// ```json
+    /// {
+    ///   "total": 12345,
+    ///   "execution": 10000,
+    ///   "hostCalls": 2000,
+    ///   "storage": 345,
+    ///   "entrypoints": [
+    ///     {
+    ///       "contract": { "index": 0, "subindex": 0 },
+    ///       "entrypoint": "transfer",
+    ///       "execution": 10000,
+    ///       "hostCalls": [
+    ///         { "hostFunction": "GetParameterSize", "calls": 1, "energy": 2000 }
+    ///       ]
+    ///     }
+    ///   ]
+    /// }
+    /// ```
+    pub fn to_json(&self) -> serde_json::Value {
+        let entrypoints: Vec<_> = self
+            .entrypoints
+            .iter()
+            .map(|((address, entrypoint), ep)| {
+                let host_calls: Vec<_> = ep
+                    .host_calls
+                    .iter()
+                    .map(|(host_function, host_call)| {
+                        serde_json::json!({
+                            "hostFunction": host_function.to_string(),
+                            "calls": host_call.calls,
+                            "energy": host_call.energy,
+                        })
+                    })
+                    .collect();
+                serde_json::json!({
+                    "contract": { "index": address.index, "subindex": address.subindex },
+                    "entrypoint": entrypoint.as_entrypoint_name().to_string(),
+                    "execution": ep.execution,
+                    "hostCalls": host_calls,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "total": self.total(),
+            "execution": self.execution(),
+            "hostCalls": self.host_calls(),
+            "storage": self.storage,
+            "entrypoints": entrypoints,
+        })
+    }
+
+    /// Convert the profile to the folded stack format, which can be turned
+    /// into a flamegraph with tools such as `inferno-flamegraph` or
+    /// `flamegraph.pl`.
+    ///
+    /// Each line consists of a call stack, where the frames are of the form
+    /// `<index,subindex>.entrypoint` and separated by `;`, followed by a
+    /// space and the energy used in that stack. The storage energy is
+    /// reported on a separate `storage` line.
+    pub fn to_folded_stacks(&self) -> String {
+        let mut out = String::new();
+        for (stack, energy) in self.stacks.iter() {
+            let frames: Vec<_> = stack
+                .iter()
+                .map(|(address, entrypoint)| {
+                    format!("{address}.{}", entrypoint.as_entrypoint_name())
+                })
+                .collect();
+            out.push_str(&format!("{} {energy}\n", frames.join(";")));
+        }
+        if self.storage > 0 {
+            out.push_str(&format!("storage {}\n", self.storage));
+        }
+        out
+    }
+}
+
+impl EntrypointEnergy {
+    /// The energy used by all host calls in the entrypoint.
+    pub fn host_calls_energy(&self) -> u64 { self.host_calls.values().map(|hc| hc.energy).sum() }
+}
+
+/// Helper for attributing energy to the calls on the stack.
+#[derive(Default)]
+struct ProfileBuilder {
+    profile:     EnergyProfile,
+    /// The energy used at the previous trace element.
+    last_energy: u64,
+}
+
+impl ProfileBuilder {
+    /// Attribute the energy used since the previous trace element to the
+    /// current call.
+    fn attribute(&mut self, stack: &[Call<'_>], energy_used: Energy) {
+        let delta = energy_used.energy.saturating_sub(self.last_energy);
+        self.last_energy = self.last_energy.max(energy_used.energy);
+        let Some(call) = stack.last() else {
+            return;
+        };
+        let delta = delta * INTERPRETER_ENERGY_PER_ENERGY;
+        self.profile
+            .entrypoints
+            .entry((call.address, call.entrypoint.to_owned()))
+            .or_default()
+            .execution += delta;
+        let stack = stack.iter().map(|call| (call.address, call.entrypoint.to_owned())).collect();
+        *self.profile.stacks.entry(stack).or_insert(0) += delta;
+    }
+}
+
+impl<'a> TraceVisitor<'a> for ProfileBuilder {
+    fn event(&mut self, stack: &[Call<'a>], event: TraceEvent<'a>, _rolled_back: bool) {
+        match event {
+            TraceEvent::Element {
+                energy_used,
+                ..
+            }
+            | TraceEvent::Failed {
+                energy_used,
+                ..
+            } => self.attribute(stack, energy_used),
+            TraceEvent::Debug(_) => {}
+        }
+    }
+}
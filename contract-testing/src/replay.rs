@@ -0,0 +1,288 @@
+//! Replaying transactions from a node on a local [`Chain`].
+//!
+//! The modules, contract instances and accounts involved in a transaction are
+//! queried from a node in the parent block of the transaction, loaded into a
+//! fresh chain, and the transaction is executed again with debug output
+//! enabled. The node is an [`ExternalNodeBackend`], so it can be replaced by
+//! a [`FixtureNode`] with recorded responses in tests.
+use crate::{state::persistent_state_from_entries, types::*};
+use concordium_rust_sdk::{
+    self as sdk,
+    base::{
+        base::AccountAddressEq,
+        contracts_common::Timestamp,
+        hashes::TransactionHash,
+        smart_contracts::ContractTraceElement,
+        transactions::{BlockItem, Payload},
+    },
+    types::{AccountTransactionDetails, AccountTransactionEffects, BlockItemSummaryDetails},
+    v2::BlockIdentifier,
+};
+use futures::TryStreamExt;
+
+impl Chain {
+    /// Replay a finalized contract init or update transaction from a node.
+    ///
+    /// The modules, contract instances and accounts involved in the
+    /// transaction are queried in the parent block of the transaction and
+    /// loaded into a fresh chain with the block time and exchange rates of the
+    /// block of the transaction. The modules are loaded with debug output
+    /// enabled, so the result contains the debug output of the contracts.
+    ///
+    /// Only the contracts and accounts affected by the transaction are loaded.
+    /// A contract that only queries the balance of an account or contract that
+    /// is otherwise not involved will therefore see a missing account or
+    /// contract. The accounts have the balances from the node but no keys, so
+    /// signature checks fail.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use concordium_smart_contract_testing::*;
+    /// let node =
+    ///     GrpcNode::new(Endpoint::from_static("http://node.testnet.concordium.com:20000")).unwrap();
+    /// let hash = "<transaction hash>".parse().unwrap();
+    /// let replayed = Chain::replay_transaction(&node, hash).unwrap();
+    /// if let ReplayResult::Update(result) = &replayed.result {
+    ///     println!("{:#?}", result);
+    /// }
+    /// ```
+    pub fn replay_transaction(
+        node: &impl ExternalNodeBackend,
+        transaction: TransactionHash,
+    ) -> Result<ReplayedTransaction, ReplayError> {
+        let transaction = node.transaction(transaction)?;
+        let block = transaction.parent_block;
+        let mut chain = Chain::new_with_time_and_rates(
+            transaction.block_time,
+            transaction.micro_ccd_per_euro,
+            transaction.euro_per_energy,
+        )
+        .map_err(|_| ReplayError::InvalidExchangeRates)?;
+
+        for address in
+            std::iter::once(transaction.sender).chain(transaction.accounts.iter().copied())
+        {
+            let balance = node.account_balance(block, address)?.ok_or_else(not_found)?;
+            chain.accounts.insert(
+                AccountAddressEq::from(address),
+                Account::new_with_balance(address, balance),
+            );
+        }
+        let mut module_references = transaction.modules.clone();
+        if let ReplayPayload::InitContract {
+            payload,
+            ..
+        } = &transaction.payload
+        {
+            module_references.push(payload.mod_ref);
+        }
+        for address in &transaction.contracts {
+            let instance = node.instance(block, *address)?.ok_or_else(not_found)?;
+            module_references.push(instance.module_reference);
+            chain.next_contract_index = chain.next_contract_index.max(address.index + 1);
+            chain.contracts.insert(*address, Contract {
+                address:          *address,
+                module_reference: instance.module_reference,
+                contract_name:    instance.contract_name,
+                state:            persistent_state_from_entries(instance.state),
+                owner:            instance.owner,
+                self_balance:     instance.self_balance,
+            });
+        }
+        for module_reference in module_references {
+            if chain.modules.contains_key(&module_reference) {
+                continue;
+            }
+            let wasm_module = node.module_source(block, module_reference)?.ok_or_else(not_found)?;
+            let module = ContractModule::from_wasm_module(wasm_module, true)
+                .map_err(|e| ReplayError::InvalidModule(module_reference, e))?;
+            chain.modules.insert(module_reference, module);
+        }
+
+        let signer =
+            Signer::with_keys(transaction.num_signatures).unwrap_or(Signer::with_one_key());
+        let result = match transaction.payload.clone() {
+            ReplayPayload::InitContract {
+                payload,
+                address,
+            } => {
+                // Make sure the contract gets the same address as on the node.
+                chain.next_contract_index = address.index;
+                ReplayResult::InitContract(chain.contract_init(
+                    signer,
+                    transaction.sender,
+                    transaction.energy_reserved,
+                    payload,
+                ))
+            }
+            ReplayPayload::Update {
+                payload,
+            } => ReplayResult::Update(chain.contract_update(
+                signer,
+                transaction.sender,
+                transaction.sender.into(),
+                transaction.energy_reserved,
+                payload,
+            )),
+        };
+        Ok(ReplayedTransaction {
+            transaction,
+            chain,
+            result,
+        })
+    }
+}
+
+/// The error for a module, contract or account involved in the transaction
+/// that does not exist in the parent block.
+fn not_found() -> ReplayError { query_error(sdk::endpoints::QueryError::NotFound) }
+
+/// Convert an error from the node into a [`ReplayError`].
+fn query_error(error: impl Into<sdk::endpoints::QueryError>) -> ReplayError {
+    ReplayError::Node(ExternalNodeError::from(error.into()))
+}
+
+impl GrpcNode {
+    /// Get a finalized contract init or update transaction, along with the
+    /// block it is in. See [`ExternalNodeBackend::transaction`].
+    pub(crate) fn replay_transaction(
+        &self,
+        transaction: TransactionHash,
+    ) -> Result<ReplayTransaction, ReplayError> {
+        self.with_client(|mut client| async move {
+            let status = client.get_block_item_status(&transaction).await.map_err(query_error)?;
+            let Some((block, summary)) = status.is_finalized() else {
+                return Err(ReplayError::NotFinalized(transaction));
+            };
+            let (block, summary) = (*block, summary.clone());
+            let block_info = client
+                .get_block_info(BlockIdentifier::Given(block))
+                .await
+                .map_err(query_error)?
+                .response;
+            let (euro_per_energy, micro_ccd_per_euro) = match client
+                .get_block_chain_parameters(BlockIdentifier::Given(block))
+                .await
+                .map_err(query_error)?
+                .response
+            {
+                sdk::v2::ChainParameters::V0(p) => (p.euro_per_energy, p.micro_ccd_per_euro),
+                sdk::v2::ChainParameters::V1(p) => (p.euro_per_energy, p.micro_ccd_per_euro),
+                sdk::v2::ChainParameters::V2(p) => (p.euro_per_energy, p.micro_ccd_per_euro),
+            };
+
+            // Find the transaction itself among the items of the block.
+            let mut items = client
+                .get_block_items(BlockIdentifier::Given(block))
+                .await
+                .map_err(query_error)?
+                .response;
+            let item = loop {
+                match items.try_next().await.map_err(query_error)? {
+                    Some(item) if item.hash() == transaction => break item,
+                    Some(_) => {}
+                    None => return Err(ReplayError::NotFinalized(transaction)),
+                }
+            };
+            let BlockItem::AccountTransaction(account_transaction) = item else {
+                return Err(ReplayError::UnsupportedTransaction(transaction));
+            };
+            let payload = account_transaction
+                .payload
+                .decode()
+                .map_err(|_| ReplayError::UnsupportedTransaction(transaction))?;
+
+            let effects = match &summary.details {
+                BlockItemSummaryDetails::AccountTransaction(AccountTransactionDetails {
+                    effects,
+                    ..
+                }) => Some(effects),
+                _ => None,
+            };
+            // Contracts upgraded in the transaction need the module they were upgraded to.
+            let modules = match effects {
+                Some(AccountTransactionEffects::ContractUpdateIssued {
+                    effects,
+                }) => effects
+                    .iter()
+                    .filter_map(|element| match element {
+                        ContractTraceElement::Upgraded {
+                            to,
+                            ..
+                        } => Some(*to),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            let mut contracts = summary.affected_contracts();
+            let payload = match payload {
+                Payload::InitContract {
+                    payload,
+                } => {
+                    let Some(AccountTransactionEffects::ContractInitialized {
+                        data,
+                    }) = effects
+                    else {
+                        // The init failed, so it did not create a contract.
+                        return Err(ReplayError::UnsupportedTransaction(transaction));
+                    };
+                    // The initialized contract does not exist in the parent block.
+                    contracts.retain(|address| *address != data.address);
+                    ReplayPayload::InitContract {
+                        payload,
+                        address: data.address,
+                    }
+                }
+                Payload::Update {
+                    payload,
+                } => ReplayPayload::Update {
+                    payload,
+                },
+                _ => return Err(ReplayError::UnsupportedTransaction(transaction)),
+            };
+
+            let sender = account_transaction.header.sender;
+            let mut accounts = summary.affected_addresses();
+            accounts.retain(|address| *address != sender);
+            Ok(ReplayTransaction {
+                hash: transaction,
+                block,
+                parent_block: block_info.block_parent,
+                // The node never returns timestamps < 0, so it is safe to cast it to `u64`.
+                block_time: Timestamp::from_timestamp_millis(
+                    block_info.block_slot_time.timestamp_millis() as u64,
+                ),
+                euro_per_energy,
+                micro_ccd_per_euro,
+                sender,
+                num_signatures: account_transaction.signature.num_keys(),
+                energy_reserved: account_transaction.header.energy_amount,
+                payload,
+                contracts,
+                modules,
+                accounts,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that a transaction that has not been recorded in a fixture is
+    /// reported.
+    #[test]
+    fn test_not_recorded() {
+        let path = std::env::temp_dir()
+            .join(format!("concordium-replay-fixture-{}.json", std::process::id()));
+        let node = FixtureNode::replay(&path).expect("A missing fixture file has no responses");
+        let hash = TransactionHash::new([0; 32]);
+        assert!(matches!(
+            Chain::replay_transaction(&node, hash),
+            Err(ReplayError::Node(ExternalNodeError::NotRecorded { .. }))
+        ));
+    }
+}
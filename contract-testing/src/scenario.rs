@@ -0,0 +1,468 @@
+//! A runner for declarative scenario files.
+//!
+//! See [`Scenario`] for the format.
+use crate::{impls::module_load_v1, types::*};
+use concordium_rust_sdk::{
+    base::{
+        base::Energy,
+        contracts_common::{
+            AccountAddress, Address, Amount, ContractAddress, Duration, ModuleReference,
+            OwnedContractName, OwnedReceiveName, Timestamp,
+        },
+    },
+    smart_contracts::engine::v1,
+};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, path::Path};
+
+/// The energy reserved for contract steps that do not specify it.
+const DEFAULT_ENERGY: Energy = Energy {
+    energy: 100_000,
+};
+
+impl Scenario {
+    /// Load a scenario from a file. Files with the extension `.yaml` or
+    /// `.yml` are parsed as YAML, and all other files as JSON.
+    ///
+    /// The paths of the modules in the scenario are relative to the directory
+    /// of the file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ScenarioLoadError> {
+        let path = path.as_ref();
+        let to_error = |kind| ScenarioLoadError {
+            path: path.to_path_buf(),
+            kind,
+        };
+        let contents = std::fs::read(path).map_err(|e| to_error(e.into()))?;
+        let is_yaml = matches!(path.extension().and_then(|ext| ext.to_str()), Some("yaml" | "yml"));
+        let mut scenario: Scenario = if is_yaml {
+            serde_yaml::from_slice(&contents).map_err(|e| to_error(e.into()))?
+        } else {
+            serde_json::from_slice(&contents).map_err(|e| to_error(e.into()))?
+        };
+        scenario.base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(scenario)
+    }
+
+    /// Execute the scenario on a new [`Chain`], checking the expectations
+    /// along the way. Returns the chain after the last step, so that tests
+    /// can make further checks.
+    ///
+    /// The first failing step or unmet expectation is returned as an error,
+    /// which includes a diff between the expected and actual outcome.
+    pub fn run(&self) -> Result<Chain, ScenarioError> {
+        let chain = match self.block_time {
+            Some(millis) => Chain::new_with_time(Timestamp::from_timestamp_millis(millis)),
+            None => Chain::new(),
+        };
+        let mut runner = Runner {
+            scenario: self,
+            chain,
+            accounts: BTreeMap::new(),
+            modules: BTreeMap::new(),
+            instances: BTreeMap::new(),
+        };
+        runner.setup().map_err(|kind| self.error("during setup".to_string(), kind))?;
+        for (i, step) in self.steps.iter().enumerate() {
+            runner
+                .step(step)
+                .map_err(|kind| self.error(format!("at step {} ({})", i + 1, step.name()), kind))?;
+        }
+        Ok(runner.chain)
+    }
+
+    /// Construct an error for this scenario.
+    fn error(&self, location: String, kind: ScenarioErrorKind) -> ScenarioError {
+        ScenarioError {
+            scenario: self.name.clone(),
+            location,
+            kind,
+        }
+    }
+}
+
+impl ScenarioStep {
+    /// The name of the step as written in the scenario file.
+    fn name(&self) -> &'static str {
+        match self {
+            ScenarioStep::Init(_) => "init",
+            ScenarioStep::Update(_) => "update",
+            ScenarioStep::Invoke(_) => "invoke",
+            ScenarioStep::Transfer(_) => "transfer",
+            ScenarioStep::TickTime(_) => "tickTime",
+            ScenarioStep::ExpectBalances(_) => "expectBalances",
+        }
+    }
+}
+
+/// The state of a running scenario.
+struct Runner<'a> {
+    scenario:  &'a Scenario,
+    chain:     Chain,
+    /// The addresses of the accounts by name.
+    accounts:  BTreeMap<&'a str, AccountAddress>,
+    /// The deployed modules by name.
+    modules:   BTreeMap<&'a str, ModuleReference>,
+    /// The initialized contract instances by name.
+    instances: BTreeMap<&'a str, ContractAddress>,
+}
+
+impl<'a> Runner<'a> {
+    /// Create the accounts and deploy the modules.
+    fn setup(&mut self) -> Result<(), ScenarioErrorKind> {
+        for (name, account) in self.scenario.accounts.iter() {
+            let address = match &account.address {
+                Some(address) => address
+                    .parse()
+                    .map_err(|_| ScenarioErrorKind::InvalidAddress(address.clone()))?,
+                None => AccountAddress(Sha256::digest(name.as_bytes()).into()),
+            };
+            let balance = parse_amount(&account.balance)?;
+            self.chain.create_account(Account::new(address, balance));
+            self.accounts.insert(name, address);
+        }
+        for (name, module) in self.scenario.modules.iter() {
+            let sender = self.account(&module.sender)?;
+            let module = module_load_v1(self.scenario.base_dir.join(&module.path))?;
+            let deployment = self.chain.module_deploy_v1(Signer::with_one_key(), sender, module)?;
+            self.modules.insert(name, deployment.module_reference);
+        }
+        Ok(())
+    }
+
+    /// Execute a single step.
+    fn step(&mut self, step: &'a ScenarioStep) -> Result<(), ScenarioErrorKind> {
+        match step {
+            ScenarioStep::Init(init) => self.init(init),
+            ScenarioStep::Update(update) => self.update(update, true),
+            ScenarioStep::Invoke(update) => self.update(update, false),
+            ScenarioStep::Transfer(transfer) => {
+                let from = self.account(&transfer.from)?;
+                let to = self.account(&transfer.to)?;
+                let amount = parse_amount(&transfer.amount)?;
+                self.chain.account_transfer(Signer::with_one_key(), from, to, amount)?;
+                Ok(())
+            }
+            ScenarioStep::TickTime(millis) => {
+                self.chain.tick_block_time(Duration::from_millis(*millis))?;
+                Ok(())
+            }
+            ScenarioStep::ExpectBalances(balances) => self.expect_balances(balances),
+        }
+    }
+
+    /// Execute a [`ScenarioStep::Init`].
+    fn init(&mut self, init: &'a ScenarioInit) -> Result<(), ScenarioErrorKind> {
+        let sender = self.account(&init.sender)?;
+        let mod_ref = *self
+            .modules
+            .get(init.module.as_str())
+            .ok_or_else(|| ScenarioErrorKind::UnknownModule(init.module.clone()))?;
+        let init_name = OwnedContractName::new(format!("init_{}", init.contract))
+            .map_err(|_| ScenarioErrorKind::InvalidName(init.contract.clone()))?;
+        let payload = InitContractJsonPayload {
+            amount: parse_optional_amount(&init.amount)?,
+            mod_ref,
+            init_name,
+            param: init.param.clone(),
+        };
+        let energy = init.energy.map_or(DEFAULT_ENERGY, Energy::from);
+        match self.chain.contract_init_json(Signer::with_one_key(), sender, energy, payload) {
+            Ok(success) => {
+                check_success(&init.expect, Ok(None), success.events)?;
+                self.instances.insert(&init.instance, success.success.contract_address);
+                Ok(())
+            }
+            Err(error) => check_failure(&init.expect, error),
+        }
+    }
+
+    /// Execute a [`ScenarioStep::Update`], or a [`ScenarioStep::Invoke`] if
+    /// `persist` is false.
+    fn update(&mut self, update: &ScenarioUpdate, persist: bool) -> Result<(), ScenarioErrorKind> {
+        let sender = self.account(&update.sender)?;
+        let address = *self
+            .instances
+            .get(update.instance.as_str())
+            .ok_or_else(|| ScenarioErrorKind::UnknownInstance(update.instance.clone()))?;
+        let contract_name = self
+            .chain
+            .get_contract(address)
+            .expect("Initialized instances exist")
+            .contract_name
+            .as_contract_name()
+            .contract_name()
+            .to_string();
+        let receive_name = OwnedReceiveName::new(format!("{contract_name}.{}", update.entrypoint))
+            .map_err(|_| ScenarioErrorKind::InvalidName(update.entrypoint.clone()))?;
+        let payload = UpdateContractJsonPayload {
+            amount: parse_optional_amount(&update.amount)?,
+            address,
+            receive_name,
+            message: update.param.clone(),
+        };
+        let energy = update.energy.map_or(DEFAULT_ENERGY, Energy::from);
+        let sender_address = Address::Account(sender);
+        let result = if persist {
+            self.chain.contract_update_json(
+                Signer::with_one_key(),
+                sender,
+                sender_address,
+                energy,
+                payload,
+            )
+        } else {
+            self.chain.contract_invoke_json(sender, sender_address, energy, payload)
+        };
+        match result {
+            Ok(success) => {
+                let events = success.events.into_iter().map(|(_, event)| event).collect();
+                check_success(&update.expect, success.return_value, events)
+            }
+            Err(error) => check_failure(&update.expect, error),
+        }
+    }
+
+    /// Execute a [`ScenarioStep::ExpectBalances`].
+    fn expect_balances(&self, balances: &ScenarioBalances) -> Result<(), ScenarioErrorKind> {
+        let mut expected = BTreeMap::new();
+        let mut actual = BTreeMap::new();
+        for (name, amount) in balances.accounts.iter() {
+            let address = self.account(name)?;
+            let balance = self.chain.account_balance(address).map(|balance| balance.total);
+            expected.insert(format!("account {name}"), Some(parse_amount(amount)?));
+            actual.insert(format!("account {name}"), balance);
+        }
+        for (name, amount) in balances.instances.iter() {
+            let address = *self
+                .instances
+                .get(name.as_str())
+                .ok_or_else(|| ScenarioErrorKind::UnknownInstance(name.clone()))?;
+            expected.insert(format!("instance {name}"), Some(parse_amount(amount)?));
+            actual.insert(format!("instance {name}"), self.chain.contract_balance(address));
+        }
+        if expected != actual {
+            let to_json = |balances: BTreeMap<String, Option<Amount>>| -> serde_json::Value {
+                balances
+                    .into_iter()
+                    .map(|(name, balance)| {
+                        let balance = balance.map_or("missing".to_string(), |b| b.to_string());
+                        (name, serde_json::Value::String(balance))
+                    })
+                    .collect::<serde_json::Map<_, _>>()
+                    .into()
+            };
+            compare("balances", &to_json(expected), &to_json(actual))?;
+        }
+        Ok(())
+    }
+
+    /// Get the address of an account by name.
+    fn account(&self, name: &str) -> Result<AccountAddress, ScenarioErrorKind> {
+        self.accounts
+            .get(name)
+            .copied()
+            .ok_or_else(|| ScenarioErrorKind::UnknownAccount(name.to_string()))
+    }
+}
+
+/// Check the outcome of a successful contract step. The events are only
+/// required to be decodable if the expectation has events.
+fn check_success(
+    expect: &ScenarioExpectation,
+    return_value: Result<Option<serde_json::Value>, ReturnValueDecodeError>,
+    events: Vec<Option<serde_json::Value>>,
+) -> Result<(), ScenarioErrorKind> {
+    if expect.reject_code.is_some() || expect.reject_reason.is_some() {
+        return Err(ScenarioErrorKind::UnexpectedSuccess);
+    }
+    if let Some(expected) = &expect.events {
+        let events = events
+            .into_iter()
+            .enumerate()
+            .map(|(index, event)| {
+                event.ok_or(ScenarioErrorKind::DecodeEvent {
+                    index,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        compare("events", &expected.clone().into(), &events.into())?;
+    }
+    if let Some(expected) = &expect.return_value {
+        compare("return value", expected, &return_value?.unwrap_or_default())?;
+    }
+    Ok(())
+}
+
+/// Check the outcome of a failed contract step. The failure is only accepted
+/// if the expectation has a reject code or reason, and the contract rejected
+/// with them.
+fn check_failure(
+    expect: &ScenarioExpectation,
+    error: ContractJsonError,
+) -> Result<(), ScenarioErrorKind> {
+    if expect.reject_code.is_none() && expect.reject_reason.is_none() {
+        return Err(error.into());
+    }
+    let (reject_code, reject_reason) = match &error {
+        ContractJsonError::Init {
+            error,
+            reject_reason,
+        } => {
+            let reject_code = match &error.kind {
+                ContractInitErrorKind::ExecutionError {
+                    error:
+                        InitExecutionError::Reject {
+                            reason,
+                            ..
+                        },
+                    ..
+                } => Some(*reason),
+                _ => None,
+            };
+            (reject_code, reject_reason)
+        }
+        ContractJsonError::Invoke {
+            error,
+            reject_reason,
+        } => {
+            let reject_code = match &error.kind {
+                ContractInvokeErrorKind::ExecutionError {
+                    failure_kind:
+                        v1::InvokeFailure::ContractReject {
+                            code,
+                            ..
+                        },
+                } => Some(*code),
+                _ => None,
+            };
+            (reject_code, reject_reason)
+        }
+        _ => return Err(error.into()),
+    };
+    // Other failures, such as running out of energy, are never expected.
+    let Some(reject_code) = reject_code else {
+        return Err(error.into());
+    };
+    if let Some(expected) = expect.reject_code {
+        compare("reject code", &expected.into(), &reject_code.into())?;
+    }
+    if let Some(expected) = &expect.reject_reason {
+        compare("reject reason", expected, &reject_reason.clone().unwrap_or_default())?;
+    }
+    Ok(())
+}
+
+/// Compare an expected and actual JSON value, and return a mismatch with a
+/// line diff if they differ.
+fn compare(
+    what: &str,
+    expected: &serde_json::Value,
+    actual: &serde_json::Value,
+) -> Result<(), ScenarioErrorKind> {
+    if expected == actual {
+        return Ok(());
+    }
+    let pretty = |value| serde_json::to_string_pretty(value).unwrap_or_default();
+    Err(ScenarioErrorKind::Mismatch {
+        what: what.to_string(),
+        diff: line_diff(&pretty(expected), &pretty(actual)),
+    })
+}
+
+/// Compute a diff of the lines in `expected` and `actual`, where removed lines
+/// are prefixed with `-`, added lines with `+` and common lines with a space.
+///
+/// It uses the longest common subsequence of lines, which is fine for the
+/// small values compared in scenarios.
+fn line_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+    // lcs[i][j] is the length of the longest common subsequence of
+    // expected[i..] and actual[j..].
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            out.push(format!("  {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if j < actual.len() && (i == expected.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            out.push(format!("+ {}", actual[j]));
+            j += 1;
+        } else {
+            out.push(format!("- {}", expected[i]));
+            i += 1;
+        }
+    }
+    out.join("\n")
+}
+
+/// Parse an amount in CCD.
+fn parse_amount(amount: &str) -> Result<Amount, ScenarioErrorKind> {
+    amount.parse().map_err(|_| ScenarioErrorKind::InvalidAmount(amount.to_string()))
+}
+
+/// Parse an optional amount in CCD, which defaults to zero.
+fn parse_optional_amount(amount: &Option<String>) -> Result<Amount, ScenarioErrorKind> {
+    amount.as_deref().map_or(Ok(Amount::zero()), parse_amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the diff marks the changed lines.
+    #[test]
+    fn test_line_diff() {
+        let diff = line_diff("[\n  1,\n  2,\n  3\n]", "[\n  1,\n  4,\n  3\n]");
+        assert_eq!(diff, "  [\n    1,\n-   2,\n+   4,\n    3\n  ]");
+    }
+
+    /// Test that YAML scenarios are parsed and that steps are executed in
+    /// order with the expectations checked.
+    #[test]
+    fn test_run_scenario() {
+        let scenario: Scenario = serde_yaml::from_str(
+            r#"
+name: Transfers
+blockTime: 1000
+accounts:
+  alice:
+    balance: "100"
+  bob:
+    balance: "0"
+steps:
+  - transfer:
+      from: alice
+      to: bob
+      amount: "10"
+  - tickTime: 500
+  - expectBalances:
+      accounts:
+        bob: "10"
+"#,
+        )
+        .expect("Scenario is valid");
+        let chain = scenario.run().expect("Scenario succeeds");
+        assert_eq!(chain.block_time(), Timestamp::from_timestamp_millis(1500));
+
+        let mut scenario = scenario;
+        scenario.steps.push(ScenarioStep::ExpectBalances(ScenarioBalances {
+            accounts:  [("bob".to_string(), "11".to_string())].into(),
+            instances: BTreeMap::new(),
+        }));
+        let err = scenario.run().expect_err("Balance differs");
+        assert_eq!(err.location, "at step 4 (expectBalances)");
+        assert!(matches!(err.kind, ScenarioErrorKind::Mismatch { .. }));
+    }
+}
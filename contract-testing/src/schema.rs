@@ -0,0 +1,338 @@
+//! Contract calls where parameters, return values, events and reject reasons
+//! are given as JSON, and converted with the schema embedded in the module.
+//!
+//! Modules get an embedded schema when they are built with `cargo concordium
+//! build --schema-embed`.
+use crate::types::*;
+use concordium_rust_sdk::{
+    base::{
+        base::Energy,
+        contracts_common::{
+            schema::{Type, VersionedModuleSchema, VersionedSchemaError},
+            AccountAddress, Address, ContractAddress, Cursor, ModuleReference,
+        },
+        smart_contracts::{ContractEvent, OwnedParameter},
+        transactions::{InitContractPayload, UpdateContractPayload},
+    },
+    smart_contracts::engine::{utils, v1},
+};
+
+impl ContractModule {
+    /// Get the schema embedded in the module.
+    ///
+    /// Returns an error if the module has no embedded schema, or if it cannot
+    /// be parsed.
+    pub fn embedded_schema(&self) -> Result<VersionedModuleSchema, ModuleSchemaError> {
+        Ok(utils::get_embedded_schema_v1(self.source.source.as_ref())?)
+    }
+}
+
+impl Chain {
+    /// Like [`Chain::contract_init`], except that the parameter is given as
+    /// JSON, which is encoded with the schema embedded in the module. The
+    /// events and reject reason are decoded to JSON with the schema.
+    ///
+    /// If the schema does not describe the parameter, then the parameter must
+    /// be `null`, in which case the empty parameter is used.
+    pub fn contract_init_json(
+        &mut self,
+        signer: Signer,
+        sender: AccountAddress,
+        energy_reserved: Energy,
+        payload: InitContractJsonPayload,
+    ) -> Result<ContractInitJsonSuccess, ContractJsonError> {
+        let schema = self.module_schema(payload.mod_ref)?;
+        let contract_name = payload.init_name.as_contract_name().contract_name().to_string();
+        let param = encode_parameter(schema.get_init_param_schema(&contract_name), &payload.param)?;
+
+        let result = self.contract_init(signer, sender, energy_reserved, InitContractPayload {
+            amount: payload.amount,
+            mod_ref: payload.mod_ref,
+            init_name: payload.init_name,
+            param,
+        });
+        match result {
+            Ok(success) => {
+                let event_schema = schema.get_event_schema(&contract_name).ok();
+                let events =
+                    success.events.iter().map(|event| decode_event(&event_schema, event)).collect();
+                Ok(ContractInitJsonSuccess {
+                    success,
+                    events,
+                })
+            }
+            Err(error) => {
+                let reject_reason = match &error.kind {
+                    ContractInitErrorKind::ExecutionError {
+                        error:
+                            InitExecutionError::Reject {
+                                return_value,
+                                ..
+                            },
+                        ..
+                    } => schema
+                        .get_init_error_schema(&contract_name)
+                        .ok()
+                        .and_then(|ty| decode(&ty, return_value)),
+                    _ => None,
+                };
+                Err(ContractJsonError::Init {
+                    error,
+                    reject_reason,
+                })
+            }
+        }
+    }
+
+    /// Like [`Chain::contract_update`], except that the message is given as
+    /// JSON, which is encoded with the schema embedded in the module of the
+    /// contract. The return value, events and reject reason are decoded to
+    /// JSON with the schemas of the contracts.
+    ///
+    /// If the schema does not describe the parameter, then the message must be
+    /// `null`, in which case the empty parameter is used.
+    pub fn contract_update_json(
+        &mut self,
+        signer: Signer,
+        invoker: AccountAddress,
+        sender: Address,
+        energy_reserved: Energy,
+        payload: UpdateContractJsonPayload,
+    ) -> Result<ContractInvokeJsonSuccess, ContractJsonError> {
+        let (payload, schema) = self.encode_update_payload(payload)?;
+        let result =
+            self.contract_update(signer, invoker, sender, energy_reserved, payload.clone());
+        self.decode_invoke_result(&payload, &schema, result)
+    }
+
+    /// Like [`Chain::contract_invoke`], except that the message is given as
+    /// JSON, which is encoded with the schema embedded in the module of the
+    /// contract. The return value, events and reject reason are decoded to
+    /// JSON with the schemas of the contracts.
+    ///
+    /// If the schema does not describe the parameter, then the message must be
+    /// `null`, in which case the empty parameter is used.
+    pub fn contract_invoke_json(
+        &self,
+        invoker: AccountAddress,
+        sender: Address,
+        energy_reserved: Energy,
+        payload: UpdateContractJsonPayload,
+    ) -> Result<ContractInvokeJsonSuccess, ContractJsonError> {
+        let (payload, schema) = self.encode_update_payload(payload)?;
+        let result = self.contract_invoke(invoker, sender, energy_reserved, payload.clone());
+        self.decode_invoke_result(&payload, &schema, result)
+    }
+
+    /// Get the schema embedded in a module.
+    fn module_schema(
+        &self,
+        module_reference: ModuleReference,
+    ) -> Result<VersionedModuleSchema, ContractJsonError> {
+        let module = self.get_module(module_reference).ok_or(ModuleDoesNotExist {
+            module_reference,
+        })?;
+        Ok(module.embedded_schema()?)
+    }
+
+    /// Get the schema embedded in the module of a contract instance.
+    fn contract_schema(
+        &self,
+        address: ContractAddress,
+    ) -> Result<VersionedModuleSchema, ContractJsonError> {
+        let contract = self.get_contract(address).ok_or(ContractDoesNotExist {
+            address,
+        })?;
+        self.module_schema(contract.module_reference)
+    }
+
+    /// Encode the message of the payload with the schema of the contract.
+    /// Returns the payload and the schema.
+    fn encode_update_payload(
+        &self,
+        payload: UpdateContractJsonPayload,
+    ) -> Result<(UpdateContractPayload, VersionedModuleSchema), ContractJsonError> {
+        let schema = self.contract_schema(payload.address)?;
+        let receive_name = payload.receive_name.as_receive_name();
+        let message = encode_parameter(
+            schema.get_receive_param_schema(
+                receive_name.contract_name(),
+                &receive_name.entrypoint_name().to_string(),
+            ),
+            &payload.message,
+        )?;
+        let payload = UpdateContractPayload {
+            amount: payload.amount,
+            address: payload.address,
+            receive_name: payload.receive_name,
+            message,
+        };
+        Ok((payload, schema))
+    }
+
+    /// Decode the return value, events and reject reason of an update or
+    /// invocation.
+    fn decode_invoke_result(
+        &self,
+        payload: &UpdateContractPayload,
+        schema: &VersionedModuleSchema,
+        result: Result<ContractInvokeSuccess, ContractInvokeError>,
+    ) -> Result<ContractInvokeJsonSuccess, ContractJsonError> {
+        let receive_name = payload.receive_name.as_receive_name();
+        let contract_name = receive_name.contract_name();
+        let entrypoint_name = receive_name.entrypoint_name().to_string();
+        match result {
+            Ok(success) => {
+                // The update has been executed at this point, so a return value
+                // that cannot be decoded is not an error of the whole call.
+                let return_value =
+                    match schema.get_receive_return_value_schema(contract_name, &entrypoint_name) {
+                        Ok(ty) => decode(&ty, &success.return_value)
+                            .map(Some)
+                            .ok_or(ReturnValueDecodeError),
+                        Err(_) => Ok(None),
+                    };
+                let events = success
+                    .events()
+                    .flat_map(|(address, events)| {
+                        let event_schema = self.contract_event_schema(address);
+                        events
+                            .iter()
+                            .map(move |event| (address, decode_event(&event_schema, event)))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+                Ok(ContractInvokeJsonSuccess {
+                    success,
+                    return_value,
+                    events,
+                })
+            }
+            Err(error) => {
+                let reject_reason = match &error.kind {
+                    ContractInvokeErrorKind::ExecutionError {
+                        failure_kind:
+                            v1::InvokeFailure::ContractReject {
+                                data,
+                                ..
+                            },
+                    } => schema
+                        .get_receive_error_schema(contract_name, &entrypoint_name)
+                        .ok()
+                        .and_then(|ty| decode(&ty, data)),
+                    _ => None,
+                };
+                Err(ContractJsonError::Invoke {
+                    error,
+                    reject_reason,
+                })
+            }
+        }
+    }
+
+    /// Get the event schema of a contract instance, if it has one.
+    fn contract_event_schema(&self, address: ContractAddress) -> Option<Type> {
+        let contract = self.get_contract(address)?;
+        let schema = self.get_module(contract.module_reference)?.embedded_schema().ok()?;
+        schema.get_event_schema(contract.contract_name.as_contract_name().contract_name()).ok()
+    }
+
+    /// Decode an event logged by a contract instance with the embedded schema.
+    /// Returns `None` if there is no event schema or the event does not match
+    /// it.
+    pub(crate) fn decode_event_json(
+        &self,
+        address: ContractAddress,
+        event: &ContractEvent,
+    ) -> Option<serde_json::Value> {
+        decode_event(&self.contract_event_schema(address), event)
+    }
+
+    /// Decode a parameter sent to an entrypoint of a contract instance with
+    /// the embedded schema. Returns `None` if there is no parameter schema or
+    /// the parameter does not match it.
+    pub(crate) fn decode_parameter_json(
+        &self,
+        address: ContractAddress,
+        entrypoint: &str,
+        parameter: &[u8],
+    ) -> Option<serde_json::Value> {
+        let contract = self.get_contract(address)?;
+        let schema = self.get_module(contract.module_reference)?.embedded_schema().ok()?;
+        let ty = schema
+            .get_receive_param_schema(
+                contract.contract_name.as_contract_name().contract_name(),
+                entrypoint,
+            )
+            .ok()?;
+        decode(&ty, parameter)
+    }
+}
+
+/// Encode a JSON parameter with the type from the schema. If the schema does
+/// not describe the parameter, then only `null` is accepted and encoded as the
+/// empty parameter.
+fn encode_parameter(
+    ty: Result<Type, VersionedSchemaError>,
+    value: &serde_json::Value,
+) -> Result<OwnedParameter, ContractJsonError> {
+    let ty = match ty {
+        Ok(ty) => ty,
+        Err(_) if value.is_null() => return Ok(OwnedParameter::empty()),
+        Err(e) => return Err(ContractJsonError::MissingParameterType(e)),
+    };
+    let bytes = ty.serial_value(value).map_err(|e| ContractJsonError::Encode(e.to_string()))?;
+    OwnedParameter::try_from(bytes).map_err(|_| {
+        ContractJsonError::Encode("The parameter exceeds the maximum size allowed.".to_string())
+    })
+}
+
+/// Decode an event with the event schema, if there is one.
+fn decode_event(event_schema: &Option<Type>, event: &ContractEvent) -> Option<serde_json::Value> {
+    decode(event_schema.as_ref()?, event.as_ref())
+}
+
+/// Decode bytes to JSON with the type. Returns `None` if the bytes do not
+/// match the type.
+fn decode(ty: &Type, bytes: &[u8]) -> Option<serde_json::Value> {
+    let mut cursor = Cursor::new(bytes);
+    let value = ty.to_json(&mut cursor).ok()?;
+    // All the bytes must be used.
+    if cursor.offset != bytes.len() {
+        return None;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use concordium_rust_sdk::base::contracts_common::{
+        schema::{Fields, Type},
+        to_bytes,
+    };
+
+    /// Test that parameters are encoded with the schema, and that values are
+    /// only decoded if all bytes match the type.
+    #[test]
+    fn test_encode_and_decode() {
+        let ty = Type::Struct(Fields::Named(vec![
+            ("amount".to_string(), Type::U64),
+            ("flag".to_string(), Type::Bool),
+        ]));
+        let value = serde_json::json!({ "amount": 42, "flag": true });
+
+        let param = encode_parameter(Ok(ty.clone()), &value).expect("Value matches the type");
+        assert_eq!(param.as_ref(), to_bytes(&(42u64, true)).as_slice());
+        assert_eq!(decode(&ty, param.as_ref()), Some(value));
+        assert_eq!(decode(&ty, &to_bytes(&(42u64, true, 0u8))), None);
+
+        encode_parameter(Ok(ty), &serde_json::json!({ "amount": 42 })).expect_err("Missing field");
+        let empty = encode_parameter(
+            Err(VersionedSchemaError::NoParamsInReceive),
+            &serde_json::Value::Null,
+        )
+        .expect("Null is accepted without a schema");
+        assert!(empty.as_ref().is_empty());
+    }
+}
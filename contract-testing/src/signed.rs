@@ -0,0 +1,324 @@
+//! Transactions with explicit headers, which are verified like the node does
+//! before they are executed. Signed transactions are also verified against the
+//! keys of the sender account.
+//!
+//! A chain in strict mode, see [`Chain::set_strict_transactions`], only accepts
+//! module deployments, contract inits and contract updates as signed
+//! transactions.
+use crate::types::*;
+use concordium_rust_sdk::base::{
+    base::Nonce,
+    common::types::TransactionTime,
+    contracts_common::{AccountAddress, Address, Amount, Timestamp},
+    transactions::{self, AccountTransaction, EncodedPayload, Payload},
+};
+
+impl Chain {
+    /// Check whether the chain is in strict mode, where module deployments,
+    /// contract inits and contract updates must be signed transactions.
+    pub fn is_strict_transactions(&self) -> bool { self.parameters.strict_transactions }
+
+    /// Enable or disable strict mode.
+    ///
+    /// In strict mode, [`Chain::module_deploy_v1`], [`Chain::contract_init`]
+    /// and [`Chain::contract_update`] fail with
+    /// [`TransactionVerificationError::UnsignedTransaction`], and the signed
+    /// variants, e.g., [`Chain::contract_update_signed`], must be used instead.
+    /// The signed variants can also be used when strict mode is disabled.
+    pub fn set_strict_transactions(&mut self, strict: bool) {
+        self.parameters.strict_transactions = strict;
+    }
+
+    /// Verify a signed transaction like the node does before accepting it,
+    /// without executing it.
+    ///
+    /// The transaction is not accepted if
+    ///  - its expiry time is before the block time,
+    ///  - the sender account does not exist,
+    ///  - its nonce is not the [next nonce](Account::next_nonce) of the sender,
+    ///    or
+    ///  - its signatures do not satisfy the thresholds of the sender's
+    ///    [keys](Account::keys).
+    pub fn verify_transaction(
+        &self,
+        transaction: &AccountTransaction<EncodedPayload>,
+    ) -> Result<(), TransactionVerificationError> {
+        let header = &transaction.header;
+        let sender = self.verify_header(header.sender, header.nonce, header.expiry)?;
+        if !transactions::verify_transaction(&sender.keys, transaction) {
+            return Err(TransactionVerificationError::IncorrectSignature);
+        }
+        Ok(())
+    }
+
+    /// Submit an unsigned transaction with an explicit nonce and expiry time.
+    ///
+    /// The transaction is not accepted if its expiry time is before the block
+    /// time, the sender account does not exist, or its nonce is not the
+    /// [next nonce](Account::next_nonce) of the sender. This allows testing
+    /// that replayed, out of order and expired transactions are not accepted,
+    /// without signing them. The chain must not be in strict mode.
+    ///
+    /// If the transaction is accepted, it is executed like with, e.g.,
+    /// [`Chain::contract_update`], and the outcome is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut chain = Chain::new();
+    /// chain.create_account(Account::new(AccountAddress([0; 32]), Amount::from_ccd(1000)));
+    /// chain.create_account(Account::new(AccountAddress([1; 32]), Amount::from_ccd(1000)));
+    /// let transfer = Transaction {
+    ///     signer:          Signer::with_one_key(),
+    ///     sender:          AccountAddress([0; 32]),
+    ///     nonce:           Nonce::from(1),
+    ///     expiry:          TransactionTime::from_seconds(100),
+    ///     energy_reserved: Energy::from(0),
+    ///     payload:         Payload::Transfer {
+    ///         to_address: AccountAddress([1; 32]),
+    ///         amount:     Amount::from_ccd(10),
+    ///     },
+    /// };
+    /// assert!(chain.submit_transaction(transfer.clone()).unwrap().is_success());
+    /// // The nonce has been used.
+    /// assert!(matches!(
+    ///     chain.submit_transaction(transfer),
+    ///     Err(TransactionVerificationError::DuplicateNonce { .. })
+    /// ));
+    /// ```
+    pub fn submit_transaction(
+        &mut self,
+        transaction: Transaction,
+    ) -> Result<BlockItemOutcome, TransactionVerificationError> {
+        if self.parameters.strict_transactions {
+            return Err(TransactionVerificationError::UnsignedTransaction);
+        }
+        self.verify_header(transaction.sender, transaction.nonce, transaction.expiry)?;
+        let Transaction {
+            signer,
+            sender,
+            energy_reserved,
+            payload,
+            ..
+        } = transaction;
+        let outcome = match payload {
+            Payload::DeployModule {
+                module,
+            } => BlockItemOutcome::ModuleDeploy(self.module_deploy_v1(signer, sender, module)),
+            Payload::InitContract {
+                payload,
+            } => BlockItemOutcome::ContractInit(self.contract_init(
+                signer,
+                sender,
+                energy_reserved,
+                payload,
+            )),
+            Payload::Update {
+                payload,
+            } => BlockItemOutcome::ContractUpdate(self.contract_update(
+                signer,
+                sender,
+                Address::Account(sender),
+                energy_reserved,
+                payload,
+            )),
+            Payload::Transfer {
+                to_address,
+                amount,
+            } => BlockItemOutcome::AccountTransfer(
+                self.account_transfer(signer, sender, to_address, amount),
+            ),
+            Payload::TransferWithMemo {
+                to_address,
+                memo,
+                amount,
+            } => BlockItemOutcome::AccountTransfer(
+                self.account_transfer_with_memo(signer, sender, to_address, amount, memo),
+            ),
+            Payload::TransferWithSchedule {
+                to,
+                schedule,
+            } => BlockItemOutcome::AccountTransfer(
+                self.account_transfer_with_schedule(signer, sender, to, schedule),
+            ),
+            _ => return Err(TransactionVerificationError::UnexpectedPayload),
+        };
+        Ok(outcome)
+    }
+
+    /// Verify the expiry time and nonce of a transaction from `sender`, and
+    /// return the sender account.
+    fn verify_header(
+        &self,
+        sender: AccountAddress,
+        nonce: Nonce,
+        expiry: TransactionTime,
+    ) -> Result<&Account, TransactionVerificationError> {
+        let expiry = Timestamp::from_timestamp_millis(expiry.seconds.saturating_mul(1000));
+        if expiry < self.parameters.block_time {
+            return Err(TransactionVerificationError::Expired {
+                expiry,
+                block_time: self.parameters.block_time,
+            });
+        }
+        let account = self
+            .get_account(sender)
+            .ok_or(TransactionVerificationError::SenderDoesNotExist(sender))?;
+        if nonce < account.nonce {
+            return Err(TransactionVerificationError::DuplicateNonce {
+                nonce,
+                next_nonce: account.nonce,
+            });
+        }
+        if nonce > account.nonce {
+            return Err(TransactionVerificationError::NonceTooLarge {
+                nonce,
+                next_nonce: account.nonce,
+            });
+        }
+        Ok(account)
+    }
+
+    /// Deploy a smart contract module from a signed transaction.
+    ///
+    /// The transaction is verified with [`Chain::verify_transaction`] and must
+    /// have a module deployment payload. If it is accepted, the module is
+    /// deployed like with [`Chain::module_deploy_v1`], where the number of
+    /// signatures determines the cost.
+    pub fn module_deploy_v1_signed(
+        &mut self,
+        transaction: &AccountTransaction<EncodedPayload>,
+    ) -> Result<ModuleDeploySuccess, ModuleDeployError> {
+        let accepted = self.accept_transaction(transaction, |payload| match payload {
+            Payload::DeployModule {
+                module,
+            } => Some(module),
+            _ => None,
+        });
+        let (signer, module) = accepted.map_err(|error| ModuleDeployError {
+            energy_used:     0.into(),
+            transaction_fee: Amount::zero(),
+            kind:            error.into(),
+        })?;
+        let sender = transaction.header.sender;
+        self.without_strict_transactions(|chain| chain.module_deploy_v1(signer, sender, module))
+    }
+
+    /// Initialize a contract from a signed transaction.
+    ///
+    /// The transaction is verified with [`Chain::verify_transaction`] and must
+    /// have a contract init payload. If it is accepted, the contract is
+    /// initialized like with [`Chain::contract_init`], with the energy of the
+    /// transaction header.
+    pub fn contract_init_signed(
+        &mut self,
+        transaction: &AccountTransaction<EncodedPayload>,
+    ) -> Result<ContractInitSuccess, ContractInitError> {
+        let accepted = self.accept_transaction(transaction, |payload| match payload {
+            Payload::InitContract {
+                payload,
+            } => Some(payload),
+            _ => None,
+        });
+        let (signer, payload) = accepted.map_err(|error| ContractInitError {
+            energy_used:     0.into(),
+            transaction_fee: Amount::zero(),
+            kind:            error.into(),
+        })?;
+        let header = &transaction.header;
+        self.without_strict_transactions(|chain| {
+            chain.contract_init(signer, header.sender, header.energy_amount, payload)
+        })
+    }
+
+    /// Update a contract from a signed transaction.
+    ///
+    /// The transaction is verified with [`Chain::verify_transaction`] and must
+    /// have a contract update payload. If it is accepted, the contract is
+    /// updated like with [`Chain::contract_update`], where the sender of the
+    /// transaction is both the invoker and the sender, with the energy of the
+    /// transaction header.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use concordium_smart_contract_testing::*;
+    /// # use concordium_rust_sdk::base::transactions::send;
+    /// let mut chain = Chain::builder().strict_transactions().build().unwrap();
+    /// let account = Account::new_from_seed(AccountAddress([0; 32]), Amount::from_ccd(1000), 1);
+    /// let keys = account.signing_keys().unwrap().clone();
+    /// chain.create_account(account);
+    /// let transaction = send::update_contract(
+    ///     &keys,
+    ///     AccountAddress([0; 32]),
+    ///     chain.account(AccountAddress([0; 32])).unwrap().next_nonce(),
+    ///     TransactionTime::from_seconds(3600),
+    ///     UpdateContractPayload {
+    ///         address:      ContractAddress::new(0, 0),
+    ///         receive_name: OwnedReceiveName::new_unchecked("counter.increment".into()),
+    ///         message:      OwnedParameter::empty(),
+    ///         amount:       Amount::zero(),
+    ///     },
+    ///     Energy::from(10000),
+    /// );
+    /// chain.contract_update_signed(&transaction).unwrap();
+    /// ```
+    pub fn contract_update_signed(
+        &mut self,
+        transaction: &AccountTransaction<EncodedPayload>,
+    ) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+        let accepted = self.accept_transaction(transaction, |payload| match payload {
+            Payload::Update {
+                payload,
+            } => Some(payload),
+            _ => None,
+        });
+        let (signer, payload) = accepted.map_err(|error| ContractInvokeError {
+            energy_used:     0.into(),
+            transaction_fee: Amount::zero(),
+            trace_elements:  Vec::new(),
+            kind:            error.into(),
+        })?;
+        let header = &transaction.header;
+        self.without_strict_transactions(|chain| {
+            chain.contract_update(
+                signer,
+                header.sender,
+                Address::Account(header.sender),
+                header.energy_amount,
+                payload,
+            )
+        })
+    }
+
+    /// Verify a signed transaction and decode its payload with `expected`,
+    /// which returns `None` if the payload is of an unexpected type.
+    ///
+    /// Returns a signer with the number of signatures of the transaction.
+    fn accept_transaction<P>(
+        &self,
+        transaction: &AccountTransaction<EncodedPayload>,
+        expected: impl FnOnce(Payload) -> Option<P>,
+    ) -> Result<(Signer, P), TransactionVerificationError> {
+        self.verify_transaction(transaction)?;
+        let payload = transaction
+            .payload
+            .decode()
+            .map_err(|_| TransactionVerificationError::InvalidPayload)?;
+        let payload = expected(payload).ok_or(TransactionVerificationError::UnexpectedPayload)?;
+        // A valid signature has at least one key.
+        let signer = Signer::with_keys(transaction.signature.num_keys())
+            .map_err(|_| TransactionVerificationError::IncorrectSignature)?;
+        Ok((signer, payload))
+    }
+
+    /// Run `f` with strict mode disabled, so it can execute a transaction that
+    /// has already been verified.
+    pub(crate) fn without_strict_transactions<A>(&mut self, f: impl FnOnce(&mut Self) -> A) -> A {
+        let strict = std::mem::replace(&mut self.parameters.strict_transactions, false);
+        let result = f(self);
+        self.parameters.strict_transactions = strict;
+        result
+    }
+}
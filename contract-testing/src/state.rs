@@ -0,0 +1,521 @@
+//! Typed access to the state of contract instances.
+//!
+//! The state of a contract instance is a key-value store, where the
+//! `concordium-std` types such as `StateMap`, `StateSet` and `StateBox` are
+//! stored under prefixed keys. The [`ContractStateApi`] implements
+//! [`HasStateApi`] on top of the persistent state trie, so that the state can
+//! be read with the same types that the contract uses.
+use crate::types::*;
+use concordium_rust_sdk::{
+    base::contracts_common::ContractAddress,
+    smart_contracts::engine::{v1::trie, InterpreterEnergy},
+};
+use concordium_std::{
+    DeserialWithState, HasStateApi, HasStateEntry, ParseError, ParseResult, Read, Seek, SeekFrom,
+    StateError, Write,
+};
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+impl Chain {
+    /// Get a read-only view of the state of a contract instance, which
+    /// implements [`HasStateApi`].
+    ///
+    /// The view reads from the state trie as it was at the time of the call,
+    /// so later updates to the contract are not reflected in it. The state is
+    /// not copied, and entries are only read when they are looked up.
+    pub fn contract_state_api(
+        &self,
+        address: ContractAddress,
+    ) -> Result<ContractStateApi, ContractDoesNotExist> {
+        let contract = self.contracts.get(&address).ok_or(ContractDoesNotExist {
+            address,
+        })?;
+        Ok(ContractStateApi::from_persistent_state(&contract.state, true))
+    }
+
+    /// Read the root of the state of a contract instance as the type `S`,
+    /// which is usually the `State` type of the contract.
+    ///
+    /// Since the state of a contract is generic over the [`HasStateApi`], it
+    /// must be instantiated with [`ContractStateApi`], i.e., if the contract
+    /// state is declared as
+    ///
+    /// ```ignore
+    /// #[derive(Serial, DeserialWithState)]
+    /// #[concordium(state_parameter = "S")]
+    /// struct State<S = StateApi> {
+    ///     balances: StateMap<Address, Amount, S>,
+    /// }
+    /// ```
+    ///
+    /// then it can be read with
+    ///
+    /// ```ignore
+    /// let state: State<ContractStateApi> = chain.contract_state(contract_address)?;
+    /// let balance = state.balances.get(&Address::Account(ACC_0)).map(|b| *b);
+    /// ```
+    ///
+    /// The returned value works on the state at the time of the call. See
+    /// [`Chain::contract_state_api`].
+    pub fn contract_state<S: DeserialWithState<ContractStateApi>>(
+        &self,
+        address: ContractAddress,
+    ) -> Result<S, ContractStateError> {
+        let state_api = self.contract_state_api(address)?;
+        state_api.read_root().map_err(ContractStateError::Parse)
+    }
+
+    /// Modify the state of a contract instance directly, without invoking
+    /// the contract. This is useful for setting up states that would take
+    /// many transactions to reach, e.g., a token contract with many holders.
+    ///
+    /// The closure is given a modifiable [`ContractStateApi`] of the state,
+    /// and the modified entries are written to the state of the contract once
+    /// the closure returns. No energy is charged and no
+    /// events are produced.
+    ///
+    /// ```ignore
+    /// chain.contract_state_modify(contract_address, |state_api| {
+    ///     let mut state: State<ContractStateApi> =
+    ///         state_api.read_root().expect("State should be valid");
+    ///     state.balances.insert(Address::Account(ACC_0), Amount::from_ccd(10));
+    ///     state_api.write_root(&state);
+    /// })?;
+    /// ```
+    ///
+    /// New `StateMap`s and similar can be created with a
+    /// `StateBuilder::open(state_api.clone())`.
+    pub fn contract_state_modify<A>(
+        &mut self,
+        address: ContractAddress,
+        f: impl FnOnce(&mut ContractStateApi) -> A,
+    ) -> Result<A, ContractDoesNotExist> {
+        let contract = self.contracts.get_mut(&address).ok_or(ContractDoesNotExist {
+            address,
+        })?;
+        let mut state_api = ContractStateApi::from_persistent_state(&contract.state, false);
+        let result = f(&mut state_api);
+        contract.state = state_api.to_persistent_state();
+        Ok(result)
+    }
+}
+
+impl ContractStateApi {
+    /// Create a view of a persistent state. The state is shared, not copied.
+    fn from_persistent_state(state: &trie::PersistentState, read_only: bool) -> Self {
+        Self {
+            state: state.clone(),
+            entries: Rc::new(RefCell::new(BTreeMap::new())),
+            read_only,
+        }
+    }
+
+    /// Construct a persistent state from the state of the view with the
+    /// modified entries applied to it.
+    fn to_persistent_state(&self) -> trie::PersistentState {
+        // An empty loader is fine currently, as we do not use caching in this lib.
+        let mut loader = trie::Loader::new(&[][..]);
+        let mut mutable_state = self.state.thaw();
+        {
+            let inner = mutable_state.get_inner(&mut loader);
+            let mut state_trie = inner.lock();
+            for (key, data) in self.entries.borrow().iter() {
+                match data.borrow().clone() {
+                    Some(value) => {
+                        state_trie
+                            .insert(&mut loader, key, value)
+                            .expect("No iterators exist on the thawed state.");
+                    }
+                    None => {
+                        state_trie
+                            .delete(&mut loader, key)
+                            .expect("No iterators exist on the thawed state.");
+                    }
+                }
+            }
+        }
+        let mut collector = trie::SizeCollector::default();
+        mutable_state.freeze(&mut loader, &mut collector)
+    }
+
+    /// The keys and values of all entries in the state.
+    ///
+    /// This reads the whole state, so prefer looking up or iterating over
+    /// the entries that are needed.
+    pub fn entries(&self) -> BTreeMap<Vec<u8>, Vec<u8>> {
+        self.entries_with_prefix(&[])
+            .into_iter()
+            .filter_map(|(key, data)| Some((key, data.borrow().clone()?)))
+            .collect()
+    }
+
+    /// Whether the state can be modified.
+    pub fn is_read_only(&self) -> bool { self.read_only }
+
+    /// Construct an entry for the given key and data.
+    fn make_entry(&self, key: &[u8], data: &ContractStateEntryData) -> ContractStateEntry {
+        ContractStateEntry {
+            key:       key.to_vec(),
+            data:      Rc::clone(data),
+            position:  0,
+            read_only: self.read_only,
+        }
+    }
+
+    /// Get the data of an entry, or `None` if it does not exist. Entries
+    /// that are not in `entries` are looked up in the trie and added, so all
+    /// handles to the entry share the data.
+    fn lookup_data(&self, key: &[u8]) -> Option<ContractStateEntryData> {
+        if let Some(data) = self.entries.borrow().get(key) {
+            return data.borrow().is_some().then(|| Rc::clone(data));
+        }
+        // An empty loader is fine currently, as we do not use caching in this lib.
+        let mut loader = trie::Loader::new(&[][..]);
+        let value = self.state.lookup(&mut loader, key)?;
+        let data = Rc::new(RefCell::new(Some(value)));
+        self.entries.borrow_mut().insert(key.to_vec(), Rc::clone(&data));
+        Some(data)
+    }
+
+    /// Get the data of the existing entries with the given prefix, ordered by
+    /// their keys. Only the part of the trie with the prefix is traversed.
+    fn entries_with_prefix(&self, prefix: &[u8]) -> BTreeMap<Vec<u8>, ContractStateEntryData> {
+        let mut entries = self.entries.borrow_mut();
+        for (key, value) in state_entries_with_prefix(&self.state.thaw(), prefix) {
+            entries.entry(key).or_insert_with(|| Rc::new(RefCell::new(Some(value))));
+        }
+        entries
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .filter(|(_, data)| data.borrow().is_some())
+            .map(|(key, data)| (key.clone(), Rc::clone(data)))
+            .collect()
+    }
+
+    /// Mark the entry with the given key and data as deleted. The deleted
+    /// entry is replaced, so handles to it are not affected if an entry with
+    /// the same key is created later.
+    fn remove_entry(&self, key: Vec<u8>, data: &ContractStateEntryData) {
+        *data.borrow_mut() = None;
+        self.entries.borrow_mut().insert(key, Rc::new(RefCell::new(None)));
+    }
+
+    /// Remove the entries with the given prefix and mark them as deleted.
+    /// Returns whether any entries were removed.
+    fn remove_prefix(&mut self, prefix: &[u8]) -> bool {
+        let entries = self.entries_with_prefix(prefix);
+        let removed = !entries.is_empty();
+        for (key, data) in entries {
+            self.remove_entry(key, &data);
+        }
+        removed
+    }
+}
+
+impl HasStateApi for ContractStateApi {
+    type EntryType = ContractStateEntry;
+    type IterType = std::vec::IntoIter<ContractStateEntry>;
+
+    /// Create an entry, or reset the existing entry with the key to be empty.
+    /// Fails if the state is read-only.
+    fn create_entry(&mut self, key: &[u8]) -> Result<Self::EntryType, StateError> {
+        if self.read_only {
+            return Err(StateError::SubtreeLocked);
+        }
+        let data = Rc::clone(
+            self.entries
+                .borrow_mut()
+                .entry(key.to_vec())
+                .and_modify(|data| *data.borrow_mut() = Some(Vec::new()))
+                .or_insert_with(|| Rc::new(RefCell::new(Some(Vec::new())))),
+        );
+        Ok(self.make_entry(key, &data))
+    }
+
+    fn lookup_entry(&self, key: &[u8]) -> Option<Self::EntryType> {
+        self.lookup_data(key).map(|data| self.make_entry(key, &data))
+    }
+
+    /// Delete an entry. Fails if the state is read-only, or if the entry does
+    /// not exist.
+    fn delete_entry(&mut self, entry: Self::EntryType) -> Result<(), StateError> {
+        if self.read_only {
+            return Err(StateError::SubtreeLocked);
+        }
+        let data = self.lookup_data(&entry.key).ok_or(StateError::EntryNotFound)?;
+        self.remove_entry(entry.key, &data);
+        Ok(())
+    }
+
+    /// Delete all entries with the given prefix. Fails if the state is
+    /// read-only.
+    fn delete_prefix(&mut self, prefix: &[u8]) -> Result<bool, StateError> {
+        if self.read_only {
+            return Err(StateError::SubtreeLocked);
+        }
+        Ok(self.remove_prefix(prefix))
+    }
+
+    /// Get an iterator over the entries with the given prefix, ordered by
+    /// their keys.
+    ///
+    /// Returns an error if no entries have the prefix, like the iterators on
+    /// the chain. Unlike on the chain, the iterator does not lock the entries.
+    fn iterator(&self, prefix: &[u8]) -> Result<Self::IterType, StateError> {
+        let entries: Vec<_> = self
+            .entries_with_prefix(prefix)
+            .iter()
+            .map(|(key, data)| self.make_entry(key, data))
+            .collect();
+        if entries.is_empty() {
+            return Err(StateError::SubtreeWithPrefixNotFound);
+        }
+        Ok(entries.into_iter())
+    }
+
+    /// Iterators do not lock the state, so nothing needs to be done.
+    fn delete_iterator(&mut self, _iter: Self::IterType) {}
+}
+
+impl ContractStateEntry {
+    /// Fails if the entry cannot be modified.
+    fn check_writable(&self) -> Result<(), ContractStateEntryError> {
+        if self.read_only {
+            return Err(ContractStateEntryError::ReadOnly);
+        }
+        Ok(())
+    }
+}
+
+impl HasStateEntry for ContractStateEntry {
+    type Error = ContractStateEntryError;
+    type StateEntryData = ContractStateEntryData;
+    type StateEntryKey = Vec<u8>;
+
+    fn move_to_start(&mut self) { self.position = 0; }
+
+    fn size(&self) -> Result<u32, Self::Error> {
+        let data = self.data.borrow();
+        let data = data.as_ref().ok_or(ContractStateEntryError::EntryDeleted)?;
+        Ok(data.len() as u32)
+    }
+
+    fn truncate(&mut self, new_size: u32) -> Result<(), Self::Error> {
+        if self.size()? > new_size {
+            self.resize(new_size)?;
+        }
+        Ok(())
+    }
+
+    fn get_key(&self) -> &[u8] { &self.key }
+
+    fn resize(&mut self, new_size: u32) -> Result<(), Self::Error> {
+        self.check_writable()?;
+        let mut data = self.data.borrow_mut();
+        let data = data.as_mut().ok_or(ContractStateEntryError::EntryDeleted)?;
+        data.resize(new_size as usize, 0);
+        self.position = self.position.min(new_size);
+        Ok(())
+    }
+}
+
+impl Read for ContractStateEntry {
+    fn read(&mut self, buf: &mut [u8]) -> ParseResult<usize> {
+        let data = self.data.borrow();
+        let data = data.as_ref().ok_or_else(ParseError::default)?;
+        let start = (self.position as usize).min(data.len());
+        let len = buf.len().min(data.len() - start);
+        buf[..len].copy_from_slice(&data[start..start + len]);
+        self.position += len as u32;
+        Ok(len)
+    }
+}
+
+impl Write for ContractStateEntry {
+    type Err = ContractStateEntryError;
+
+    /// Write at the current position, extending the entry if needed. Fails if
+    /// the state is read-only.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Err> {
+        self.check_writable()?;
+        let end = u32::try_from(buf.len())
+            .ok()
+            .and_then(|len| self.position.checked_add(len))
+            .ok_or(ContractStateEntryError::Offset)?;
+        if self.size()? < end {
+            self.resize(end)?;
+        }
+        let mut data = self.data.borrow_mut();
+        let data = data.as_mut().ok_or(ContractStateEntryError::EntryDeleted)?;
+        data[self.position as usize..end as usize].copy_from_slice(buf);
+        self.position = end;
+        Ok(buf.len())
+    }
+}
+
+impl Seek for ContractStateEntry {
+    type Err = ContractStateEntryError;
+
+    /// Seek with the same semantics as the entries on the chain, i.e., it is
+    /// not possible to seek beyond the end of the entry.
+    fn seek(&mut self, pos: SeekFrom) -> Result<u32, Self::Err> {
+        let end = i64::from(self.size()?);
+        let new_position = match pos {
+            SeekFrom::Start(offset) => i64::from(offset),
+            SeekFrom::End(delta) => end + i64::from(delta),
+            SeekFrom::Current(delta) => i64::from(self.position) + i64::from(delta),
+        };
+        if !(0..=end).contains(&new_position) {
+            return Err(ContractStateEntryError::Offset);
+        }
+        self.position = new_position as u32;
+        Ok(self.position)
+    }
+}
+
+/// Get the keys and values of all entries in a state.
+///
+/// The entries are read from a fresh generation of a copy of the state, so
+/// iterators that a contract holds on the state are not affected.
+pub(crate) fn mutable_state_entries(state: &trie::MutableState) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    state_entries_with_prefix(state, &[])
+}
+
+/// Get the keys and values of the entries with the given prefix in a state,
+/// like [`mutable_state_entries`].
+fn state_entries_with_prefix(
+    state: &trie::MutableState,
+    prefix: &[u8],
+) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    // An empty loader is fine currently, as we do not use caching in this lib.
+    let mut loader = trie::Loader::new(&[][..]);
+    let mut mutable_state = state.clone().make_fresh_generation(&mut loader);
+    let inner = mutable_state.get_inner(&mut loader);
+    let mut state_trie = inner.lock();
+    // The traversal is not charged for, so the energy is unlimited.
+    let mut energy = InterpreterEnergy::new(u64::MAX);
+    let mut entries = BTreeMap::new();
+    if let Some(mut iter) = state_trie
+        .iter(&mut loader, prefix)
+        .expect("No other iterators exist on the new generation.")
+    {
+        while let Some(entry) = state_trie
+            .next(&mut loader, &mut iter, &mut energy)
+            .expect("The energy for traversing the state is unlimited.")
+        {
+            let value = state_trie
+                .with_entry(entry, &mut loader, |value| value.to_vec())
+                .expect("Entry returned by the iterator exists.");
+            entries.insert(iter.get_key().to_vec(), value);
+        }
+        state_trie.delete_iter(&iter);
+    }
+    entries
+}
+
+/// Construct a persistent state with the given entries.
+pub(crate) fn persistent_state_from_entries(
+    entries: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+) -> trie::PersistentState {
+    // An empty loader is fine currently, as we do not use caching in this lib.
+    let mut loader = trie::Loader::new(&[][..]);
+    let mut mutable_state = trie::PersistentState::Empty.thaw();
+    {
+        let inner = mutable_state.get_inner(&mut loader);
+        let mut state_trie = inner.lock();
+        for (key, value) in entries {
+            state_trie
+                .insert(&mut loader, &key, value)
+                .expect("No iterators exist on the new state.");
+        }
+    }
+    let mut collector = trie::SizeCollector::default();
+    mutable_state.freeze(&mut loader, &mut collector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use concordium_std::{to_bytes, StateBuilder, StateMap};
+
+    /// Test that a `StateMap` can be read and iterated, and that the state
+    /// cannot be modified.
+    #[test]
+    fn test_read_state_map() {
+        let prefix = [1u8, 0, 0, 0, 0, 0, 0, 0];
+        let mut entries = BTreeMap::new();
+        entries.insert(Vec::new(), prefix.to_vec());
+        for (key, value) in [(2u8, 20u64), (1, 10)] {
+            let mut entry_key = prefix.to_vec();
+            entry_key.extend(to_bytes(&key));
+            entries.insert(entry_key, to_bytes(&value));
+        }
+        let mut state_api =
+            ContractStateApi::from_persistent_state(&persistent_state_from_entries(entries), true);
+
+        let map: StateMap<u8, u64, ContractStateApi> =
+            state_api.read_root().expect("Root is a map");
+        assert_eq!(map.get(&1).map(|v| *v), Some(10));
+        assert_eq!(map.get(&3).map(|v| *v), None);
+        let items: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(items, [(1, 10), (2, 20)]);
+
+        assert!(state_api.create_entry(&[42]).is_err());
+        let mut root = state_api.lookup_entry(&[]).expect("Root exists");
+        assert_eq!(root.write(&[0]), Err(ContractStateEntryError::ReadOnly));
+    }
+
+    /// Test that a modifiable state supports creating, writing and deleting
+    /// entries via the `concordium-std` types.
+    #[test]
+    fn test_modify_state() {
+        let mut state_api =
+            ContractStateApi::from_persistent_state(&trie::PersistentState::Empty, false);
+        let mut state_builder = StateBuilder::open(state_api.clone());
+        let mut map: StateMap<u8, u64, ContractStateApi> = state_builder.new_map();
+        map.insert(1, 10);
+        map.insert(2, 20);
+        state_api.write_root(&map);
+
+        let mut map: StateMap<u8, u64, ContractStateApi> =
+            state_api.read_root().expect("Root is a map");
+        assert_eq!(map.remove_and_get(&1), Some(10));
+        let items: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(items, [(2, 20)]);
+
+        let mut entry = state_api.create_entry(&[42]).expect("State is modifiable");
+        entry.write_all(&[1, 2, 3]).expect("Entry exists");
+        state_api.delete_entry(state_api.lookup_entry(&[42]).expect("Entry exists")).unwrap();
+        assert_eq!(entry.size(), Err(ContractStateEntryError::EntryDeleted));
+        assert!(!state_api.entries().contains_key(&[42][..]));
+    }
+
+    /// Test that modifications are applied to the entries of the trie, and
+    /// that a deleted entry is not affected by a new entry with the same key.
+    #[test]
+    fn test_modify_persistent_state() {
+        let state = persistent_state_from_entries([
+            (vec![1], vec![10]),
+            (vec![2, 0], vec![20]),
+            (vec![2, 1], vec![21]),
+            (vec![3], vec![30]),
+        ]);
+        let mut state_api = ContractStateApi::from_persistent_state(&state, false);
+        let entry = state_api.lookup_entry(&[1]).expect("Entry exists in the trie");
+        state_api.delete_entry(state_api.lookup_entry(&[1]).expect("Entry exists")).unwrap();
+        state_api.create_entry(&[1]).expect("State is modifiable").write_all(&[11]).unwrap();
+        assert_eq!(entry.size(), Err(ContractStateEntryError::EntryDeleted));
+        assert_eq!(state_api.delete_prefix(&[2]), Ok(true));
+        assert!(state_api.iterator(&[2]).is_err());
+        let mut entry = state_api.lookup_entry(&[3]).expect("Entry exists in the trie");
+        entry.write_all(&[31]).unwrap();
+
+        let expected = BTreeMap::from([(vec![1], vec![11]), (vec![3], vec![31])]);
+        assert_eq!(state_api.entries(), expected);
+        let modified =
+            ContractStateApi::from_persistent_state(&state_api.to_persistent_state(), true);
+        assert_eq!(modified.entries(), expected);
+        // The original state is unchanged.
+        assert_eq!(ContractStateApi::from_persistent_state(&state, true).entries().len(), 4);
+    }
+}
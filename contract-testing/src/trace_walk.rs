@@ -0,0 +1,218 @@
+//! Reconstruction of the calls between contracts from the trace elements of
+//! an update or invocation.
+//!
+//! The trace elements are a flat list, where a call is only visible through
+//! the elements it produces, and failed calls contain the elements that were
+//! rolled back. [`walk`] turns them into a stack of calls, and reports when a
+//! call starts, what happens in it and when it ends to a [`TraceVisitor`].
+use crate::types::*;
+use concordium_rust_sdk::{
+    base::{
+        base::Energy,
+        contracts_common::{ContractAddress, EntrypointName},
+        smart_contracts::ContractTraceElement,
+    },
+    smart_contracts::engine::v1::DebugTracker,
+};
+
+/// A call to a contract entrypoint on the stack reconstructed by [`walk`].
+pub(crate) struct Call<'a> {
+    pub(crate) address:    ContractAddress,
+    pub(crate) entrypoint: EntrypointName<'a>,
+    /// Whether the call is waiting for an interrupt, e.g., a call to another
+    /// contract, to be resumed.
+    interrupted:           bool,
+}
+
+/// Something that happened in the call at the top of the stack.
+pub(crate) enum TraceEvent<'a> {
+    /// A trace element was produced.
+    Element {
+        trace_element: &'a ContractTraceElement,
+        /// The energy used so far.
+        energy_used:   Energy,
+        /// The host calls and debug statements since the previous element.
+        debug_trace:   &'a DebugTracker,
+    },
+    /// Host calls and debug statements were recorded before an interrupt that
+    /// does not produce a trace element, e.g., a query.
+    Debug(&'a DebugTracker),
+    /// The call failed.
+    Failed {
+        error:       &'a InvokeExecutionError,
+        /// The energy used so far.
+        energy_used: Energy,
+        /// The host calls and debug statements before the failure.
+        debug_trace: &'a DebugTracker,
+    },
+}
+
+/// Callbacks for the calls and events found by [`walk`]. The stack is given
+/// with the current call at the end.
+pub(crate) trait TraceVisitor<'a> {
+    /// A call was pushed on the stack.
+    fn enter(&mut self, _stack: &[Call<'a>], _rolled_back: bool) {}
+
+    /// Something happened in the current call.
+    fn event(&mut self, stack: &[Call<'a>], event: TraceEvent<'a>, rolled_back: bool);
+
+    /// The current call is about to be popped from the stack, because it
+    /// completed or failed, or because the trace ended before it did.
+    fn exit(&mut self, _stack: &[Call<'a>], _rolled_back: bool) {}
+}
+
+/// Visit the trace elements in order and report the calls and events to the
+/// visitor. Elements in failures are visited recursively and are always
+/// rolled back.
+///
+/// Calls that never completed, which happens if the update failed, are exited
+/// at the end.
+pub(crate) fn walk<'a>(
+    trace_elements: &'a [DebugTraceElement],
+    rolled_back: bool,
+    visitor: &mut impl TraceVisitor<'a>,
+) {
+    let mut walker = Walker {
+        stack: Vec::new(),
+        visitor,
+    };
+    walker.visit(trace_elements, rolled_back);
+    while !walker.stack.is_empty() {
+        walker.exit(rolled_back);
+    }
+}
+
+/// The state of [`walk`].
+struct Walker<'a, 'v, V> {
+    stack:   Vec<Call<'a>>,
+    visitor: &'v mut V,
+}
+
+impl<'a, 'v, V: TraceVisitor<'a>> Walker<'a, 'v, V> {
+    /// Visit the trace elements in order. Failures are visited recursively.
+    fn visit(&mut self, trace_elements: &'a [DebugTraceElement], rolled_back: bool) {
+        for element in trace_elements {
+            match element {
+                DebugTraceElement::Regular {
+                    entrypoint,
+                    trace_element,
+                    energy_used,
+                    debug_trace,
+                } => {
+                    match trace_element {
+                        // Transfers and upgrades happen while the contract is interrupted.
+                        ContractTraceElement::Transferred {
+                            ..
+                        }
+                        | ContractTraceElement::Upgraded {
+                            ..
+                        } => {}
+                        ContractTraceElement::Resumed {
+                            ..
+                        } => self.set_interrupted(false),
+                        ContractTraceElement::Interrupted {
+                            ..
+                        }
+                        | ContractTraceElement::Updated {
+                            ..
+                        } => self.enter(
+                            trace_element.affected_address(),
+                            entrypoint.as_entrypoint_name(),
+                            rolled_back,
+                        ),
+                    }
+                    self.event(
+                        TraceEvent::Element {
+                            trace_element,
+                            energy_used: *energy_used,
+                            debug_trace,
+                        },
+                        rolled_back,
+                    );
+                    match trace_element {
+                        ContractTraceElement::Interrupted {
+                            ..
+                        } => self.set_interrupted(true),
+                        ContractTraceElement::Updated {
+                            ..
+                        } => self.exit(rolled_back),
+                        _ => {}
+                    }
+                }
+                DebugTraceElement::Debug {
+                    entrypoint,
+                    address,
+                    debug_trace,
+                } => {
+                    self.enter(*address, entrypoint.as_entrypoint_name(), rolled_back);
+                    self.event(TraceEvent::Debug(debug_trace), rolled_back);
+                }
+                DebugTraceElement::WithFailures {
+                    contract_address,
+                    entrypoint,
+                    error,
+                    trace_elements,
+                    energy_used,
+                    debug_trace,
+                } => {
+                    self.visit(trace_elements, true);
+                    self.enter(*contract_address, entrypoint.as_entrypoint_name(), true);
+                    self.event(
+                        TraceEvent::Failed {
+                            error,
+                            energy_used: *energy_used,
+                            debug_trace,
+                        },
+                        true,
+                    );
+                    self.exit(true);
+                }
+            }
+        }
+    }
+
+    /// Make sure that the given contract and entrypoint is the top of the
+    /// stack. A new call is pushed unless the top is the same entrypoint and
+    /// it is not waiting for an interrupt.
+    fn enter(
+        &mut self,
+        address: ContractAddress,
+        entrypoint: EntrypointName<'a>,
+        rolled_back: bool,
+    ) {
+        let is_top = self.stack.last().map_or(false, |call| {
+            call.address == address && call.entrypoint == entrypoint && !call.interrupted
+        });
+        if !is_top {
+            self.stack.push(Call {
+                address,
+                entrypoint,
+                interrupted: false,
+            });
+            self.visitor.enter(&self.stack, rolled_back);
+        }
+    }
+
+    /// Report an event in the current call. Events outside of any call are
+    /// ignored.
+    fn event(&mut self, event: TraceEvent<'a>, rolled_back: bool) {
+        if !self.stack.is_empty() {
+            self.visitor.event(&self.stack, event, rolled_back);
+        }
+    }
+
+    /// Set whether the top of the stack is waiting for an interrupt.
+    fn set_interrupted(&mut self, interrupted: bool) {
+        if let Some(call) = self.stack.last_mut() {
+            call.interrupted = interrupted;
+        }
+    }
+
+    /// Pop the current call from the stack.
+    fn exit(&mut self, rolled_back: bool) {
+        if !self.stack.is_empty() {
+            self.visitor.exit(&self.stack, rolled_back);
+            self.stack.pop();
+        }
+    }
+}
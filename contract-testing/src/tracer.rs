@@ -0,0 +1,509 @@
+//! Step-by-step execution traces of contract updates and invocations.
+use crate::{
+    trace_walk::{self, Call, TraceEvent, TraceVisitor},
+    types::*,
+};
+use concordium_rust_sdk::{
+    base::{
+        base::Energy,
+        contracts_common::{AccountAddress, Address},
+        smart_contracts::{ContractTraceElement, OwnedParameter},
+        transactions::UpdateContractPayload,
+    },
+    smart_contracts::engine::v1::{self, DebugTracker},
+};
+use std::{collections::BTreeMap, fmt};
+
+impl ContractInvokeSuccess {
+    /// Get a step-by-step trace of the update or invocation. See
+    /// [`ExecutionTrace`] for details.
+    pub fn execution_trace(&self) -> ExecutionTrace<'_> {
+        ExecutionTrace::new(&self.trace_elements, false)
+    }
+}
+
+impl ContractInvokeError {
+    /// Get a step-by-step trace of the update or invocation until the
+    /// failure. See [`ExecutionTrace`] for details.
+    pub fn execution_trace(&self) -> ExecutionTrace<'_> {
+        ExecutionTrace::new(&self.trace_elements, true)
+    }
+}
+
+impl ContractCallTraced {
+    /// Get a step-by-step trace of the update or invocation. See
+    /// [`ExecutionTrace`] for details.
+    pub fn execution_trace(&self) -> ExecutionTrace<'_> {
+        match &self.result {
+            Ok(success) => success.execution_trace(),
+            Err(error) => error.execution_trace(),
+        }
+    }
+}
+
+impl Chain {
+    /// Like [`Chain::contract_update`], except that the host calls that
+    /// interrupt the contracts, with their arguments and responses, and the
+    /// changes to the state of the contracts are also recorded for each call
+    /// to a contract entrypoint. See [`CallRecord`] for details.
+    ///
+    /// The state of the executing contract is read each time it is
+    /// interrupted, returns or fails, so this is meant for debugging rather
+    /// than for every update in a test.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use concordium_smart_contract_testing::*;
+    /// # let mut chain = Chain::new();
+    /// # let payload: UpdateContractPayload = todo!();
+    /// # const ACC: AccountAddress = AccountAddress([0; 32]);
+    /// let traced = chain.contract_update_traced(
+    ///     Signer::with_one_key(),
+    ///     ACC,
+    ///     Address::Account(ACC),
+    ///     Energy::from(10000),
+    ///     payload,
+    /// );
+    /// // Print the calls, host calls, debug statements and state changes.
+    /// eprintln!("{traced}");
+    /// ```
+    pub fn contract_update_traced(
+        &mut self,
+        signer: Signer,
+        invoker: AccountAddress,
+        sender: Address,
+        energy_reserved: Energy,
+        payload: UpdateContractPayload,
+    ) -> ContractCallTraced {
+        *self.call_records.get_mut() = Some(Vec::new());
+        let result = self.contract_update(signer, invoker, sender, energy_reserved, payload);
+        self.call_records_take(result)
+    }
+
+    /// Like [`Chain::contract_invoke`], except that the host calls and state
+    /// changes are recorded as for [`Chain::contract_update_traced`].
+    pub fn contract_invoke_traced(
+        &self,
+        invoker: AccountAddress,
+        sender: Address,
+        energy_reserved: Energy,
+        payload: UpdateContractPayload,
+    ) -> ContractCallTraced {
+        *self.call_records.borrow_mut() = Some(Vec::new());
+        let result = self.contract_invoke(invoker, sender, energy_reserved, payload);
+        self.call_records_take(result)
+    }
+
+    /// Stop recording and return the records with the result. If the update
+    /// or invocation failed, all of its effects are rolled back.
+    fn call_records_take(
+        &self,
+        result: Result<ContractInvokeSuccess, ContractInvokeError>,
+    ) -> ContractCallTraced {
+        let mut records = self.call_records.take().unwrap_or_default();
+        if result.is_err() {
+            for record in &mut records {
+                record.rolled_back = true;
+            }
+        }
+        ContractCallTraced {
+            result,
+            records,
+        }
+    }
+
+    /// Whether the current invocation is traced.
+    pub(crate) fn is_traced(&self) -> bool { self.call_records.borrow().is_some() }
+
+    /// The number of records of the current invocation.
+    pub(crate) fn call_records_len(&self) -> usize {
+        self.call_records.borrow().as_ref().map_or(0, Vec::len)
+    }
+
+    /// Add a record to the current invocation, if it is traced.
+    pub(crate) fn record_call(&self, record: CallRecord) {
+        if let Some(records) = self.call_records.borrow_mut().as_mut() {
+            records.push(record);
+        }
+    }
+
+    /// Mark the records after the checkpoint as rolled back.
+    pub(crate) fn roll_back_call_records(&self, checkpoint: usize) {
+        if let Some(records) = self.call_records.borrow_mut().as_mut() {
+            for record in records.iter_mut().skip(checkpoint) {
+                record.rolled_back = true;
+            }
+        }
+    }
+}
+
+/// Get the arguments of a host call that interrupted a contract.
+pub(crate) fn host_interrupt(interrupt: &v1::Interrupt) -> HostInterrupt {
+    match interrupt {
+        v1::Interrupt::Transfer {
+            to,
+            amount,
+        } => HostInterrupt::Transfer {
+            to:     *to,
+            amount: *amount,
+        },
+        v1::Interrupt::Call {
+            address,
+            parameter,
+            name,
+            amount,
+        } => HostInterrupt::Call {
+            address:    *address,
+            entrypoint: name.clone(),
+            parameter:  OwnedParameter::new_unchecked(parameter.clone()),
+            amount:     *amount,
+        },
+        v1::Interrupt::Upgrade {
+            module_ref,
+        } => HostInterrupt::Upgrade {
+            module_reference: *module_ref,
+        },
+        v1::Interrupt::QueryAccountBalance {
+            address,
+        } => HostInterrupt::QueryAccountBalance {
+            address: *address,
+        },
+        v1::Interrupt::QueryContractBalance {
+            address,
+        } => HostInterrupt::QueryContractBalance {
+            address: *address,
+        },
+        v1::Interrupt::QueryExchangeRates => HostInterrupt::QueryExchangeRates,
+        v1::Interrupt::CheckAccountSignature {
+            address,
+            payload,
+        } => HostInterrupt::CheckAccountSignature {
+            address: *address,
+            payload: payload.clone(),
+        },
+        v1::Interrupt::QueryAccountKeys {
+            address,
+        } => HostInterrupt::QueryAccountKeys {
+            address: *address,
+        },
+    }
+}
+
+/// Get the response that a contract is resumed with after an interrupt.
+pub(crate) fn host_interrupt_response(response: &v1::InvokeResponse) -> HostInterruptResponse {
+    match response {
+        v1::InvokeResponse::Success {
+            data,
+            ..
+        } => HostInterruptResponse::Success {
+            data: data.clone(),
+        },
+        v1::InvokeResponse::Failure {
+            kind:
+                v1::InvokeFailure::ContractReject {
+                    code,
+                    data,
+                },
+        } => HostInterruptResponse::Reject {
+            code: *code,
+            data: data.to_vec(),
+        },
+        v1::InvokeResponse::Failure {
+            kind,
+        } => HostInterruptResponse::Failure(format!("{kind:?}")),
+    }
+}
+
+/// Get the changes between two versions of the state of a contract.
+pub(crate) fn state_changes_between(
+    before: &BTreeMap<Vec<u8>, Vec<u8>>,
+    after: &BTreeMap<Vec<u8>, Vec<u8>>,
+) -> Vec<StateChange> {
+    let mut changes = Vec::new();
+    for (key, old) in before {
+        match after.get(key) {
+            Some(new) if new == old => {}
+            new => changes.push(StateChange {
+                key: key.clone(),
+                old: Some(old.clone()),
+                new: new.cloned(),
+            }),
+        }
+    }
+    for (key, new) in after {
+        if !before.contains_key(key) {
+            changes.push(StateChange {
+                key: key.clone(),
+                old: None,
+                new: Some(new.clone()),
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.key.cmp(&b.key));
+    changes
+}
+
+/// Helper for collecting the steps of an [`ExecutionTrace`].
+#[derive(Default)]
+struct TraceBuilder<'a> {
+    steps: Vec<ExecutionTraceStep<'a>>,
+}
+
+impl<'a> ExecutionTrace<'a> {
+    /// Construct the trace from the trace elements.
+    fn new(trace_elements: &'a [DebugTraceElement], rolled_back: bool) -> Self {
+        let mut builder = TraceBuilder::default();
+        trace_walk::walk(trace_elements, rolled_back, &mut builder);
+        Self {
+            steps: builder.steps,
+        }
+    }
+
+    /// Iterate over the steps in the order they happened.
+    pub fn iter(&self) -> impl Iterator<Item = &ExecutionTraceStep<'a>> { self.steps.iter() }
+}
+
+impl<'a> TraceBuilder<'a> {
+    /// Add the host calls and debug statements in the order they happened.
+    fn debug_trace(
+        &mut self,
+        stack: &[Call<'a>],
+        debug_trace: &'a DebugTracker,
+        rolled_back: bool,
+    ) {
+        let host_calls = debug_trace.host_call_trace.iter().map(|(index, host_call)| {
+            (*index, ExecutionTraceStepKind::HostCall {
+                host_function: host_call.host_function,
+                energy_used:   host_call.energy_used,
+            })
+        });
+        let debug_prints = debug_trace
+            .emitted_events
+            .iter()
+            .map(|(index, statement)| (*index, ExecutionTraceStepKind::DebugPrint(statement)));
+        let mut kinds: Vec<_> = host_calls.chain(debug_prints).collect();
+        kinds.sort_by_key(|(index, _)| *index);
+        for (_, kind) in kinds {
+            self.push(stack, rolled_back, kind);
+        }
+    }
+
+    /// Add a step for the current call.
+    fn push(&mut self, stack: &[Call<'a>], rolled_back: bool, kind: ExecutionTraceStepKind<'a>) {
+        let Some(call) = stack.last() else {
+            return;
+        };
+        self.steps.push(ExecutionTraceStep {
+            depth: stack.len() - 1,
+            address: call.address,
+            entrypoint: call.entrypoint,
+            rolled_back,
+            kind,
+        });
+    }
+}
+
+impl<'a> TraceVisitor<'a> for TraceBuilder<'a> {
+    fn enter(&mut self, stack: &[Call<'a>], rolled_back: bool) {
+        self.push(stack, rolled_back, ExecutionTraceStepKind::Enter);
+    }
+
+    fn event(&mut self, stack: &[Call<'a>], event: TraceEvent<'a>, rolled_back: bool) {
+        match event {
+            TraceEvent::Element {
+                trace_element,
+                debug_trace,
+                ..
+            } => {
+                self.debug_trace(stack, debug_trace, rolled_back);
+                self.push(stack, rolled_back, ExecutionTraceStepKind::TraceElement(trace_element));
+                if let ContractTraceElement::Updated {
+                    ..
+                } = trace_element
+                {
+                    self.push(stack, rolled_back, ExecutionTraceStepKind::Exit);
+                }
+            }
+            TraceEvent::Debug(debug_trace) => self.debug_trace(stack, debug_trace, rolled_back),
+            TraceEvent::Failed {
+                error,
+                debug_trace,
+                ..
+            } => {
+                self.debug_trace(stack, debug_trace, rolled_back);
+                self.push(stack, rolled_back, ExecutionTraceStepKind::Failed(error));
+            }
+        }
+    }
+}
+
+impl fmt::Display for ExecutionTrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{step}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ExecutionTraceStep<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indent = "  ".repeat(self.depth);
+        let rolled_back = if self.rolled_back {
+            " (rolled back)"
+        } else {
+            ""
+        };
+        match &self.kind {
+            ExecutionTraceStepKind::Enter => {
+                write!(f, "{indent}call {}.{}{rolled_back}", self.address, self.entrypoint)
+            }
+            ExecutionTraceStepKind::HostCall {
+                host_function,
+                energy_used,
+            } => write!(
+                f,
+                "{indent}  host call {host_function} using {energy_used} interpreter \
+                 energy{rolled_back}"
+            ),
+            ExecutionTraceStepKind::DebugPrint(statement) => {
+                write!(f, "{indent}  debug: {statement}{rolled_back}")
+            }
+            ExecutionTraceStepKind::TraceElement(element) => {
+                write!(f, "{indent}  ")?;
+                match element {
+                    ContractTraceElement::Interrupted {
+                        events,
+                        ..
+                    } => write!(f, "interrupted after logging {} events", events.len())?,
+                    ContractTraceElement::Resumed {
+                        success,
+                        ..
+                    } => write!(f, "resumed, the interrupt succeeded: {success}")?,
+                    ContractTraceElement::Transferred {
+                        amount,
+                        to,
+                        ..
+                    } => write!(f, "transferred {amount} to {to}")?,
+                    ContractTraceElement::Upgraded {
+                        from,
+                        to,
+                        ..
+                    } => write!(f, "upgraded from module {from} to {to}")?,
+                    ContractTraceElement::Updated {
+                        data,
+                    } => write!(
+                        f,
+                        "updated by {:?} with {} and a parameter of {} bytes, logging {} events",
+                        data.instigator,
+                        data.amount,
+                        data.message.as_ref().len(),
+                        data.events.len()
+                    )?,
+                }
+                write!(f, "{rolled_back}")
+            }
+            ExecutionTraceStepKind::Failed(error) => {
+                write!(f, "{indent}  failed: {error:?}{rolled_back}")
+            }
+            ExecutionTraceStepKind::Exit => write!(f, "{indent}  return{rolled_back}"),
+        }
+    }
+}
+
+impl fmt::Display for ContractCallTraced {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.execution_trace())?;
+        if let Err(error) = &self.result {
+            writeln!(f, "{error}")?;
+        }
+        if !self.records.is_empty() {
+            writeln!(f, "host calls and state changes:")?;
+        }
+        for record in &self.records {
+            writeln!(f, "{record}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CallRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indent = "  ".repeat(self.depth);
+        let rolled_back = if self.rolled_back {
+            " (rolled back)"
+        } else {
+            ""
+        };
+        write!(f, "{indent}{}.{}: ", self.address, self.entrypoint)?;
+        match &self.kind {
+            CallRecordKind::Interrupt(interrupt) => write!(f, "{interrupt:?}")?,
+            CallRecordKind::Resumed(HostInterruptResponse::Success {
+                data,
+            }) => match data {
+                Some(data) => write!(f, "resumed with {}", hex(data))?,
+                None => write!(f, "resumed")?,
+            },
+            CallRecordKind::Resumed(HostInterruptResponse::Reject {
+                code,
+                data,
+            }) => write!(f, "resumed after a reject with code {code} and {}", hex(data))?,
+            CallRecordKind::Resumed(HostInterruptResponse::Failure(reason)) => {
+                write!(f, "resumed after a failure: {reason}")?
+            }
+            CallRecordKind::StateChanges(changes) => {
+                write!(f, "state changes{rolled_back}")?;
+                for StateChange {
+                    key,
+                    old,
+                    new,
+                } in changes
+                {
+                    let value = |value: &Option<Vec<u8>>| match value {
+                        Some(value) => hex(value),
+                        None => "(none)".to_string(),
+                    };
+                    write!(f, "\n{indent}  {}: {} -> {}", hex(key), value(old), value(new))?;
+                }
+                return Ok(());
+            }
+        }
+        write!(f, "{rolled_back}")
+    }
+}
+
+/// Format bytes as hex.
+fn hex(bytes: &[u8]) -> String {
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!("0x{hex}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that created, modified and deleted keys are found.
+    #[test]
+    fn test_state_changes_between() {
+        let before = BTreeMap::from([(vec![0], vec![1]), (vec![1], vec![2]), (vec![2], vec![3])]);
+        let after = BTreeMap::from([(vec![0], vec![1]), (vec![1], vec![4]), (vec![3], vec![5])]);
+        assert_eq!(state_changes_between(&before, &after), [
+            StateChange {
+                key: vec![1],
+                old: Some(vec![2]),
+                new: Some(vec![4]),
+            },
+            StateChange {
+                key: vec![2],
+                old: Some(vec![3]),
+                new: None,
+            },
+            StateChange {
+                key: vec![3],
+                old: None,
+                new: Some(vec![5]),
+            },
+        ]);
+    }
+}
@@ -1,21 +1,25 @@
+use crate::fuzz::display_fuzz_trace;
 use concordium_rust_sdk as sdk;
 use concordium_rust_sdk::{
     base::{
-        base::{AccountAddressEq, Energy},
-        common::types::{CredentialIndex, KeyIndex, Signature},
+        base::{AccountAddressEq, Energy, Nonce},
+        common::types::{CredentialIndex, KeyIndex, Signature, TransactionTime},
         constants::ED25519_SIGNATURE_LENGTH,
         contracts_common::{
-            self, AccountAddress, AccountBalance, Address, Amount, ContractAddress, Deserial,
-            EntrypointName, ExchangeRate, ModuleReference, OwnedContractName, OwnedEntrypointName,
-            OwnedPolicy, ParseResult, SlotTime, Timestamp,
+            self, schema::VersionedSchemaError, AccountAddress, AccountBalance, Address, Amount,
+            ContractAddress, Deserial, Duration, EntrypointName, ExchangeRate, ExchangeRates,
+            ModuleReference, OwnedContractName, OwnedEntrypointName, OwnedPolicy, ParseError,
+            ParseResult, SlotTime, Timestamp,
         },
-        hashes::BlockHash,
-        id::types::SchemeId,
+        hashes::{BlockHash, TransactionHash},
+        id::types::{AccountKeys, SchemeId},
         smart_contracts::{
             ContractEvent, ContractTraceElement, InstanceUpdatedEvent, OwnedParameter,
-            OwnedReceiveName, WasmVersion,
+            OwnedReceiveName, WasmModule, WasmVersion,
+        },
+        transactions::{
+            AccountAccessStructure, InitContractPayload, Payload, UpdateContractPayload,
         },
-        transactions::AccountAccessStructure,
     },
     smart_contracts::engine::{
         v1::{
@@ -24,10 +28,13 @@ use concordium_rust_sdk::{
         wasm::artifact,
         InterpreterEnergy,
     },
+    types::smart_contracts::{ContractContext, InvokeContractResult},
 };
 use std::{
+    cell::RefCell,
     collections::{BTreeMap, BTreeSet},
     path::PathBuf,
+    rc::Rc,
     sync::Arc,
 };
 use thiserror::Error;
@@ -36,31 +43,38 @@ use thiserror::Error;
 #[derive(Debug, Clone)]
 pub struct ContractModule {
     /// Size of the module in bytes. Used for cost accounting.
-    pub size:     u64,
+    pub size:                u64,
     /// The runnable module.
     pub artifact: Arc<artifact::Artifact<v1::ProcessedImports, artifact::CompiledFunction>>,
+    /// The module source that the artifact was created from.
+    pub(crate) source:       Arc<WasmModule>,
+    /// Whether debug output was allowed when the artifact was created.
+    pub(crate) enable_debug: bool,
 }
 
 /// The chain parameters.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct ChainParameters {
     /// The block time viewable inside the smart contracts.
     /// Defaults to `0`.
-    pub(crate) block_time:         SlotTime,
+    pub(crate) block_time:          SlotTime,
     /// MicroCCD per Euro ratio.
-    pub(crate) micro_ccd_per_euro: ExchangeRate,
+    pub(crate) micro_ccd_per_euro:  ExchangeRate,
     /// Euro per Energy ratio.
-    pub(crate) euro_per_energy:    ExchangeRate,
+    pub(crate) euro_per_energy:     ExchangeRate,
+    /// The time it takes for a reduction or removal of stake to take effect.
+    /// Defaults to 21 days.
+    pub(crate) stake_cooldown:      Duration,
+    /// Whether contract inits, updates and module deployments must be signed
+    /// transactions. Defaults to `false`.
+    pub(crate) strict_transactions: bool,
 }
 
-/// The connection and runtime needed for communicating with an external node.
+/// The connection needed for communicating with an external node.
 #[derive(Debug)]
 pub(crate) struct ExternalNodeConnection {
-    /// An instantiated v2 Client from the Rust SDK. Used for communicating with
-    /// a node.
-    pub(crate) client:      concordium_rust_sdk::v2::Client,
-    /// A Tokio runtime used to execute the async methods of the `client`.
-    pub(crate) runtime:     tokio::runtime::Runtime,
+    /// The backend used for the queries, e.g., a [`GrpcNode`].
+    pub(crate) backend:     Box<dyn ExternalNodeBackend>,
     /// The block used for queries.
     pub(crate) query_block: BlockHash,
     /// External accounts that are verified to exist in the `query_block`.
@@ -87,6 +101,154 @@ pub struct Chain {
     pub(crate) next_contract_index: u64,
     /// An optional connection to an external node.
     pub(crate) external_node_connection: Option<ExternalNodeConnection>,
+    /// The instrumented modules and the counts of their probes, if coverage
+    /// is enabled with [`Chain::coverage_enable`].
+    pub(crate) coverage: Option<RefCell<crate::coverage::CoverageState>>,
+    /// The state of the fork mode, if the chain is a fork of the external node
+    /// configured with [`ChainBuilder::fork`].
+    pub(crate) fork: Option<Fork>,
+    /// The records of the current invocation, while it is traced with
+    /// [`Chain::contract_update_traced`] or [`Chain::contract_invoke_traced`].
+    pub(crate) call_records: RefCell<Option<Vec<CallRecord>>>,
+}
+
+/// The state of a [`Chain`] in fork mode, where accounts, contracts and
+/// modules are loaded from the external node on first access.
+#[derive(Debug, Default)]
+pub(crate) struct Fork {
+    /// The items that were looked up during an invocation, but did not exist
+    /// on the chain.
+    pub(crate) missing:                 RefCell<ForkItems>,
+    /// The items that are known not to exist on the external node, so they
+    /// are not queried again.
+    pub(crate) absent:                  ForkItems,
+    /// The number of contracts on the external node, once it has been
+    /// queried. Local contracts get indices after these.
+    pub(crate) external_contract_count: Option<u64>,
+}
+
+/// Accounts, contracts and modules, used for tracking the items to load in
+/// fork mode.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ForkItems {
+    pub(crate) accounts:  BTreeSet<AccountAddress>,
+    pub(crate) contracts: BTreeSet<ContractAddress>,
+    pub(crate) modules:   BTreeSet<ModuleReference>,
+}
+
+/// Code coverage of the deployed modules of a [`Chain`], recorded when
+/// enabled with [`Chain::coverage_enable`].
+///
+/// The coverage is recorded by instrumenting the Wasm code of the modules, so
+/// it includes every function of a module, not only the init functions and
+/// entrypoints. The counts can be mapped to source lines when the modules are
+/// built with DWARF debug information, and be written in the LCOV format with
+/// [`Coverage::write_lcov`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    /// The coverage of each module.
+    pub modules: BTreeMap<ModuleReference, ModuleCoverage>,
+}
+
+/// The coverage of a module.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleCoverage {
+    /// The coverage of the functions defined in the module, ordered by their
+    /// index.
+    pub functions: Vec<FunctionCoverage>,
+    /// The reason the module could not be instrumented, if it could not. The
+    /// module is then executed without instrumentation and has no functions.
+    pub error:     Option<CoverageError>,
+}
+
+/// The coverage of a function in a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    /// The index of the function in the module, including imported functions.
+    pub index:    u32,
+    /// The demangled name of the function from the name section, the name it
+    /// is exported with, or `func[<index>]` if it has neither.
+    pub name:     String,
+    /// The number of calls of the function.
+    pub count:    u64,
+    /// The source location of the start of the function, if known.
+    pub location: Option<SourceLocation>,
+    /// The basic blocks of the function, except the one at the start of the
+    /// function.
+    pub blocks:   Vec<BlockCoverage>,
+    /// The branches of the function, i.e., the `if` and `br_if`
+    /// instructions.
+    pub branches: Vec<BranchCoverage>,
+}
+
+/// The coverage of a basic block in a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockCoverage {
+    /// The offset of the first instruction of the block in the code section.
+    pub offset:   u32,
+    /// The number of executions of the block.
+    pub count:    u64,
+    /// The source location of the block, if known.
+    pub location: Option<SourceLocation>,
+}
+
+/// The coverage of a conditional branch in a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchCoverage {
+    /// The offset of the branch instruction in the code section.
+    pub offset:    u32,
+    /// The number of times the `then` branch of an `if` was executed, or a
+    /// `br_if` branched.
+    pub taken:     u64,
+    /// The number of times the `else` branch of an `if` was executed, or a
+    /// `br_if` did not branch.
+    pub not_taken: u64,
+    /// The source location of the branch, if known.
+    pub location:  Option<SourceLocation>,
+}
+
+/// A line in a source file, from the DWARF debug information of a module.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SourceLocation {
+    /// The path of the source file.
+    pub file: PathBuf,
+    /// The line number, starting from `1`.
+    pub line: u32,
+}
+
+/// The reason a module could not be instrumented for recording coverage.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum CoverageError {
+    /// The module could not be parsed.
+    #[error("The module is malformed: {0}")]
+    Malformed(&'static str),
+    /// The module uses an instruction that the instrumentation does not
+    /// support, e.g., from a proposal that is not part of Wasm 1.0. The
+    /// offset is relative to the start of the code section.
+    #[error("The instruction with opcode {opcode:#04x} at offset {offset} is not supported.")]
+    UnsupportedInstruction {
+        opcode: u8,
+        offset: usize,
+    },
+    /// The instrumented module could not be validated and compiled.
+    #[error("The instrumented module is invalid: {0}")]
+    Instantiation(String),
+}
+
+/// A snapshot of the state of a [`Chain`].
+///
+/// Created with [`Chain::snapshot`] and used with [`Chain::restore`] to roll
+/// the chain back to the point where the snapshot was taken.
+///
+/// Taking a snapshot is cheap, since the contract states and module artifacts
+/// are shared with the chain rather than copied.
+#[derive(Debug, Clone)]
+pub struct ChainSnapshot {
+    pub(crate) parameters:          ChainParameters,
+    pub(crate) accounts:            BTreeMap<AccountAddressEq, Account>,
+    pub(crate) modules:             BTreeMap<ModuleReference, ContractModule>,
+    pub(crate) contracts:           BTreeMap<ContractAddress, Contract>,
+    pub(crate) next_contract_index: u64,
 }
 
 /// A builder for the [`Chain`].
@@ -94,6 +256,8 @@ pub struct Chain {
 pub struct ChainBuilder {
     /// The configured endpoint for an external node connection.
     pub(crate) external_node_endpoint: Option<sdk::v2::Endpoint>,
+    /// The configured backend for an external node connection.
+    pub(crate) external_node_backend: Option<Box<dyn ExternalNodeBackend>>,
     /// The block hash to be used for external queries. If this is not set, then
     /// the last final block hash is used instead.
     pub(crate) external_query_block: Option<BlockHash>,
@@ -111,6 +275,22 @@ pub struct ChainBuilder {
     pub(crate) block_time: Option<Timestamp>,
     /// Whether the block time should be set via the external node.
     pub(crate) block_time_from_external: bool,
+    /// Whether the chain should be a fork of the external node.
+    pub(crate) fork: bool,
+    /// Whether the chain should only accept signed transactions.
+    pub(crate) strict_transactions: bool,
+}
+
+/// A block under construction, used for executing multiple transactions in
+/// one block with [`Chain::block`].
+///
+/// All transactions in the block observe the same block time.
+#[derive(Debug)]
+pub struct BlockBuilder<'a> {
+    /// The chain that the transactions are executed on.
+    pub(crate) chain:    &'a mut Chain,
+    /// The outcomes of the transactions executed so far, in order.
+    pub(crate) outcomes: Vec<BlockItemOutcome>,
 }
 
 /// A smart contract instance.
@@ -136,10 +316,112 @@ pub struct Account {
     pub address: AccountAddress,
     /// The account balance.
     pub balance: AccountBalance,
-    /// Account policy.
-    pub policy:  OwnedPolicy,
+    /// The policy of the first credential of the account.
+    pub policy: OwnedPolicy,
     /// Account's public keys.
-    pub keys:    AccountAccessStructure,
+    pub keys: AccountAccessStructure,
+    /// Amounts locked due to scheduled transfers, keyed by their release
+    /// time. The sum of the amounts is included in the locked balance.
+    pub(crate) release_schedule: BTreeMap<Timestamp, Amount>,
+    /// Information about the stake of the account, if it is a baker or a
+    /// delegator. The staked amount itself is part of the `balance`.
+    pub(crate) stake: Option<AccountStake>,
+    /// The key pairs of the account, if it was created with them. The public
+    /// keys of these are the `keys`.
+    pub(crate) signing_keys: Option<AccountKeys>,
+    /// The nonce of the next transaction from the account.
+    pub(crate) nonce: Nonce,
+    /// The policies of the credentials of the account other than the first
+    /// one, whose policy is `policy`.
+    pub(crate) additional_policies: Vec<OwnedPolicy>,
+}
+
+/// A builder for the [`OwnedPolicy`] of an identity credential, i.e., the
+/// identity provider, validity period and revealed attributes that contracts
+/// see with `ctx.policies()`.
+///
+/// The attributes are set with typed methods, e.g.,
+/// [`nationality`](Self::nationality), and validated by
+/// [`build`](Self::build).
+#[derive(Debug, Clone)]
+pub struct PolicyBuilder {
+    /// The identity provider that issued the identity.
+    pub(crate) identity_provider: u32,
+    /// The time the credential was created.
+    pub(crate) created_at:        Timestamp,
+    /// The time the credential is valid to.
+    pub(crate) valid_to:          Timestamp,
+    /// The attribute values, keyed by their tags.
+    pub(crate) attributes:        BTreeMap<u8, Vec<u8>>,
+}
+
+/// The reasons for why a [`PolicyBuilder`] could not build a policy.
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum PolicyBuilderError {
+    /// An attribute value is longer than the 31 bytes allowed.
+    #[error("The value of attribute {tag} is {length} bytes, but at most 31 bytes are allowed.")]
+    AttributeTooLong {
+        /// The tag of the attribute.
+        tag:    u8,
+        /// The length of the value.
+        length: usize,
+    },
+    /// The country of residence or nationality is not a two letter country
+    /// code, e.g., `DK`.
+    #[error("The value of attribute {tag} is not a two letter country code: {value:?}")]
+    InvalidCountryCode {
+        /// The tag of the attribute.
+        tag:   u8,
+        /// The value of the attribute.
+        value: Vec<u8>,
+    },
+    /// The date of birth is not a valid date in the format `YYYYMMDD`.
+    #[error("The date of birth is not a valid date in the format YYYYMMDD: {0:?}")]
+    InvalidDateOfBirth(Vec<u8>),
+    /// The credential is valid to a time before it was created.
+    #[error(
+        "The credential is valid to {valid_to}, which is before it was created at {created_at}."
+    )]
+    InvalidValidity {
+        /// The time the credential was created.
+        created_at: Timestamp,
+        /// The time the credential is valid to.
+        valid_to:   Timestamp,
+    },
+}
+
+/// Information about the stake of an account that is a baker or a delegator.
+///
+/// See [`Chain::account_add_baker`] and [`Chain::account_add_delegation`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountStake {
+    /// Whether the account is a baker or a delegator.
+    pub kind:             StakeKind,
+    /// Whether rewards paid with [`Chain::account_reward`] are added to the
+    /// stake.
+    pub restake_earnings: bool,
+    /// A pending reduction or removal of the stake, which takes effect after
+    /// the cooldown period.
+    pub pending_change:   Option<PendingStakeChange>,
+}
+
+/// The kind of stake an account has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StakeKind {
+    /// The account is a baker.
+    Baker,
+    /// The account delegates its stake.
+    Delegator,
+}
+
+/// A pending reduction of the stake of an account. A reduction to zero
+/// removes the stake entirely.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PendingStakeChange {
+    /// The block time at which the change takes effect.
+    pub effective_time: Timestamp,
+    /// The stake after the change.
+    pub new_stake:      Amount,
 }
 
 /// A signature with account's keys.
@@ -165,6 +447,31 @@ impl From<BTreeMap<CredentialIndex, BTreeMap<KeyIndex, Signature>>> for AccountS
 impl AccountSignatures {
     /// Return the number of signatures contained in the structure.
     pub fn num_signatures(&self) -> u32 { self.sigs.values().map(|v| v.len() as u32).sum() }
+
+    /// Sign a message with all the keys.
+    ///
+    /// The signatures can be checked by contracts with
+    /// `check_account_signature`, which checks them on the message as is.
+    pub fn sign(keys: &AccountKeys, message: &[u8]) -> Self { Self::from(keys.sign_data(message)) }
+
+    /// Sign a message with some of the keys, given by their credential and
+    /// key indices, e.g., for testing signatures below the thresholds.
+    ///
+    /// Indices of keys that do not exist are ignored.
+    pub fn sign_with(
+        keys: &AccountKeys,
+        message: &[u8],
+        indices: &[(CredentialIndex, KeyIndex)],
+    ) -> Self {
+        let mut keys = keys.clone();
+        for (credential_index, credential) in keys.keys.iter_mut() {
+            credential
+                .keys
+                .retain(|key_index, _| indices.contains(&(*credential_index, *key_index)));
+        }
+        keys.keys.retain(|_, credential| !credential.keys.is_empty());
+        Self::sign(&keys, message)
+    }
 }
 
 impl contracts_common::Serial for AccountSignatures {
@@ -233,6 +540,86 @@ impl contracts_common::Deserial for AccountSignatures {
     }
 }
 
+/// A transaction with an explicit nonce and expiry time, which is submitted
+/// with [`Chain::submit_transaction`]. Unlike an
+/// [`AccountTransaction`](sdk::base::transactions::AccountTransaction), it is
+/// not signed.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    /// The signer with a number of keys, which affects the cost.
+    pub signer:          Signer,
+    /// The sender account. For contract updates, it is both the invoker and
+    /// the sender.
+    pub sender:          AccountAddress,
+    /// The nonce, which must be the next nonce of the sender.
+    pub nonce:           Nonce,
+    /// The expiry time, which must not be before the block time.
+    pub expiry:          TransactionTime,
+    /// The energy reserved for contract inits and updates. It is not used for
+    /// module deployments and transfers, since their cost is computed.
+    pub energy_reserved: Energy,
+    /// The payload. Module deployments, contract inits and updates, and
+    /// transfers, also with a memo or a schedule, are supported.
+    pub payload:         Payload,
+}
+
+/// The reasons for why a transaction is not accepted by the [`Chain`].
+/// These correspond to the reasons for why the node does not accept a
+/// transaction, so no fee is charged.
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum TransactionVerificationError {
+    /// The chain is in strict mode, see [`Chain::set_strict_transactions`],
+    /// and the transaction was not signed.
+    #[error("Only signed transactions are accepted.")]
+    UnsignedTransaction,
+    /// The expiry time of the transaction is before the block time.
+    #[error("The transaction expired at {expiry}, which is before the block time {block_time}.")]
+    Expired {
+        /// The expiry time of the transaction.
+        expiry:     Timestamp,
+        /// The block time of the chain.
+        block_time: Timestamp,
+    },
+    /// The sender account does not exist.
+    #[error("The sender account {0} does not exist.")]
+    SenderDoesNotExist(AccountAddress),
+    /// The nonce has already been used by the sender.
+    #[error("The nonce {nonce} has already been used, the next nonce is {next_nonce}.")]
+    DuplicateNonce {
+        /// The nonce of the transaction.
+        nonce:      Nonce,
+        /// The next nonce of the sender.
+        next_nonce: Nonce,
+    },
+    /// The nonce is larger than the next nonce of the sender.
+    #[error("The nonce {nonce} is too large, the next nonce is {next_nonce}.")]
+    NonceTooLarge {
+        /// The nonce of the transaction.
+        nonce:      Nonce,
+        /// The next nonce of the sender.
+        next_nonce: Nonce,
+    },
+    /// The signatures do not satisfy the thresholds of the sender's keys.
+    #[error("The signatures are invalid or below the thresholds of the sender account.")]
+    IncorrectSignature,
+    /// The payload could not be decoded.
+    #[error("The payload could not be decoded.")]
+    InvalidPayload,
+    /// The payload is not of the expected type, e.g., a contract update was
+    /// given to [`Chain::contract_init_signed`], or it is not supported.
+    #[error("The payload is of an unexpected type.")]
+    UnexpectedPayload,
+}
+
+/// The error returned when signing with an [`Account`] that was not created
+/// with signing keys, e.g., with [`Account::new_from_seed`].
+#[derive(Debug, Error)]
+#[error("The account {address} has no signing keys.")]
+pub struct MissingSigningKeysError {
+    /// The address of the account.
+    pub address: AccountAddress,
+}
+
 /// A signer with a number of keys, the amount of which affects the cost of
 /// transactions.
 #[derive(Copy, Clone, Debug)]
@@ -299,6 +686,10 @@ pub enum ModuleDeployErrorKind {
     /// The module version is not supported.
     #[error("Wasm version {0} is not supported")]
     UnsupportedModuleVersion(WasmVersion),
+    /// The transaction was not accepted, because it is not signed correctly
+    /// or the chain only accepts signed transactions.
+    #[error("The transaction was not accepted: {0}")]
+    Verification(#[from] TransactionVerificationError),
 }
 
 /// An error that can occur while loading a smart contract module.
@@ -336,6 +727,871 @@ pub struct ModuleReadError(#[from] pub(crate) anyhow::Error);
 #[error("The module is invalid to: {0}")]
 pub struct ModuleInvalidError(#[from] pub(crate) anyhow::Error);
 
+/// An error that can occur while saving a [`Chain`] to a file with
+/// [`Chain::save_to`].
+#[derive(Debug, Error)]
+#[error("Could not save the chain to the file '{path}' due to: {kind}")]
+pub struct ChainSaveError {
+    /// The file the chain was saved to.
+    pub path: PathBuf,
+    /// The reason why saving the chain failed.
+    pub kind: ChainSaveErrorKind,
+}
+
+/// The specific reason why saving a [`Chain`] failed.
+#[derive(Debug, Error)]
+pub enum ChainSaveErrorKind {
+    /// Failed to write to the file.
+    #[error("Could not write to the file due to: {0}")]
+    Io(#[from] std::io::Error),
+    /// Failed to serialize the state of a contract.
+    #[error("Could not serialize the contract state due to: {0}")]
+    State(anyhow::Error),
+}
+
+/// An error that can occur while loading a [`Chain`] from a file with
+/// [`Chain::load_from`].
+#[derive(Debug, Error)]
+#[error("Could not load the chain from the file '{path}' due to: {kind}")]
+pub struct ChainLoadError {
+    /// The file the chain was loaded from.
+    pub path: PathBuf,
+    /// The reason why loading the chain failed.
+    pub kind: ChainLoadErrorKind,
+}
+
+/// The specific reason why loading a [`Chain`] failed.
+#[derive(Debug, Error)]
+pub enum ChainLoadErrorKind {
+    /// Failed to read the file.
+    #[error("Could not read the file due to: {0}")]
+    Io(#[from] std::io::Error),
+    /// The file does not start with the expected magic bytes.
+    #[error("The file is not a chain file")]
+    NotAChainFile,
+    /// The file format version is not supported by this version of the
+    /// library.
+    #[error("The chain file format version {0} is not supported")]
+    UnsupportedVersion(u32),
+    /// The file contents could not be parsed.
+    #[error("The chain file is malformed")]
+    Parse(ParseError),
+    /// The file contains data after the end of the chain.
+    #[error("The chain file has trailing data")]
+    TrailingData,
+    /// The stored exchange rates are out of the allowed range.
+    #[error("The stored exchange rates are invalid")]
+    InvalidExchangeRates,
+    /// A stored module could not be turned into an artifact.
+    #[error("The stored module {0} is invalid: {1}")]
+    InvalidModule(ModuleReference, ModuleInvalidError),
+    /// The state of a contract could not be deserialized.
+    #[error("Could not deserialize the contract state due to: {0}")]
+    State(anyhow::Error),
+}
+
+/// Represents a successful transfer between two accounts.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AccountTransferSuccess {
+    /// The energy used for the transfer.
+    pub energy_used:     Energy,
+    /// Cost of transaction.
+    pub transaction_fee: Amount,
+}
+
+/// An error that occurred during [`Chain::account_transfer`] or one of its
+/// variants.
+#[derive(Debug, Error)]
+#[error(
+    "Account transfer failed after consuming {energy_used}NRG ({transaction_fee} microCCD) with \
+     error {kind}."
+)]
+pub struct AccountTransferError {
+    /// The energy used.
+    pub energy_used:     Energy,
+    /// The transaction fee. This is the amount charged to the `sender`
+    /// account.
+    pub transaction_fee: Amount,
+    /// The specific reason for why the transfer failed.
+    pub kind:            AccountTransferErrorKind,
+}
+
+/// The specific kind of error that occurred during [`Chain::account_transfer`]
+/// or one of its variants.
+#[derive(Debug, Error)]
+pub enum AccountTransferErrorKind {
+    /// The sender account does not exist.
+    #[error("Sender account does not exist: {0}")]
+    SenderDoesNotExist(AccountDoesNotExist),
+    /// The sender does not have sufficient funds to pay for the transaction.
+    #[error("Sender does not have sufficient funds to pay for the transaction.")]
+    InsufficientFunds,
+    /// The receiver account does not exist.
+    #[error("Receiver account does not exist: {0}")]
+    ReceiverDoesNotExist(AccountDoesNotExist),
+    /// The sender does not have sufficient available funds for the transfer
+    /// after paying the transaction fee.
+    #[error("The amount {amount} exceeds the available balance of the sender.")]
+    AmountTooLarge {
+        /// The amount that was attempted to be transferred.
+        amount: Amount,
+    },
+    /// The release schedule of a scheduled transfer is invalid.
+    #[error("The release schedule is invalid: {0}")]
+    InvalidSchedule(#[from] ReleaseScheduleError),
+}
+
+/// The reasons why the release schedule of a scheduled transfer is invalid.
+#[derive(Debug, Error, PartialEq, Eq, Clone, Copy)]
+pub enum ReleaseScheduleError {
+    /// The schedule has no releases.
+    #[error("The schedule has no releases.")]
+    Empty,
+    /// The schedule has more than 255 releases.
+    #[error("The schedule has more than 255 releases.")]
+    TooManyReleases,
+    /// The release times are not strictly increasing.
+    #[error("The release times are not strictly increasing.")]
+    NonIncreasing,
+    /// One of the releases has a zero amount.
+    #[error("One of the releases has a zero amount.")]
+    ZeroAmount,
+    /// The first release time is before the current block time.
+    #[error("The first release time is before the current block time.")]
+    FirstReleaseExpired,
+    /// The sender and receiver are the same account.
+    #[error("Scheduled transfers to oneself are not allowed.")]
+    SelfTransfer,
+    /// The sum of the amounts in the schedule overflows.
+    #[error("The sum of the amounts in the schedule overflows.")]
+    AmountOverflow,
+}
+
+/// An error that occurred while changing the stake of an account.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum StakeError {
+    /// The account does not exist.
+    #[error("Account '{0}' does not exist.")]
+    AccountDoesNotExist(AccountAddress),
+    /// The account is already a baker or a delegator.
+    #[error("The account is already staking.")]
+    AlreadyStaking,
+    /// The account is neither a baker nor a delegator.
+    #[error("The account is not staking.")]
+    NotStaking,
+    /// The stake exceeds the total balance of the account.
+    #[error("The stake {stake} exceeds the total balance of the account.")]
+    InsufficientFunds {
+        /// The requested stake.
+        stake: Amount,
+    },
+    /// A stake of zero was requested when adding a baker or delegator.
+    #[error("The stake must be positive.")]
+    ZeroStake,
+    /// The account already has a pending stake change.
+    #[error("The account already has a pending stake change.")]
+    PendingChange,
+}
+
+/// A file with baseline energy measurements for named test cases, used for
+/// catching regressions in energy usage.
+///
+/// See [`EnergyBaseline::new`] and [`EnergyBaseline::check`].
+#[derive(Debug, Clone)]
+pub struct EnergyBaseline {
+    /// The path to the JSON file with the baseline.
+    pub(crate) path:              PathBuf,
+    /// The allowed increase over the baseline in percent.
+    pub(crate) tolerance_percent: u64,
+    /// Whether regressions only produce a warning.
+    pub(crate) warn_only:         bool,
+}
+
+/// An error that occurred in [`EnergyBaseline::check`].
+#[derive(Debug, Error)]
+#[error("Energy baseline check of '{name}' in '{path}' failed: {kind}")]
+pub struct EnergyBaselineError {
+    /// The baseline file.
+    pub path: PathBuf,
+    /// The name of the test case.
+    pub name: String,
+    /// The reason why the check failed.
+    pub kind: EnergyBaselineErrorKind,
+}
+
+/// The specific reason why an [`EnergyBaseline::check`] failed.
+#[derive(Debug, Error)]
+pub enum EnergyBaselineErrorKind {
+    /// The energy used exceeds the baseline by more than the tolerance.
+    #[error(
+        "Used {energy_used}NRG, which exceeds the baseline of {baseline}NRG by more than \
+         {tolerance_percent}%. If the increase is expected, rerun with the environment variable \
+         `CONCORDIUM_BLESS_ENERGY=1` to update the baseline."
+    )]
+    Regression {
+        /// The energy in the baseline.
+        baseline:          Energy,
+        /// The energy used in this run.
+        energy_used:       Energy,
+        /// The tolerance in percent.
+        tolerance_percent: u64,
+    },
+    /// Could not read or write the baseline file.
+    #[error("Could not access the baseline file due to: {0}")]
+    Io(#[from] std::io::Error),
+    /// The baseline file is not valid JSON.
+    #[error("The baseline file is invalid: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// A view of the state of a contract instance, which implements
+/// [`HasStateApi`](concordium_std::HasStateApi).
+///
+/// This allows working with the state using the `concordium-std` types used in
+/// the contract, such as `StateMap`, `StateSet` and `StateBox`.
+///
+/// The view is backed by the persistent state trie of the instance at the time
+/// it was created. Entries are read from the trie when they are looked up or
+/// iterated, so the cost of a lookup does not depend on the size of the state.
+///
+/// The views returned by [`Chain::contract_state`] and
+/// [`Chain::contract_state_api`] are read-only, meaning that creating, deleting
+/// and writing to entries fails. The view given to the closure in
+/// [`Chain::contract_state_modify`] can be modified.
+#[derive(Debug, Clone)]
+pub struct ContractStateApi {
+    /// The state of the instance when the view was created.
+    pub(crate) state:     trie::PersistentState,
+    /// The entries that have been looked up, created or deleted, ordered by
+    /// their keys. They take precedence over the entries in `state`, and
+    /// deleted entries have no data.
+    pub(crate) entries:   Rc<RefCell<BTreeMap<Vec<u8>, ContractStateEntryData>>>,
+    /// Whether modifications are disallowed.
+    pub(crate) read_only: bool,
+}
+
+/// The data of an entry in a [`ContractStateApi`]. It is shared between the
+/// state and the [`ContractStateEntry`] handles, and is `None` once the entry
+/// is deleted.
+pub(crate) type ContractStateEntryData = Rc<RefCell<Option<Vec<u8>>>>;
+
+/// An entry in a [`ContractStateApi`], which implements
+/// [`HasStateEntry`](concordium_std::HasStateEntry).
+#[derive(Debug)]
+pub struct ContractStateEntry {
+    /// The key of the entry.
+    pub(crate) key:       Vec<u8>,
+    /// The data of the entry.
+    pub(crate) data:      ContractStateEntryData,
+    /// The current position in the data.
+    pub(crate) position:  u32,
+    /// Whether modifications are disallowed.
+    pub(crate) read_only: bool,
+}
+
+/// An error that occurred when operating on a [`ContractStateEntry`].
+#[derive(Debug, Default, Error, Clone, Copy, PartialEq, Eq)]
+pub enum ContractStateEntryError {
+    /// The state cannot be modified.
+    #[default]
+    #[error("The contract state is read-only.")]
+    ReadOnly,
+    /// The entry has been deleted.
+    #[error("The entry has been deleted.")]
+    EntryDeleted,
+    /// Attempted to seek outside of the entry.
+    #[error("The offset is outside of the entry.")]
+    Offset,
+}
+
+/// The payload for [`Chain::contract_init_json`], where the parameter is given
+/// as JSON and encoded with the schema embedded in the module.
+#[derive(Debug, Clone)]
+pub struct InitContractJsonPayload {
+    /// Deposit this amount of CCD.
+    pub amount:    Amount,
+    /// Reference to the module from which to initialize the instance.
+    pub mod_ref:   ModuleReference,
+    /// Name of the contract in the module.
+    pub init_name: OwnedContractName,
+    /// The parameter as JSON.
+    pub param:     serde_json::Value,
+}
+
+/// The payload for [`Chain::contract_update_json`] and
+/// [`Chain::contract_invoke_json`], where the message is given as JSON and
+/// encoded with the schema embedded in the module of the contract.
+#[derive(Debug, Clone)]
+pub struct UpdateContractJsonPayload {
+    /// Send the given amount of CCD together with the message to the
+    /// contract instance.
+    pub amount:       Amount,
+    /// Address of the contract instance to invoke.
+    pub address:      ContractAddress,
+    /// Name of the method to invoke on the contract.
+    pub receive_name: OwnedReceiveName,
+    /// The message as JSON.
+    pub message:      serde_json::Value,
+}
+
+/// Represents a successful [`Chain::contract_init_json`].
+#[derive(Debug)]
+pub struct ContractInitJsonSuccess {
+    /// The result without JSON decoding.
+    pub success: ContractInitSuccess,
+    /// The events decoded with the event schema of the contract. An event is
+    /// `None` if there is no event schema or the event could not be decoded
+    /// with it.
+    pub events:  Vec<Option<serde_json::Value>>,
+}
+
+/// Represents a successful [`Chain::contract_update_json`] or
+/// [`Chain::contract_invoke_json`].
+#[derive(Debug)]
+pub struct ContractInvokeJsonSuccess {
+    /// The result without JSON decoding.
+    pub success:      ContractInvokeSuccess,
+    /// The return value decoded with the schema, or `None` if the schema does
+    /// not describe the return value.
+    ///
+    /// This is an error if the return value does not match the type in the
+    /// schema. The update has still been executed, and the raw return value
+    /// is available in [`ContractInvokeSuccess::return_value`].
+    pub return_value: Result<Option<serde_json::Value>, ReturnValueDecodeError>,
+    /// The events decoded with the event schema of the contract that emitted
+    /// them, paired with the address of that contract. An event is `None` if
+    /// there is no event schema or the event could not be decoded with it.
+    pub events:       Vec<(ContractAddress, Option<serde_json::Value>)>,
+}
+
+/// The return value of a successful [`Chain::contract_update_json`] or
+/// [`Chain::contract_invoke_json`] does not match the type in the schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Could not decode the return value with the schema.")]
+pub struct ReturnValueDecodeError;
+
+/// An error that occurred when reading the schema embedded in a module.
+#[derive(Debug, Error)]
+#[error("Could not get the embedded schema: {0}")]
+pub struct ModuleSchemaError(#[from] pub(crate) anyhow::Error);
+
+/// An error that occurred in [`Chain::contract_init_json`],
+/// [`Chain::contract_update_json`] or [`Chain::contract_invoke_json`].
+#[derive(Debug, Error)]
+pub enum ContractJsonError {
+    /// The module does not exist.
+    #[error("{0}")]
+    ModuleDoesNotExist(#[from] ModuleDoesNotExist),
+    /// The contract instance does not exist.
+    #[error("{0}")]
+    ContractDoesNotExist(#[from] ContractDoesNotExist),
+    /// The module has no embedded schema, or it is invalid.
+    #[error("{0}")]
+    Schema(#[from] ModuleSchemaError),
+    /// The schema does not describe the parameter, and it is not `null`.
+    #[error("The schema does not describe the parameter: {0:?}")]
+    MissingParameterType(VersionedSchemaError),
+    /// The parameter does not match the type in the schema.
+    #[error("Could not encode the parameter with the schema: {0}")]
+    Encode(String),
+    /// The initialization failed.
+    #[error("{error}")]
+    Init {
+        /// The error without JSON decoding.
+        error:         ContractInitError,
+        /// The reject reason decoded with the error schema, if the contract
+        /// rejected and the schema describes the error.
+        reject_reason: Option<serde_json::Value>,
+    },
+    /// The update or invocation failed.
+    #[error("{error}")]
+    Invoke {
+        /// The error without JSON decoding.
+        error:         ContractInvokeError,
+        /// The reject reason decoded with the error schema, if the contract
+        /// rejected and the schema describes the error.
+        reject_reason: Option<serde_json::Value>,
+    },
+}
+
+/// A declarative description of a flow of transactions and the expected
+/// outcomes, which can be loaded from a YAML or JSON file with
+/// [`Scenario::from_file`] and executed with [`Scenario::run`].
+///
+/// Accounts, modules and contract instances are referred to by names chosen
+/// in the scenario. Amounts are given in CCD as strings, e.g., `"10.5"`, and
+/// parameters, return values, events and reject reasons are given as JSON,
+/// which is converted with the schemas embedded in the modules.
+///
+/// ```yaml
+/// name: Vote for lunch
+/// accounts:
+///   alice:
+///     balance: "1000"
+/// modules:
+///   voting:
+///     path: concordium-out/module.wasm.v1
+///     sender: alice
+/// steps:
+///   - init:
+///       sender: alice
+///       module: voting
+///       contract: voting
+///       instance: election
+///       param:
+///         description: Lunch
+///         options: [Pizza, Sushi]
+///         end_time: "2030-01-01T00:00:00Z"
+///   - update:
+///       sender: alice
+///       instance: election
+///       entrypoint: vote
+///       param: 0
+///   - invoke:
+///       sender: alice
+///       instance: election
+///       entrypoint: getNumberOfVotes
+///       param: 0
+///       expect:
+///         returnValue: 1
+///   - tickTime: 3600000
+///   - expectBalances:
+///       instances:
+///         election: "0"
+/// ```
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Scenario {
+    /// The name of the scenario, used in error messages.
+    #[serde(default)]
+    pub name:            String,
+    /// The initial block time in milliseconds since the Unix epoch.
+    #[serde(default)]
+    pub block_time:      Option<u64>,
+    /// The accounts to create, by name.
+    #[serde(default)]
+    pub accounts:        BTreeMap<String, ScenarioAccount>,
+    /// The modules to load and deploy, by name.
+    #[serde(default)]
+    pub modules:         BTreeMap<String, ScenarioModule>,
+    /// The steps to execute in order.
+    #[serde(default)]
+    pub steps:           Vec<ScenarioStep>,
+    /// The directory that module paths are relative to.
+    #[serde(skip)]
+    pub(crate) base_dir: PathBuf,
+}
+
+/// An account created in a [`Scenario`].
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ScenarioAccount {
+    /// The address in base58. If omitted, the address is derived from the
+    /// name of the account.
+    #[serde(default)]
+    pub address: Option<String>,
+    /// The balance in CCD.
+    pub balance: String,
+}
+
+/// A module loaded with [`module_load_v1`](crate::module_load_v1) and
+/// deployed in a [`Scenario`].
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ScenarioModule {
+    /// The path to the module file, relative to the scenario file.
+    pub path:   PathBuf,
+    /// The name of the account that deploys the module.
+    pub sender: String,
+}
+
+/// A step in a [`Scenario`].
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScenarioStep {
+    /// Initialize a contract instance.
+    Init(ScenarioInit),
+    /// Update a contract instance.
+    Update(ScenarioUpdate),
+    /// Invoke a contract instance without persisting the changes.
+    Invoke(ScenarioUpdate),
+    /// Transfer CCD between two accounts.
+    Transfer(ScenarioTransfer),
+    /// Advance the block time by the given number of milliseconds.
+    TickTime(u64),
+    /// Check the balances of accounts and contract instances.
+    ExpectBalances(ScenarioBalances),
+}
+
+/// A [`ScenarioStep::Init`].
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ScenarioInit {
+    /// The name of the sender account.
+    pub sender:   String,
+    /// The name of the module.
+    pub module:   String,
+    /// The name of the contract in the module, without the `init_` prefix.
+    pub contract: String,
+    /// The name used for referring to the new instance in later steps.
+    pub instance: String,
+    /// The parameter as JSON.
+    #[serde(default)]
+    pub param:    serde_json::Value,
+    /// The amount in CCD to send to the contract.
+    #[serde(default)]
+    pub amount:   Option<String>,
+    /// The maximum energy to use.
+    #[serde(default)]
+    pub energy:   Option<u64>,
+    /// The expected outcome. By default, the step is expected to succeed.
+    #[serde(default)]
+    pub expect:   ScenarioExpectation,
+}
+
+/// A [`ScenarioStep::Update`] or [`ScenarioStep::Invoke`].
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ScenarioUpdate {
+    /// The name of the invoker account, which is also the sender.
+    pub sender:     String,
+    /// The name of the contract instance.
+    pub instance:   String,
+    /// The name of the entrypoint.
+    pub entrypoint: String,
+    /// The parameter as JSON.
+    #[serde(default)]
+    pub param:      serde_json::Value,
+    /// The amount in CCD to send to the contract.
+    #[serde(default)]
+    pub amount:     Option<String>,
+    /// The maximum energy to use.
+    #[serde(default)]
+    pub energy:     Option<u64>,
+    /// The expected outcome. By default, the step is expected to succeed.
+    #[serde(default)]
+    pub expect:     ScenarioExpectation,
+}
+
+/// A [`ScenarioStep::Transfer`].
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ScenarioTransfer {
+    /// The name of the sender account.
+    pub from:   String,
+    /// The name of the receiver account.
+    pub to:     String,
+    /// The amount in CCD.
+    pub amount: String,
+}
+
+/// A [`ScenarioStep::ExpectBalances`].
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ScenarioBalances {
+    /// The expected total balances in CCD of accounts, by name.
+    #[serde(default)]
+    pub accounts:  BTreeMap<String, String>,
+    /// The expected balances in CCD of contract instances, by name.
+    #[serde(default)]
+    pub instances: BTreeMap<String, String>,
+}
+
+/// The expected outcome of a contract step in a [`Scenario`]. Only the
+/// fields that are present are checked.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ScenarioExpectation {
+    /// The expected events decoded to JSON. The step fails if an event cannot
+    /// be decoded with the event schema of the contract that logged it.
+    #[serde(default)]
+    pub events:        Option<Vec<serde_json::Value>>,
+    /// The expected return value decoded to JSON.
+    #[serde(default)]
+    pub return_value:  Option<serde_json::Value>,
+    /// The expected reject code. If present, the step is expected to fail.
+    #[serde(default)]
+    pub reject_code:   Option<i32>,
+    /// The expected reject reason decoded to JSON. If present, the step is
+    /// expected to fail.
+    #[serde(default)]
+    pub reject_reason: Option<serde_json::Value>,
+}
+
+/// An error that occurred in [`Scenario::from_file`].
+#[derive(Debug, Error)]
+#[error("Could not load the scenario file '{path}' due to: {kind}")]
+pub struct ScenarioLoadError {
+    /// The scenario file.
+    pub path: PathBuf,
+    /// The reason for why the file could not be loaded.
+    pub kind: ScenarioLoadErrorKind,
+}
+
+/// The specific reason why [`Scenario::from_file`] failed.
+#[derive(Debug, Error)]
+pub enum ScenarioLoadErrorKind {
+    /// The file could not be read.
+    #[error("Could not read the file due to: {0}")]
+    Io(#[from] std::io::Error),
+    /// The file is not a valid JSON scenario.
+    #[error("The JSON scenario is invalid: {0}")]
+    Json(#[from] serde_json::Error),
+    /// The file is not a valid YAML scenario.
+    #[error("The YAML scenario is invalid: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+}
+
+/// An error that occurred in [`Scenario::run`].
+#[derive(Debug, Error)]
+#[error("Scenario '{scenario}' failed {location}: {kind}")]
+pub struct ScenarioError {
+    /// The name of the scenario.
+    pub scenario: String,
+    /// Where the scenario failed, e.g., `at step 2 (update)`.
+    pub location: String,
+    /// The reason for why the scenario failed.
+    pub kind:     ScenarioErrorKind,
+}
+
+/// The specific reason why [`Scenario::run`] failed.
+#[derive(Debug, Error)]
+pub enum ScenarioErrorKind {
+    /// An account name is not defined in the scenario.
+    #[error("The account '{0}' is not defined.")]
+    UnknownAccount(String),
+    /// A module name is not defined in the scenario.
+    #[error("The module '{0}' is not defined.")]
+    UnknownModule(String),
+    /// An instance name has not been initialized in an earlier step.
+    #[error("The instance '{0}' has not been initialized.")]
+    UnknownInstance(String),
+    /// An amount is not a valid CCD amount.
+    #[error("The amount '{0}' is not a valid CCD amount.")]
+    InvalidAmount(String),
+    /// An account address is not valid base58.
+    #[error("The account address '{0}' is invalid.")]
+    InvalidAddress(String),
+    /// A contract or entrypoint name is invalid.
+    #[error("The name '{0}' is not a valid contract or entrypoint name.")]
+    InvalidName(String),
+    /// A module could not be loaded.
+    #[error("{0}")]
+    ModuleLoad(#[from] ModuleLoadError),
+    /// A module could not be deployed.
+    #[error("{0}")]
+    ModuleDeploy(#[from] ModuleDeployError),
+    /// A contract step failed unexpectedly.
+    #[error("{0}")]
+    Contract(#[from] ContractJsonError),
+    /// The return value of a contract step could not be decoded with the
+    /// schema.
+    #[error("{0}")]
+    DecodeReturnValue(#[from] ReturnValueDecodeError),
+    /// An event of a contract step could not be decoded with the event
+    /// schema, or the contract has no event schema.
+    #[error("Event {index} could not be decoded with the event schema of the contract.")]
+    DecodeEvent {
+        /// The index of the event among the events of the step.
+        index: usize,
+    },
+    /// A transfer failed.
+    #[error("{0}")]
+    Transfer(#[from] AccountTransferError),
+    /// The block time overflowed.
+    #[error("{0}")]
+    BlockTime(#[from] BlockTimeOverflow),
+    /// A contract step succeeded, but was expected to fail.
+    #[error("The step succeeded, but was expected to fail.")]
+    UnexpectedSuccess,
+    /// The outcome differs from the expectation.
+    #[error("The {what} differs from the expectation (- expected, + actual):\n{diff}")]
+    Mismatch {
+        /// What was compared, e.g., `events`.
+        what: String,
+        /// A line diff of the expected and actual values as pretty-printed
+        /// JSON.
+        diff: String,
+    },
+}
+
+/// A stateful fuzzing harness, which executes random sequences of actions on
+/// a [`Chain`] and checks invariants over the chain after every action.
+///
+/// Failing sequences are shrunk to a minimal sequence that still breaks an
+/// invariant, which is reported with the seed that reproduces it.
+///
+/// See [`ChainFuzzer::new`] for an example.
+pub struct ChainFuzzer {
+    /// The chain that every run starts from.
+    pub(crate) chain:      Chain,
+    /// The actions to choose from.
+    pub(crate) actions:    Vec<FuzzAction>,
+    /// The invariants checked after every action.
+    pub(crate) invariants: Vec<FuzzInvariant>,
+    /// The seed of the first run.
+    pub(crate) seed:       u64,
+    /// The number of random sequences to try.
+    pub(crate) runs:       u64,
+    /// The maximum length of a sequence.
+    pub(crate) max_steps:  usize,
+}
+
+/// The random number generator given to the actions of a [`ChainFuzzer`].
+///
+/// It is the `StdRng` of version 0.8 of the `rand` crate, so values can be
+/// generated with the `rand::Rng` trait of that version.
+pub type FuzzRng = rand::rngs::StdRng;
+
+/// A named generator of [`FuzzStep`]s in a [`ChainFuzzer`].
+pub(crate) struct FuzzAction {
+    pub(crate) name:     String,
+    pub(crate) generate: Box<dyn Fn(&mut FuzzRng, &Chain) -> FuzzStep>,
+}
+
+/// A named invariant in a [`ChainFuzzer`].
+pub(crate) struct FuzzInvariant {
+    pub(crate) name:  String,
+    pub(crate) check: Box<dyn Fn(&Chain) -> Result<(), String>>,
+}
+
+/// A transaction or time tick generated by an action in a [`ChainFuzzer`].
+#[derive(Debug, Clone)]
+pub enum FuzzStep {
+    /// Update a contract with [`Chain::contract_update`].
+    Update {
+        /// The invoker account, which pays for the transaction.
+        invoker: AccountAddress,
+        /// The sender of the message.
+        sender:  Address,
+        /// The maximum energy to use.
+        energy:  Energy,
+        /// The contract, entrypoint, parameter and amount.
+        payload: UpdateContractPayload,
+    },
+    /// Transfer CCD with [`Chain::account_transfer`].
+    Transfer {
+        /// The sender account.
+        sender:   AccountAddress,
+        /// The receiver account.
+        receiver: AccountAddress,
+        /// The amount to transfer.
+        amount:   Amount,
+    },
+    /// Advance the block time with [`Chain::tick_block_time`].
+    TickTime(Duration),
+}
+
+/// A step in a failing sequence found by a [`ChainFuzzer`].
+#[derive(Debug, Clone)]
+pub struct FuzzTraceStep {
+    /// The name of the action that generated the step.
+    pub action:  String,
+    /// The step.
+    pub step:    FuzzStep,
+    /// A description of the outcome of the step, e.g., whether the
+    /// transaction failed.
+    pub outcome: String,
+}
+
+/// An invariant violation found by [`ChainFuzzer::run`].
+#[derive(Debug, Error)]
+#[error(
+    "Invariant '{invariant}' failed: {message}\nReproduce with seed {seed}. Shrunk sequence of \
+     {} steps:\n{}",
+    .steps.len(),
+    display_fuzz_trace(.steps)
+)]
+pub struct FuzzFailure {
+    /// The seed of the run that found the failure. The failure can be
+    /// reproduced with `.seed(seed).runs(1)`.
+    pub seed:      u64,
+    /// The name of the invariant that failed.
+    pub invariant: String,
+    /// The message from the invariant.
+    pub message:   String,
+    /// The shrunk sequence of steps leading to the failure.
+    pub steps:     Vec<FuzzTraceStep>,
+}
+
+/// A fuzz target which sends arbitrary parameter bytes to a single receive
+/// entrypoint of a contract on a prepared [`Chain`].
+///
+/// Every input is executed on the chain as it was when the target was
+/// created, and the outcome is checked for traps, running out of energy and
+/// CCD that is created or lost. See [`EntrypointFuzzTarget::new`] for how to
+/// use it with `cargo fuzz`.
+pub struct EntrypointFuzzTarget {
+    /// The chain on which the inputs are executed.
+    pub(crate) chain:        Chain,
+    /// The state of the chain before any input is executed.
+    pub(crate) snapshot:     ChainSnapshot,
+    /// The invoker account.
+    pub(crate) invoker:      AccountAddress,
+    /// The sender of the messages.
+    pub(crate) sender:       Address,
+    /// The energy reserved for each update.
+    pub(crate) energy:       Energy,
+    /// The contract to update.
+    pub(crate) address:      ContractAddress,
+    /// The entrypoint to update.
+    pub(crate) receive_name: OwnedReceiveName,
+    /// The amount sent with each update.
+    pub(crate) amount:       Amount,
+}
+
+/// A problem found by [`EntrypointFuzzTarget::execute`].
+///
+/// A contract rejecting an input is not a finding, since that is how
+/// contracts are expected to handle invalid parameters.
+#[derive(Debug, Error)]
+pub enum EntrypointFuzzFinding {
+    /// The contract trapped, e.g., because it panicked while parsing the
+    /// parameter.
+    #[error("The contract trapped on the input: {error}")]
+    Trap {
+        /// The error returned by the update.
+        error: ContractInvokeError,
+    },
+    /// The update ran out of energy.
+    #[error("The contract ran out of energy on the input: {error}")]
+    OutOfEnergy {
+        /// The error returned by the update.
+        error: ContractInvokeError,
+    },
+    /// The total amount of CCD on the chain changed by more than the
+    /// transaction fee.
+    #[error(
+        "The total amount of CCD changed from {before} to {after}, but the transaction fee was \
+         {transaction_fee}."
+    )]
+    BalanceInconsistency {
+        /// The total amount of CCD on accounts and contracts before the
+        /// update.
+        before:          Amount,
+        /// The total amount of CCD on accounts and contracts after the
+        /// update.
+        after:           Amount,
+        /// The transaction fee charged for the update.
+        transaction_fee: Amount,
+    },
+    /// The total amount of CCD on accounts and contracts overflows before or
+    /// after the update, so CCD must have been created.
+    #[error("The total amount of CCD on accounts and contracts overflows.")]
+    BalanceOverflow,
+}
+
+/// An error that occurred in [`Chain::contract_state`].
+#[derive(Debug, Error)]
+pub enum ContractStateError {
+    /// The contract instance does not exist.
+    #[error("{0}")]
+    ContractDoesNotExist(#[from] ContractDoesNotExist),
+    /// The root of the state could not be deserialized into the given type.
+    #[error("Could not deserialize the contract state into the given type.")]
+    Parse(ParseError),
+}
+
 /// Represents a successful initialization of a contract.
 #[derive(Debug)]
 pub struct ContractInitSuccess {
@@ -406,6 +1662,14 @@ pub enum ContractInitErrorKind {
     /// The parameter is too large.
     #[error("The provided parameter exceeds the maximum size allowed")]
     ParameterTooLarge,
+    /// The accounts and modules needed could not be loaded from the external
+    /// node in fork mode.
+    #[error("Could not load from the external node of the fork: {0}")]
+    ForkLoad(ExternalNodeError),
+    /// The transaction was not accepted, because it is not signed correctly
+    /// or the chain only accepts signed transactions.
+    #[error("The transaction was not accepted: {0}")]
+    Verification(#[from] TransactionVerificationError),
 }
 
 /// The reason for why a contract initialization failed during execution.
@@ -449,6 +1713,34 @@ pub struct ContractInvokeSuccess {
     pub new_balance:     Amount,
 }
 
+/// The outcome of a single transaction executed as part of a block with
+/// [`Chain::block`].
+#[derive(Debug)]
+pub enum BlockItemOutcome {
+    /// The outcome of [`BlockBuilder::module_deploy_v1`].
+    ModuleDeploy(Result<ModuleDeploySuccess, ModuleDeployError>),
+    /// The outcome of [`BlockBuilder::contract_init`].
+    ContractInit(Result<ContractInitSuccess, ContractInitError>),
+    /// The outcome of [`BlockBuilder::contract_update`].
+    ContractUpdate(Result<ContractInvokeSuccess, ContractInvokeError>),
+    /// The outcome of [`BlockBuilder::account_transfer`] and its variants.
+    AccountTransfer(Result<AccountTransferSuccess, AccountTransferError>),
+}
+
+/// A summary of a block executed with [`Chain::block`].
+#[derive(Debug)]
+pub struct BlockSummary {
+    /// The block time shared by all transactions in the block.
+    pub block_time:      SlotTime,
+    /// The outcomes of the transactions in the block, in the order that they
+    /// were executed.
+    pub outcomes:        Vec<BlockItemOutcome>,
+    /// The total energy used by the transactions in the block.
+    pub energy_used:     Energy,
+    /// The total transaction fees paid by the transactions in the block.
+    pub transaction_fee: Amount,
+}
+
 /// Represents a successful external contract invocation.
 #[derive(Debug)]
 pub struct ContractInvokeExternalSuccess {
@@ -492,6 +1784,49 @@ pub struct HostCallInfo<'a> {
     pub rolled_back:   bool,
 }
 
+/// A breakdown of the energy used by a contract update or invocation.
+///
+/// Created with [`ContractInvokeSuccess::energy_profile`] or
+/// [`ContractInvokeError::energy_profile`].
+///
+/// All values are measured in [`InterpreterEnergy`], where 1000 interpreter
+/// energy is one [`Energy`]. The energy used in the part of execution that
+/// has been rolled back is included, since it is still paid for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnergyProfile {
+    /// The energy used per contract and entrypoint.
+    pub entrypoints: BTreeMap<(ContractAddress, OwnedEntrypointName), EntrypointEnergy>,
+    /// The energy used for storing the resulting contract states at the end of
+    /// the transaction.
+    pub storage:     u64,
+    /// The energy used per call stack, where each stack is a list of
+    /// contracts and entrypoints starting with the outermost call. Used for
+    /// producing flamegraphs with [`EnergyProfile::to_folded_stacks`].
+    pub stacks:      BTreeMap<Vec<(ContractAddress, OwnedEntrypointName)>, u64>,
+}
+
+/// The energy used by a single entrypoint of a contract, as part of an
+/// [`EnergyProfile`].
+///
+/// Energy charged before the execution starts, such as the base cost of the
+/// transaction, is included in the `execution` of the first entrypoint.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntrypointEnergy {
+    /// The energy used for executing Wasm code, i.e., excluding host calls.
+    pub execution:  u64,
+    /// The number of calls and the energy used per host function.
+    pub host_calls: BTreeMap<HostFunctionV1, HostFunctionEnergy>,
+}
+
+/// The number of calls of a host function and the total energy used by them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HostFunctionEnergy {
+    /// The number of calls.
+    pub calls:  usize,
+    /// The total energy used by the calls.
+    pub energy: u64,
+}
+
 impl ContractInvokeSuccess {
     /// Extract all the events logged by all the contracts in the invocation.
     /// The events are returned in the order that they are emitted, and are
@@ -516,6 +1851,7 @@ impl ContractInvokeSuccess {
     /// invocation. The return value is an iterator over triples `(from, amount,
     /// to)` where `from` is the sender contract, and `to` is the receiver
     /// account. The transfers are returned in the order that they occurred.
+    /// The receiver is the exact alias of the account that the contract used.
     ///
     /// Only tranfers from effective trace elements are included. See
     /// [`Self::effective_trace_elements`] for more details.
@@ -938,6 +2274,253 @@ fn debug_events_worker(
     }
 }
 
+/// A step-by-step execution trace of a contract update or invocation.
+///
+/// The trace is reconstructed from the [`DebugTraceElement`]s, so it includes
+/// the calls between contracts, the host calls and `concordium_dbg!`
+/// statements in the order they happened, and the trace elements of the
+/// update. Host calls and debug statements are only recorded for modules that
+/// are deployed with debug output enabled, e.g., with
+/// [`Chain::module_deploy_v1_debug`].
+///
+/// The Wasm functions executed inside a contract and the arguments of host
+/// calls are not visible outside of the execution engine, so they are not part
+/// of the trace. The arguments and responses of host calls that interrupt the
+/// contract, and the changes to the state, are recorded by
+/// [`Chain::contract_update_traced`] and [`Chain::contract_invoke_traced`].
+///
+/// Created with [`ContractInvokeSuccess::execution_trace`] or
+/// [`ContractInvokeError::execution_trace`], and displayed in a human-readable
+/// form with [`Display`](std::fmt::Display).
+#[derive(Debug)]
+pub struct ExecutionTrace<'a> {
+    /// The steps in the order they happened.
+    pub steps: Vec<ExecutionTraceStep<'a>>,
+}
+
+/// A step in an [`ExecutionTrace`].
+#[derive(Debug)]
+pub struct ExecutionTraceStep<'a> {
+    /// The depth of the call to the contract, where the contract called by
+    /// the transaction has depth `0`.
+    pub depth:       usize,
+    /// The contract which performed the step.
+    pub address:     ContractAddress,
+    /// The entrypoint which performed the step.
+    pub entrypoint:  EntrypointName<'a>,
+    /// Whether the effects of the step were rolled back because of a failure.
+    pub rolled_back: bool,
+    /// What happened.
+    pub kind:        ExecutionTraceStepKind<'a>,
+}
+
+/// The kind of an [`ExecutionTraceStep`].
+#[derive(Debug)]
+pub enum ExecutionTraceStepKind<'a> {
+    /// The entrypoint was called.
+    Enter,
+    /// A host function was called. Only the function and its energy usage
+    /// are reported by the execution engine.
+    HostCall {
+        /// The host function.
+        host_function: HostFunctionV1,
+        /// The interpreter energy used by the call.
+        energy_used:   InterpreterEnergy,
+    },
+    /// A `concordium_dbg!` statement was executed.
+    DebugPrint(&'a EmittedDebugStatement),
+    /// A trace element was produced, e.g., a transfer or a call to another
+    /// contract.
+    TraceElement(&'a ContractTraceElement),
+    /// The entrypoint failed.
+    Failed(&'a InvokeExecutionError),
+    /// The entrypoint returned successfully.
+    Exit,
+}
+
+/// The call tree of a contract update or invocation, which can be displayed
+/// in a human-readable form with [`Display`](std::fmt::Display).
+///
+/// The tree shows the calls between contracts with their sender, amount and
+/// parameter, and the transfers, upgrades, logged events and failures in each
+/// call. Calls whose effects were rolled back are marked as such.
+///
+/// Created with [`ContractInvokeSuccess::call_tree`] or
+/// [`ContractInvokeError::call_tree`]. Events and parameters are decoded to
+/// JSON if a chain is provided with [`CallTree::with_schemas`].
+#[derive(Debug)]
+pub struct CallTree<'a> {
+    /// The calls made by the transaction. This is a single call unless the
+    /// top-level call failed before completing.
+    pub calls:        Vec<CallTreeNode<'a>>,
+    /// The reason the update or invocation failed, if it did.
+    pub(crate) error: Option<&'a ContractInvokeErrorKind>,
+    /// The chain used for decoding events and parameters.
+    pub(crate) chain: Option<&'a Chain>,
+}
+
+/// A call to a contract entrypoint in a [`CallTree`].
+#[derive(Debug)]
+pub struct CallTreeNode<'a> {
+    /// The contract called.
+    pub address:     ContractAddress,
+    /// The entrypoint called.
+    pub entrypoint:  EntrypointName<'a>,
+    /// The sender of the call, if the call completed.
+    pub sender:      Option<Address>,
+    /// The amount sent with the call, if the call completed.
+    pub amount:      Option<Amount>,
+    /// The parameter of the call, if the call completed.
+    pub parameter:   Option<&'a [u8]>,
+    /// What happened in the call, in order.
+    pub items:       Vec<CallTreeItem<'a>>,
+    /// The reason the call failed, if it did.
+    pub failure:     Option<&'a InvokeExecutionError>,
+    /// Whether the effects of the call were rolled back.
+    pub rolled_back: bool,
+}
+
+/// Something that happened in a [`CallTreeNode`].
+#[derive(Debug)]
+pub enum CallTreeItem<'a> {
+    /// A call to another contract.
+    Call(CallTreeNode<'a>),
+    /// Events logged by the contract.
+    Events(&'a [ContractEvent]),
+    /// A transfer to an account.
+    Transfer {
+        /// The amount transferred.
+        amount: Amount,
+        /// The receiving account.
+        to:     AccountAddress,
+    },
+    /// An upgrade of the contract module.
+    Upgrade {
+        /// The module before the upgrade.
+        from: ModuleReference,
+        /// The module after the upgrade.
+        to:   ModuleReference,
+    },
+}
+
+/// A change of a key in the state of a contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateChange {
+    /// The key.
+    pub key: Vec<u8>,
+    /// The value before the change, or `None` if the key was created.
+    pub old: Option<Vec<u8>>,
+    /// The value after the change, or `None` if the key was deleted.
+    pub new: Option<Vec<u8>>,
+}
+
+/// The outcome of [`Chain::contract_update_traced`] or
+/// [`Chain::contract_invoke_traced`].
+#[derive(Debug)]
+pub struct ContractCallTraced {
+    /// The result of the update or invocation.
+    pub result:  Result<ContractInvokeSuccess, ContractInvokeError>,
+    /// The host calls and state changes of each call to a contract entrypoint,
+    /// in the order they happened.
+    pub records: Vec<CallRecord>,
+}
+
+/// A host call or state change of a call to a contract entrypoint, recorded
+/// by [`Chain::contract_update_traced`] and [`Chain::contract_invoke_traced`].
+#[derive(Debug)]
+pub struct CallRecord {
+    /// The depth of the call to the contract, where the contract called by
+    /// the transaction has depth `0`.
+    pub depth:       usize,
+    /// The contract that was executing.
+    pub address:     ContractAddress,
+    /// The entrypoint that was executing.
+    pub entrypoint:  OwnedEntrypointName,
+    /// Whether the effects were rolled back because of a failure.
+    pub rolled_back: bool,
+    /// What happened.
+    pub kind:        CallRecordKind,
+}
+
+/// The kind of a [`CallRecord`].
+#[derive(Debug)]
+pub enum CallRecordKind {
+    /// The contract interrupted its execution with a host call that is
+    /// handled by the chain. The records of a called contract follow this
+    /// record.
+    Interrupt(HostInterrupt),
+    /// The contract was resumed with the response to the interrupt.
+    Resumed(HostInterruptResponse),
+    /// The contract changed its state since the call started or since the
+    /// previous record of state changes.
+    ///
+    /// The changes are recorded when the contract is interrupted, returns,
+    /// rejects or traps, so several writes to a key in between are seen as one
+    /// change. The changes of a contract that rejects or traps are recorded as
+    /// rolled back.
+    StateChanges(Vec<StateChange>),
+}
+
+/// A host call that interrupts the execution of a contract, with its
+/// arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostInterrupt {
+    /// Transfer CCD to an account.
+    Transfer {
+        to:     AccountAddress,
+        amount: Amount,
+    },
+    /// Call an entrypoint of a contract.
+    Call {
+        address:    ContractAddress,
+        entrypoint: OwnedEntrypointName,
+        parameter:  OwnedParameter,
+        amount:     Amount,
+    },
+    /// Upgrade the contract to a new module.
+    Upgrade {
+        module_reference: ModuleReference,
+    },
+    /// Query the balance of an account.
+    QueryAccountBalance {
+        address: AccountAddress,
+    },
+    /// Query the balance of a contract.
+    QueryContractBalance {
+        address: ContractAddress,
+    },
+    /// Query the exchange rates.
+    QueryExchangeRates,
+    /// Check signatures on a payload with the keys of an account.
+    CheckAccountSignature {
+        address: AccountAddress,
+        payload: Vec<u8>,
+    },
+    /// Query the public keys of an account.
+    QueryAccountKeys {
+        address: AccountAddress,
+    },
+}
+
+/// The response to a [`HostInterrupt`], which is what the contract sees when
+/// it is resumed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostInterruptResponse {
+    /// The host call succeeded. The data is the return value of a called
+    /// contract or the serialized result of a query, if any.
+    Success {
+        data: Option<Vec<u8>>,
+    },
+    /// A called contract rejected with a reject code and a return value.
+    Reject {
+        code: i32,
+        data: Vec<u8>,
+    },
+    /// The host call failed for another reason, e.g., because the account or
+    /// contract does not exist. The reason is formatted with `Debug`.
+    Failure(String),
+}
+
 /// A wrapper for [`ContractTraceElement`], which provides additional
 /// information for testing and debugging. Most notably, it contains trace
 /// elements for failures, which are normally discarded by the node.
@@ -1073,6 +2656,14 @@ pub enum ContractInvokeErrorKind {
     /// The parameter is too large.
     #[error("The provided parameter exceeds the maximum size allowed")]
     ParameterTooLarge,
+    /// The accounts, contracts and modules needed could not be loaded from the
+    /// external node in fork mode.
+    #[error("Could not load from the external node of the fork: {0}")]
+    ForkLoad(ExternalNodeError),
+    /// The transaction was not accepted, because it is not signed correctly
+    /// or the chain only accepts signed transactions.
+    #[error("The transaction was not accepted: {0}")]
+    Verification(#[from] TransactionVerificationError),
 }
 
 /// The error returned when external contract invocations fail.
@@ -1164,6 +2755,17 @@ pub struct AccountDoesNotExist {
     pub address: AccountAddress,
 }
 
+/// An error that occurred in [`Chain::account_alias`].
+#[derive(Debug, Error)]
+pub enum AccountAliasError {
+    /// The account does not exist.
+    #[error("{0}")]
+    AccountDoesNotExist(#[from] AccountDoesNotExist),
+    /// The alias number is not less than `2^24`.
+    #[error("Alias number {0} is too large. It must be less than 2^24.")]
+    CounterTooLarge(u32),
+}
+
 /// The provided exchange rates are not valid.
 /// Meaning that they do not correspond to one energy costing less than
 /// `u64::MAX / 100_000_000_000`.
@@ -1207,6 +2809,14 @@ pub enum SetupExternalNodeError {
         /// The inner error.
         error: sdk::v2::RPCError,
     },
+    /// Could not check the existence of the specified query block or the last
+    /// final block with the configured backend, e.g., because the response
+    /// has not been recorded in a [`FixtureNode`].
+    #[error("Could not check the query block with the external node backend due to: {error}")]
+    Backend {
+        /// The inner error.
+        error: Box<ExternalNodeError>,
+    },
 }
 
 /// Errors that occur while trying to communicate with an external node.
@@ -1224,6 +2834,67 @@ pub enum ExternalNodeError {
     /// The query timed out.
     #[error("The query timed out.")]
     QueryTimeout,
+    /// The response to the query has not been recorded in the [`FixtureNode`],
+    /// which only replays recorded responses.
+    #[error("The response to the query `{query}` has not been recorded.")]
+    NotRecorded {
+        /// The query.
+        query: String,
+    },
+    /// The [`FixtureNode`] could not connect to the node for recording a
+    /// response.
+    #[error("Could not connect to the node for recording a response: {error}")]
+    CannotConnect {
+        /// The inner error.
+        error: Box<SetupExternalNodeError>,
+    },
+    /// A contract instance is a V0 instance, which this library does not
+    /// support.
+    #[error("The contract {address} is a V0 instance, which is not supported.")]
+    UnsupportedInstance {
+        /// The address of the instance.
+        address: ContractAddress,
+    },
+    /// A module is a V0 module, which this library does not support.
+    #[error("The module {module_reference} is a V0 module, which is not supported.")]
+    UnsupportedModule {
+        /// The reference of the module.
+        module_reference: ModuleReference,
+    },
+    /// A module loaded from the external node is invalid.
+    #[error("The module {module_reference} from the external node is invalid: {error}")]
+    InvalidModule {
+        /// The reference of the module.
+        module_reference: ModuleReference,
+        /// The reason why the module is invalid.
+        error:            ModuleInvalidError,
+    },
+    /// The fixture file of a [`FixtureNode`] could not be read or written.
+    #[error("Could not access the fixture file '{path}' due to: {kind}")]
+    FixtureFile {
+        /// The fixture file.
+        path: PathBuf,
+        /// The reason why the file could not be accessed.
+        kind: FixtureFileErrorKind,
+    },
+}
+
+/// The specific reason why the fixture file of a [`FixtureNode`] could not
+/// be accessed.
+#[derive(Debug, Error)]
+pub enum FixtureFileErrorKind {
+    /// Could not read or write the file.
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    /// The file is not valid JSON, or a response has an unexpected format.
+    #[error("The file is invalid: {0}")]
+    Json(#[from] serde_json::Error),
+    /// A recorded response is invalid.
+    #[error("The response to the query `{query}` is invalid.")]
+    InvalidResponse {
+        /// The query.
+        query: String,
+    },
 }
 
 /// The error returned when an external node has not been configured prior to
@@ -1261,6 +2932,20 @@ pub enum ChainBuilderError {
          both be used."
     )]
     ConflictingBlockTime,
+    /// Could not configure the external node because both the
+    /// [`ChainBuilder::external_node_connection`] and
+    /// [`ChainBuilder::external_node_backend`] were provided, which is not
+    /// allowed.
+    #[error(
+        "Conflicting external node configuration: `external_node_connection` and \
+         `external_node_backend` cannot both be used."
+    )]
+    ConflictingExternalNode,
+    /// Could not configure fork mode because no external node was
+    /// configured with [`ChainBuilder::external_node_connection`] or
+    /// [`ChainBuilder::external_node_backend`].
+    #[error("Fork mode requires an external node to be configured.")]
+    ForkWithoutExternalNode,
     /// Could not configure the microCCD/euro exchange rate because both the
     /// [`ChainBuilder::micro_ccd_per_euro`] and
     /// [`ChainBuilder::micro_ccd_per_euro_from_external`] were provided, which
@@ -1359,3 +3044,250 @@ impl From<ExternalAccountAddress> for ExternalAddress {
 impl From<ExternalContractAddress> for ExternalAddress {
     fn from(addr: ExternalContractAddress) -> Self { Self::Contract(addr) }
 }
+
+/// A contract init or update transaction to replay, along with the block it
+/// was finalized in.
+#[derive(Debug, Clone)]
+pub struct ReplayTransaction {
+    /// The hash of the transaction.
+    pub hash:               TransactionHash,
+    /// The block the transaction is in.
+    pub block:              BlockHash,
+    /// The parent of `block`. The state of the chain in this block is loaded
+    /// before the transaction is replayed.
+    ///
+    /// Strictly speaking, the transaction is executed on the state after the
+    /// transactions that precede it in `block`, so the replay can differ if
+    /// they touched the same contracts or accounts.
+    pub parent_block:       BlockHash,
+    /// The slot time of `block`, which is the block time seen by the
+    /// contracts.
+    pub block_time:         Timestamp,
+    /// The euro per energy exchange rate in `block`.
+    pub euro_per_energy:    ExchangeRate,
+    /// The microCCD per euro exchange rate in `block`.
+    pub micro_ccd_per_euro: ExchangeRate,
+    /// The sender of the transaction.
+    pub sender:             AccountAddress,
+    /// The number of signatures on the transaction.
+    pub num_signatures:     u32,
+    /// The energy reserved for the transaction.
+    pub energy_reserved:    Energy,
+    /// The payload of the transaction.
+    pub payload:            ReplayPayload,
+    /// The contracts that existed before the transaction and were involved in
+    /// it.
+    pub contracts:          Vec<ContractAddress>,
+    /// The modules needed besides those of the `contracts`, e.g., the modules
+    /// contracts were upgraded to.
+    pub modules:            Vec<ModuleReference>,
+    /// The accounts involved in the transaction, besides the sender.
+    pub accounts:           Vec<AccountAddress>,
+}
+
+/// The payload of a [`ReplayTransaction`].
+#[derive(Debug, Clone)]
+pub enum ReplayPayload {
+    /// Initialize a contract.
+    InitContract {
+        payload: InitContractPayload,
+        /// The address of the contract that was initialized.
+        address: ContractAddress,
+    },
+    /// Update a contract.
+    Update {
+        payload: UpdateContractPayload,
+    },
+}
+
+/// A contract instance queried from an external node, e.g., for replaying a
+/// transaction or for loading it into a fork.
+#[derive(Debug, Clone)]
+pub struct ExternalInstance {
+    /// The module of the contract.
+    pub module_reference: ModuleReference,
+    /// The name of the contract.
+    pub contract_name:    OwnedContractName,
+    /// The owner of the contract.
+    pub owner:            AccountAddress,
+    /// The balance of the contract.
+    pub self_balance:     Amount,
+    /// The keys and values of the entries in the state of the contract.
+    pub state:            BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+/// A node queried via gRPC. It is used as [`ExternalNodeBackend`] by
+/// [`ChainBuilder::external_node_connection`], and it can be used for
+/// [`Chain::replay_transaction`].
+///
+/// Create it with [`GrpcNode::new`].
+#[derive(Debug)]
+pub struct GrpcNode {
+    /// The client for communicating with the node.
+    pub(crate) client:  sdk::v2::Client,
+    /// A Tokio runtime used to execute the async methods of the `client`.
+    pub(crate) runtime: tokio::runtime::Runtime,
+}
+
+/// The outcome of [`Chain::replay_transaction`].
+#[derive(Debug)]
+pub struct ReplayedTransaction {
+    /// The transaction that was replayed.
+    pub transaction: ReplayTransaction,
+    /// The chain the transaction was replayed on, in the state after the
+    /// transaction.
+    pub chain:       Chain,
+    /// The result of executing the transaction, with debug output enabled.
+    pub result:      ReplayResult,
+}
+
+/// The result of a replayed transaction.
+#[derive(Debug)]
+pub enum ReplayResult {
+    /// The result of a contract init.
+    InitContract(Result<ContractInitSuccess, ContractInitError>),
+    /// The result of a contract update.
+    Update(Result<ContractInvokeSuccess, ContractInvokeError>),
+}
+
+/// An error that occurred while replaying a transaction with
+/// [`Chain::replay_transaction`].
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    /// A query to the node failed.
+    #[error("Could not query the node: {0}")]
+    Node(#[from] ExternalNodeError),
+    /// The transaction is not finalized.
+    #[error("The transaction {0} is not finalized.")]
+    NotFinalized(TransactionHash),
+    /// The transaction is not a contract init or update.
+    #[error("The transaction {0} is not a contract init or update.")]
+    UnsupportedTransaction(TransactionHash),
+    /// A module could not be loaded.
+    #[error("The module {0} is invalid: {1}")]
+    InvalidModule(ModuleReference, ModuleInvalidError),
+    /// The exchange rates of the block are not valid for a [`Chain`].
+    #[error("The exchange rates of the block are invalid.")]
+    InvalidExchangeRates,
+}
+
+/// The queries to an external node used by [`Chain::contract_invoke_external`]
+/// and the related methods, by fork mode and by [`Chain::replay_transaction`].
+///
+/// It is implemented by [`GrpcNode`], which queries a node via gRPC, and by
+/// [`FixtureNode`], which records the responses of a node to a file and serves
+/// them afterwards. A backend is configured with
+/// [`ChainBuilder::external_node_backend`]. Backends must be [`Send`], so a
+/// [`Chain`] can be moved to another thread, e.g., by a [`NodeEmulator`].
+pub trait ExternalNodeBackend: std::fmt::Debug + Send {
+    /// Get the hash of the given block, or of the last final block if `None`.
+    ///
+    /// Returns an [`ExternalNodeError::QueryError`] with
+    /// [`QueryError::NotFound`](sdk::endpoints::QueryError::NotFound) if the
+    /// block does not exist.
+    fn block_hash(&self, block: Option<BlockHash>) -> Result<BlockHash, ExternalNodeError>;
+
+    /// Get the slot time of a block.
+    fn block_time(&self, block: BlockHash) -> Result<Timestamp, ExternalNodeError>;
+
+    /// Get the exchange rates in a block.
+    fn exchange_rates(&self, block: BlockHash) -> Result<ExchangeRates, ExternalNodeError>;
+
+    /// Check that an account exists in a block.
+    fn check_account(
+        &self,
+        block: BlockHash,
+        address: AccountAddress,
+    ) -> Result<(), ExternalNodeError>;
+
+    /// Check that a contract exists in a block.
+    fn check_contract(
+        &self,
+        block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<(), ExternalNodeError>;
+
+    /// Invoke a contract at the end of a block.
+    fn invoke_instance(
+        &self,
+        block: BlockHash,
+        context: &ContractContext,
+    ) -> Result<InvokeContractResult, ExternalNodeError>;
+
+    /// Check whether a contract exists in a block.
+    fn contract_exists(
+        &self,
+        block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<bool, ExternalNodeError>;
+
+    /// Get the source of a module in a block, or `None` if it does not exist.
+    fn module_source(
+        &self,
+        block: BlockHash,
+        module_reference: ModuleReference,
+    ) -> Result<Option<WasmModule>, ExternalNodeError>;
+
+    /// Get a contract instance, including its state, in a block, or `None` if
+    /// it does not exist.
+    fn instance(
+        &self,
+        block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<Option<ExternalInstance>, ExternalNodeError>;
+
+    /// Get the balance of an account in a block, or `None` if it does not
+    /// exist.
+    fn account_balance(
+        &self,
+        block: BlockHash,
+        address: AccountAddress,
+    ) -> Result<Option<AccountBalance>, ExternalNodeError>;
+
+    /// Get a finalized contract init or update transaction, along with the
+    /// block it is in.
+    fn transaction(&self, transaction: TransactionHash) -> Result<ReplayTransaction, ReplayError>;
+}
+
+/// An [`ExternalNodeBackend`] which serves responses recorded in a JSON file.
+///
+/// Create it with [`FixtureNode::record`], which records the responses of a
+/// node on first use, or with [`FixtureNode::replay`], which only serves the
+/// recorded responses.
+#[derive(Debug)]
+pub struct FixtureNode {
+    /// The JSON file with the recorded responses.
+    pub(crate) path:      PathBuf,
+    /// The endpoint of the node to record from, if recording.
+    pub(crate) endpoint:  Option<sdk::v2::Endpoint>,
+    /// The node to record from, connected when the first query is not
+    /// recorded.
+    pub(crate) node:      std::sync::OnceLock<GrpcNode>,
+    /// The recorded responses, keyed by the query.
+    pub(crate) responses: std::sync::Mutex<BTreeMap<String, serde_json::Value>>,
+}
+
+/// A local emulator of a node, which serves a subset of the gRPC API of the
+/// node and executes the transactions it receives on a [`Chain`].
+///
+/// This lets frontends, deploy scripts and other clients of the node, e.g.,
+/// the [`v2::Client`](sdk::v2::Client) of the SDK, run against a
+/// deterministic local chain. See [`NodeEmulator::serve`] for serving it.
+#[derive(Debug)]
+pub struct NodeEmulator {
+    /// The chain on which the transactions are executed.
+    pub(crate) chain:        Chain,
+    /// The outcomes of the transactions sent to the emulator.
+    pub(crate) transactions: BTreeMap<TransactionHash, sdk::v2::generated::BlockItemSummary>,
+}
+
+/// The errors that can occur while serving a [`NodeEmulator`].
+#[derive(Debug, Error)]
+pub enum EmulatorError {
+    /// The listener could not be used by the server.
+    #[error("Could not use the listener: {0}")]
+    Listener(#[from] std::io::Error),
+    /// The gRPC server failed.
+    #[error("The gRPC server failed: {0}")]
+    Server(#[from] tonic::transport::Error),
+}
@@ -104,3 +104,89 @@ fn test() {
         .expect("Return value should be deserializable.");
     assert_eq!(rv, 0, "Signature check should succeed, the return value should be 0.");
 }
+
+/// Test that accounts with keys generated from a seed can sign messages that
+/// contracts accept, and that signatures below the account threshold are
+/// rejected.
+#[test]
+fn test_seeded_keys() {
+    let keys = || {
+        account_keys_from_seed(3, AccountThreshold::TWO, &[
+            (0.into(), SignatureThreshold::ONE, &[0.into()]),
+            (1.into(), SignatureThreshold::TWO, &[0.into(), 1.into()]),
+        ])
+    };
+    // The same seed gives the same keys.
+    assert_eq!(AccountAccessStructure::from(&keys()), AccountAccessStructure::from(&keys()));
+
+    let mut chain = Chain::new();
+    chain.create_account(Account::new_with_signing_keys(
+        helpers::ACC_0,
+        AccountBalance {
+            total:  Amount::from_ccd(1000),
+            staked: Amount::zero(),
+            locked: Amount::zero(),
+        },
+        keys(),
+    ));
+    let module = module_load_v1_raw(helpers::wasm_test_file("account-signature-checks.wasm"))
+        .expect("module should exist");
+    let mod_ref = chain
+        .module_deploy_v1(Signer::with_one_key(), helpers::ACC_0, module)
+        .expect("Deploying valid module should work")
+        .module_reference;
+    let contract = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                init_name: OwnedContractName::new_unchecked("init_contract".into()),
+                mod_ref,
+                param: OwnedParameter::empty(),
+                amount: Amount::zero(),
+            },
+        )
+        .expect("Initializing valid contract should work")
+        .contract_address;
+
+    let message = [5u8; 30];
+    let check_signature = |signatures: &AccountSignatures| {
+        let result = chain
+            .contract_invoke(
+                helpers::ACC_0,
+                Address::Account(helpers::ACC_0),
+                Energy::from(100000),
+                UpdateContractPayload {
+                    address:      contract,
+                    receive_name: OwnedReceiveName::new_unchecked(
+                        "contract.check_signature".into(),
+                    ),
+                    message:      OwnedParameter::from_serial(&(
+                        helpers::ACC_0,
+                        message.to_vec(),
+                        signatures,
+                    ))
+                    .expect("Enough space."),
+                    amount:       Amount::zero(),
+                },
+            )
+            .expect("Querying contract should work");
+        contracts_common::from_bytes::<u64>(&result.return_value)
+            .expect("Return value should be deserializable.")
+    };
+
+    let account = chain.account(helpers::ACC_0).expect("Account exists");
+    let signatures = account.sign_message(&message).expect("The account has signing keys");
+    assert_eq!(signatures.num_signatures(), 3);
+    assert_eq!(check_signature(&signatures), 0, "All keys satisfy the thresholds.");
+
+    // Only one of the two credentials signs, which is below the account threshold.
+    let signatures = AccountSignatures::sign_with(
+        account.signing_keys().expect("The account has signing keys"),
+        &message,
+        &[(0.into(), 0.into())],
+    );
+    assert_eq!(signatures.num_signatures(), 1);
+    assert_ne!(check_signature(&signatures), 0, "The account threshold is not met.");
+}
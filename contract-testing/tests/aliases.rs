@@ -0,0 +1,165 @@
+//! This module tests that account aliases refer to the same account in all
+//! `Chain` methods, and that contracts see the exact alias that was used.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Test that aliases can be generated for existing accounts only, and that
+/// balances can be queried through them.
+#[test]
+fn test_account_alias() {
+    let mut chain = Chain::new();
+    let initial_balance = Amount::from_ccd(1000);
+    chain.create_account(Account::new(helpers::ACC_1, initial_balance));
+
+    let canonical = chain.account_alias(helpers::ACC_1, 0).expect("Account exists");
+    let alias = chain.account_alias(helpers::ACC_1, 1).expect("Account exists");
+    assert_ne!(canonical, helpers::ACC_1);
+    assert_ne!(canonical, alias);
+    assert!(canonical.is_alias(&alias));
+    assert_eq!(Some(canonical), helpers::ACC_1.get_alias(0));
+
+    assert_eq!(chain.account_balance_available(alias), Some(initial_balance));
+    assert_eq!(chain.account(canonical).expect("Account exists").address, helpers::ACC_1);
+
+    assert!(matches!(
+        chain.account_alias(helpers::ACC_1, 1 << 24),
+        Err(AccountAliasError::CounterTooLarge(n)) if n == 1 << 24
+    ));
+    assert!(matches!(
+        chain.account_alias(helpers::ACC_0, 0),
+        Err(AccountAliasError::AccountDoesNotExist(_))
+    ));
+}
+
+/// Test that an invoker using an alias is seen as that alias by the contract,
+/// and that the amount reserved for the invocation is subtracted when the
+/// contract queries the balance of the invoker through another alias.
+#[test]
+fn test_invoker_alias() {
+    let mut chain = Chain::new();
+    let initial_balance = Amount::from_ccd(1000000);
+    chain.create_account(Account::new(helpers::ACC_0, initial_balance));
+    chain.create_account(Account::new(helpers::ACC_1, initial_balance));
+    let invoker = chain.account_alias(helpers::ACC_1, 1).expect("Account exists");
+    let queried = chain.account_alias(helpers::ACC_1, 2).expect("Account exists");
+
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file("queries-account-balance.wasm"))
+                .expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   res_deploy.module_reference,
+                init_name: OwnedContractName::new_unchecked("init_contract".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Initializing valid contract should work");
+
+    let update_amount = Amount::from_ccd(123);
+    let energy_limit = Energy::from(100000);
+    let invoker_reserved_amount = update_amount + chain.calculate_energy_cost(energy_limit);
+
+    // The contract will query the balance of another alias of the invoker, and
+    // assert that the three balances match this input.
+    let expected_balance = initial_balance - invoker_reserved_amount;
+    let input_param = (queried, expected_balance, Amount::zero(), Amount::zero());
+
+    let res_update = chain
+        .contract_update(
+            Signer::with_one_key(),
+            invoker,
+            Address::Account(invoker),
+            energy_limit,
+            UpdateContractPayload {
+                address:      res_init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("contract.query".into()),
+                message:      OwnedParameter::from_serial(&input_param)
+                    .expect("Parameter has valid size"),
+                amount:       update_amount,
+            },
+        )
+        .expect("Updating valid contract should work");
+
+    assert_eq!(
+        chain.account_balance_available(helpers::ACC_1),
+        Some(initial_balance - res_update.transaction_fee - update_amount)
+    );
+    assert!(matches!(res_update.effective_trace_elements_cloned()[..], [
+        ContractTraceElement::Updated {
+            data: InstanceUpdatedEvent {
+                instigator: Address::Account(instigator),
+                ..
+            },
+        }
+    ] if instigator == invoker));
+}
+
+/// Test that a transfer to an alias is recorded with that alias, and that the
+/// amount is added to the account.
+#[test]
+fn test_transfer_to_alias() {
+    let mut chain = Chain::new();
+    let initial_balance = Amount::from_ccd(10000);
+    chain.create_account(Account::new(helpers::ACC_0, initial_balance));
+    chain.create_account(Account::new(helpers::ACC_1, initial_balance));
+    let receiver = chain.account_alias(helpers::ACC_1, 0).expect("Account exists");
+
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file("transfer.wasm"))
+                .expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   res_deploy.module_reference,
+                init_name: OwnedContractName::new_unchecked("init_transfer".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Initializing valid contract should work");
+    let contract_address = res_init.contract_address;
+
+    // Forward 123 microCCD to the alias.
+    let amount = Amount::from_micro_ccd(123);
+    let res_update = chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address: contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("transfer.forward".into()),
+                message: OwnedParameter::from_serial(&receiver).expect("Parameter has valid size"),
+                amount,
+            },
+        )
+        .expect("Updating contract should succeed");
+
+    assert_eq!(res_update.account_transfers().collect::<Vec<_>>(), [(
+        contract_address,
+        amount,
+        receiver
+    )]);
+    assert_eq!(chain.account_balance_available(helpers::ACC_1), Some(initial_balance + amount));
+}
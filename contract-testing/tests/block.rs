@@ -0,0 +1,100 @@
+//! This module contains tests for executing multiple transactions in a single
+//! block with `Chain::block`.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Test that transactions in a block are executed in order, share the block
+/// time, and that the summary adds up the energy and fees.
+#[test]
+fn test_block_with_multiple_transactions() {
+    let mut chain = Chain::new_with_time(Timestamp::from_timestamp_millis(1000));
+    let initial_balance = Amount::from_ccd(10000);
+    chain.create_account(Account::new(helpers::ACC_0, initial_balance));
+
+    let summary = chain.block(|b| {
+        let mod_ref = b
+            .module_deploy_v1(
+                Signer::with_one_key(),
+                helpers::ACC_0,
+                module_load_v1_raw(helpers::wasm_test_file("transfer.wasm"))
+                    .expect("module should exist"),
+            )
+            .as_ref()
+            .expect("Deploying valid module should work")
+            .module_reference;
+
+        let contract_address = b
+            .contract_init(
+                Signer::with_one_key(),
+                helpers::ACC_0,
+                Energy::from(10000),
+                InitContractPayload {
+                    mod_ref,
+                    init_name: OwnedContractName::new_unchecked("init_transfer".into()),
+                    param: OwnedParameter::empty(),
+                    amount: Amount::zero(),
+                },
+            )
+            .as_ref()
+            .expect("Initializing valid contract should work")
+            .contract_address;
+
+        b.contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address:      contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("transfer.deposit".into()),
+                message:      OwnedParameter::empty(),
+                amount:       Amount::from_micro_ccd(1000),
+            },
+        )
+        .as_ref()
+        .expect("Updating contract should succeed");
+
+        // The deposit is visible to later transactions in the same block.
+        assert_eq!(
+            b.chain().contract_balance(contract_address),
+            Some(Amount::from_micro_ccd(1000))
+        );
+
+        // Fails, since the contract does not exist.
+        b.contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address:      ContractAddress::new(42, 0),
+                receive_name: OwnedReceiveName::new_unchecked("transfer.deposit".into()),
+                message:      OwnedParameter::empty(),
+                amount:       Amount::zero(),
+            },
+        )
+        .as_ref()
+        .expect_err("Updating a non-existing contract should fail");
+    });
+
+    assert_eq!(summary.block_time, Timestamp::from_timestamp_millis(1000));
+    assert_eq!(chain.block_time(), Timestamp::from_timestamp_millis(1000));
+    assert_eq!(summary.outcomes.len(), 4);
+    assert_eq!(summary.num_failed(), 1);
+    assert!(matches!(summary.outcomes[..], [
+        BlockItemOutcome::ModuleDeploy(Ok(_)),
+        BlockItemOutcome::ContractInit(Ok(_)),
+        BlockItemOutcome::ContractUpdate(Ok(_)),
+        BlockItemOutcome::ContractUpdate(Err(_)),
+    ]));
+
+    let total_fee = summary
+        .outcomes
+        .iter()
+        .fold(Amount::zero(), |acc, outcome| acc + outcome.transaction_fee());
+    assert_eq!(summary.transaction_fee, total_fee);
+    assert_eq!(
+        chain.account_balance_available(helpers::ACC_0),
+        Some(initial_balance - summary.transaction_fee - Amount::from_micro_ccd(1000))
+    );
+}
@@ -0,0 +1,76 @@
+//! This module tests the call trees of contract updates.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Test that the call tree of a transfer from a contract includes the call
+/// with its sender, amount and parameter, and the transfer.
+#[test]
+fn test_call_tree_of_transfer() {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(10000)));
+
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file("transfer.wasm"))
+                .expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   res_deploy.module_reference,
+                init_name: OwnedContractName::new_unchecked("init_transfer".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Initializing valid contract should work");
+
+    let res_update = chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address:      res_init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("transfer.forward".into()),
+                message:      OwnedParameter::from_serial(&helpers::ACC_0)
+                    .expect("Parameter has valid size"),
+                amount:       Amount::from_micro_ccd(123),
+            },
+        )
+        .expect("Updating contract should succeed");
+
+    let tree = res_update.call_tree().with_schemas(&chain);
+    let [call] = &tree.calls[..] else {
+        panic!("Expected a single call, got {tree:?}");
+    };
+    assert_eq!(call.address, res_init.contract_address);
+    assert_eq!(call.entrypoint, EntrypointName::new_unchecked("forward"));
+    assert_eq!(call.sender, Some(Address::Account(helpers::ACC_0)));
+    assert_eq!(call.amount, Some(Amount::from_micro_ccd(123)));
+    assert_eq!(call.parameter.map(<[u8]>::len), Some(32));
+    assert!(!call.rolled_back);
+    assert!(matches!(call.items[..], [CallTreeItem::Transfer {
+        amount,
+        to,
+    }] if amount == Amount::from_micro_ccd(123) && to == helpers::ACC_0));
+
+    let display = tree.to_string();
+    let lines = display.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3, "Unexpected tree:\n{display}");
+    assert!(lines[0].starts_with(&format!(
+        "{}.forward from {}",
+        res_init.contract_address,
+        helpers::ACC_0
+    )));
+    assert_eq!(lines[1], "  parameter: 32 bytes");
+    assert!(lines[2].starts_with("  transferred "));
+}
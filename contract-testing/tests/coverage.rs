@@ -0,0 +1,112 @@
+//! This module tests the code coverage recorded by the chain.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Deploy the transfer module, initialize a contract from it and call the
+/// `forward` entrypoint twice. Returns the module reference and the last
+/// update.
+fn run_transfer(chain: &mut Chain) -> (ModuleReference, ContractInvokeSuccess) {
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(10000)));
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file("transfer.wasm"))
+                .expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   res_deploy.module_reference,
+                init_name: OwnedContractName::new_unchecked("init_transfer".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Initializing valid contract should work");
+
+    let mut last_update = None;
+    for _ in 0..2 {
+        let update = chain
+            .contract_update(
+                Signer::with_one_key(),
+                helpers::ACC_0,
+                Address::Account(helpers::ACC_0),
+                Energy::from(10000),
+                UpdateContractPayload {
+                    address:      res_init.contract_address,
+                    receive_name: OwnedReceiveName::new_unchecked("transfer.forward".into()),
+                    message:      OwnedParameter::from_serial(&helpers::ACC_0)
+                        .expect("Parameter has valid size"),
+                    amount:       Amount::from_micro_ccd(123),
+                },
+            )
+            .expect("Updating contract should succeed");
+        last_update = Some(update);
+    }
+    (res_deploy.module_reference, last_update.expect("The contract is updated"))
+}
+
+/// Test that the calls of the functions are counted, including the functions
+/// that were never called, and that the probes are not visible in the debug
+/// traces.
+#[test]
+fn test_coverage_of_transfer() {
+    let mut chain = Chain::new();
+    assert_eq!(chain.coverage(), None);
+    chain.coverage_enable();
+    let (module_reference, update) = run_transfer(&mut chain);
+
+    let coverage = chain.coverage_disable().expect("Coverage is enabled");
+    assert_eq!(chain.coverage(), None);
+    let module = coverage.module(module_reference).expect("Module is deployed");
+    assert_eq!(module.error, None);
+    let count = |name| module.function(name).map(|function| function.count);
+    assert_eq!(count("init_transfer"), Some(1));
+    assert_eq!(count("transfer.forward"), Some(2));
+    assert_eq!(count("transfer.send"), Some(0));
+    // The blocks and branches of a function are only executed if the function
+    // is called.
+    for function in &module.functions {
+        assert!(function.count <= 2, "{} is called at most twice", function.name);
+        let blocks = function.blocks.iter().map(|block| block.count);
+        let branches = function.branches.iter().flat_map(|branch| [branch.taken, branch.not_taken]);
+        if function.count == 0 {
+            assert!(blocks.chain(branches).all(|count| count == 0));
+        }
+    }
+
+    let not_executed = coverage.not_executed();
+    let not_executed = not_executed.get(&module_reference).expect("Not all executed");
+    assert!(!not_executed.contains(&"transfer.forward"));
+    assert!(not_executed.contains(&"transfer.send"));
+    assert!(coverage.to_string().contains("       2  transfer.forward\n"));
+    // The test module has no debug information, so there are no source lines.
+    assert_eq!(coverage.to_lcov(), "");
+    assert_eq!(update.emitted_debug_prints().count(), 0);
+}
+
+/// Test that the instrumented module is executed with the same outcome and
+/// host calls as the deployed module, but with more energy used by the probes.
+#[test]
+fn test_coverage_outcome() {
+    let mut chain = Chain::new();
+    let (_, update) = run_transfer(&mut chain);
+    let mut chain_with_coverage = Chain::new();
+    chain_with_coverage.coverage_enable();
+    let (_, update_with_coverage) = run_transfer(&mut chain_with_coverage);
+
+    assert_eq!(update_with_coverage.return_value, update.return_value);
+    assert_eq!(update_with_coverage.state_changed, update.state_changed);
+    assert_eq!(
+        update_with_coverage.events().collect::<Vec<_>>(),
+        update.events().collect::<Vec<_>>()
+    );
+    assert_eq!(update_with_coverage.host_calls().count(), update.host_calls().count());
+    assert!(update_with_coverage.energy_used > update.energy_used);
+}
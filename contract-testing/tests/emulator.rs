@@ -0,0 +1,180 @@
+//! This module tests the local node emulator with the gRPC client of the SDK.
+use concordium_rust_sdk::{
+    base::{
+        base::Nonce,
+        transactions::{send, BlockItem},
+    },
+    types::{
+        smart_contracts::{ContractContext, InstanceInfo},
+        AccountTransactionDetails, AccountTransactionEffects, BlockItemSummaryDetails,
+    },
+    v2::{self, AccountIdentifier, BlockIdentifier},
+};
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Create an emulator with the `transfer` contract and two accounts, and
+/// return it with the address of the contract.
+fn emulator() -> (NodeEmulator, ContractAddress) {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new_from_seed(helpers::ACC_0, Amount::from_ccd(1000), 0));
+    chain.create_account(Account::new_from_seed(helpers::ACC_1, Amount::zero(), 1));
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file("transfer.wasm"))
+                .expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   res_deploy.module_reference,
+                init_name: OwnedContractName::new_unchecked("init_transfer".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Initializing valid contract should work");
+    (NodeEmulator::new(chain), res_init.contract_address)
+}
+
+/// Serve the emulator on a free port, and return a client connected to it
+/// along with the runtime for the client.
+fn connect(emulator: NodeEmulator) -> (tokio::runtime::Runtime, v2::Client) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Binding works");
+    let address = listener.local_addr().expect("The listener has an address");
+    std::thread::spawn(move || emulator.serve(listener));
+    let runtime = tokio::runtime::Runtime::new().expect("Creating the runtime works");
+    let endpoint =
+        v2::Endpoint::from_shared(format!("http://{address}")).expect("The endpoint is valid");
+    let client = runtime.block_on(v2::Client::new(endpoint)).expect("Connecting works");
+    (runtime, client)
+}
+
+/// Test the queries for accounts and contracts, and invoking a contract.
+#[test]
+fn test_queries() {
+    let (emulator, contract) = emulator();
+    let (runtime, mut client) = connect(emulator);
+    runtime.block_on(async {
+        let account = client
+            .get_account_info(
+                &AccountIdentifier::Address(helpers::ACC_1),
+                BlockIdentifier::LastFinal,
+            )
+            .await
+            .expect("The account exists")
+            .response;
+        assert_eq!(account.account_address, helpers::ACC_1);
+        assert_eq!(account.account_amount, Amount::zero());
+        assert_eq!(account.account_nonce, Nonce::from(1));
+
+        let instance = client
+            .get_instance_info(contract, BlockIdentifier::LastFinal)
+            .await
+            .expect("The contract exists")
+            .response;
+        let InstanceInfo::V1 {
+            owner,
+            name,
+            methods,
+            ..
+        } = instance
+        else {
+            panic!("The contract is a V1 contract");
+        };
+        assert_eq!(owner, helpers::ACC_0);
+        assert_eq!(name.as_contract_name().get_chain_name(), "init_transfer");
+        assert!(methods.contains(&OwnedReceiveName::new_unchecked("transfer.deposit".into())));
+
+        let invoke = |method: &str| ContractContext {
+            invoker: None,
+            contract,
+            amount: Amount::from_micro_ccd(10),
+            method: OwnedReceiveName::new_unchecked(method.into()),
+            parameter: OwnedParameter::empty(),
+            energy: None,
+        };
+        let result = client
+            .invoke_instance(BlockIdentifier::LastFinal, &invoke("transfer.deposit"))
+            .await
+            .expect("Invoking works")
+            .response;
+        assert!(matches!(result, InvokeContractResult::Success { .. }));
+        let result = client
+            .invoke_instance(BlockIdentifier::LastFinal, &invoke("transfer.missing"))
+            .await
+            .expect("Invoking works")
+            .response;
+        assert!(matches!(result, InvokeContractResult::Failure {
+            reason: RejectReason::InvalidReceiveMethod { .. },
+            ..
+        }));
+
+        // The invocation does not change the contract.
+        let instance = client
+            .get_instance_info(contract, BlockIdentifier::LastFinal)
+            .await
+            .expect("The contract exists")
+            .response;
+        assert!(matches!(instance, InstanceInfo::V1 { amount, .. } if amount == Amount::zero()));
+
+        assert!(matches!(
+            client.get_instance_info(ContractAddress::new(99, 0), BlockIdentifier::LastFinal).await,
+            Err(v2::QueryError::NotFound)
+        ));
+        // Methods that are not supported by the emulator fail.
+        assert!(client.get_consensus_info().await.is_err());
+    });
+}
+
+/// Test that a signed transfer is executed and that its status can be queried.
+#[test]
+fn test_send_transaction() {
+    let (emulator, _) = emulator();
+    let sender = emulator.chain().account(helpers::ACC_0).unwrap();
+    let keys = sender.signing_keys().unwrap().clone();
+    let nonce = sender.next_nonce();
+    let (runtime, mut client) = connect(emulator);
+    let transaction = BlockItem::AccountTransaction(send::transfer(
+        &keys,
+        helpers::ACC_0,
+        nonce,
+        TransactionTime::from_seconds(100),
+        helpers::ACC_1,
+        Amount::from_ccd(10),
+    ));
+
+    runtime.block_on(async {
+        let hash =
+            client.send_block_item(&transaction).await.expect("Sending the transaction works");
+        assert_eq!(hash, transaction.hash());
+
+        let status = client.get_block_item_status(&hash).await.expect("The transaction is known");
+        let (_, summary) = status.is_finalized().expect("The transaction is finalized");
+        assert!(matches!(
+            &summary.details,
+            BlockItemSummaryDetails::AccountTransaction(AccountTransactionDetails {
+                effects: AccountTransactionEffects::AccountTransfer { amount, to },
+                ..
+            }) if *amount == Amount::from_ccd(10) && *to == helpers::ACC_1
+        ));
+        let receiver = client
+            .get_account_info(
+                &AccountIdentifier::Address(helpers::ACC_1),
+                BlockIdentifier::LastFinal,
+            )
+            .await
+            .expect("The account exists")
+            .response;
+        assert_eq!(receiver.account_amount, Amount::from_ccd(10));
+
+        // The same transaction cannot be sent twice.
+        assert!(client.send_block_item(&transaction).await.is_err());
+    });
+}
@@ -0,0 +1,63 @@
+//! This module contains tests for the energy profiles of contract updates.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Test that the energy profile accounts for all the energy used, and that the
+/// call stacks include the contract that was called.
+#[test]
+fn test_energy_profile_of_transfer() {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(10000)));
+
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file("transfer.wasm"))
+                .expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   res_deploy.module_reference,
+                init_name: OwnedContractName::new_unchecked("init_transfer".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Initializing valid contract should work");
+
+    let res_update = chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address:      res_init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("transfer.forward".into()),
+                message:      OwnedParameter::from_serial(&helpers::ACC_0)
+                    .expect("Parameter has valid size"),
+                amount:       Amount::from_micro_ccd(123),
+            },
+        )
+        .expect("Updating contract should succeed");
+
+    let profile = res_update.energy_profile();
+    assert_eq!(profile.total(), res_update.energy_used.energy * 1000);
+    assert_eq!(profile.per_contract().keys().collect::<Vec<_>>(), [&res_init.contract_address]);
+
+    let folded = profile.to_folded_stacks();
+    assert!(folded
+        .lines()
+        .all(|line| line.starts_with("<0,0>.forward ") || line.starts_with("storage ")));
+
+    let json = profile.to_json();
+    assert_eq!(json["total"], profile.total());
+    assert_eq!(json["entrypoints"][0]["entrypoint"], "forward");
+}
@@ -0,0 +1,57 @@
+//! This module tests the fuzz target for receive entrypoints.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Test that arbitrary inputs to an entrypoint that transfers CCD never create
+/// or lose CCD, and that the inputs do not affect each other.
+#[test]
+fn test_entrypoint_fuzz_target() {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(10000)));
+    chain.create_account(Account::new(helpers::ACC_1, Amount::from_ccd(10000)));
+
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file("transfer.wasm"))
+                .expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   res_deploy.module_reference,
+                init_name: OwnedContractName::new_unchecked("init_transfer".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Initializing valid contract should work");
+
+    let mut target = EntrypointFuzzTarget::new(
+        chain,
+        helpers::ACC_0,
+        res_init.contract_address,
+        OwnedReceiveName::new_unchecked("transfer.forward".into()),
+    )
+    .amount(Amount::from_micro_ccd(123));
+    let initial_balance = target.chain().account_balance_available(helpers::ACC_1);
+
+    let inputs: [&[u8]; 4] = [&[], &[1; 32], &[0; 31], &[2; 100]];
+    for input in inputs {
+        let finding = target.execute(input);
+        assert!(
+            !matches!(finding, Some(EntrypointFuzzFinding::BalanceInconsistency { .. })),
+            "Unexpected finding: {finding:?}"
+        );
+    }
+    // The forward to ACC_1 is undone before the next input is executed.
+    target.execute(&[1; 32]);
+    target.execute(&[]);
+    assert_eq!(target.chain().account_balance_available(helpers::ACC_1), initial_balance);
+}
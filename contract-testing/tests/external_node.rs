@@ -0,0 +1,83 @@
+//! This module tests external accounts and contracts with responses recorded
+//! in the fixture file `tests/fixtures/external_node.json`, so it runs without
+//! network access.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// The fixture file with the recorded responses.
+const FIXTURE: &str = "tests/fixtures/external_node.json";
+
+/// The block in which the responses were recorded.
+fn query_block() -> BlockHash { BlockHash::new([1; 32]) }
+
+/// The external contract in the fixture.
+const CONTRACT: ContractAddress = ContractAddress::new(5, 0);
+
+/// Build a chain which uses the fixture as external node.
+fn fixture_chain() -> Chain {
+    Chain::builder()
+        .external_node_backend(FixtureNode::replay(FIXTURE).expect("Loading the fixture works"))
+        .external_query_block(query_block())
+        .build()
+        .expect("Building the chain from the fixture works")
+}
+
+/// Test that external accounts and contracts are added and that an external
+/// contract is invoked with the recorded response.
+#[test]
+fn test_external_invoke() {
+    let mut chain = fixture_chain();
+    assert_eq!(chain.external_query_block().unwrap(), query_block());
+    let account = chain.add_external_account(helpers::ACC_0).expect("The account is recorded");
+    let contract = chain.add_external_contract(CONTRACT).expect("The contract is recorded");
+
+    let success = chain
+        .contract_invoke_external(
+            Some(ExternalAddress::Account(account)),
+            Energy::from(10000),
+            InvokeExternalContractPayload {
+                amount:       Amount::zero(),
+                address:      contract,
+                receive_name: OwnedReceiveName::new_unchecked("counter.view".into()),
+                message:      OwnedParameter::empty(),
+            },
+            None,
+        )
+        .expect("The invocation succeeds");
+    assert_eq!(success.return_value, 5u64.to_le_bytes());
+    assert_eq!(success.energy_used, Energy::from(1234));
+    assert!(success.trace_elements.is_empty());
+
+    // Queries that were not recorded fail instead of querying a node.
+    assert!(matches!(
+        chain.add_external_account(helpers::ACC_1),
+        Err(ExternalNodeError::NotRecorded { .. })
+    ));
+}
+
+/// Test that the recorded response of an invocation is deserialized into an
+/// [`InvokeContractResult`] that serializes to the same JSON.
+#[test]
+fn test_invoke_instance_round_trip() {
+    let contents = std::fs::read(FIXTURE).expect("The fixture exists");
+    let responses: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_slice(&contents).expect("The fixture is valid JSON");
+    let recorded = responses
+        .iter()
+        .find_map(|(query, response)| query.starts_with("invoke_instance ").then_some(response))
+        .expect("An invocation is recorded");
+
+    let fixture = FixtureNode::replay(FIXTURE).expect("Loading the fixture works");
+    let result = fixture
+        .invoke_instance(query_block(), &ContractContext {
+            invoker:   Some(Address::Account(helpers::ACC_0)),
+            contract:  CONTRACT,
+            amount:    Amount::zero(),
+            method:    OwnedReceiveName::new_unchecked("counter.view".into()),
+            parameter: OwnedParameter::empty(),
+            energy:    Some(Energy::from(10000)),
+        })
+        .expect("The invocation is recorded");
+    assert!(matches!(result, InvokeContractResult::Success { .. }));
+    assert_eq!(&serde_json::to_value(&result).expect("The result serializes"), recorded);
+}
@@ -0,0 +1,204 @@
+//! This module tests fork mode, where contracts, accounts and modules are
+//! lazily loaded from an external node.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// The index of the contract on the mocked node.
+const CONTRACT_INDEX: u64 = 7;
+
+/// An external node with the `transfer` contract at [`CONTRACT_INDEX`] and the
+/// accounts [`helpers::ACC_0`] and [`helpers::ACC_1`]. The contracts with
+/// smaller indices exist as well.
+#[derive(Debug)]
+struct MockNode {
+    module: WasmModule,
+}
+
+impl MockNode {
+    fn new() -> Self {
+        Self {
+            module: module_load_v1_raw(helpers::wasm_test_file("transfer.wasm"))
+                .expect("module should exist"),
+        }
+    }
+}
+
+impl ExternalNodeBackend for MockNode {
+    fn block_hash(&self, _block: Option<BlockHash>) -> Result<BlockHash, ExternalNodeError> {
+        Ok(BlockHash::new([1; 32]))
+    }
+
+    fn block_time(&self, _block: BlockHash) -> Result<Timestamp, ExternalNodeError> {
+        Ok(Timestamp::from_timestamp_millis(0))
+    }
+
+    fn exchange_rates(&self, _block: BlockHash) -> Result<ExchangeRates, ExternalNodeError> {
+        Ok(ExchangeRates {
+            euro_per_energy:    ExchangeRate::new_unchecked(1, 50000),
+            micro_ccd_per_euro: ExchangeRate::new_unchecked(50000, 1),
+        })
+    }
+
+    fn check_account(
+        &self,
+        _block: BlockHash,
+        _address: AccountAddress,
+    ) -> Result<(), ExternalNodeError> {
+        Ok(())
+    }
+
+    fn check_contract(
+        &self,
+        _block: BlockHash,
+        _address: ContractAddress,
+    ) -> Result<(), ExternalNodeError> {
+        Ok(())
+    }
+
+    fn invoke_instance(
+        &self,
+        _block: BlockHash,
+        _context: &ContractContext,
+    ) -> Result<InvokeContractResult, ExternalNodeError> {
+        Err(ExternalNodeError::NotRecorded {
+            query: "invoke_instance".into(),
+        })
+    }
+
+    fn contract_exists(
+        &self,
+        _block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<bool, ExternalNodeError> {
+        Ok(address.index <= CONTRACT_INDEX && address.subindex == 0)
+    }
+
+    fn module_source(
+        &self,
+        _block: BlockHash,
+        module_reference: ModuleReference,
+    ) -> Result<Option<WasmModule>, ExternalNodeError> {
+        Ok((module_reference == self.module.get_module_ref()).then(|| self.module.clone()))
+    }
+
+    fn instance(
+        &self,
+        _block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<Option<ExternalInstance>, ExternalNodeError> {
+        Ok((address == ContractAddress::new(CONTRACT_INDEX, 0)).then(|| ExternalInstance {
+            module_reference: self.module.get_module_ref(),
+            contract_name:    OwnedContractName::new_unchecked("init_transfer".into()),
+            owner:            helpers::ACC_0,
+            self_balance:     Amount::from_micro_ccd(1000),
+            state:            Default::default(),
+        }))
+    }
+
+    fn account_balance(
+        &self,
+        _block: BlockHash,
+        address: AccountAddress,
+    ) -> Result<Option<AccountBalance>, ExternalNodeError> {
+        let total = if address == helpers::ACC_0 {
+            Amount::from_ccd(1000)
+        } else if address == helpers::ACC_1 {
+            Amount::zero()
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(AccountBalance {
+            total,
+            staked: Amount::zero(),
+            locked: Amount::zero(),
+        }))
+    }
+
+    fn transaction(&self, transaction: TransactionHash) -> Result<ReplayTransaction, ReplayError> {
+        Err(ReplayError::NotFinalized(transaction))
+    }
+}
+
+/// Build a chain that is a fork of the [`MockNode`].
+fn fork_chain() -> Chain {
+    Chain::builder()
+        .external_node_backend(MockNode::new())
+        .fork()
+        .build()
+        .expect("Building the fork works")
+}
+
+/// Test that an update loads the contract and the accounts it uses, including
+/// the receiver of a transfer from the contract, and that the changes are
+/// kept locally.
+#[test]
+fn test_fork_update() {
+    let mut chain = fork_chain();
+    let contract = ContractAddress::new(CONTRACT_INDEX, 0);
+    assert!(chain.is_fork());
+    assert!(!chain.contract_exists(contract));
+
+    let parameter = OwnedParameter::from_serial(&(helpers::ACC_1, Amount::from_micro_ccd(17)))
+        .expect("Parameter has valid size");
+    chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address:      contract,
+                receive_name: OwnedReceiveName::new_unchecked("transfer.send".into()),
+                message:      parameter,
+                amount:       Amount::zero(),
+            },
+        )
+        .expect("Updating the forked contract should succeed");
+
+    assert_eq!(chain.contract_balance(contract), Some(Amount::from_micro_ccd(1000 - 17)));
+    assert_eq!(chain.account_balance_available(helpers::ACC_1), Some(Amount::from_micro_ccd(17)));
+}
+
+/// Test that a contract initialized from a forked module gets an index after
+/// the contracts on the external node.
+#[test]
+fn test_fork_init() {
+    let mut chain = fork_chain();
+    let module_reference = MockNode::new().module.get_module_ref();
+
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   module_reference,
+                init_name: OwnedContractName::new_unchecked("init_transfer".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Initializing from the forked module should work");
+
+    assert_eq!(res_init.contract_address, ContractAddress::new(CONTRACT_INDEX + 1, 0));
+}
+
+/// Test that accounts and contracts that do not exist on the external node
+/// are not loaded.
+#[test]
+fn test_fork_load_missing() {
+    let mut chain = fork_chain();
+    assert!(chain.fork_load_account(helpers::ACC_1).unwrap());
+    assert!(!chain.fork_load_account(AccountAddress([9; 32])).unwrap());
+    assert!(!chain.fork_load_contract(ContractAddress::new(CONTRACT_INDEX + 1, 0)).unwrap());
+    assert!(!chain.contract_exists(ContractAddress::new(CONTRACT_INDEX + 1, 0)));
+}
+
+/// Test that fork mode requires an external node.
+#[test]
+fn test_fork_without_external_node() {
+    assert!(matches!(
+        Chain::builder().fork().build(),
+        Err(ChainBuilderError::ForkWithoutExternalNode)
+    ));
+}
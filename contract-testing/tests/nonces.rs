@@ -0,0 +1,146 @@
+//! This module tests that transactions use the nonces of their senders, and
+//! that transactions submitted with an explicit nonce and expiry time are
+//! rejected like the node does.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Create a transfer of one CCD from `ACC_0` to `ACC_1` with the given nonce
+/// and expiry time in seconds.
+fn transfer(nonce: u64, expiry: u64) -> Transaction {
+    Transaction {
+        signer:          Signer::with_one_key(),
+        sender:          helpers::ACC_0,
+        nonce:           Nonce::from(nonce),
+        expiry:          TransactionTime::from_seconds(expiry),
+        energy_reserved: Energy::from(0),
+        payload:         Payload::Transfer {
+            to_address: helpers::ACC_1,
+            amount:     Amount::from_ccd(1),
+        },
+    }
+}
+
+/// Test that each transaction that is charged a fee uses a nonce, whether it
+/// succeeds or not.
+#[test]
+fn test_transactions_use_nonces() {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(1000)));
+    chain.create_account(Account::new(helpers::ACC_1, Amount::zero()));
+    let next_nonce = |chain: &Chain, address| chain.account(address).unwrap().next_nonce();
+    assert_eq!(next_nonce(&chain, helpers::ACC_0), Nonce::from(1));
+
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file("call-counter.wasm"))
+                .expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   res_deploy.module_reference,
+                init_name: OwnedContractName::new_unchecked("init_counter".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Initializing valid contract should work");
+    assert_eq!(next_nonce(&chain, helpers::ACC_0), Nonce::from(3));
+
+    // A rejected update is charged, so it also uses a nonce.
+    chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address:      res_init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("counter.missing".into()),
+                message:      OwnedParameter::empty(),
+                amount:       Amount::zero(),
+            },
+        )
+        .expect_err("Calling a missing entrypoint should fail");
+    assert_eq!(next_nonce(&chain, helpers::ACC_0), Nonce::from(4));
+
+    // A transfer that the sender cannot pay for is not included in a block.
+    chain
+        .account_transfer(Signer::with_one_key(), helpers::ACC_1, helpers::ACC_0, Amount::zero())
+        .expect_err("The sender cannot pay the fee");
+    assert_eq!(next_nonce(&chain, helpers::ACC_1), Nonce::from(1));
+    chain
+        .account_transfer(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            helpers::ACC_1,
+            Amount::from_ccd(1),
+        )
+        .expect("Transfer should work");
+    assert_eq!(next_nonce(&chain, helpers::ACC_0), Nonce::from(5));
+}
+
+/// Test that replayed, out of order and expired transactions are not accepted,
+/// and that rejected submissions are not charged.
+#[test]
+fn test_submit_transaction() {
+    let mut chain = Chain::new_with_time(Timestamp::from_timestamp_millis(10_000));
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(1000)));
+    chain.create_account(Account::new(helpers::ACC_1, Amount::zero()));
+
+    // Out of order.
+    assert_eq!(
+        chain.submit_transaction(transfer(2, 100)).expect_err("Nonce gap should fail"),
+        TransactionVerificationError::NonceTooLarge {
+            nonce:      Nonce::from(2),
+            next_nonce: Nonce::from(1),
+        }
+    );
+    // Expired.
+    assert!(matches!(
+        chain.submit_transaction(transfer(1, 5)),
+        Err(TransactionVerificationError::Expired { .. })
+    ));
+    assert_eq!(chain.account_balance_available(helpers::ACC_0), Some(Amount::from_ccd(1000)));
+
+    let outcome = chain.submit_transaction(transfer(1, 100)).expect("Transfer is accepted");
+    assert!(outcome.is_success());
+    assert_eq!(
+        chain.account_balance_available(helpers::ACC_0),
+        Some(Amount::from_ccd(999) - outcome.transaction_fee())
+    );
+    chain.submit_transaction(transfer(2, 100)).expect("The next nonce is accepted");
+
+    // Replayed.
+    assert_eq!(
+        chain.submit_transaction(transfer(1, 100)).expect_err("Replay should fail"),
+        TransactionVerificationError::DuplicateNonce {
+            nonce:      Nonce::from(1),
+            next_nonce: Nonce::from(3),
+        }
+    );
+
+    // Unknown senders.
+    assert_eq!(
+        chain
+            .submit_transaction(Transaction {
+                sender: AccountAddress([2; 32]),
+                ..transfer(1, 100)
+            })
+            .expect_err("Unknown sender should fail"),
+        TransactionVerificationError::SenderDoesNotExist(AccountAddress([2; 32]))
+    );
+
+    // Unsigned transactions are not accepted in strict mode.
+    chain.set_strict_transactions(true);
+    assert_eq!(
+        chain.submit_transaction(transfer(3, 100)).expect_err("Strict mode should fail"),
+        TransactionVerificationError::UnsignedTransaction
+    );
+}
@@ -0,0 +1,93 @@
+//! This module tests saving a chain with deployed modules and contract
+//! instances to a file and loading it again.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Update the `fib` contract with the parameter `n`, which stores the `n`th
+/// Fibonacci number in the state and returns it.
+fn update_fib(chain: &mut Chain, address: ContractAddress, n: u64) -> Vec<u8> {
+    chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(100000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address,
+                receive_name: OwnedReceiveName::new_unchecked("fib.receive".into()),
+                message: OwnedParameter::from_serial(&n).expect("Parameter has valid size"),
+            },
+        )
+        .expect("Updating valid contract should work")
+        .return_value
+}
+
+/// Invoke the `fib.view` entrypoint, which returns the number in the state.
+fn view_fib(chain: &Chain, address: ContractAddress) -> Vec<u8> {
+    chain
+        .contract_invoke(
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                amount: Amount::zero(),
+                address,
+                receive_name: OwnedReceiveName::new_unchecked("fib.view".into()),
+                message: OwnedParameter::empty(),
+            },
+        )
+        .expect("Invoking view should work")
+        .return_value
+}
+
+/// Test that the loaded chain has the same modules and contract states as the
+/// saved chain, and that the loaded contract can be invoked and updated.
+#[test]
+fn test_save_and_load_contracts() {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(100_000)));
+    let deployment = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file("fib.wasm")).expect("Module should exist."),
+        )
+        .expect("Deploying valid module should work");
+    let init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                amount:    Amount::zero(),
+                mod_ref:   deployment.module_reference,
+                init_name: OwnedContractName::new_unchecked("init_fib".into()),
+                param:     OwnedParameter::empty(),
+            },
+        )
+        .expect("Initializing valid contract should work");
+    let address = init.contract_address;
+    assert_eq!(update_fib(&mut chain, address, 6), u64::to_le_bytes(13));
+
+    let path = std::env::temp_dir()
+        .join(format!("concordium-persistence-contracts-{}.chain", std::process::id()));
+    chain.save_to(&path).expect("Saving the chain works");
+    let mut loaded = Chain::load_from(&path).expect("Loading the chain works");
+    let _ = std::fs::remove_file(&path);
+
+    assert!(loaded.modules.contains_key(&deployment.module_reference));
+    let state_entries =
+        |chain: &Chain| chain.contract_state_api(address).expect("The contract exists").entries();
+    assert_eq!(state_entries(&loaded), state_entries(&chain));
+    assert_eq!(view_fib(&loaded, address), view_fib(&chain, address));
+    assert_eq!(
+        loaded.account_balance_available(helpers::ACC_0),
+        chain.account_balance_available(helpers::ACC_0)
+    );
+
+    // The loaded contract can be updated like the original.
+    assert_eq!(update_fib(&mut loaded, address, 7), update_fib(&mut chain, address, 7));
+    assert_eq!(view_fib(&loaded, address), u64::to_le_bytes(21));
+    assert_eq!(state_entries(&loaded), state_entries(&chain));
+}
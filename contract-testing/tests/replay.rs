@@ -0,0 +1,218 @@
+//! This module tests replaying transactions from a mocked node.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// An external node with one transaction, which updates the `transfer`
+/// contract, and the state of its parent block. The instance is left out with
+/// `without_instance`.
+#[derive(Debug)]
+struct MockNode {
+    transaction: ReplayTransaction,
+    module:      WasmModule,
+    instance:    Option<ExternalInstance>,
+}
+
+impl MockNode {
+    fn new(transaction: ReplayTransaction) -> Self {
+        let module = module_load_v1_raw(helpers::wasm_test_file("transfer.wasm"))
+            .expect("module should exist");
+        let instance = ExternalInstance {
+            module_reference: module.get_module_ref(),
+            contract_name:    OwnedContractName::new_unchecked("init_transfer".into()),
+            owner:            helpers::ACC_0,
+            self_balance:     Amount::zero(),
+            state:            Default::default(),
+        };
+        Self {
+            transaction,
+            module,
+            instance: Some(instance),
+        }
+    }
+
+    fn without_instance(self) -> Self {
+        Self {
+            instance: None,
+            ..self
+        }
+    }
+
+    /// Check that a query is made in the parent block of the transaction.
+    fn check_block(&self, block: BlockHash) {
+        assert_eq!(block, self.transaction.parent_block, "Queries are made in the parent block");
+    }
+}
+
+impl ExternalNodeBackend for MockNode {
+    fn block_hash(&self, _block: Option<BlockHash>) -> Result<BlockHash, ExternalNodeError> {
+        Ok(self.transaction.block)
+    }
+
+    fn block_time(&self, _block: BlockHash) -> Result<Timestamp, ExternalNodeError> {
+        Ok(self.transaction.block_time)
+    }
+
+    fn exchange_rates(&self, _block: BlockHash) -> Result<ExchangeRates, ExternalNodeError> {
+        Ok(ExchangeRates {
+            euro_per_energy:    self.transaction.euro_per_energy,
+            micro_ccd_per_euro: self.transaction.micro_ccd_per_euro,
+        })
+    }
+
+    fn check_account(
+        &self,
+        _block: BlockHash,
+        _address: AccountAddress,
+    ) -> Result<(), ExternalNodeError> {
+        Ok(())
+    }
+
+    fn check_contract(
+        &self,
+        _block: BlockHash,
+        _address: ContractAddress,
+    ) -> Result<(), ExternalNodeError> {
+        Ok(())
+    }
+
+    fn invoke_instance(
+        &self,
+        _block: BlockHash,
+        _context: &ContractContext,
+    ) -> Result<InvokeContractResult, ExternalNodeError> {
+        Err(ExternalNodeError::NotRecorded {
+            query: "invoke_instance".into(),
+        })
+    }
+
+    fn contract_exists(
+        &self,
+        _block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<bool, ExternalNodeError> {
+        Ok(self.transaction.contracts.contains(&address))
+    }
+
+    fn module_source(
+        &self,
+        block: BlockHash,
+        module_reference: ModuleReference,
+    ) -> Result<Option<WasmModule>, ExternalNodeError> {
+        self.check_block(block);
+        Ok((module_reference == self.module.get_module_ref()).then(|| self.module.clone()))
+    }
+
+    fn instance(
+        &self,
+        block: BlockHash,
+        address: ContractAddress,
+    ) -> Result<Option<ExternalInstance>, ExternalNodeError> {
+        self.check_block(block);
+        Ok(self.instance.clone().filter(|_| self.transaction.contracts.contains(&address)))
+    }
+
+    fn account_balance(
+        &self,
+        block: BlockHash,
+        address: AccountAddress,
+    ) -> Result<Option<AccountBalance>, ExternalNodeError> {
+        self.check_block(block);
+        let total = if address == helpers::ACC_0 {
+            Amount::from_ccd(1000)
+        } else if address == helpers::ACC_1 {
+            Amount::zero()
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(AccountBalance {
+            total,
+            staked: Amount::zero(),
+            locked: Amount::zero(),
+        }))
+    }
+
+    fn transaction(&self, transaction: TransactionHash) -> Result<ReplayTransaction, ReplayError> {
+        if transaction == self.transaction.hash {
+            Ok(self.transaction.clone())
+        } else {
+            Err(ReplayError::NotFinalized(transaction))
+        }
+    }
+}
+
+/// Test that an update is replayed on the state of its parent block on the
+/// node, with the block time of the original transaction.
+#[test]
+fn test_replay_update() {
+    let address = ContractAddress::new(5, 0);
+    let hash = TransactionHash::new([3; 32]);
+    let node =
+        MockNode::new(update_transaction(hash, BlockHash::new([2; 32]), UpdateContractPayload {
+            address,
+            receive_name: OwnedReceiveName::new_unchecked("transfer.forward".into()),
+            message: OwnedParameter::from_serial(&helpers::ACC_1)
+                .expect("Parameter has valid size"),
+            amount: Amount::from_micro_ccd(123),
+        }));
+
+    let replayed = Chain::replay_transaction(&node, hash).expect("Replaying should succeed");
+    let ReplayResult::Update(result) = &replayed.result else {
+        panic!("The transaction is an update");
+    };
+    result.as_ref().expect("The update should succeed");
+    assert_eq!(replayed.chain.block_time(), Timestamp::from_timestamp_millis(1000));
+    assert_eq!(
+        replayed.chain.account_balance_available(helpers::ACC_1),
+        Some(Amount::from_micro_ccd(123))
+    );
+    assert!(replayed.chain.get_contract(address).is_some());
+}
+
+/// Test that replaying fails if a contract involved in the transaction does
+/// not exist on the node.
+#[test]
+fn test_replay_missing_instance() {
+    let hash = TransactionHash::new([3; 32]);
+    let node =
+        MockNode::new(update_transaction(hash, BlockHash::new([2; 32]), UpdateContractPayload {
+            address:      ContractAddress::new(5, 0),
+            receive_name: OwnedReceiveName::new_unchecked("transfer.forward".into()),
+            message:      OwnedParameter::empty(),
+            amount:       Amount::zero(),
+        }))
+        .without_instance();
+    assert!(matches!(
+        Chain::replay_transaction(&node, hash),
+        Err(ReplayError::Node(ExternalNodeError::QueryError { .. }))
+    ));
+    assert!(matches!(
+        Chain::replay_transaction(&node, TransactionHash::new([4; 32])),
+        Err(ReplayError::NotFinalized(_))
+    ));
+}
+
+/// Create an update transaction sent by `ACC_0`, which involves the contract
+/// updated and `ACC_1`.
+fn update_transaction(
+    hash: TransactionHash,
+    parent_block: BlockHash,
+    payload: UpdateContractPayload,
+) -> ReplayTransaction {
+    ReplayTransaction {
+        hash,
+        block: BlockHash::new([1; 32]),
+        parent_block,
+        block_time: Timestamp::from_timestamp_millis(1000),
+        euro_per_energy: ExchangeRate::new_unchecked(1, 50000),
+        micro_ccd_per_euro: ExchangeRate::new_unchecked(50000, 1),
+        sender: helpers::ACC_0,
+        num_signatures: 1,
+        energy_reserved: Energy::from(10000),
+        contracts: vec![payload.address],
+        payload: ReplayPayload::Update {
+            payload,
+        },
+        modules: Vec::new(),
+        accounts: vec![helpers::ACC_1],
+    }
+}
@@ -0,0 +1,165 @@
+//! This module tests running scenarios with contract steps, where the modules
+//! are loaded from files with an embedded schema.
+use concordium_rust_sdk::base::{
+    contracts_common::schema::{ContractV3, FunctionV2, ModuleV3, Type, VersionedModuleSchema},
+    smart_contracts::ModuleSource,
+};
+use concordium_smart_contract_testing::*;
+use std::path::{Path, PathBuf};
+mod helpers;
+
+/// Encode a number as unsigned LEB128, which is used for sizes in Wasm.
+fn leb128(mut value: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return out;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Load a test module, embed the schema in it and write it to `dir` as a
+/// versioned module file, which is the format produced by `cargo concordium
+/// build`.
+fn write_module(dir: &Path, file_name: &str, schema: &VersionedModuleSchema) {
+    let mut source =
+        std::fs::read(helpers::wasm_test_file(file_name)).expect("module should exist");
+    let name = b"concordium-schema";
+    let mut contents = leb128(name.len());
+    contents.extend_from_slice(name);
+    contents.extend_from_slice(&to_bytes(schema));
+    // A custom section has id 0.
+    source.push(0);
+    source.extend_from_slice(&leb128(contents.len()));
+    source.extend_from_slice(&contents);
+    let module = WasmModule {
+        version: WasmVersion::V1,
+        source:  ModuleSource::from(source),
+    };
+    std::fs::write(dir.join(file_name), to_bytes(&module)).expect("Writing the module works");
+}
+
+/// Create a directory with the `fib` and `caller` modules and a scenario file
+/// with the contents.
+fn scenario_dir(test_name: &str, scenario: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("concordium-scenario-{test_name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("Creating the directory works");
+    let fib_schema = VersionedModuleSchema::V3(ModuleV3 {
+        contracts: [("fib".to_string(), ContractV3 {
+            init:    None,
+            receive: [
+                ("receive".to_string(), FunctionV2 {
+                    parameter:    Some(Type::U64),
+                    error:        None,
+                    return_value: Some(Type::U64),
+                }),
+                ("view".to_string(), FunctionV2 {
+                    parameter:    None,
+                    error:        None,
+                    return_value: Some(Type::U64),
+                }),
+            ]
+            .into(),
+            event:   None,
+        })]
+        .into(),
+    });
+    write_module(&dir, "fib.wasm", &fib_schema);
+    // The caller contract is used without parameters, so the schema is empty.
+    let caller_schema = VersionedModuleSchema::V3(ModuleV3 {
+        contracts: Default::default(),
+    });
+    write_module(&dir, "caller.wasm", &caller_schema);
+    std::fs::write(dir.join("scenario.yaml"), scenario).expect("Writing the scenario works");
+    dir
+}
+
+/// The scenario used in the tests, where `{fib_expect}` is replaced with the
+/// expectation of the update of `fib`.
+const SCENARIO: &str = r#"
+name: Contracts
+accounts:
+  alice:
+    balance: "1000"
+modules:
+  fib:
+    path: fib.wasm
+    sender: alice
+  caller:
+    path: caller.wasm
+    sender: alice
+steps:
+  - init:
+      sender: alice
+      module: fib
+      contract: fib
+      instance: fib
+  - update:
+      sender: alice
+      instance: fib
+      entrypoint: receive
+      param: 6
+      expect: {fib_expect}
+  - invoke:
+      sender: alice
+      instance: fib
+      entrypoint: view
+      expect:
+        returnValue: 13
+  - init:
+      sender: alice
+      module: caller
+      contract: caller
+      instance: caller
+  - update:
+      sender: alice
+      instance: caller
+      entrypoint: fail
+      expect:
+        rejectCode: -17
+  - expectBalances:
+      instances:
+        fib: "0"
+        caller: "0"
+"#;
+
+/// Test that init and update steps are executed, and that the return values
+/// and reject codes are checked.
+#[test]
+fn test_scenario_contract_steps() {
+    let dir = scenario_dir("steps", &SCENARIO.replace("{fib_expect}", "\n        returnValue: 13"));
+    let scenario = Scenario::from_file(dir.join("scenario.yaml")).expect("Scenario is valid");
+    let chain = scenario.run().expect("Scenario succeeds");
+    // The caller instance is the second instance.
+    assert!(chain.get_contract(ContractAddress::new(1, 0)).is_some());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Test that a return value that differs from the expectation, and a step
+/// that succeeds when it is expected to reject, fail the scenario.
+#[test]
+fn test_scenario_unmet_expectations() {
+    let dir = scenario_dir(
+        "return-value",
+        &SCENARIO.replace("{fib_expect}", "\n        returnValue: 14"),
+    );
+    let scenario = Scenario::from_file(dir.join("scenario.yaml")).expect("Scenario is valid");
+    let err = scenario.run().expect_err("The return value differs");
+    assert_eq!(err.location, "at step 2 (update)");
+    assert!(
+        matches!(err.kind, ScenarioErrorKind::Mismatch { ref what, .. } if what == "return value")
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let dir = scenario_dir("reject", &SCENARIO.replace("{fib_expect}", "\n        rejectCode: -1"));
+    let scenario = Scenario::from_file(dir.join("scenario.yaml")).expect("Scenario is valid");
+    let err = scenario.run().expect_err("The update does not reject");
+    assert_eq!(err.location, "at step 2 (update)");
+    assert!(matches!(err.kind, ScenarioErrorKind::UnexpectedSuccess));
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,127 @@
+//! This module tests contract calls where the parameters and return values are
+//! converted with the schema embedded in the module, as done by `cargo
+//! concordium build --schema-embed`.
+use concordium_rust_sdk::base::{
+    contracts_common::schema::{ContractV3, FunctionV2, ModuleV3, Type, VersionedModuleSchema},
+    smart_contracts::ModuleSource,
+};
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Encode a number as unsigned LEB128, which is used for sizes in Wasm.
+fn leb128(mut value: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return out;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Load a test module and embed the schema in it, like `cargo concordium build
+/// --schema-embed` does.
+fn module_with_schema(file_name: &str, schema: &VersionedModuleSchema) -> WasmModule {
+    let mut source =
+        std::fs::read(helpers::wasm_test_file(file_name)).expect("module should exist");
+    let name = b"concordium-schema";
+    let mut contents = leb128(name.len());
+    contents.extend_from_slice(name);
+    contents.extend_from_slice(&to_bytes(schema));
+    // A custom section has id 0.
+    source.push(0);
+    source.extend_from_slice(&leb128(contents.len()));
+    source.extend_from_slice(&contents);
+    WasmModule {
+        version: WasmVersion::V1,
+        source:  ModuleSource::from(source),
+    }
+}
+
+/// Test that an update whose return value does not match the schema is still
+/// reported as a successful update, which has been executed and charged.
+#[test]
+fn test_update_json_undecodable_return_value() {
+    let mut chain = Chain::new();
+    let initial_balance = Amount::from_ccd(10000);
+    chain.create_account(Account::new(helpers::ACC_0, initial_balance));
+
+    // An enum without variants cannot be decoded from any bytes.
+    let schema = VersionedModuleSchema::V3(ModuleV3 {
+        contracts: [("counter".to_string(), ContractV3 {
+            init:    None,
+            receive: [("inc".to_string(), FunctionV2 {
+                parameter:    None,
+                error:        None,
+                return_value: Some(Type::Enum(Vec::new())),
+            })]
+            .into(),
+            event:   None,
+        })]
+        .into(),
+    });
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_with_schema("call-counter.wasm", &schema),
+        )
+        .expect("Deploying valid module should work");
+    let module = chain.get_module(res_deploy.module_reference).expect("Module exists");
+    assert!(module.embedded_schema().is_ok());
+
+    let res_init = chain
+        .contract_init_json(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractJsonPayload {
+                amount:    Amount::zero(),
+                mod_ref:   res_deploy.module_reference,
+                init_name: OwnedContractName::new_unchecked("init_counter".into()),
+                param:     serde_json::Value::Null,
+            },
+        )
+        .expect("Initializing valid contract should work");
+
+    let balance_before = chain.account_balance_available(helpers::ACC_0).unwrap();
+    let res_update = chain
+        .contract_update_json(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractJsonPayload {
+                amount:       Amount::zero(),
+                address:      res_init.success.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("counter.inc".into()),
+                message:      serde_json::Value::Null,
+            },
+        )
+        .expect("The update succeeds even though the return value cannot be decoded");
+    assert_eq!(res_update.return_value, Err(ReturnValueDecodeError));
+    assert_eq!(
+        chain.account_balance_available(helpers::ACC_0),
+        Some(balance_before - res_update.success.transaction_fee)
+    );
+
+    // A parameter is not accepted when the schema does not describe it.
+    let err = chain
+        .contract_update_json(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractJsonPayload {
+                amount:       Amount::zero(),
+                address:      res_init.success.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("counter.inc".into()),
+                message:      serde_json::json!(1),
+            },
+        )
+        .expect_err("The parameter has no type in the schema");
+    assert!(matches!(err, ContractJsonError::MissingParameterType(_)));
+}
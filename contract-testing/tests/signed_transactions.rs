@@ -0,0 +1,171 @@
+//! This module tests signed transactions, which are verified against the keys
+//! of the sender account, and strict mode, where the chain only accepts signed
+//! transactions.
+use concordium_rust_sdk::base::transactions::send;
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// The expiry time used for the transactions.
+const EXPIRY: TransactionTime = TransactionTime {
+    seconds: 100,
+};
+
+/// Test deploying, initializing and updating with signed transactions in strict
+/// mode, and that invalid transactions are not accepted.
+#[test]
+fn test_signed_transactions() {
+    let mut chain = Chain::builder().strict_transactions().build().expect("Chain builds");
+    let initial_balance = Amount::from_ccd(1000000);
+    chain.create_account(Account::new_from_seed(helpers::ACC_0, initial_balance, 0));
+    chain.create_account(Account::new_from_seed(helpers::ACC_1, initial_balance, 1));
+    let keys = chain.account(helpers::ACC_0).unwrap().signing_keys().unwrap().clone();
+    let other_keys = chain.account(helpers::ACC_1).unwrap().signing_keys().unwrap().clone();
+    let module = module_load_v1_raw(helpers::wasm_test_file("call-counter.wasm"))
+        .expect("module should exist");
+
+    // Unsigned transactions are not accepted in strict mode.
+    let err = chain
+        .module_deploy_v1(Signer::with_one_key(), helpers::ACC_0, module.clone())
+        .expect_err("Unsigned deployment should fail");
+    assert!(matches!(
+        err.kind,
+        ModuleDeployErrorKind::Verification(TransactionVerificationError::UnsignedTransaction)
+    ));
+
+    let deployment = send::deploy_module(&keys, helpers::ACC_0, 1.into(), EXPIRY, module);
+    let res_deploy =
+        chain.module_deploy_v1_signed(&deployment).expect("Signed deployment should work");
+    assert_eq!(chain.account(helpers::ACC_0).unwrap().next_nonce(), 2.into());
+
+    // The same transaction cannot be executed twice.
+    let err =
+        chain.module_deploy_v1_signed(&deployment).expect_err("Replayed deployment should fail");
+    assert!(matches!(
+        err.kind,
+        ModuleDeployErrorKind::Verification(TransactionVerificationError::DuplicateNonce { .. })
+    ));
+
+    let init_payload = InitContractPayload {
+        mod_ref:   res_deploy.module_reference,
+        init_name: OwnedContractName::new_unchecked("init_counter".into()),
+        param:     OwnedParameter::empty(),
+        amount:    Amount::zero(),
+    };
+    let init = |nonce: u64| {
+        send::init_contract(
+            &keys,
+            helpers::ACC_0,
+            nonce.into(),
+            EXPIRY,
+            init_payload.clone(),
+            Energy::from(10000),
+        )
+    };
+    let err = chain.contract_init_signed(&init(3)).expect_err("Nonce gap should fail");
+    assert!(matches!(
+        err.kind,
+        ContractInitErrorKind::Verification(TransactionVerificationError::NonceTooLarge { .. })
+    ));
+    assert_eq!(err.transaction_fee, Amount::zero());
+    let res_init = chain.contract_init_signed(&init(2)).expect("Signed init should work");
+
+    let update_payload = UpdateContractPayload {
+        address:      res_init.contract_address,
+        receive_name: OwnedReceiveName::new_unchecked("counter.inc".into()),
+        message:      OwnedParameter::empty(),
+        amount:       Amount::zero(),
+    };
+    // Signed with the keys of another account.
+    let update = send::update_contract(
+        &other_keys,
+        helpers::ACC_0,
+        3.into(),
+        EXPIRY,
+        update_payload.clone(),
+        Energy::from(10000),
+    );
+    let err = chain.contract_update_signed(&update).expect_err("Wrong keys should fail");
+    assert!(matches!(
+        err.kind,
+        ContractInvokeErrorKind::Verification(TransactionVerificationError::IncorrectSignature)
+    ));
+
+    // An update given to the wrong method is not accepted.
+    let update = send::update_contract(
+        &keys,
+        helpers::ACC_0,
+        3.into(),
+        EXPIRY,
+        update_payload,
+        Energy::from(10000),
+    );
+    let err = chain.contract_init_signed(&update).expect_err("Wrong payload should fail");
+    assert!(matches!(
+        err.kind,
+        ContractInitErrorKind::Verification(TransactionVerificationError::UnexpectedPayload)
+    ));
+
+    let balance_before = chain.account_balance_available(helpers::ACC_0).unwrap();
+    let res_update = chain.contract_update_signed(&update).expect("Signed update should work");
+    assert_eq!(
+        chain.account_balance_available(helpers::ACC_0),
+        Some(balance_before - res_update.transaction_fee)
+    );
+    assert_eq!(chain.account(helpers::ACC_0).unwrap().next_nonce(), 4.into());
+
+    // Transactions are not accepted after their expiry time.
+    chain.tick_block_time(Duration::from_seconds(101)).expect("Block time does not overflow");
+    let update = send::update_contract(
+        &keys,
+        helpers::ACC_0,
+        4.into(),
+        EXPIRY,
+        UpdateContractPayload {
+            address:      res_init.contract_address,
+            receive_name: OwnedReceiveName::new_unchecked("counter.inc".into()),
+            message:      OwnedParameter::empty(),
+            amount:       Amount::zero(),
+        },
+        Energy::from(10000),
+    );
+    assert!(matches!(
+        chain.verify_transaction(&update),
+        Err(TransactionVerificationError::Expired { .. })
+    ));
+}
+
+/// Test that signed transactions can be used without strict mode, and that they
+/// respect the account thresholds.
+#[test]
+fn test_signed_transactions_thresholds() {
+    let mut chain = Chain::new();
+    let keys = account_keys_from_seed(2, AccountThreshold::TWO, &[
+        (0.into(), SignatureThreshold::ONE, &[0.into()]),
+        (1.into(), SignatureThreshold::ONE, &[0.into()]),
+    ]);
+    chain.create_account(Account::new_with_signing_keys(
+        helpers::ACC_0,
+        AccountBalance {
+            total:  Amount::from_ccd(1000),
+            staked: Amount::zero(),
+            locked: Amount::zero(),
+        },
+        keys.clone(),
+    ));
+    let module = module_load_v1_raw(helpers::wasm_test_file("call-counter.wasm"))
+        .expect("module should exist");
+
+    // Only one of the two credentials signs.
+    let mut single_credential = keys.clone();
+    single_credential.keys.remove(&1.into());
+    let deployment =
+        send::deploy_module(&single_credential, helpers::ACC_0, 1.into(), EXPIRY, module.clone());
+    assert_eq!(
+        chain.verify_transaction(&deployment),
+        Err(TransactionVerificationError::IncorrectSignature)
+    );
+
+    let deployment = send::deploy_module(&keys, helpers::ACC_0, 1.into(), EXPIRY, module);
+    chain.module_deploy_v1_signed(&deployment).expect("Deployment with both keys should work");
+    assert_eq!(chain.account(helpers::ACC_0).unwrap().next_nonce(), 2.into());
+}
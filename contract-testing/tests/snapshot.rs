@@ -0,0 +1,93 @@
+//! This module tests that restoring a snapshot of a chain rolls back modules,
+//! contracts and their states.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Deploy a module from the test folder.
+fn deploy(chain: &mut Chain, file_name: &str) -> ModuleReference {
+    chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file(file_name)).expect("module should exist"),
+        )
+        .expect("Deploying valid module should work")
+        .module_reference
+}
+
+/// Initialize a contract without a parameter.
+fn init(chain: &mut Chain, mod_ref: ModuleReference, init_name: &str) -> ContractAddress {
+    chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref,
+                init_name: OwnedContractName::new_unchecked(init_name.into()),
+                param: OwnedParameter::empty(),
+                amount: Amount::zero(),
+            },
+        )
+        .expect("Initializing valid contract should work")
+        .contract_address
+}
+
+/// Increment the counter of the `call-counter` contract.
+fn increment(chain: &mut Chain, address: ContractAddress) {
+    chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address,
+                receive_name: OwnedReceiveName::new_unchecked("counter.inc".into()),
+                message: OwnedParameter::empty(),
+                amount: Amount::zero(),
+            },
+        )
+        .expect("Updating valid contract should work");
+}
+
+/// Look up the counter in the root of the state of the `call-counter`
+/// contract.
+fn counter(chain: &Chain, address: ContractAddress) -> Option<Vec<u8>> {
+    chain.contract_state_lookup(address, &[0, 0, 0, 0, 0, 0, 0, 0])
+}
+
+/// Test that modules, contracts, contract states and the next contract index
+/// are restored, and that updates after restoring do not affect the snapshot,
+/// even though the state tries are shared.
+#[test]
+fn test_snapshot_and_restore_contracts() {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(10000)));
+    let counter_module = deploy(&mut chain, "call-counter.wasm");
+    let counter_contract = init(&mut chain, counter_module, "init_counter");
+    increment(&mut chain, counter_contract);
+    let snapshot = chain.snapshot();
+
+    let fib_module = deploy(&mut chain, "fib.wasm");
+    let fib_contract = init(&mut chain, fib_module, "init_fib");
+    assert_eq!(fib_contract, ContractAddress::new(1, 0));
+    increment(&mut chain, counter_contract);
+    assert_eq!(counter(&chain, counter_contract), Some(2u64.to_le_bytes().to_vec()));
+
+    for _ in 0..2 {
+        chain.restore(&snapshot);
+        assert!(!chain.modules.contains_key(&fib_module));
+        assert!(chain.modules.contains_key(&counter_module));
+        assert!(chain.get_contract(fib_contract).is_none());
+        assert_eq!(counter(&chain, counter_contract), Some(1u64.to_le_bytes().to_vec()));
+
+        // The index of the removed contract is used again.
+        let contract = init(&mut chain, counter_module, "init_counter");
+        assert_eq!(contract, fib_contract);
+        assert_eq!(counter(&chain, contract), Some(0u64.to_le_bytes().to_vec()));
+        // Modifying the shared state does not change the snapshot.
+        increment(&mut chain, counter_contract);
+        assert_eq!(counter(&chain, counter_contract), Some(2u64.to_le_bytes().to_vec()));
+    }
+}
@@ -0,0 +1,157 @@
+//! This module tests the execution traces and the recorded host calls of
+//! contract updates and invocations.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Test that the trace of a transfer from a contract includes the call, the
+/// host call performing the transfer, the transfer and the return, and that
+/// the arguments and response of the transfer are recorded. The state is not
+/// changed, so no state changes are recorded.
+#[test]
+fn test_execution_trace_of_transfer() {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(10000)));
+
+    let res_deploy = chain
+        .module_deploy_v1_debug(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file("transfer.wasm"))
+                .expect("module should exist"),
+            true,
+        )
+        .expect("Deploying valid module should work");
+
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   res_deploy.module_reference,
+                init_name: OwnedContractName::new_unchecked("init_transfer".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Initializing valid contract should work");
+
+    let traced = chain.contract_update_traced(
+        Signer::with_one_key(),
+        helpers::ACC_0,
+        Address::Account(helpers::ACC_0),
+        Energy::from(10000),
+        UpdateContractPayload {
+            address:      res_init.contract_address,
+            receive_name: OwnedReceiveName::new_unchecked("transfer.forward".into()),
+            message:      OwnedParameter::from_serial(&helpers::ACC_0)
+                .expect("Parameter has valid size"),
+            amount:       Amount::from_micro_ccd(123),
+        },
+    );
+    let update = traced.result.as_ref().expect("Updating contract should succeed");
+    let records = traced.records.iter().map(|record| &record.kind).collect::<Vec<_>>();
+    assert!(
+        traced.records.iter().all(|record| record.depth == 0 && !record.rolled_back),
+        "The records are of the called contract"
+    );
+    assert!(matches!(records[..], [
+        CallRecordKind::Interrupt(HostInterrupt::Transfer {
+            to,
+            amount,
+        }),
+        CallRecordKind::Resumed(HostInterruptResponse::Success {
+            data: None,
+        }),
+    ] if *to == helpers::ACC_0 && *amount == Amount::from_micro_ccd(123)));
+
+    let trace = update.execution_trace();
+    let steps = trace.iter().collect::<Vec<_>>();
+    assert!(steps.iter().all(|step| step.depth == 0
+        && step.address == res_init.contract_address
+        && step.entrypoint == EntrypointName::new_unchecked("forward")
+        && !step.rolled_back));
+    assert!(matches!(steps.first().map(|step| &step.kind), Some(ExecutionTraceStepKind::Enter)));
+    assert!(matches!(steps.last().map(|step| &step.kind), Some(ExecutionTraceStepKind::Exit)));
+    assert!(steps.iter().any(|step| matches!(step.kind, ExecutionTraceStepKind::HostCall { .. })));
+    assert!(steps.iter().any(|step| matches!(
+        step.kind,
+        ExecutionTraceStepKind::TraceElement(ContractTraceElement::Transferred { .. })
+    )));
+    let display = trace.to_string();
+    assert!(display
+        .lines()
+        .any(|line| line.contains("transferred")
+            && line.ends_with(&format!("to {}", helpers::ACC_0))));
+}
+
+/// Test that a call to another contract is recorded with its arguments, and
+/// that the caller is resumed with the reject code of the called contract.
+#[test]
+fn test_invoke_traced_call() {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(10000)));
+
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file("caller.wasm"))
+                .expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   res_deploy.module_reference,
+                init_name: OwnedContractName::new_unchecked("init_caller".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Initializing valid contract should work");
+
+    // Call the "fail" entrypoint of the same contract, which rejects with -17.
+    let parameter = (
+        1u32, // instruction
+        res_init.contract_address,
+        OwnedParameter::empty(),
+        EntrypointName::new_unchecked("fail"),
+        Amount::zero(),
+    );
+    let traced = chain.contract_invoke_traced(
+        helpers::ACC_0,
+        Address::Account(helpers::ACC_0),
+        Energy::from(10000),
+        UpdateContractPayload {
+            address:      res_init.contract_address,
+            receive_name: OwnedReceiveName::new_unchecked("caller.call".into()),
+            message:      OwnedParameter::from_serial(&parameter)
+                .expect("Parameter has valid size"),
+            amount:       Amount::zero(),
+        },
+    );
+    traced.result.as_ref().expect("The caller handles the rejection");
+    let first = traced.records.first().expect("The call is recorded");
+    assert_eq!(first.depth, 0);
+    assert!(matches!(
+        &first.kind,
+        CallRecordKind::Interrupt(HostInterrupt::Call { address, entrypoint, .. })
+            if *address == res_init.contract_address && entrypoint.as_entrypoint_name() == EntrypointName::new_unchecked("fail")
+    ));
+    let last = traced.records.last().expect("The response is recorded");
+    assert_eq!(last.depth, 0);
+    assert!(!last.rolled_back);
+    assert!(matches!(
+        last.kind,
+        CallRecordKind::Resumed(HostInterruptResponse::Reject {
+            code: -17,
+            ..
+        })
+    ));
+    assert!(traced.to_string().contains("resumed after a reject with code -17"));
+}
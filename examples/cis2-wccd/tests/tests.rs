@@ -2,6 +2,7 @@
 use cis2_wccd::*;
 use concordium_cis2::*;
 use concordium_smart_contract_testing::*;
+use rand::Rng;
 
 /// The tests accounts.
 const ALICE: AccountAddress = AccountAddress([0; 32]);
@@ -534,6 +535,83 @@ fn test_no_execution_of_state_mutative_functions_when_paused() {
     assert_contract_paused_error(&update_operator);
 }
 
+/// Fuzz wrapping, unwrapping and transferring, and check that the CCD balance
+/// of the contract always equals the total supply of wCCD.
+#[test]
+fn test_fuzz_total_supply() {
+    let (chain, contract_address, _update) = initialize_contract_with_alice_tokens();
+
+    // Pick Alice or Bob at random.
+    fn account(rng: &mut impl Rng) -> (AccountAddress, Address) {
+        if rng.gen() {
+            (ALICE, ALICE_ADDR)
+        } else {
+            (BOB, BOB_ADDR)
+        }
+    }
+    let update =
+        move |receive_name: &str, amount: Amount, message: OwnedParameter| UpdateContractPayload {
+            amount,
+            receive_name: OwnedReceiveName::new_unchecked(format!("cis2_wCCD.{receive_name}")),
+            address: contract_address,
+            message,
+        };
+
+    ChainFuzzer::new(chain)
+        .action("wrap", move |rng, _chain| {
+            let (sender, _) = account(rng);
+            let (to, _) = account(rng);
+            let params = WrapParams {
+                to:   Receiver::Account(to),
+                data: AdditionalData::empty(),
+            };
+            let amount = Amount::from_micro_ccd(rng.gen_range(0..1000));
+            let message = OwnedParameter::from_serial(&params).expect("Wrap params");
+            FuzzStep::update(sender, update("wrap", amount, message))
+        })
+        .action("unwrap", move |rng, _chain| {
+            let (sender, owner) = account(rng);
+            let (receiver, _) = account(rng);
+            let params = UnwrapParams {
+                amount: TokenAmountU64(rng.gen_range(0..1000)),
+                owner,
+                receiver: Receiver::Account(receiver),
+                data: AdditionalData::empty(),
+            };
+            let message = OwnedParameter::from_serial(&params).expect("Unwrap params");
+            FuzzStep::update(sender, update("unwrap", Amount::zero(), message))
+        })
+        .action("transfer", move |rng, _chain| {
+            let (sender, from) = account(rng);
+            let (to, _) = account(rng);
+            let params = TransferParams::from(vec![concordium_cis2::Transfer {
+                from,
+                to: Receiver::Account(to),
+                token_id: TOKEN_ID_WCCD,
+                amount: TokenAmountU64(rng.gen_range(0..1000)),
+                data: AdditionalData::empty(),
+            }]);
+            let message = OwnedParameter::from_serial(&params).expect("Transfer params");
+            FuzzStep::update(sender, update("transfer", Amount::zero(), message))
+        })
+        .invariant("total supply equals the CCD balance", move |chain| {
+            let total_supply: u64 =
+                get_balances(chain, contract_address).0.iter().map(|amount| amount.0).sum();
+            let balance =
+                chain.contract_balance(contract_address).ok_or("The contract does not exist.")?;
+            if balance.micro_ccd() != total_supply {
+                return Err(format!(
+                    "The contract holds {balance} but the total supply is {total_supply} wCCD."
+                ));
+            }
+            Ok(())
+        })
+        .seed(0)
+        .runs(50)
+        .run()
+        .unwrap_or_else(|failure| panic!("{failure}"));
+}
+
 // Helpers:
 
 /// Helper function that initializes the contract and wraps 100 microCCD into
@@ -15,7 +15,7 @@
 //!      contract-version1/concordium-out/module.wasm.v1 -- --manifest-path
 //!      contract-version1/Cargo.toml
 use concordium_smart_contract_testing::*;
-use concordium_std::Deserial;
+use concordium_std::{Deserial, HasStateApi, Serial};
 use smart_contract_upgrade::UpgradeParams;
 
 const ACC_ADDR_OWNER: AccountAddress = AccountAddress([0u8; 32]);
@@ -28,6 +28,14 @@ pub struct State {
     new_state: String,
 }
 
+/// The state of `contract_version1`.
+#[derive(Serial, Deserial, Debug, PartialEq, Eq)]
+pub struct StateV1 {
+    admin:                    AccountAddress,
+    not_to_be_migrated_state: String,
+    to_be_migrated_state:     String,
+}
+
 fn setup_chain_and_contract() -> (Chain, ContractInitSuccess) {
     let mut chain = Chain::new();
 
@@ -208,3 +216,75 @@ fn test_upgrade_with_migration_function() {
         new_state: "This is the new state.".to_string(),
     });
 }
+
+#[test]
+fn test_upgrade_migrates_modified_state() {
+    let (mut chain, initialization) = setup_chain_and_contract();
+
+    // Set up the state and balance of `contract_version1` directly.
+    chain
+        .contract_state_modify(initialization.contract_address, |state_api| {
+            let mut state: StateV1 = state_api.read_root().expect("State should be valid");
+            state.to_be_migrated_state = "Modified state.".to_string();
+            state_api.write_root(&state);
+        })
+        .expect("Contract should exist");
+    chain
+        .contract_set_balance(initialization.contract_address, Amount::from_ccd(42))
+        .expect("Contract should exist");
+
+    let state: StateV1 =
+        chain.contract_state(initialization.contract_address).expect("State should be readable");
+    assert_eq!(state.to_be_migrated_state, "Modified state.");
+
+    // Deploy 'contract_version2' module (built with [Cargo Concordium](https://developer.concordium.software/en/mainnet/smart-contracts/guides/setup-tools.html#cargo-concordium)).
+    let deployment = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            ACC_ADDR_OWNER,
+            module_load_v1("../contract-version2/concordium-out/module.wasm.v1")
+                .expect("`Contract version2` module should be loaded"),
+        )
+        .expect("`Contract version2` deployment should always succeed");
+
+    let input_parameter = UpgradeParams {
+        module:  deployment.module_reference,
+        migrate: Some((
+            OwnedEntrypointName::new("migration".to_string())
+                .expect("`migration` should be a valid name"),
+            OwnedParameter::empty(),
+        )),
+    };
+
+    chain
+        .contract_update(
+            Signer::with_one_key(),
+            ACC_ADDR_OWNER,
+            Address::Account(ACC_ADDR_OWNER),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address:      initialization.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked(
+                    "smart_contract_upgrade.upgrade".into(),
+                ),
+                message:      OwnedParameter::from_serial(&input_parameter)
+                    .expect("`UpgradeParams` should be a valid inut parameter"),
+                amount:       Amount::zero(),
+            },
+        )
+        .expect("Upgrade should succeed");
+
+    let state: State = chain
+        .contract_state(initialization.contract_address)
+        .expect("Migrated state should be readable");
+    assert_eq!(state, State {
+        admin:     ACC_ADDR_OWNER,
+        old_state: "Modified state.".to_string(),
+        new_state: "This is the new state.".to_string(),
+    });
+    assert_eq!(
+        chain.contract_balance(initialization.contract_address),
+        Some(Amount::from_ccd(42)),
+        "The balance should be unaffected by the upgrade"
+    );
+}
@@ -51,6 +51,50 @@ fn test_amount_forward_on_correct_policy() {
     )]);
 }
 
+/// Test that sending money via the contract fails when one of the sender's
+/// credentials has a country of residence outside Denmark, even if another
+/// credential is from Denmark.
+#[test]
+fn test_amount_forward_rejected_on_foreign_credential() {
+    let (mut chain, contract_address) = init();
+
+    // Create the account BOB, who has a Danish and a Swedish credential.
+    let danish = PolicyBuilder::new()
+        .country_of_residence("DK")
+        .nationality("DK")
+        .build()
+        .expect("Policy is valid.");
+    let swedish = PolicyBuilder::new()
+        .identity_provider(1)
+        .country_of_residence("SE")
+        .age(18, chain.block_time())
+        .build()
+        .expect("Policy is valid.");
+    let mut bob = Account::new_with_policy(
+        BOB,
+        AccountBalance::new(Amount::from_ccd(1000), Amount::zero(), Amount::zero())
+            .expect("Staked + locked < total."),
+        danish,
+    );
+    bob.add_credential_policy(swedish);
+    chain.create_account(bob);
+
+    // Send money from Bob to Alice.
+    let update = chain
+        .contract_update(SIGNER, BOB, BOB_ADDR, Energy::from(50_000), UpdateContractPayload {
+            amount:       Amount::from_ccd(10),
+            address:      contract_address,
+            receive_name: OwnedReceiveName::new_unchecked(
+                "transfer-policy-check.receive".to_string(),
+            ),
+            message:      OwnedParameter::empty(),
+        })
+        .expect_err("Contract update fails.");
+
+    // Check that the contract rejected because of the Swedish credential.
+    assert_eq!(update.parse_return_value(), Ok(ContractError::NotLocalSender));
+}
+
 // Helpers:
 
 /// Construct a policy with the provided country code.